@@ -0,0 +1,644 @@
+use std::collections::HashMap;
+use std::fs::{OpenOptions, create_dir_all, read};
+use std::io::{Error as IoError, ErrorKind, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use actix_web::cookie::Key;
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{
+    Error as HashError,
+    PasswordHash,
+    PasswordHasher,
+    PasswordVerifier,
+    SaltString,
+};
+use argon2::{Algorithm, Argon2, Params, Version};
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use shared::api::{AccountInfo, ApiErrorBody, ApiErrorCode};
+use thiserror::Error;
+
+use crate::utils::api_error::ApiError;
+use crate::utils::journal::Journal;
+
+pub mod oidc;
+pub mod session;
+
+const JOURNAL_FILE: &str = "accounts.jsonl";
+const KEY_FILE: &str = "session.key";
+/// Expired revocations kept in the journal before it's compacted.
+const MAX_EXPIRED_REVOCATIONS: usize = 1024;
+
+/// How accounts and their sessions behave.
+#[derive(Clone, Debug)]
+pub struct AccountsConfig {
+    /// Time after which a session has to log in again.
+    pub session_ttl: Duration,
+    /// Only send the session cookie over HTTPS.
+    pub secure_cookies: bool,
+    /// Cost of the argon2id password hashes.
+    pub password_params: Params,
+    /// Usernames allowed to run events, regardless of case.
+    ///
+    /// Stored accounts only open once every configured username is registered,
+    /// so nobody else can claim one.
+    pub admins: Vec<String>,
+    /// Usernames allowed to moderate the canvas, admins always are.
+    pub moderators: Vec<String>,
+}
+
+impl Default for AccountsConfig {
+    fn default() -> Self {
+        Self {
+            session_ttl: Duration::from_secs(30 * 24 * 60 * 60),
+            secure_cookies: true,
+            password_params: Params::DEFAULT,
+            admins: Vec::new(),
+            moderators: Vec::new(),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum AccountError {
+    #[error("Usernames are 3 to 24 letters, digits, dashes or underscores.")]
+    InvalidUsername,
+
+    #[error("Passwords are 8 to 128 characters long.")]
+    InvalidPassword,
+
+    #[error("The username {0} is already taken.")]
+    UsernameTaken(String),
+
+    #[error("The username or password is wrong.")]
+    InvalidCredentials,
+
+    #[error("You need to log in first.")]
+    Unauthenticated,
+
+    #[error("Your session expired, log in again.")]
+    SessionExpired,
+
+    #[error("Only admins can do this.")]
+    Forbidden,
+
+    #[error("Only moderators can do this.")]
+    NotModerator,
+
+    #[error("The admin or moderator {0} isn't registered, register it before granting it a role.")]
+    UnknownRole(String),
+
+    #[error("The account storage failed: {0}")]
+    Storage(#[from] IoError),
+
+    #[error("The password couldn't be hashed: {0}")]
+    Hashing(#[from] HashError),
+}
+
+impl ResponseError for AccountError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InvalidUsername | Self::InvalidPassword => StatusCode::BAD_REQUEST,
+            Self::UsernameTaken(_) => StatusCode::CONFLICT,
+            Self::InvalidCredentials | Self::Unauthenticated | Self::SessionExpired => {
+                StatusCode::UNAUTHORIZED
+            },
+            Self::Forbidden | Self::NotModerator => StatusCode::FORBIDDEN,
+            Self::UnknownRole(_) | Self::Storage(_) | Self::Hashing(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            },
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let body = match self {
+            Self::InvalidUsername | Self::InvalidPassword => {
+                ApiErrorBody::new(ApiErrorCode::InvalidRequest, self.to_string())
+            },
+            Self::UsernameTaken(_) => ApiErrorBody::new(ApiErrorCode::Conflict, self.to_string()),
+            Self::InvalidCredentials | Self::Unauthenticated | Self::SessionExpired => {
+                ApiErrorBody::new(ApiErrorCode::Unauthorized, self.to_string())
+            },
+            Self::Forbidden | Self::NotModerator => {
+                ApiErrorBody::new(ApiErrorCode::Forbidden, self.to_string())
+            },
+            Self::UnknownRole(_) | Self::Storage(_) | Self::Hashing(_) => {
+                ApiErrorBody::new(ApiErrorCode::Internal, "The account couldn't be updated.")
+            },
+        };
+
+        ApiError::new(self.status_code(), body).error_response()
+    }
+}
+
+/// A registered user.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Account {
+    pub id: u64,
+    pub username: String,
+    /// PHC string of the argon2id password hash, `None` for accounts that only
+    /// log in through an identity provider.
+    #[serde(default)]
+    password_hash: Option<String>,
+    /// Unix milliseconds of the registration.
+    pub created_at: u64,
+}
+
+impl Account {
+    pub fn info(&self) -> AccountInfo {
+        AccountInfo {
+            id: self.id,
+            username: self
+                .username
+                .clone(),
+        }
+    }
+}
+
+/// A change to the accounts, as stored in the journal.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum AccountEntry {
+    Registered(Account),
+    /// An identity provider user that logs into `account`.
+    Linked {
+        account: u64,
+        issuer: String,
+        subject: String,
+    },
+    /// A session that logged out before it expired.
+    SessionRevoked {
+        session: u64,
+        expires_at: u64,
+    },
+}
+
+#[derive(Default)]
+struct AccountsState {
+    /// Indexed by `id - 1`.
+    accounts: Vec<Account>,
+    /// Lowercase usernames to ids, names are unique regardless of case.
+    by_name: HashMap<String, u64>,
+    /// Issuer and subject of provider users to ids.
+    identities: HashMap<(String, String), u64>,
+    /// Revoked sessions until they'd expire anyway.
+    revoked: HashMap<u64, u64>,
+    /// Revocations still in the journal that expired, it's compacted once
+    /// there are too many.
+    expired_revocations: usize,
+}
+
+impl AccountsState {
+    fn account(&self, id: u64) -> Option<&Account> {
+        self.accounts
+            .get(usize::try_from(id.checked_sub(1)?).ok()?)
+    }
+
+    fn id_of(&self, username: &str) -> Option<u64> {
+        self.by_name
+            .get(&username.to_lowercase())
+            .copied()
+    }
+
+    /// Forgets the revocations of sessions expired at `now`.
+    fn prune_revoked(&mut self, now: u64) {
+        let before = self
+            .revoked
+            .len();
+
+        self.revoked
+            .retain(|_, expires_at| *expires_at > now);
+        self.expired_revocations += before
+            - self
+                .revoked
+                .len();
+    }
+
+    /// The entries that rebuild this state, without expired revocations.
+    fn entries(&self) -> Vec<AccountEntry> {
+        let mut identities = self
+            .identities
+            .iter()
+            .collect::<Vec<_>>();
+        let mut revoked = self
+            .revoked
+            .iter()
+            .collect::<Vec<_>>();
+
+        identities.sort_by_key(|&(_, account)| *account);
+        revoked.sort_unstable();
+
+        self.accounts
+            .iter()
+            .cloned()
+            .map(AccountEntry::Registered)
+            .chain(
+                identities
+                    .into_iter()
+                    .map(|((issuer, subject), account)| AccountEntry::Linked {
+                        account: *account,
+                        issuer: issuer.clone(),
+                        subject: subject.clone(),
+                    }),
+            )
+            .chain(
+                revoked
+                    .into_iter()
+                    .map(|(session, expires_at)| AccountEntry::SessionRevoked {
+                        session: *session,
+                        expires_at: *expires_at,
+                    }),
+            )
+            .collect()
+    }
+
+    fn apply(&mut self, entry: AccountEntry) {
+        match entry {
+            AccountEntry::Registered(account) => {
+                self.by_name
+                    .insert(
+                        account
+                            .username
+                            .to_lowercase(),
+                        account.id,
+                    );
+                self.accounts
+                    .push(account);
+            },
+            AccountEntry::Linked { account, issuer, subject } => {
+                self.identities
+                    .insert((issuer, subject), account);
+            },
+            AccountEntry::SessionRevoked { session, expires_at } => {
+                self.revoked
+                    .insert(session, expires_at);
+            },
+        }
+    }
+}
+
+/// A valid username close to what a provider suggested.
+fn username_from(hint: &str) -> String {
+    let username = hint
+        .chars()
+        .filter_map(|character| match character {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => Some(character),
+            ' ' | '.' => Some('_'),
+            _ => None,
+        })
+        .take(20)
+        .collect::<String>();
+
+    if username.len() < 3 { "user".to_owned() } else { username }
+}
+
+fn valid_username(username: &str) -> bool {
+    (3..=24).contains(&username.len())
+        && username
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_')
+}
+
+/// Registered users and their sessions.
+///
+/// Sessions are signed cookies, only the ones revoked by logging out are
+/// remembered. When opened from a directory every change is appended to a
+/// journal and the signing key is kept next to it so sessions survive a
+/// restart.
+pub struct Accounts {
+    config: AccountsConfig,
+    key: Key,
+    state: RwLock<AccountsState>,
+    journal: Option<Mutex<Journal<AccountEntry>>>,
+    /// Checked against for unknown usernames so they take as long as a wrong
+    /// password.
+    dummy_hash: String,
+}
+
+impl Accounts {
+    /// Accounts that only live in memory.
+    pub fn new(config: AccountsConfig) -> Result<Self, AccountError> {
+        Self::with_state(config, Key::generate(), AccountsState::default(), None)
+    }
+
+    /// Loads the accounts stored in `dir`, creating the directory and the
+    /// session signing key if needed.
+    pub fn open(dir: impl AsRef<Path>, config: AccountsConfig) -> Result<Self, AccountError> {
+        let dir = dir.as_ref();
+        create_dir_all(dir)?;
+
+        let key = match read(dir.join(KEY_FILE)) {
+            Ok(bytes) if bytes.len() >= 64 => Key::from(&bytes),
+            Ok(_) => {
+                return Err(
+                    IoError::new(ErrorKind::InvalidData, "The session key is too short.").into()
+                );
+            },
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                let key = Key::generate();
+                let mut options = OpenOptions::new();
+                options
+                    .write(true)
+                    .create_new(true);
+
+                #[cfg(unix)]
+                std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+                let mut file = options.open(dir.join(KEY_FILE))?;
+                file.write_all(key.master())?;
+                file.sync_all()?;
+
+                key
+            },
+            Err(error) => return Err(error.into()),
+        };
+
+        let (journal, entries) = Journal::open(dir.join(JOURNAL_FILE))?;
+        let mut state = AccountsState::default();
+
+        for entry in entries {
+            state.apply(entry);
+        }
+
+        if let Some(username) = config
+            .admins
+            .iter()
+            .chain(&config.moderators)
+            .find(|username| {
+                state
+                    .id_of(username)
+                    .is_none()
+            })
+        {
+            return Err(AccountError::UnknownRole(username.clone()));
+        }
+
+        Self::with_state(config, key, state, Some(Mutex::new(journal)))
+    }
+
+    fn with_state(
+        config: AccountsConfig,
+        key: Key,
+        state: AccountsState,
+        journal: Option<Mutex<Journal<AccountEntry>>>,
+    ) -> Result<Self, AccountError> {
+        let mut accounts = Self {
+            config,
+            key,
+            state: RwLock::new(state),
+            journal,
+            dummy_hash: String::new(),
+        };
+
+        accounts.dummy_hash = accounts.hash_password("not a password")?;
+
+        Ok(accounts)
+    }
+
+    #[inline]
+    pub fn config(&self) -> &AccountsConfig {
+        &self.config
+    }
+
+    fn argon2(&self) -> Argon2<'static> {
+        Argon2::new(
+            Algorithm::Argon2id,
+            Version::V0x13,
+            self.config
+                .password_params
+                .clone(),
+        )
+    }
+
+    fn hash_password(&self, password: &str) -> Result<String, AccountError> {
+        Ok(self
+            .argon2()
+            .hash_password(password.as_bytes(), &SaltString::generate(&mut OsRng))?
+            .to_string())
+    }
+
+    fn append(&self, entry: &AccountEntry) -> Result<(), AccountError> {
+        if let Some(journal) = &self.journal {
+            journal
+                .lock()
+                .append(entry)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether `account` is listed in [`AccountsConfig::admins`].
+    pub fn is_admin(&self, account: u64) -> bool {
+        let state = self
+            .state
+            .read();
+
+        self.config
+            .admins
+            .iter()
+            .any(|username| state.id_of(username) == Some(account))
+    }
+
+    /// Whether `account` is listed in [`AccountsConfig::moderators`] or is an
+    /// admin.
+    pub fn is_moderator(&self, account: u64) -> bool {
+        let state = self
+            .state
+            .read();
+
+        self.config
+            .moderators
+            .iter()
+            .chain(
+                &self
+                    .config
+                    .admins,
+            )
+            .any(|username| state.id_of(username) == Some(account))
+    }
+
+    /// Whether `account` holds a higher role than `other`, admins outrank
+    /// moderators, who outrank everyone else.
+    pub fn outranks(&self, account: u64, other: u64) -> bool {
+        let rank = |account| {
+            if self.is_admin(account) {
+                2
+            } else if self.is_moderator(account) {
+                1
+            } else {
+                0
+            }
+        };
+
+        rank(account) > rank(other)
+    }
+
+    pub fn account(&self, id: u64) -> Option<Account> {
+        self.state
+            .read()
+            .account(id)
+            .cloned()
+    }
+
+    /// Creates an account, `now` is in unix milliseconds.
+    ///
+    /// Hashing is slow on purpose so this should run on the blocking pool.
+    pub fn register(
+        &self,
+        username: &str,
+        password: &str,
+        now: u64,
+    ) -> Result<Account, AccountError> {
+        if !valid_username(username) {
+            return Err(AccountError::InvalidUsername);
+        }
+
+        if !(8..=128).contains(
+            &password
+                .chars()
+                .count(),
+        ) {
+            return Err(AccountError::InvalidPassword);
+        }
+
+        let taken = |state: &AccountsState| {
+            state
+                .by_name
+                .contains_key(&username.to_lowercase())
+        };
+
+        if taken(
+            &self
+                .state
+                .read(),
+        ) {
+            return Err(AccountError::UsernameTaken(username.to_owned()));
+        }
+
+        let password_hash = self.hash_password(password)?;
+        let mut state = self
+            .state
+            .write();
+
+        // someone else could have registered it while hashing.
+        if taken(&state) {
+            return Err(AccountError::UsernameTaken(username.to_owned()));
+        }
+
+        let account = Account {
+            id: state
+                .accounts
+                .len() as u64
+                + 1,
+            username: username.to_owned(),
+            password_hash: Some(password_hash),
+            created_at: now,
+        };
+        let entry = AccountEntry::Registered(account.clone());
+
+        self.append(&entry)?;
+        state.apply(entry);
+
+        Ok(account)
+    }
+
+    /// The account matching both `username` and `password`.
+    ///
+    /// Hashing is slow on purpose so this should run on the blocking pool.
+    pub fn verify(&self, username: &str, password: &str) -> Result<Account, AccountError> {
+        let account = {
+            let state = self
+                .state
+                .read();
+
+            state
+                .by_name
+                .get(&username.to_lowercase())
+                .and_then(|id| state.account(*id))
+                .cloned()
+        };
+        let stored = account
+            .as_ref()
+            .and_then(|account| {
+                account
+                    .password_hash
+                    .as_deref()
+            })
+            .unwrap_or(&self.dummy_hash);
+
+        match self
+            .argon2()
+            .verify_password(password.as_bytes(), &PasswordHash::new(stored)?)
+        {
+            // accounts without a password never match, even the dummy one.
+            Ok(()) => account
+                .filter(|account| {
+                    account
+                        .password_hash
+                        .is_some()
+                })
+                .ok_or(AccountError::InvalidCredentials),
+            Err(HashError::Password) => Err(AccountError::InvalidCredentials),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// The account of the provider user `subject` at `issuer`, registering one
+    /// named after `username_hint` on their first login.
+    pub fn external(
+        &self,
+        issuer: &str,
+        subject: &str,
+        username_hint: &str,
+        now: u64,
+    ) -> Result<Account, AccountError> {
+        let identity = (issuer.to_owned(), subject.to_owned());
+        let mut state = self
+            .state
+            .write();
+
+        if let Some(account) = state
+            .identities
+            .get(&identity)
+            .and_then(|id| state.account(*id))
+        {
+            return Ok(account.clone());
+        }
+
+        let base = username_from(username_hint);
+        let username = (1..)
+            .map(|suffix| match suffix {
+                1 => base.clone(),
+                _ => format!("{base}-{suffix}"),
+            })
+            .find(|username| {
+                !state
+                    .by_name
+                    .contains_key(&username.to_lowercase())
+            })
+            .unwrap_or(base);
+        let account = Account {
+            id: state
+                .accounts
+                .len() as u64
+                + 1,
+            username,
+            password_hash: None,
+            created_at: now,
+        };
+        let (issuer, subject) = identity;
+
+        for entry in [
+            AccountEntry::Registered(account.clone()),
+            AccountEntry::Linked { account: account.id, issuer, subject },
+        ] {
+            self.append(&entry)?;
+            state.apply(entry);
+        }
+
+        Ok(account)
+    }
+}
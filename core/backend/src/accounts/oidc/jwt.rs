@@ -0,0 +1,183 @@
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::jwk::{AlgorithmParameters, Jwk, KeyAlgorithm, PublicKeyUse};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, decode_header};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::accounts::oidc::OidcError;
+
+/// Seconds of clock difference tolerated with the provider.
+const LEEWAY_SECS: u64 = 60;
+/// Smallest RSA modulus accepted for signatures.
+pub const MIN_MODULUS_BITS: usize = 2048;
+
+/// The `RS256` signing keys of a JWKS document, keys of any other kind are
+/// left out rather than failing the whole document.
+#[derive(Clone, Debug, Default)]
+pub struct SigningKeys {
+    keys: Vec<Jwk>,
+}
+
+impl SigningKeys {
+    pub fn from_document(document: &Value) -> Self {
+        let keys = document
+            .get("keys")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|key| Jwk::deserialize(key).ok())
+            .filter(usable)
+            .collect();
+
+        Self { keys }
+    }
+
+    /// The key a token naming `kid` is signed with, without a key id only a
+    /// single candidate is unambiguous.
+    pub fn find(&self, kid: Option<&str>) -> Option<&Jwk> {
+        let mut candidates = self
+            .keys
+            .iter()
+            .filter(|key| {
+                kid.is_none()
+                    || key
+                        .common
+                        .key_id
+                        .as_deref()
+                        == kid
+            });
+
+        match (candidates.next(), candidates.next()) {
+            (Some(key), None) => Some(key),
+            (Some(key), Some(_)) if kid.is_some() => Some(key),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `key` is an RSA key for signatures long enough to trust.
+fn usable(key: &Jwk) -> bool {
+    let AlgorithmParameters::RSA(rsa) = &key.algorithm else {
+        return false;
+    };
+
+    key.common
+        .key_algorithm
+        .is_none_or(|algorithm| algorithm == KeyAlgorithm::RS256)
+        && key
+            .common
+            .public_key_use
+            .as_ref()
+            .is_none_or(|usage| *usage == PublicKeyUse::Signature)
+        && URL_SAFE_NO_PAD
+            .decode(&rsa.n)
+            .is_ok_and(|modulus| {
+                let leading = modulus
+                    .iter()
+                    .position(|byte| *byte != 0)
+                    .unwrap_or(modulus.len());
+
+                (modulus.len() - leading) * 8 >= MIN_MODULUS_BITS
+            })
+}
+
+/// Audiences are either a single string or a list.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// The claims of an ID token CanvaDot cares about.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct IdClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: Audience,
+    /// Unix seconds.
+    pub exp: u64,
+    /// Unix seconds.
+    pub iat: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub azp: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preferred_username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+/// The key id an ID token names in its header.
+pub fn key_id(token: &str) -> Result<Option<String>, OidcError> {
+    decode_header(token)
+        .map(|header| header.kid)
+        .map_err(|_| OidcError::InvalidToken("it isn't a JWT"))
+}
+
+/// What an ID token has to match to be accepted.
+pub struct Expected<'a> {
+    pub issuer: &'a str,
+    pub client_id: &'a str,
+    pub nonce: &'a str,
+    /// Unix seconds.
+    pub now: u64,
+}
+
+/// Checks the signature and claims of a compact ID token signed with `key`.
+pub fn verify(token: &str, key: &Jwk, expected: &Expected) -> Result<IdClaims, OidcError> {
+    let key = DecodingKey::from_jwk(key)
+        .map_err(|_| OidcError::InvalidToken("signed with an invalid key"))?;
+    let mut validation = Validation::new(Algorithm::RS256);
+
+    // checked below against the given time instead of the clock.
+    validation.validate_exp = false;
+    validation.set_issuer(&[expected.issuer]);
+    validation.set_audience(&[expected.client_id]);
+    validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+    let claims = decode::<IdClaims>(token, &key, &validation)
+        .map_err(|error| {
+            OidcError::InvalidToken(match error.kind() {
+                ErrorKind::InvalidSignature => "the signature doesn't match",
+                ErrorKind::InvalidAlgorithm => "only RS256 signatures are accepted",
+                ErrorKind::InvalidIssuer => "it was issued by another provider",
+                ErrorKind::InvalidAudience => "it was issued for another client",
+                _ => "it isn't a valid ID token",
+            })
+        })?
+        .claims;
+
+    if claims
+        .azp
+        .as_deref()
+        .is_some_and(|azp| azp != expected.client_id)
+    {
+        return Err(OidcError::InvalidToken("it was issued for another client"));
+    }
+
+    if claims
+        .exp
+        .saturating_add(LEEWAY_SECS)
+        <= expected.now
+        || claims.iat > expected.now + LEEWAY_SECS
+    {
+        return Err(OidcError::InvalidToken("it expired"));
+    }
+
+    if claims
+        .nonce
+        .as_deref()
+        != Some(expected.nonce)
+    {
+        return Err(OidcError::InvalidToken("it belongs to another login"));
+    }
+
+    Ok(claims)
+}
@@ -0,0 +1,469 @@
+use std::collections::HashMap;
+use std::io::Error as IoError;
+use std::sync::Arc;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use jsonwebtoken::jwk::Jwk;
+use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use shared::api::{ApiErrorBody, ApiErrorCode};
+use thiserror::Error;
+use url::Url;
+
+use crate::accounts::AccountError;
+use crate::accounts::oidc::jwt::{Expected, IdClaims, SigningKeys};
+use crate::utils::api_error::ApiError;
+use crate::utils::http::{FetchRequest, HttpClient};
+
+pub mod jwt;
+
+/// Name of the cookie tying a login callback to the browser that started it.
+pub const STATE_COOKIE: &str = "canvadot_oidc_state";
+/// Milliseconds a started login may take to come back.
+pub const LOGIN_TTL_MS: u64 = 10 * 60 * 1000;
+/// Milliseconds before unknown key ids make the keys get fetched again.
+const KEYS_REFRESH_MS: u64 = 60 * 1000;
+/// Started logins kept at most, the oldest are dropped first.
+const MAX_PENDING: usize = 10_000;
+
+/// The identity provider users can log in with.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct OidcConfig {
+    /// Issuer URL, discovery happens below `/.well-known/openid-configuration`.
+    pub issuer: String,
+    pub client_id: String,
+    /// Sent with HTTP basic authentication, public clients rely on PKCE alone.
+    pub client_secret: Option<String>,
+    /// Where the provider sends users back to, the callback route.
+    pub redirect_uri: String,
+    #[serde(default = "default_scopes")]
+    pub scopes: String,
+    /// Where users land once they're logged in.
+    #[serde(default = "default_post_login_redirect")]
+    pub post_login_redirect: String,
+}
+
+fn default_scopes() -> String {
+    "openid profile email".to_owned()
+}
+
+fn default_post_login_redirect() -> String {
+    "/".to_owned()
+}
+
+impl OidcConfig {
+    pub fn new(
+        issuer: impl Into<String>,
+        client_id: impl Into<String>,
+        redirect_uri: impl Into<String>,
+    ) -> Self {
+        Self {
+            issuer: issuer.into(),
+            client_id: client_id.into(),
+            client_secret: None,
+            redirect_uri: redirect_uri.into(),
+            scopes: default_scopes(),
+            post_login_redirect: default_post_login_redirect(),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum OidcError {
+    #[error("The identity provider couldn't be reached: {0}")]
+    Transport(#[from] IoError),
+
+    #[error("The identity provider misbehaved: {0}")]
+    Provider(String),
+
+    #[error("The identity provider refused the login: {0}")]
+    Denied(String),
+
+    #[error("The login expired or was started in another browser.")]
+    InvalidState,
+
+    #[error("The identity token was rejected as {0}.")]
+    InvalidToken(&'static str),
+
+    #[error(transparent)]
+    Account(#[from] AccountError),
+}
+
+impl ResponseError for OidcError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Transport(_) | Self::Provider(_) => StatusCode::BAD_GATEWAY,
+            Self::Denied(_) | Self::InvalidState | Self::InvalidToken(_) => {
+                StatusCode::UNAUTHORIZED
+            },
+            Self::Account(error) => error.status_code(),
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let body = match self {
+            Self::Transport(_) | Self::Provider(_) => {
+                log::warn!("{self}");
+
+                ApiErrorBody::new(
+                    ApiErrorCode::Unavailable,
+                    "The identity provider couldn't be reached.",
+                )
+            },
+            Self::Denied(_) | Self::InvalidState | Self::InvalidToken(_) => {
+                ApiErrorBody::new(ApiErrorCode::Unauthorized, self.to_string())
+            },
+            Self::Account(error) => return error.error_response(),
+        };
+
+        ApiError::new(self.status_code(), body).error_response()
+    }
+}
+
+/// The part of the discovery document the login needs.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Deserialize)]
+struct TokenError {
+    error: String,
+    #[serde(default)]
+    error_description: Option<String>,
+}
+
+struct PendingLogin {
+    verifier: String,
+    nonce: String,
+    expires_at: u64,
+}
+
+/// A login handed to the provider, `url` is where the browser goes next and
+/// `state` has to come back with it.
+pub struct LoginRedirect {
+    pub url: Url,
+    pub state: String,
+}
+
+fn random_token() -> String {
+    let mut bytes = [0; 32];
+    OsRng.fill_bytes(&mut bytes);
+
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn parse_url(url: &str) -> Result<Url, OidcError> {
+    Url::parse(url)
+        .map_err(|error| OidcError::Provider(format!("{url} isn't a valid URL: {error}")))
+}
+
+/// Authorization code logins with PKCE against one OpenID Connect provider.
+///
+/// The discovery document is fetched on the first login and kept, the signing
+/// keys are fetched again whenever a token names a key that isn't known yet.
+pub struct OidcProvider {
+    config: OidcConfig,
+    client: Box<dyn HttpClient>,
+    metadata: Mutex<Option<Arc<ProviderMetadata>>>,
+    /// The last fetched keys and when they were fetched.
+    keys: Mutex<Option<(Arc<SigningKeys>, u64)>>,
+    pending: Mutex<HashMap<String, PendingLogin>>,
+}
+
+impl OidcProvider {
+    pub fn new(config: OidcConfig, client: impl HttpClient + 'static) -> Self {
+        Self {
+            config,
+            client: Box::new(client),
+            metadata: Mutex::new(None),
+            keys: Mutex::new(None),
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    #[inline]
+    pub fn config(&self) -> &OidcConfig {
+        &self.config
+    }
+
+    async fn fetch_json<T: DeserializeOwned>(&self, request: FetchRequest) -> Result<T, OidcError> {
+        let url = request
+            .url
+            .clone();
+        let response = self
+            .client
+            .send(request)
+            .await?;
+
+        if !(200..300).contains(&response.status) {
+            return Err(OidcError::Provider(format!("{url} answered {}", response.status)));
+        }
+
+        serde_json::from_slice(&response.body)
+            .map_err(|error| OidcError::Provider(format!("{url} sent unexpected JSON: {error}")))
+    }
+
+    /// The discovery document of the issuer.
+    pub async fn metadata(&self) -> Result<Arc<ProviderMetadata>, OidcError> {
+        if let Some(metadata) = &*self
+            .metadata
+            .lock()
+        {
+            return Ok(metadata.clone());
+        }
+
+        let url = parse_url(&format!(
+            "{}/.well-known/openid-configuration",
+            self.config
+                .issuer
+                .trim_end_matches('/')
+        ))?;
+        let metadata = self
+            .fetch_json::<ProviderMetadata>(FetchRequest::get(url))
+            .await?;
+
+        if metadata.issuer
+            != self
+                .config
+                .issuer
+        {
+            return Err(OidcError::Provider(format!(
+                "the discovery document is for {}",
+                metadata.issuer
+            )));
+        }
+
+        let metadata = Arc::new(metadata);
+        *self
+            .metadata
+            .lock() = Some(metadata.clone());
+
+        Ok(metadata)
+    }
+
+    /// The key a token signed with `kid` has to be checked with.
+    async fn key(&self, kid: Option<&str>, now: u64) -> Result<Jwk, OidcError> {
+        let find = |keys: &SigningKeys| {
+            keys.find(kid)
+                .cloned()
+        };
+
+        let cached = self
+            .keys
+            .lock()
+            .clone();
+
+        if let Some((keys, fetched_at)) = &cached {
+            if let Some(key) = find(keys) {
+                return Ok(key);
+            }
+
+            if now < fetched_at + KEYS_REFRESH_MS {
+                return Err(OidcError::InvalidToken("signed with an unknown key"));
+            }
+        }
+
+        let metadata = self
+            .metadata()
+            .await?;
+        let keys = Arc::new(SigningKeys::from_document(
+            &self
+                .fetch_json::<Value>(FetchRequest::get(parse_url(&metadata.jwks_uri)?))
+                .await?,
+        ));
+
+        *self
+            .keys
+            .lock() = Some((keys.clone(), now));
+
+        find(&keys).ok_or(OidcError::InvalidToken("signed with an unknown key"))
+    }
+
+    /// Starts a login at `now` unix milliseconds.
+    pub async fn start_login(&self, now: u64) -> Result<LoginRedirect, OidcError> {
+        let metadata = self
+            .metadata()
+            .await?;
+        let state = random_token();
+        let verifier = random_token();
+        let nonce = random_token();
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+
+        let mut url = parse_url(&metadata.authorization_endpoint)?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair(
+                "client_id",
+                &self
+                    .config
+                    .client_id,
+            )
+            .append_pair(
+                "redirect_uri",
+                &self
+                    .config
+                    .redirect_uri,
+            )
+            .append_pair(
+                "scope",
+                &self
+                    .config
+                    .scopes,
+            )
+            .append_pair("state", &state)
+            .append_pair("nonce", &nonce)
+            .append_pair("code_challenge", &challenge)
+            .append_pair("code_challenge_method", "S256");
+
+        let mut pending = self
+            .pending
+            .lock();
+
+        pending.retain(|_, login| login.expires_at > now);
+
+        if pending.len() >= MAX_PENDING {
+            let oldest = pending
+                .iter()
+                .min_by_key(|(_, login)| login.expires_at)
+                .map(|(state, _)| state.clone());
+
+            if let Some(oldest) = oldest {
+                pending.remove(&oldest);
+            }
+        }
+
+        pending.insert(
+            state.clone(),
+            PendingLogin {
+                verifier,
+                nonce,
+                expires_at: now + LOGIN_TTL_MS,
+            },
+        );
+
+        Ok(LoginRedirect { url, state })
+    }
+
+    /// Exchanges the `code` the provider sent back with `state` for the
+    /// verified claims of the user.
+    pub async fn finish_login(
+        &self,
+        state: &str,
+        code: &str,
+        now: u64,
+    ) -> Result<IdClaims, OidcError> {
+        let login = self
+            .pending
+            .lock()
+            .remove(state)
+            .filter(|login| login.expires_at > now)
+            .ok_or(OidcError::InvalidState)?;
+        let metadata = self
+            .metadata()
+            .await?;
+
+        let mut request = FetchRequest::form(
+            parse_url(&metadata.token_endpoint)?,
+            &[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                (
+                    "redirect_uri",
+                    &self
+                        .config
+                        .redirect_uri,
+                ),
+                (
+                    "client_id",
+                    &self
+                        .config
+                        .client_id,
+                ),
+                ("code_verifier", &login.verifier),
+            ],
+        );
+
+        if let Some(secret) = &self
+            .config
+            .client_secret
+        {
+            let encode = |value: &str| {
+                url::form_urlencoded::byte_serialize(value.as_bytes()).collect::<String>()
+            };
+            let credentials = format!(
+                "{}:{}",
+                encode(
+                    &self
+                        .config
+                        .client_id
+                ),
+                encode(secret)
+            );
+
+            request =
+                request.header("Authorization", format!("Basic {}", STANDARD.encode(credentials)));
+        }
+
+        let response = self
+            .client
+            .send(request)
+            .await?;
+
+        if (400..500).contains(&response.status) {
+            let error = serde_json::from_slice::<TokenError>(&response.body)
+                .map(|error| match error.error_description {
+                    Some(description) => format!("{} ({description})", error.error),
+                    None => error.error,
+                })
+                .unwrap_or_else(|_| format!("the token endpoint answered {}", response.status));
+
+            return Err(OidcError::Denied(error));
+        }
+
+        if !(200..300).contains(&response.status) {
+            return Err(OidcError::Provider(format!(
+                "the token endpoint answered {}",
+                response.status
+            )));
+        }
+
+        let token = serde_json::from_slice::<TokenResponse>(&response.body)
+            .map_err(|error| {
+                OidcError::Provider(format!("the token response is invalid: {error}"))
+            })?
+            .id_token;
+        let key = self
+            .key(jwt::key_id(&token)?.as_deref(), now)
+            .await?;
+
+        jwt::verify(
+            &token,
+            &key,
+            &Expected {
+                issuer: &metadata.issuer,
+                client_id: &self
+                    .config
+                    .client_id,
+                nonce: &login.nonce,
+                now: now / 1000,
+            },
+        )
+    }
+}
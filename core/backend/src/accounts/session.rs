@@ -0,0 +1,258 @@
+use std::future::{Ready, ready};
+
+use actix_web::cookie::time::Duration as CookieDuration;
+use actix_web::cookie::{Cookie, CookieJar, SameSite};
+use actix_web::dev::Payload;
+use actix_web::web::Data;
+use actix_web::{FromRequest, HttpRequest};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+
+use crate::accounts::{AccountEntry, AccountError, Accounts, MAX_EXPIRED_REVOCATIONS};
+use crate::utils::time::unix_millis;
+
+/// Name of the cookie holding the signed session.
+pub const SESSION_COOKIE: &str = "canvadot_session";
+
+/// A logged in session, carried by the client in a signed cookie.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Session {
+    pub id: u64,
+    pub user: u64,
+    /// Unix milliseconds after which the session is no longer accepted.
+    pub expires_at: u64,
+}
+
+impl Session {
+    fn encode(&self) -> String {
+        format!("{}.{}.{}", self.id, self.user, self.expires_at)
+    }
+
+    fn decode(value: &str) -> Option<Self> {
+        let mut parts = value.split('.');
+        let session = Self {
+            id: parts
+                .next()?
+                .parse()
+                .ok()?,
+            user: parts
+                .next()?
+                .parse()
+                .ok()?,
+            expires_at: parts
+                .next()?
+                .parse()
+                .ok()?,
+        };
+
+        parts
+            .next()
+            .is_none()
+            .then_some(session)
+    }
+}
+
+impl Accounts {
+    fn session_cookie(&self, value: String, max_age: CookieDuration) -> Cookie<'static> {
+        Cookie::build(SESSION_COOKIE, value)
+            .path("/")
+            .http_only(true)
+            .secure(
+                self.config
+                    .secure_cookies,
+            )
+            .same_site(SameSite::Lax)
+            .max_age(max_age)
+            .finish()
+    }
+
+    /// Starts a session for `user` at `now` unix milliseconds, the returned
+    /// cookie should be sent along the response.
+    pub fn start_session(&self, user: u64, now: u64) -> (Session, Cookie<'static>) {
+        let ttl = self
+            .config
+            .session_ttl;
+        let session = Session {
+            id: OsRng.next_u64(),
+            user,
+            expires_at: now.saturating_add(u64::try_from(ttl.as_millis()).unwrap_or(u64::MAX)),
+        };
+
+        let mut jar = CookieJar::new();
+        jar.signed_mut(&self.key)
+            .add(self.session_cookie(
+                session.encode(),
+                CookieDuration::try_from(ttl).unwrap_or(CookieDuration::MAX),
+            ));
+
+        let cookie = jar
+            .get(SESSION_COOKIE)
+            .cloned()
+            .unwrap_or_else(|| Cookie::new(SESSION_COOKIE, ""));
+
+        (session, cookie)
+    }
+
+    /// The session a signed cookie value stands for at `now`.
+    pub fn session(&self, value: &str, now: u64) -> Result<Session, AccountError> {
+        let mut jar = CookieJar::new();
+        jar.add_original(Cookie::new(SESSION_COOKIE, value.to_owned()));
+
+        let session = jar
+            .signed(&self.key)
+            .get(SESSION_COOKIE)
+            .and_then(|cookie| Session::decode(cookie.value()))
+            .ok_or(AccountError::Unauthenticated)?;
+
+        if now >= session.expires_at {
+            return Err(AccountError::SessionExpired);
+        }
+
+        if self
+            .state
+            .read()
+            .revoked
+            .contains_key(&session.id)
+        {
+            return Err(AccountError::Unauthenticated);
+        }
+
+        Ok(session)
+    }
+
+    /// Revokes a session at `now`, the returned cookie clears it on the client.
+    pub fn end_session(
+        &self,
+        session: &Session,
+        now: u64,
+    ) -> Result<Cookie<'static>, AccountError> {
+        let entry = AccountEntry::SessionRevoked {
+            session: session.id,
+            expires_at: session.expires_at,
+        };
+        let mut state = self
+            .state
+            .write();
+
+        self.append(&entry)?;
+        state.prune_revoked(now);
+        state.apply(entry);
+
+        if state.expired_revocations > MAX_EXPIRED_REVOCATIONS
+            && let Some(journal) = &self.journal
+        {
+            journal
+                .lock()
+                .rewrite(&state.entries())?;
+            state.expired_revocations = 0;
+        }
+
+        Ok(self.session_cookie(String::new(), CookieDuration::ZERO))
+    }
+}
+
+/// The account behind the session cookie of a request.
+///
+/// Extracting it fails with `401 Unauthorized` without a valid session, use
+/// `Option<AuthenticatedUser>` for routes that also serve anonymous callers.
+#[derive(Clone, Debug)]
+pub struct AuthenticatedUser {
+    pub id: u64,
+    pub username: String,
+    pub session: Session,
+}
+
+impl AuthenticatedUser {
+    fn extract(request: &HttpRequest) -> Result<Self, AccountError> {
+        let accounts = request
+            .app_data::<Data<Accounts>>()
+            .ok_or(AccountError::Unauthenticated)?;
+        let cookie = request
+            .cookie(SESSION_COOKIE)
+            .ok_or(AccountError::Unauthenticated)?;
+        let session = accounts.session(cookie.value(), unix_millis())?;
+        let account = accounts
+            .account(session.user)
+            .ok_or(AccountError::Unauthenticated)?;
+
+        Ok(Self {
+            id: account.id,
+            username: account.username,
+            session,
+        })
+    }
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = AccountError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(request: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Self::extract(request))
+    }
+}
+
+/// An [`AuthenticatedUser`] listed in [`AccountsConfig::admins`].
+///
+/// Extracting it fails with `403 Forbidden` for anyone else.
+///
+/// [`AccountsConfig::admins`]: crate::accounts::AccountsConfig::admins
+#[derive(Clone, Debug)]
+pub struct AdminUser(pub AuthenticatedUser);
+
+impl AdminUser {
+    fn extract(request: &HttpRequest) -> Result<Self, AccountError> {
+        let user = AuthenticatedUser::extract(request)?;
+        let admin = request
+            .app_data::<Data<Accounts>>()
+            .is_some_and(|accounts| accounts.is_admin(user.id));
+
+        if !admin {
+            return Err(AccountError::Forbidden);
+        }
+
+        Ok(Self(user))
+    }
+}
+
+impl FromRequest for AdminUser {
+    type Error = AccountError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(request: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Self::extract(request))
+    }
+}
+
+/// An [`AuthenticatedUser`] listed in [`AccountsConfig::moderators`] or
+/// [`AccountsConfig::admins`].
+///
+/// Extracting it fails with `403 Forbidden` for anyone else.
+///
+/// [`AccountsConfig::moderators`]: crate::accounts::AccountsConfig::moderators
+/// [`AccountsConfig::admins`]: crate::accounts::AccountsConfig::admins
+#[derive(Clone, Debug)]
+pub struct ModeratorUser(pub AuthenticatedUser);
+
+impl ModeratorUser {
+    fn extract(request: &HttpRequest) -> Result<Self, AccountError> {
+        let user = AuthenticatedUser::extract(request)?;
+        let moderator = request
+            .app_data::<Data<Accounts>>()
+            .is_some_and(|accounts| accounts.is_moderator(user.id));
+
+        if !moderator {
+            return Err(AccountError::NotModerator);
+        }
+
+        Ok(Self(user))
+    }
+}
+
+impl FromRequest for ModeratorUser {
+    type Error = AccountError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(request: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Self::extract(request))
+    }
+}
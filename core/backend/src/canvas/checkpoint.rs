@@ -0,0 +1,93 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use actix_web::rt::spawn;
+use actix_web::rt::task::JoinHandle;
+use actix_web::rt::time::interval;
+use actix_web::web::{Data, block};
+use log::{error, info};
+use serde::Deserialize;
+
+use crate::canvas::CanvasStore;
+use crate::config::nonzero_seconds;
+use crate::monitoring::metrics::Metrics;
+
+/// When snapshots are taken and what's kept around afterwards.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct SnapshotPolicy {
+    /// Time between snapshots, nothing is written if the canvas didn't change.
+    #[serde(rename = "interval_secs", deserialize_with = "nonzero_seconds")]
+    pub interval: Duration,
+    /// Snapshots kept on disk, at least one is always kept.
+    pub retain: usize,
+    /// Move log segments covered by every kept snapshot to the archive
+    /// instead of deleting them, the archive keeps the canvas history.
+    pub archive_log: bool,
+}
+
+impl Default for SnapshotPolicy {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(300),
+            retain: 3,
+            archive_log: true,
+        }
+    }
+}
+
+/// Outcome of [`CanvasStore::checkpoint`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub snapshot: PathBuf,
+    /// Log revision the snapshot covers.
+    pub revision: u64,
+    /// Log segments archived or deleted.
+    pub compacted_segments: usize,
+    pub duration: Duration,
+}
+
+/// Periodically checkpoints `store` following `policy` until the runtime
+/// stops, timing every snapshot into `metrics`.
+pub fn spawn_snapshots(
+    store: Data<CanvasStore>,
+    policy: SnapshotPolicy,
+    metrics: Data<Metrics>,
+) -> JoinHandle<()> {
+    spawn(async move {
+        let mut ticker = interval(policy.interval);
+        let mut last_revision = store.revision();
+
+        // the first tick completes right away.
+        ticker
+            .tick()
+            .await;
+
+        loop {
+            ticker
+                .tick()
+                .await;
+
+            if store.revision() == last_revision {
+                continue;
+            }
+
+            let (store, policy) = (store.clone(), policy.clone());
+
+            match block(move || store.checkpoint(&policy)).await {
+                Ok(Ok(Some(checkpoint))) => {
+                    info!(
+                        "Canvas snapshot at revision {} written in {:?}, {} log segments \
+                         compacted.",
+                        checkpoint.revision, checkpoint.duration, checkpoint.compacted_segments
+                    );
+                    metrics.record_snapshot(checkpoint.duration);
+                    last_revision = checkpoint.revision;
+                },
+                Ok(Ok(None)) => break,
+                Ok(Err(canvas_error)) => error!("Canvas snapshot failed: {canvas_error}"),
+                Err(blocking_error) => error!("Canvas snapshot failed: {blocking_error}"),
+            }
+        }
+    })
+}
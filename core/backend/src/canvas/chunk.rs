@@ -0,0 +1,107 @@
+use shared::canvas::PixelColor;
+
+/// A square block of RGB cells, the unit the canvas is stored and served in.
+///
+/// Every cell remembers the canvas revision that last changed it,
+/// so clients can catch up on a chunk without downloading it again.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chunk {
+    size: u16,
+    cells: Vec<u8>,
+    cell_versions: Vec<u64>,
+    version: u64,
+}
+
+impl Chunk {
+    /// A chunk with every cell set to white.
+    pub fn new(size: u16) -> Self {
+        let side = usize::from(size);
+
+        Self {
+            size,
+            cells: PixelColor::WHITE
+                .to_array()
+                .repeat(side * side),
+            cell_versions: vec![0; side * side],
+            version: 0,
+        }
+    }
+
+    /// Rebuilds a chunk from its stored cells, `cells` must hold `size * size`
+    /// RGB triplets and `cell_versions` one revision per cell.
+    pub fn from_parts(size: u16, cells: Vec<u8>, cell_versions: Vec<u64>) -> Self {
+        debug_assert_eq!(cells.len(), usize::from(size) * usize::from(size) * 3);
+        debug_assert_eq!(cell_versions.len(), usize::from(size) * usize::from(size));
+
+        let version = cell_versions
+            .iter()
+            .copied()
+            .max()
+            .unwrap_or(0);
+
+        Self { size, cells, cell_versions, version }
+    }
+
+    #[inline]
+    fn index(&self, x: u16, y: u16) -> usize {
+        usize::from(y) * usize::from(self.size) + usize::from(x)
+    }
+
+    #[inline]
+    pub fn size(&self) -> u16 {
+        self.size
+    }
+
+    /// Canvas revision of the last placement in this chunk, `0` if it was never
+    /// drawn on.
+    #[inline]
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Row major RGB bytes of every cell.
+    #[inline]
+    pub fn cells(&self) -> &[u8] {
+        &self.cells
+    }
+
+    /// Row major revision of the last change of every cell.
+    #[inline]
+    pub fn cell_versions(&self) -> &[u64] {
+        &self.cell_versions
+    }
+
+    pub fn get(&self, x: u16, y: u16) -> PixelColor {
+        let index = self.index(x, y) * 3;
+
+        PixelColor::new(self.cells[index], self.cells[index + 1], self.cells[index + 2])
+    }
+
+    pub fn set(&mut self, x: u16, y: u16, color: PixelColor, version: u64) {
+        let index = self.index(x, y);
+
+        self.cells[index * 3..index * 3 + 3].copy_from_slice(&color.to_array());
+        self.cell_versions[index] = version;
+        self.version = self
+            .version
+            .max(version);
+    }
+
+    /// Every cell changed after revision `since`, in row major order.
+    pub fn changes_since(&self, since: u64) -> Vec<(u16, u16, PixelColor)> {
+        let size = usize::from(self.size);
+
+        self.cell_versions
+            .iter()
+            .enumerate()
+            .filter(|(_, version)| **version > since)
+            .map(|(index, _)| {
+                // the index of a cell is below `size * size` so both halves fit in a `u16`.
+                #[allow(clippy::cast_possible_truncation)]
+                let (x, y) = ((index % size) as u16, (index / size) as u16);
+
+                (x, y, self.get(x, y))
+            })
+            .collect()
+    }
+}
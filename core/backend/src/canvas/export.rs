@@ -0,0 +1,162 @@
+use std::io::Write;
+use std::time::SystemTime;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use png::{BitDepth, ColorType, Encoder, EncodingError};
+use shared::api::{ApiErrorBody, ApiErrorCode};
+use shared::canvas::CanvasBounds;
+use thiserror::Error;
+
+use crate::canvas::CanvasStore;
+use crate::utils::api_error::ApiError;
+
+/// Limits of a single image export.
+#[derive(Clone, Copy, Debug)]
+pub struct ExportLimits {
+    /// Pixels the scaled image may have at most.
+    pub max_area: u64,
+    /// Largest factor a canvas pixel may be scaled by.
+    pub max_scale: u32,
+}
+
+impl Default for ExportLimits {
+    fn default() -> Self {
+        Self { max_area: 4096 * 4096, max_scale: 32 }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error(
+        "The region {}x{} at ({}, {}) is empty or outside of the canvas.",
+        .0.width, .0.height, .0.x, .0.y
+    )]
+    InvalidRegion(CanvasBounds),
+
+    #[error("The scale must be between 1 and {max}, got {scale}.")]
+    InvalidScale { scale: u32, max: u32 },
+
+    #[error("The image would have {area} pixels but at most {max} are allowed.")]
+    TooLarge { area: u64, max: u64 },
+
+    #[error("The image couldn't be encoded: {0}")]
+    Encoding(#[from] EncodingError),
+}
+
+impl ResponseError for ExportError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InvalidRegion(_) | Self::InvalidScale { .. } | Self::TooLarge { .. } => {
+                StatusCode::BAD_REQUEST
+            },
+            Self::Encoding(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let body = match self {
+            Self::InvalidRegion(_) => {
+                ApiErrorBody::new(ApiErrorCode::OutOfBounds, self.to_string())
+            },
+            Self::InvalidScale { .. } | Self::TooLarge { .. } => {
+                ApiErrorBody::new(ApiErrorCode::InvalidRequest, self.to_string())
+            },
+            Self::Encoding(_) => {
+                ApiErrorBody::new(ApiErrorCode::Internal, "The canvas couldn't be exported.")
+            },
+        };
+
+        ApiError::new(self.status_code(), body).error_response()
+    }
+}
+
+/// An encoded image of the canvas.
+pub struct PngExport {
+    /// Revision of the canvas the image shows.
+    pub revision: u64,
+    pub bytes: Vec<u8>,
+}
+
+/// Checks `region` and `scale` against the canvas and `limits` before
+/// anything is rendered.
+pub fn validate(
+    store: &CanvasStore,
+    region: CanvasBounds,
+    scale: u32,
+    limits: &ExportLimits,
+) -> Result<(), ExportError> {
+    if region.area() == 0
+        || !store
+            .bounds()
+            .contains_bounds(&region)
+    {
+        return Err(ExportError::InvalidRegion(region));
+    }
+
+    if scale == 0 || scale > limits.max_scale {
+        return Err(ExportError::InvalidScale { scale, max: limits.max_scale });
+    }
+
+    let area = region
+        .area()
+        .saturating_mul(u64::from(scale).pow(2));
+
+    if area > limits.max_area {
+        return Err(ExportError::TooLarge { area, max: limits.max_area });
+    }
+
+    Ok(())
+}
+
+/// Renders `region` as an RGB PNG with every canvas pixel drawn as a
+/// `scale`x`scale` square.
+///
+/// The revision and export time are embedded as `tEXt` chunks.
+pub fn render_png(
+    store: &CanvasStore,
+    region: CanvasBounds,
+    scale: u32,
+    limits: &ExportLimits,
+    exported_at: SystemTime,
+) -> Result<PngExport, ExportError> {
+    validate(store, region, scale, limits)?;
+
+    let (revision, pixels) = store.region(region);
+    let scale = scale as usize;
+    let row_len = region.width as usize * 3;
+    let mut bytes = Vec::new();
+
+    let mut encoder =
+        Encoder::new(&mut bytes, region.width * scale as u32, region.height * scale as u32);
+    encoder.set_color(ColorType::Rgb);
+    encoder.set_depth(BitDepth::Eight);
+    encoder.add_text_chunk("Revision".to_string(), revision.to_string())?;
+    encoder.add_text_chunk("Creation Time".to_string(), httpdate::fmt_http_date(exported_at))?;
+    encoder.add_text_chunk("Software".to_string(), "CanvaDot".to_string())?;
+
+    let mut writer = encoder.write_header()?;
+    let mut stream = writer.stream_writer()?;
+    let mut scaled_row = Vec::with_capacity(row_len * scale);
+
+    for row in pixels.chunks_exact(row_len) {
+        scaled_row.clear();
+
+        for pixel in row.chunks_exact(3) {
+            for _ in 0..scale {
+                scaled_row.extend_from_slice(pixel);
+            }
+        }
+
+        for _ in 0..scale {
+            stream
+                .write_all(&scaled_row)
+                .map_err(EncodingError::from)?;
+        }
+    }
+
+    stream.finish()?;
+    writer.finish()?;
+
+    Ok(PngExport { revision, bytes })
+}
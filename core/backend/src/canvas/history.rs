@@ -0,0 +1,571 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{File, OpenOptions, create_dir_all, read_dir, rename};
+use std::io::{
+    BufReader,
+    BufWriter,
+    ErrorKind as IoErrorKind,
+    Read,
+    Result as IoResult,
+    Seek,
+    SeekFrom,
+    Write,
+};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use shared::canvas::{ChunkCoord, PixelColor, PixelCoord, Placement};
+
+use crate::utils::binary::ReadBinary;
+
+const INDEX_EXTENSION: &str = "idx";
+/// Holds the revision up to which every placement is in the chunk files.
+const COVERED_FILE: &str = "covered";
+/// `[seq: u64][x: i32][y: i32][rgb][placed_at: u64][has author: u8][author:
+/// u64]`, little endian.
+const RECORD_LEN: usize = 36;
+/// Chunks whose stored placements are kept in memory once read.
+const CACHED_CHUNKS: usize = 16;
+
+/// The placements of some pixels, oldest first.
+type Placements = HashMap<PixelCoord, Vec<IndexedPlacement>>;
+
+/// A placement as remembered by the [`PixelIndex`], without the coordinate it
+/// is filed under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexedPlacement {
+    pub seq: u64,
+    pub color: PixelColor,
+    pub placed_at: u64,
+    pub author: Option<u64>,
+}
+
+impl IndexedPlacement {
+    fn encode(&self, coord: PixelCoord) -> [u8; RECORD_LEN] {
+        let mut record = [0; RECORD_LEN];
+
+        record[0..8].copy_from_slice(
+            &self
+                .seq
+                .to_le_bytes(),
+        );
+        record[8..12].copy_from_slice(
+            &coord
+                .x
+                .to_le_bytes(),
+        );
+        record[12..16].copy_from_slice(
+            &coord
+                .y
+                .to_le_bytes(),
+        );
+        record[16..19].copy_from_slice(
+            &self
+                .color
+                .to_array(),
+        );
+        record[19..27].copy_from_slice(
+            &self
+                .placed_at
+                .to_le_bytes(),
+        );
+
+        if let Some(author) = self.author {
+            record[27] = 1;
+            record[28..36].copy_from_slice(&author.to_le_bytes());
+        }
+
+        record
+    }
+
+    fn decode(mut record: &[u8]) -> IoResult<(PixelCoord, Self)> {
+        let seq = record.read_u64()?;
+        let coord = PixelCoord::new(record.read_i32()?, record.read_i32()?);
+        let color = PixelColor::new(record.read_u8()?, record.read_u8()?, record.read_u8()?);
+        let placed_at = record.read_u64()?;
+        let author = match record.read_u8()? {
+            0 => None,
+            _ => Some(record.read_u64()?),
+        };
+
+        Ok((coord, Self { seq, color, placed_at, author }))
+    }
+}
+
+/// Part of the history of one pixel, newest placements first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PixelHistoryPage {
+    pub placements: Vec<IndexedPlacement>,
+    /// Revision to continue before, `None` when older placements don't exist.
+    pub next_before: Option<u64>,
+}
+
+/// Every placement of the canvas filed by coordinate, so the history of a
+/// pixel is found without reading the placement log.
+///
+/// When opened from a directory the placements up to
+/// [`PixelIndex::covered`] are stored there in one file per chunk, read once a
+/// pixel of the chunk is asked for. Newer placements stay in memory until
+/// they're flushed, which the canvas does at every checkpoint.
+pub struct PixelIndex {
+    chunk_size: u16,
+    dir: Option<PathBuf>,
+    covered: u64,
+    /// The placements not stored yet.
+    recent: Placements,
+    /// The stored placements of the chunks read last, newest last.
+    cache: Mutex<VecDeque<(ChunkCoord, Arc<Placements>)>>,
+}
+
+impl PixelIndex {
+    /// An index that only lives in memory.
+    pub fn new(chunk_size: u16) -> Self {
+        Self {
+            chunk_size,
+            dir: None,
+            covered: 0,
+            recent: Placements::new(),
+            cache: Mutex::default(),
+        }
+    }
+
+    /// Opens the index stored in `dir`, or starts a new one there.
+    pub fn open(dir: impl AsRef<Path>, chunk_size: u16) -> IoResult<Self> {
+        let dir = dir
+            .as_ref()
+            .to_path_buf();
+
+        create_dir_all(&dir)?;
+
+        let covered = match File::open(dir.join(COVERED_FILE)) {
+            Ok(mut file) => file.read_u64()?,
+            Err(error) if error.kind() == IoErrorKind::NotFound => 0,
+            Err(error) => return Err(error),
+        };
+
+        Ok(Self {
+            dir: Some(dir),
+            covered,
+            ..Self::new(chunk_size)
+        })
+    }
+
+    /// Revision up to which every placement is stored on disk, `0` for an
+    /// index that only lives in memory.
+    #[inline]
+    pub fn covered(&self) -> u64 {
+        self.covered
+    }
+
+    /// Files a placement, ignoring ones not newer than the latest placement on
+    /// the same pixel or already stored so replaying a record twice is
+    /// harmless.
+    pub fn insert(&mut self, placement: &Placement) {
+        if placement.seq <= self.covered {
+            return;
+        }
+
+        let placements = self
+            .recent
+            .entry(placement.coord)
+            .or_default();
+
+        if placements
+            .last()
+            .is_some_and(|last| last.seq >= placement.seq)
+        {
+            return;
+        }
+
+        placements.push(IndexedPlacement {
+            seq: placement.seq,
+            color: placement.color,
+            placed_at: placement.placed_at,
+            author: placement.author,
+        });
+    }
+
+    /// The stored placements of `chunk`, from the cache when it was read
+    /// recently.
+    fn stored(&self, chunk: ChunkCoord) -> IoResult<Arc<Placements>> {
+        let Some(dir) = &self.dir else {
+            return Ok(Arc::default());
+        };
+
+        let mut cache = self
+            .cache
+            .lock();
+
+        if let Some(position) = cache
+            .iter()
+            .position(|(coord, _)| *coord == chunk)
+            && let Some(entry) = cache.remove(position)
+        {
+            let placements = entry
+                .1
+                .clone();
+
+            cache.push_back(entry);
+
+            return Ok(placements);
+        }
+
+        let placements = Arc::new(read_chunk(&chunk_path(dir, chunk), self.covered)?);
+
+        if cache.len() >= CACHED_CHUNKS {
+            cache.pop_front();
+        }
+
+        cache.push_back((chunk, placements.clone()));
+
+        Ok(placements)
+    }
+
+    /// Up to `limit`, but at least one, placements on `coord` older than
+    /// revision `before`, or the newest ones without it.
+    pub fn page(
+        &self,
+        coord: PixelCoord,
+        before: Option<u64>,
+        limit: usize,
+    ) -> IoResult<PixelHistoryPage> {
+        let stored = self.stored(coord.chunk(self.chunk_size))?;
+        let placements = stored
+            .get(&coord)
+            .into_iter()
+            .chain(
+                self.recent
+                    .get(&coord),
+            )
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+        let end = before.map_or(placements.len(), |before| {
+            placements.partition_point(|placement| placement.seq < before)
+        });
+        let start = end.saturating_sub(limit.max(1));
+
+        Ok(PixelHistoryPage {
+            placements: placements[start..end]
+                .iter()
+                .rev()
+                .copied()
+                .collect(),
+            next_before: (start > 0).then(|| placements[start].seq),
+        })
+    }
+
+    /// A copy of what the index holds now, to read the stored chunks
+    /// without holding the index.
+    pub fn snapshot(&self) -> IndexSnapshot {
+        IndexSnapshot {
+            chunk_size: self.chunk_size,
+            dir: self
+                .dir
+                .clone(),
+            covered: self.covered,
+            recent: self
+                .recent
+                .clone(),
+        }
+    }
+
+    /// Whether `coord` was painted after revision `seq`, which only reads its
+    /// stored chunk when placements after `seq` were stored already.
+    pub fn placed_since(&self, coord: PixelCoord, seq: u64) -> IoResult<bool> {
+        let newer = |placements: &Placements| {
+            placements
+                .get(&coord)
+                .and_then(|placements| placements.last())
+                .is_some_and(|last| last.seq > seq)
+        };
+
+        if newer(&self.recent) {
+            return Ok(true);
+        }
+
+        if self.covered <= seq {
+            return Ok(false);
+        }
+
+        Ok(newer(&*self.stored(coord.chunk(self.chunk_size))?))
+    }
+
+    /// The placements up to revision `seq` that aren't stored yet, `None` if
+    /// there is nothing to store or no directory to store it in.
+    pub fn unflushed(&self, seq: u64) -> Option<IndexBatch> {
+        let dir = self
+            .dir
+            .clone()?;
+
+        if seq <= self.covered {
+            return None;
+        }
+
+        let mut chunks = HashMap::<ChunkCoord, Vec<(PixelCoord, IndexedPlacement)>>::new();
+
+        for (&coord, placements) in &self.recent {
+            for placement in placements
+                .iter()
+                .take_while(|placement| placement.seq <= seq)
+            {
+                chunks
+                    .entry(coord.chunk(self.chunk_size))
+                    .or_default()
+                    .push((coord, *placement));
+            }
+        }
+
+        for placements in chunks.values_mut() {
+            placements.sort_unstable_by_key(|(_, placement)| placement.seq);
+        }
+
+        Some(IndexBatch { dir, seq, chunks })
+    }
+
+    /// Forgets the placements `batch` stored, they're read from disk from now
+    /// on.
+    pub fn flushed(&mut self, batch: &IndexBatch) {
+        if batch.seq <= self.covered {
+            return;
+        }
+
+        self.covered = batch.seq;
+        self.recent
+            .retain(|_, placements| {
+                placements.retain(|placement| placement.seq > batch.seq);
+
+                !placements.is_empty()
+            });
+        self.cache
+            .get_mut()
+            .retain(|(chunk, _)| {
+                !batch
+                    .chunks
+                    .contains_key(chunk)
+            });
+    }
+}
+
+/// What a [`PixelIndex`] held at some point, read without holding it.
+pub struct IndexSnapshot {
+    chunk_size: u16,
+    dir: Option<PathBuf>,
+    covered: u64,
+    recent: Placements,
+}
+
+impl IndexSnapshot {
+    /// The pixels whose newest placement `reverted` matches, each with the
+    /// color of its newest placement that doesn't, white if there is none.
+    ///
+    /// Pixels already showing that color are left out, the rest come in row
+    /// major order. Every stored chunk is read, one at a time, as far as it
+    /// was stored when the snapshot was taken.
+    pub fn restores(
+        &self,
+        reverted: impl Fn(PixelCoord, &IndexedPlacement) -> bool,
+    ) -> IoResult<Vec<(PixelCoord, PixelColor)>> {
+        let mut recent = HashMap::<ChunkCoord, Vec<PixelCoord>>::new();
+
+        for coord in self
+            .recent
+            .keys()
+        {
+            recent
+                .entry(coord.chunk(self.chunk_size))
+                .or_default()
+                .push(*coord);
+        }
+
+        let mut chunks = recent
+            .keys()
+            .copied()
+            .collect::<HashSet<_>>();
+
+        if let Some(dir) = &self.dir {
+            chunks.extend(list_chunks(dir)?);
+        }
+
+        let mut restores = Vec::new();
+
+        for chunk in chunks {
+            let mut placements = match &self.dir {
+                Some(dir) => read_chunk(&chunk_path(dir, chunk), self.covered)?,
+                None => Placements::new(),
+            };
+
+            for coord in recent
+                .get(&chunk)
+                .into_iter()
+                .flatten()
+            {
+                placements
+                    .entry(*coord)
+                    .or_default()
+                    .extend(&self.recent[coord]);
+            }
+
+            restores.extend(
+                placements
+                    .iter()
+                    .filter_map(|(&coord, placements)| {
+                        let current = placements.last()?;
+
+                        if !reverted(coord, current) {
+                            return None;
+                        }
+
+                        let color = placements
+                            .iter()
+                            .rev()
+                            .find(|placement| !reverted(coord, placement))
+                            .map_or(PixelColor::WHITE, |placement| placement.color);
+
+                        (color != current.color).then_some((coord, color))
+                    }),
+            );
+        }
+
+        restores.sort_unstable_by_key(|(coord, _)| (coord.y, coord.x));
+
+        Ok(restores)
+    }
+}
+
+/// Placements of a [`PixelIndex`] on their way to disk, written without
+/// holding the index.
+pub struct IndexBatch {
+    dir: PathBuf,
+    seq: u64,
+    chunks: HashMap<ChunkCoord, Vec<(PixelCoord, IndexedPlacement)>>,
+}
+
+impl IndexBatch {
+    /// Appends the placements to their chunk files, then marks every
+    /// placement up to the batch revision as stored.
+    ///
+    /// Placements appended before a crash but not marked are ignored when
+    /// read and appended again by the next batch.
+    pub fn write(&self) -> IoResult<()> {
+        for (chunk, placements) in &self.chunks {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .read(true)
+                .write(true)
+                .open(chunk_path(&self.dir, *chunk))?;
+            let len = file
+                .metadata()?
+                .len();
+            // a record torn by a crash while appending is cut off.
+            let valid_len = len - len % RECORD_LEN as u64;
+
+            if valid_len != len {
+                file.set_len(valid_len)?;
+            }
+
+            file.seek(SeekFrom::Start(valid_len))?;
+
+            let mut writer = BufWriter::new(&file);
+
+            for (coord, placement) in placements {
+                writer.write_all(&placement.encode(*coord))?;
+            }
+
+            writer.flush()?;
+            drop(writer);
+            file.sync_data()?;
+        }
+
+        let path = self
+            .dir
+            .join(COVERED_FILE);
+        let partial = path.with_extension("partial");
+        let mut file = File::create(&partial)?;
+
+        file.write_all(
+            &self
+                .seq
+                .to_le_bytes(),
+        )?;
+        file.sync_all()?;
+        rename(&partial, &path)?;
+        File::open(&self.dir)?.sync_all()?;
+
+        Ok(())
+    }
+}
+
+fn chunk_path(dir: &Path, chunk: ChunkCoord) -> PathBuf {
+    dir.join(format!("{}_{}.{INDEX_EXTENSION}", chunk.x, chunk.y))
+}
+
+/// The chunks with a file in `dir`.
+fn list_chunks(dir: &Path) -> IoResult<Vec<ChunkCoord>> {
+    let mut chunks = Vec::new();
+
+    for entry in read_dir(dir)? {
+        let path = entry?.path();
+
+        if path
+            .extension()
+            .is_none_or(|extension| extension != INDEX_EXTENSION)
+        {
+            continue;
+        }
+
+        if let Some((x, y)) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.split_once('_'))
+            && let (Ok(x), Ok(y)) = (x.parse(), y.parse())
+        {
+            chunks.push(ChunkCoord::new(x, y));
+        }
+    }
+
+    Ok(chunks)
+}
+
+/// The placements stored in a chunk file up to revision `covered`.
+///
+/// Placements not newer than the one before on the same pixel were appended
+/// again after a crash and are skipped.
+fn read_chunk(path: &Path, covered: u64) -> IoResult<Placements> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(error) if error.kind() == IoErrorKind::NotFound => return Ok(Placements::new()),
+        Err(error) => return Err(error),
+    };
+    let mut reader = BufReader::new(file);
+    let mut record = [0; RECORD_LEN];
+    let mut placements = Placements::new();
+
+    loop {
+        match reader.read_exact(&mut record) {
+            Ok(()) => {},
+            Err(error) if error.kind() == IoErrorKind::UnexpectedEof => break,
+            Err(error) => return Err(error),
+        }
+
+        let (coord, placement) = IndexedPlacement::decode(&record)?;
+
+        if placement.seq > covered {
+            continue;
+        }
+
+        let history = placements
+            .entry(coord)
+            .or_default();
+
+        if history
+            .last()
+            .is_none_or(|last| last.seq < placement.seq)
+        {
+            history.push(placement);
+        }
+    }
+
+    Ok(placements)
+}
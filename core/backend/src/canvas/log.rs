@@ -0,0 +1,573 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions, create_dir_all, read_dir, remove_file, rename};
+use std::io::{
+    BufRead,
+    BufReader,
+    Error as IoError,
+    ErrorKind as IoErrorKind,
+    Result as IoResult,
+    Seek,
+    SeekFrom,
+    Write,
+};
+use std::path::{Path, PathBuf};
+
+use crc32fast::hash as crc32;
+use shared::canvas::{BoundsChange, CanvasBounds, PixelColor, PixelCoord, Placement};
+
+use crate::utils::binary::ReadBinary;
+
+const SEGMENT_EXTENSION: &str = "log";
+const MAX_RECORD_LEN: u32 = 64 * 1024;
+
+const KIND_PLACEMENT: u8 = 0;
+const KIND_BOUNDS: u8 = 1;
+
+/// An entry of the placement log.
+///
+/// Every record is framed as `[payload length: u32][crc32 of payload:
+/// u32][payload]`, the payload starts with a kind byte followed by the little
+/// endian fields of that kind. Placements end with a byte telling whether an
+/// author follows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LogRecord {
+    Placement(Placement),
+    /// The canvas grew, takes a revision like a placement.
+    Bounds(BoundsChange),
+}
+
+impl LogRecord {
+    /// The canvas revision this record produced.
+    pub fn seq(&self) -> u64 {
+        match self {
+            Self::Placement(placement) => placement.seq,
+            Self::Bounds(change) => change.seq,
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(32);
+
+        match self {
+            Self::Placement(placement) => {
+                payload.push(KIND_PLACEMENT);
+                payload.extend_from_slice(
+                    &placement
+                        .seq
+                        .to_le_bytes(),
+                );
+                payload.extend_from_slice(
+                    &placement
+                        .coord
+                        .x
+                        .to_le_bytes(),
+                );
+                payload.extend_from_slice(
+                    &placement
+                        .coord
+                        .y
+                        .to_le_bytes(),
+                );
+                payload.extend_from_slice(
+                    &placement
+                        .color
+                        .to_array(),
+                );
+                payload.extend_from_slice(
+                    &placement
+                        .placed_at
+                        .to_le_bytes(),
+                );
+
+                match placement.author {
+                    Some(author) => {
+                        payload.push(1);
+                        payload.extend_from_slice(&author.to_le_bytes());
+                    },
+                    None => payload.push(0),
+                }
+            },
+            Self::Bounds(change) => {
+                let CanvasBounds { x, y, width, height } = change.bounds;
+
+                payload.push(KIND_BOUNDS);
+                payload.extend_from_slice(
+                    &change
+                        .seq
+                        .to_le_bytes(),
+                );
+                payload.extend_from_slice(&x.to_le_bytes());
+                payload.extend_from_slice(&y.to_le_bytes());
+                payload.extend_from_slice(&width.to_le_bytes());
+                payload.extend_from_slice(&height.to_le_bytes());
+                payload.extend_from_slice(
+                    &change
+                        .changed_at
+                        .to_le_bytes(),
+                );
+            },
+        }
+
+        // records are a few dozen bytes, far from `u32::MAX`.
+        #[allow(clippy::cast_possible_truncation)]
+        let mut frame = (payload.len() as u32)
+            .to_le_bytes()
+            .to_vec();
+        frame.extend_from_slice(&crc32(&payload).to_le_bytes());
+        frame.extend_from_slice(&payload);
+
+        frame
+    }
+
+    fn decode(mut payload: &[u8]) -> IoResult<Self> {
+        match payload.read_u8()? {
+            KIND_PLACEMENT => {
+                let seq = payload.read_u64()?;
+                let coord = PixelCoord::new(payload.read_i32()?, payload.read_i32()?);
+                let color =
+                    PixelColor::new(payload.read_u8()?, payload.read_u8()?, payload.read_u8()?);
+                let placed_at = payload.read_u64()?;
+                let author = match payload.read_u8()? {
+                    0 => None,
+                    _ => Some(payload.read_u64()?),
+                };
+
+                Ok(Self::Placement(Placement { seq, coord, color, placed_at, author }))
+            },
+            KIND_BOUNDS => {
+                let seq = payload.read_u64()?;
+                let bounds = CanvasBounds::new(
+                    payload.read_i32()?,
+                    payload.read_i32()?,
+                    payload.read_u32()?,
+                    payload.read_u32()?,
+                );
+                let changed_at = payload.read_u64()?;
+
+                Ok(Self::Bounds(BoundsChange { seq, bounds, changed_at }))
+            },
+
+            kind => Err(IoError::new(
+                IoErrorKind::InvalidData,
+                format!("Unknown log record kind {kind}."),
+            )),
+        }
+    }
+
+    /// Reads the next record, `None` on a clean end of file.
+    ///
+    /// A record cut short by the end of file, or failing its checksum with
+    /// nothing after it, was torn while being written and is reported as
+    /// `UnexpectedEof`. Any other record that fails to decode is reported as
+    /// `InvalidData`.
+    fn read(reader: &mut impl BufRead) -> IoResult<Option<(Self, u64)>> {
+        let mut header = [0; 8];
+        let mut filled = 0;
+
+        while filled < header.len() {
+            match reader.read(&mut header[filled..])? {
+                0 if filled == 0 => return Ok(None),
+                0 => return Err(IoError::new(IoErrorKind::UnexpectedEof, "Torn record header.")),
+                read => filled += read,
+            }
+        }
+
+        let mut header = header.as_slice();
+        let len = header.read_u32()?;
+        let checksum = header.read_u32()?;
+
+        if len > MAX_RECORD_LEN {
+            return Err(IoError::new(IoErrorKind::InvalidData, "Record length out of range."));
+        }
+
+        let mut payload = vec![0; len as usize];
+        reader
+            .read_exact(&mut payload)
+            .map_err(|_| IoError::new(IoErrorKind::UnexpectedEof, "Torn record payload."))?;
+
+        if crc32(&payload) != checksum {
+            // the length made it to disk before the payload did.
+            let kind = if reader
+                .fill_buf()?
+                .is_empty()
+            {
+                IoErrorKind::UnexpectedEof
+            } else {
+                IoErrorKind::InvalidData
+            };
+
+            return Err(IoError::new(kind, "Record checksum mismatch."));
+        }
+
+        Ok(Some((Self::decode(&payload)?, 8 + u64::from(len))))
+    }
+}
+
+/// Append only, segmented log of every change made to the canvas.
+///
+/// Segments are named after the first revision they may contain,
+/// records in them are strictly ordered by revision.
+pub struct PlacementLog {
+    dir: PathBuf,
+    segment: File,
+    last_seq: u64,
+}
+
+impl PlacementLog {
+    /// Opens or creates the log in `dir`.
+    ///
+    /// A record torn by a crash at the end of the newest segment is cut off,
+    /// anything else that fails to decode is reported as an error.
+    pub fn open(dir: impl AsRef<Path>) -> IoResult<Self> {
+        let dir = dir
+            .as_ref()
+            .to_path_buf();
+
+        create_dir_all(&dir)?;
+
+        let segments = list_segments(&dir)?;
+        let Some((first_seq, path)) = segments.last() else {
+            let segment = create_segment(&dir, 1)?;
+            return Ok(Self { dir, segment, last_seq: 0 });
+        };
+
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut valid_len = 0;
+        let mut last_seq = first_seq.saturating_sub(1);
+
+        loop {
+            match LogRecord::read(&mut reader) {
+                Ok(Some((record, len))) => {
+                    valid_len += len;
+                    last_seq = record.seq();
+                },
+                Ok(None) => break,
+                Err(error) if error.kind() == IoErrorKind::UnexpectedEof => break,
+                Err(error) => return Err(error),
+            }
+        }
+
+        let mut segment = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)?;
+
+        if segment
+            .metadata()?
+            .len()
+            != valid_len
+        {
+            segment.set_len(valid_len)?;
+            segment.sync_all()?;
+        }
+
+        segment.seek(SeekFrom::End(0))?;
+
+        Ok(Self { dir, segment, last_seq })
+    }
+
+    #[inline]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The oldest revision still available in the log.
+    pub fn first_seq(&self) -> IoResult<u64> {
+        Ok(list_segments(&self.dir)?
+            .first()
+            .map_or(self.last_seq + 1, |(first_seq, _)| *first_seq))
+    }
+
+    /// Revision of the newest record, `0` for an empty log.
+    #[inline]
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq
+    }
+
+    /// Appends a record, it's handed to the OS right away but only
+    /// guaranteed to be on disk after [`PlacementLog::sync`].
+    ///
+    /// A write that fails partway is cut off again, so no torn record is
+    /// left in the middle of the segment.
+    pub fn append(&mut self, record: &LogRecord) -> IoResult<()> {
+        if record.seq() <= self.last_seq {
+            return Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                format!("Record {} is older than the log head {}.", record.seq(), self.last_seq),
+            ));
+        }
+
+        let len = self
+            .segment
+            .metadata()?
+            .len();
+
+        if let Err(error) = self
+            .segment
+            .write_all(&record.encode())
+        {
+            self.segment
+                .set_len(len)?;
+            self.segment
+                .seek(SeekFrom::Start(len))?;
+
+            return Err(error);
+        }
+
+        self.last_seq = record.seq();
+
+        Ok(())
+    }
+
+    pub fn sync(&self) -> IoResult<()> {
+        self.segment
+            .sync_data()
+    }
+
+    /// A handle on the segment being written and the revision of its newest
+    /// record, to sync the log up to there without holding it.
+    ///
+    /// Records before the segment were synced when it was started.
+    pub fn sync_point(&self) -> IoResult<(File, u64)> {
+        Ok((
+            self.segment
+                .try_clone()?,
+            self.last_seq,
+        ))
+    }
+
+    /// Seals the current segment and continues in a new one,
+    /// does nothing if the current segment is still empty.
+    pub fn rotate(&mut self) -> IoResult<()> {
+        if self
+            .segment
+            .metadata()?
+            .len()
+            == 0
+        {
+            return Ok(());
+        }
+
+        self.segment
+            .sync_all()?;
+        self.segment = create_segment(&self.dir, self.last_seq + 1)?;
+
+        Ok(())
+    }
+
+    /// Removes every sealed segment whose records are all at or before
+    /// `covered_seq`, moving them into `archive` instead when given.
+    /// Returns how many segments were removed.
+    pub fn compact(&self, covered_seq: u64, archive: Option<&Path>) -> IoResult<usize> {
+        let segments = list_segments(&self.dir)?;
+        let mut removed = 0;
+
+        // a segment ends right before the next one starts, the last one is never
+        // sealed.
+        for window in segments.windows(2) {
+            let [(_, path), (next_first_seq, _)] = window else {
+                continue;
+            };
+
+            if *next_first_seq > covered_seq + 1 {
+                break;
+            }
+
+            match archive {
+                Some(archive) => {
+                    create_dir_all(archive)?;
+                    rename(
+                        path,
+                        archive.join(
+                            path.file_name()
+                                .unwrap_or_default(),
+                        ),
+                    )?;
+                },
+                None => remove_file(path)?,
+            }
+
+            removed += 1;
+        }
+
+        if removed > 0 {
+            File::open(&self.dir)?.sync_all()?;
+        }
+
+        Ok(removed)
+    }
+
+    /// Every record newer than `after_seq`, oldest first.
+    pub fn records(&self, after_seq: u64) -> IoResult<LogReader> {
+        LogReader::new(&self.dir, after_seq)
+    }
+}
+
+/// Sequential reader over every segment of a log directory.
+pub struct LogReader {
+    dirs: Vec<PathBuf>,
+    segments: VecDeque<OsString>,
+    current: Option<BufReader<File>>,
+    after_seq: u64,
+}
+
+impl LogReader {
+    pub fn new(dir: impl AsRef<Path>, after_seq: u64) -> IoResult<Self> {
+        Self::across(
+            vec![
+                dir.as_ref()
+                    .to_path_buf(),
+            ],
+            after_seq,
+        )
+    }
+
+    /// Reads the segments spread over `dirs`, where segments only ever move
+    /// from one directory to a later one, like a log and its archive.
+    ///
+    /// Directories after the first may not exist yet.
+    pub fn across(dirs: Vec<PathBuf>, after_seq: u64) -> IoResult<Self> {
+        let mut segments = BTreeMap::new();
+
+        // listed in move order so a segment moved while listing is seen at least once.
+        for (index, dir) in dirs
+            .iter()
+            .enumerate()
+        {
+            match list_segments(dir) {
+                Ok(listed) => {
+                    for (first_seq, path) in listed {
+                        segments
+                            .entry(first_seq)
+                            .or_insert_with(|| {
+                                path.file_name()
+                                    .unwrap_or_default()
+                                    .to_os_string()
+                            });
+                    }
+                },
+                Err(error) if index > 0 && error.kind() == IoErrorKind::NotFound => {},
+                Err(error) => return Err(error),
+            }
+        }
+
+        // the last segment starting at or before the wanted revision holds it.
+        let skip = segments
+            .keys()
+            .rposition(|first_seq| *first_seq <= after_seq + 1)
+            .unwrap_or(0);
+
+        Ok(Self {
+            dirs,
+            segments: segments
+                .into_values()
+                .skip(skip)
+                .collect(),
+            current: None,
+            after_seq,
+        })
+    }
+
+    fn open_segment(&self, name: &OsString) -> IoResult<File> {
+        let mut result = Err(IoError::from(IoErrorKind::NotFound));
+
+        for dir in &self.dirs {
+            result = File::open(dir.join(name));
+
+            if !matches!(&result, Err(error) if error.kind() == IoErrorKind::NotFound) {
+                break;
+            }
+        }
+
+        result
+    }
+}
+
+impl Iterator for LogReader {
+    type Item = IoResult<LogRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self
+                .current
+                .is_none()
+            {
+                let name = self
+                    .segments
+                    .pop_front()?;
+
+                match self.open_segment(&name) {
+                    Ok(file) => self.current = Some(BufReader::new(file)),
+                    Err(error) => return Some(Err(error)),
+                }
+            }
+
+            let reader = self
+                .current
+                .as_mut()?;
+
+            match LogRecord::read(reader) {
+                Ok(Some((record, _))) if record.seq() <= self.after_seq => {},
+                Ok(Some((record, _))) => return Some(Ok(record)),
+                Ok(None) => self.current = None,
+                // a record still being written at the head of the log.
+                Err(error)
+                    if error.kind() == IoErrorKind::UnexpectedEof
+                        && self
+                            .segments
+                            .is_empty() =>
+                {
+                    self.current = None;
+                },
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+}
+
+fn segment_path(dir: &Path, first_seq: u64) -> PathBuf {
+    dir.join(format!("{first_seq:020}.{SEGMENT_EXTENSION}"))
+}
+
+fn create_segment(dir: &Path, first_seq: u64) -> IoResult<File> {
+    let segment = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(segment_path(dir, first_seq))?;
+
+    File::open(dir)?.sync_all()?;
+
+    Ok(segment)
+}
+
+/// Segments in `dir` sorted by the first revision they hold.
+pub(crate) fn list_segments(dir: &Path) -> IoResult<Vec<(u64, PathBuf)>> {
+    let mut segments = Vec::new();
+
+    for entry in read_dir(dir)? {
+        let path = entry?.path();
+
+        if path
+            .extension()
+            .is_none_or(|extension| extension != SEGMENT_EXTENSION)
+        {
+            continue;
+        }
+
+        if let Some(first_seq) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| {
+                stem.parse::<u64>()
+                    .ok()
+            })
+        {
+            segments.push((first_seq, path));
+        }
+    }
+
+    segments.sort_unstable();
+
+    Ok(segments)
+}
@@ -0,0 +1,744 @@
+use std::collections::HashMap;
+use std::io::Error as IoError;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use parking_lot::{Mutex, RwLock};
+use shared::api::{ApiErrorBody, ApiErrorCode};
+use shared::canvas::{BoundsChange, CanvasBounds, ChunkCoord, PixelColor, PixelCoord, Placement};
+use shared::encoding::ChunkPayload;
+use thiserror::Error;
+
+use crate::canvas::checkpoint::{Checkpoint, SnapshotPolicy};
+use crate::canvas::chunk::Chunk;
+use crate::canvas::history::{IndexedPlacement, PixelHistoryPage, PixelIndex};
+use crate::canvas::log::{LogReader, LogRecord, PlacementLog};
+use crate::canvas::snapshot::{Snapshot, prune};
+use crate::utils::api_error::ApiError;
+
+pub mod checkpoint;
+pub mod chunk;
+pub mod export;
+pub mod history;
+pub mod log;
+pub mod snapshot;
+
+const LOG_DIR: &str = "log";
+const ARCHIVE_DIR: &str = "archive";
+const SNAPSHOT_DIR: &str = "snapshots";
+const INDEX_DIR: &str = "index";
+
+#[derive(Error, Debug)]
+pub enum CanvasError {
+    #[error("The pixel ({}, {}) is outside of the canvas.", .0.x, .0.y)]
+    OutOfBounds(PixelCoord),
+
+    #[error("The chunk ({}, {}) is outside of the canvas.", .0.x, .0.y)]
+    ChunkOutOfBounds(ChunkCoord),
+
+    #[error("The canvas storage failed: {0}")]
+    Storage(#[from] IoError),
+
+    #[error("The stored canvas uses {found}px chunks but {expected}px chunks were configured.")]
+    ChunkSizeMismatch { expected: u16, found: u16 },
+
+    #[error("The placement log starts at revision {available} but revision {needed} is needed.")]
+    MissingLog { needed: u64, available: u64 },
+
+    #[error("The canvas can only grow, {requested:?} doesn't contain {current:?}.")]
+    Shrink {
+        current: CanvasBounds,
+        requested: CanvasBounds,
+    },
+
+    #[error("The canvas is closed while the server shuts down.")]
+    Closed,
+}
+
+impl ResponseError for CanvasError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::OutOfBounds(_) => StatusCode::BAD_REQUEST,
+            Self::ChunkOutOfBounds(_) => StatusCode::NOT_FOUND,
+            Self::Closed => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Storage(_)
+            | Self::ChunkSizeMismatch { .. }
+            | Self::MissingLog { .. }
+            | Self::Shrink { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let body = match self {
+            Self::OutOfBounds(_) => ApiErrorBody::new(ApiErrorCode::OutOfBounds, self.to_string()),
+            Self::ChunkOutOfBounds(_) => {
+                ApiErrorBody::new(ApiErrorCode::NotFound, self.to_string())
+            },
+            Self::Closed => ApiErrorBody::new(ApiErrorCode::Unavailable, self.to_string()),
+            Self::Storage(_)
+            | Self::ChunkSizeMismatch { .. }
+            | Self::MissingLog { .. }
+            | Self::Shrink { .. } => {
+                ApiErrorBody::new(ApiErrorCode::Internal, "The canvas couldn't be updated.")
+            },
+        };
+
+        ApiError::new(self.status_code(), body).error_response()
+    }
+}
+
+struct CanvasState {
+    bounds: CanvasBounds,
+    chunks: HashMap<ChunkCoord, Chunk>,
+    revision: u64,
+    index: PixelIndex,
+    /// Set once the server shuts down, changes are refused from then on.
+    closed: bool,
+}
+
+impl CanvasState {
+    fn new(bounds: CanvasBounds, index: PixelIndex) -> Self {
+        Self {
+            bounds,
+            chunks: HashMap::new(),
+            revision: 0,
+            index,
+            closed: false,
+        }
+    }
+
+    fn apply(&mut self, chunk_size: u16, record: &LogRecord) {
+        match record {
+            LogRecord::Placement(placement) => {
+                let (x, y) = placement
+                    .coord
+                    .offset_in_chunk(chunk_size);
+
+                self.chunks
+                    .entry(
+                        placement
+                            .coord
+                            .chunk(chunk_size),
+                    )
+                    .or_insert_with(|| Chunk::new(chunk_size))
+                    .set(x, y, placement.color, placement.seq);
+                self.index
+                    .insert(placement);
+            },
+            LogRecord::Bounds(change) => self.bounds = change.bounds,
+        }
+
+        self.revision = record.seq();
+    }
+}
+
+/// Shared state of the canvas.
+///
+/// Chunks live in memory, when opened from a directory every change
+/// is appended to a [`PlacementLog`] before it's applied and only returned
+/// once the log is synced to disk.
+pub struct CanvasStore {
+    chunk_size: u16,
+    state: RwLock<CanvasState>,
+    log: Option<Mutex<PlacementLog>>,
+    /// Newest revision known to be on disk, held while syncing so the changes
+    /// made meanwhile share the next sync.
+    synced_seq: Mutex<u64>,
+    dir: Option<PathBuf>,
+    checkpoint_lock: Mutex<()>,
+}
+
+impl CanvasStore {
+    /// A canvas that only lives in memory.
+    pub fn new(chunk_size: u16, bounds: CanvasBounds) -> Self {
+        Self {
+            chunk_size,
+            state: RwLock::new(CanvasState::new(bounds, PixelIndex::new(chunk_size))),
+            log: None,
+            synced_seq: Mutex::new(0),
+            dir: None,
+            checkpoint_lock: Mutex::new(()),
+        }
+    }
+
+    /// Loads the canvas stored in `dir`, or starts a new one there with
+    /// `initial_bounds`.
+    ///
+    /// The newest readable snapshot is loaded and every log record after it is
+    /// replayed. Placements the snapshot covers are only read from the log and
+    /// its archive when the [`PixelIndex`] doesn't store them yet, everything
+    /// replayed is stored right away.
+    pub fn open(
+        dir: impl AsRef<Path>,
+        chunk_size: u16,
+        initial_bounds: CanvasBounds,
+    ) -> Result<Self, CanvasError> {
+        let dir = dir
+            .as_ref()
+            .to_path_buf();
+        let log = PlacementLog::open(dir.join(LOG_DIR))?;
+        let index = PixelIndex::open(dir.join(INDEX_DIR), chunk_size)?;
+
+        let mut state = CanvasState::new(initial_bounds, index);
+
+        if let Some(snapshot) = Snapshot::latest(dir.join(SNAPSHOT_DIR))? {
+            if snapshot.chunk_size != chunk_size {
+                return Err(CanvasError::ChunkSizeMismatch {
+                    expected: chunk_size,
+                    found: snapshot.chunk_size,
+                });
+            }
+
+            state.bounds = snapshot.bounds;
+            state.revision = snapshot.revision;
+            state.chunks = snapshot
+                .chunks
+                .into_iter()
+                .collect();
+        }
+
+        let available = log.first_seq()?;
+
+        if available > state.revision + 1 {
+            return Err(CanvasError::MissingLog { needed: state.revision + 1, available });
+        }
+
+        let after_seq = state
+            .index
+            .covered()
+            .min(state.revision);
+
+        for record in LogReader::across(vec![dir.join(LOG_DIR), dir.join(ARCHIVE_DIR)], after_seq)?
+        {
+            match record? {
+                record if record.seq() > state.revision => state.apply(chunk_size, &record),
+                LogRecord::Placement(placement) => state
+                    .index
+                    .insert(&placement),
+                LogRecord::Bounds(_) => {},
+            }
+        }
+
+        if let Some(batch) = state
+            .index
+            .unflushed(state.revision)
+        {
+            batch.write()?;
+            state
+                .index
+                .flushed(&batch);
+        }
+
+        Ok(Self {
+            chunk_size,
+            state: RwLock::new(state),
+            synced_seq: Mutex::new(log.last_seq()),
+            log: Some(Mutex::new(log)),
+            dir: Some(dir),
+            checkpoint_lock: Mutex::new(()),
+        })
+    }
+
+    #[inline]
+    pub fn chunk_size(&self) -> u16 {
+        self.chunk_size
+    }
+
+    pub fn bounds(&self) -> CanvasBounds {
+        self.state
+            .read()
+            .bounds
+    }
+
+    /// Revision of the last accepted change, `0` for an untouched canvas.
+    pub fn revision(&self) -> u64 {
+        self.state
+            .read()
+            .revision
+    }
+
+    /// The color of a pixel, `None` if it's outside of the canvas.
+    pub fn pixel(&self, coord: PixelCoord) -> Option<PixelColor> {
+        if !self
+            .bounds()
+            .contains(coord)
+        {
+            return None;
+        }
+
+        let (x, y) = coord.offset_in_chunk(self.chunk_size);
+
+        Some(
+            self.state
+                .read()
+                .chunks
+                .get(&coord.chunk(self.chunk_size))
+                .map_or(PixelColor::WHITE, |chunk| chunk.get(x, y)),
+        )
+    }
+
+    /// The revision and row major RGB bytes of every pixel in `region`, which
+    /// must lie inside the canvas bounds.
+    pub fn region(&self, region: CanvasBounds) -> (u64, Vec<u8>) {
+        debug_assert!(
+            self.bounds()
+                .contains_bounds(&region)
+        );
+
+        let state = self
+            .state
+            .read();
+        let blank = Chunk::new(self.chunk_size);
+        let (end_x, end_y) = (
+            i64::from(region.x) + i64::from(region.width),
+            i64::from(region.y) + i64::from(region.height),
+        );
+        let mut pixels = Vec::with_capacity(usize::try_from(region.area() * 3).unwrap_or(0));
+
+        for y in i64::from(region.y)..end_y {
+            let mut x = i64::from(region.x);
+
+            while x < end_x {
+                // the region is inside the canvas so every coordinate fits an `i32`.
+                let coord = PixelCoord::new(x as i32, y as i32);
+                let (offset_x, offset_y) = coord.offset_in_chunk(self.chunk_size);
+                let run = (end_x - x).min(i64::from(self.chunk_size - offset_x)) as usize;
+                let start = (usize::from(offset_y) * usize::from(self.chunk_size)
+                    + usize::from(offset_x))
+                    * 3;
+                let chunk = state
+                    .chunks
+                    .get(&coord.chunk(self.chunk_size))
+                    .unwrap_or(&blank);
+
+                pixels.extend_from_slice(&chunk.cells()[start..start + run * 3]);
+                x += run as i64;
+            }
+        }
+
+        (state.revision, pixels)
+    }
+
+    /// Every record newer than `after_seq` including the archived ones, `None`
+    /// for a canvas that only lives in memory.
+    ///
+    /// Records compacted without an archive are missing.
+    pub fn history(&self, after_seq: u64) -> Result<Option<LogReader>, CanvasError> {
+        let Some(dir) = &self.dir else {
+            return Ok(None);
+        };
+
+        Ok(Some(LogReader::across(vec![dir.join(LOG_DIR), dir.join(ARCHIVE_DIR)], after_seq)?))
+    }
+
+    /// The placements on one pixel, newest first.
+    ///
+    /// Reads the stored placements of the pixel's chunk unless they were read
+    /// recently.
+    pub fn pixel_history(
+        &self,
+        coord: PixelCoord,
+        before: Option<u64>,
+        limit: usize,
+    ) -> Result<PixelHistoryPage, CanvasError> {
+        let state = self
+            .state
+            .read();
+
+        if !state
+            .bounds
+            .contains(coord)
+        {
+            return Err(CanvasError::OutOfBounds(coord));
+        }
+
+        Ok(state
+            .index
+            .page(coord, before, limit)?)
+    }
+
+    /// A copy of a chunk, `None` if nothing was ever drawn on it.
+    pub fn chunk(&self, coord: ChunkCoord) -> Option<Chunk> {
+        self.state
+            .read()
+            .chunks
+            .get(&coord)
+            .cloned()
+    }
+
+    /// The wire payload of a chunk inside the canvas bounds.
+    ///
+    /// With `since` only the cells changed after that revision are sent,
+    /// unless the full chunk would be smaller. Chunks nobody drew on yet are
+    /// white with version `0`.
+    pub fn chunk_payload(
+        &self,
+        coord: ChunkCoord,
+        since: Option<u64>,
+    ) -> Result<ChunkPayload, CanvasError> {
+        let bounds = self.bounds();
+
+        if !coord
+            .bounds(self.chunk_size)
+            .is_some_and(|chunk| bounds.intersects(&chunk))
+        {
+            return Err(CanvasError::ChunkOutOfBounds(coord));
+        }
+
+        let state = self
+            .state
+            .read();
+        let blank;
+        let chunk = match state
+            .chunks
+            .get(&coord)
+        {
+            Some(chunk) => chunk,
+            None => {
+                blank = Chunk::new(self.chunk_size);
+                &blank
+            },
+        };
+
+        if let Some(since) = since {
+            let changes = chunk.changes_since(since);
+
+            if changes.len() * 7
+                < chunk
+                    .cells()
+                    .len()
+            {
+                return Ok(ChunkPayload::Delta {
+                    size: self.chunk_size,
+                    version: chunk.version(),
+                    changes,
+                });
+            }
+        }
+
+        Ok(ChunkPayload::Full {
+            size: self.chunk_size,
+            version: chunk.version(),
+            cells: chunk
+                .cells()
+                .to_vec(),
+        })
+    }
+
+    /// Validates, logs and applies a placement by `author`, `placed_at` is in
+    /// unix milliseconds.
+    ///
+    /// Blocks until the placement is on disk.
+    pub fn place(
+        &self,
+        coord: PixelCoord,
+        color: PixelColor,
+        author: Option<u64>,
+        placed_at: u64,
+    ) -> Result<Placement, CanvasError> {
+        let placement = {
+            let mut state = self
+                .state
+                .write();
+
+            if state.closed {
+                return Err(CanvasError::Closed);
+            }
+
+            if !state
+                .bounds
+                .contains(coord)
+            {
+                return Err(CanvasError::OutOfBounds(coord));
+            }
+
+            let placement = Placement {
+                seq: state.revision + 1,
+                coord,
+                color,
+                placed_at,
+                author,
+            };
+            let record = LogRecord::Placement(placement.clone());
+
+            if let Some(log) = &self.log {
+                log.lock()
+                    .append(&record)?;
+            }
+
+            state.apply(self.chunk_size, &record);
+
+            placement
+        };
+
+        self.commit(placement.seq)?;
+
+        Ok(placement)
+    }
+
+    /// Waits until the log is on disk up to `seq`, the changes logged while
+    /// another sync runs are all covered by the next one.
+    fn commit(&self, seq: u64) -> Result<(), CanvasError> {
+        let Some(log) = &self.log else {
+            return Ok(());
+        };
+
+        let mut synced_seq = self
+            .synced_seq
+            .lock();
+
+        if *synced_seq >= seq {
+            return Ok(());
+        }
+
+        let (segment, last_seq) = log
+            .lock()
+            .sync_point()?;
+
+        segment.sync_data()?;
+        *synced_seq = last_seq;
+
+        Ok(())
+    }
+
+    /// Reverts every pixel whose newest placement `reverted` matches to the
+    /// color it had before, with new placements by `author` so the history
+    /// stays intact.
+    ///
+    /// Pixels painted over since are left alone. A pixel whose earlier
+    /// placements were compacted before the [`PixelIndex`] stored them turns
+    /// white. Reads the whole stored index without holding the canvas and
+    /// blocks until the placements are on disk.
+    pub fn rollback(
+        &self,
+        reverted: impl Fn(PixelCoord, &IndexedPlacement) -> bool,
+        author: Option<u64>,
+        placed_at: u64,
+    ) -> Result<Vec<Placement>, CanvasError> {
+        let (snapshot, revision) = {
+            let state = self
+                .state
+                .read();
+
+            if state.closed {
+                return Err(CanvasError::Closed);
+            }
+
+            (
+                state
+                    .index
+                    .snapshot(),
+                state.revision,
+            )
+        };
+        let restores = snapshot.restores(reverted)?;
+        let mut placements = Vec::new();
+
+        {
+            let mut state = self
+                .state
+                .write();
+
+            if state.closed {
+                return Err(CanvasError::Closed);
+            }
+
+            for (coord, color) in restores {
+                // pixels painted while the index was read are left alone.
+                if state
+                    .index
+                    .placed_since(coord, revision)?
+                {
+                    continue;
+                }
+
+                let placement = Placement {
+                    seq: state.revision + 1,
+                    coord,
+                    color,
+                    placed_at,
+                    author,
+                };
+                let record = LogRecord::Placement(placement.clone());
+
+                if let Some(log) = &self.log {
+                    log.lock()
+                        .append(&record)?;
+                }
+
+                state.apply(self.chunk_size, &record);
+                placements.push(placement);
+            }
+        }
+
+        if let Some(last) = placements.last() {
+            self.commit(last.seq)?;
+        }
+
+        Ok(placements)
+    }
+
+    /// Logs and applies new bounds, which must contain the current ones.
+    ///
+    /// Returns `None` without taking a revision when the bounds don't change,
+    /// blocks until the change is on disk otherwise.
+    pub fn grow(
+        &self,
+        bounds: CanvasBounds,
+        changed_at: u64,
+    ) -> Result<Option<BoundsChange>, CanvasError> {
+        let change = {
+            let mut state = self
+                .state
+                .write();
+
+            if state.closed {
+                return Err(CanvasError::Closed);
+            }
+
+            if bounds == state.bounds {
+                return Ok(None);
+            }
+
+            if !bounds.contains_bounds(&state.bounds) {
+                return Err(CanvasError::Shrink { current: state.bounds, requested: bounds });
+            }
+
+            let change = BoundsChange {
+                seq: state.revision + 1,
+                bounds,
+                changed_at,
+            };
+            let record = LogRecord::Bounds(change);
+
+            if let Some(log) = &self.log {
+                log.lock()
+                    .append(&record)?;
+            }
+
+            state.apply(self.chunk_size, &record);
+
+            change
+        };
+
+        self.commit(change.seq)?;
+
+        Ok(Some(change))
+    }
+
+    /// Refuses every change from now on, the ones already being written
+    /// finish first so a snapshot taken afterwards covers all of them.
+    pub fn close(&self) {
+        self.state
+            .write()
+            .closed = true;
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state
+            .read()
+            .closed
+    }
+
+    /// A point in time copy of the whole canvas.
+    pub fn snapshot(&self) -> Snapshot {
+        let state = self
+            .state
+            .read();
+        let mut chunks = state
+            .chunks
+            .iter()
+            .map(|(coord, chunk)| (*coord, chunk.clone()))
+            .collect::<Vec<_>>();
+
+        chunks.sort_unstable_by_key(|(coord, _)| *coord);
+
+        Snapshot {
+            chunk_size: self.chunk_size,
+            bounds: state.bounds,
+            revision: state.revision,
+            chunks,
+        }
+    }
+
+    /// Flushes the log and stores a snapshot of the canvas next to it,
+    /// returns the snapshot path or `None` for a canvas that only lives in
+    /// memory.
+    pub fn write_snapshot(&self) -> Result<Option<PathBuf>, CanvasError> {
+        let (Some(dir), Some(log)) = (&self.dir, &self.log) else {
+            return Ok(None);
+        };
+
+        let _checkpoint = self
+            .checkpoint_lock
+            .lock();
+        let snapshot = self.snapshot();
+
+        log.lock()
+            .sync()?;
+
+        Ok(Some(snapshot.write(dir.join(SNAPSHOT_DIR))?))
+    }
+
+    /// Writes a snapshot, starts a new log segment, stores the [`PixelIndex`]
+    /// up to the snapshot, prunes old snapshots and compacts the log segments
+    /// every kept snapshot already covers.
+    ///
+    /// Returns `None` for a canvas that only lives in memory.
+    pub fn checkpoint(&self, policy: &SnapshotPolicy) -> Result<Option<Checkpoint>, CanvasError> {
+        let (Some(dir), Some(log)) = (&self.dir, &self.log) else {
+            return Ok(None);
+        };
+
+        let _checkpoint = self
+            .checkpoint_lock
+            .lock();
+        let start = Instant::now();
+        let snapshot = self.snapshot();
+
+        {
+            let mut log = log.lock();
+
+            log.sync()?;
+            log.rotate()?;
+        }
+
+        let path = snapshot.write(dir.join(SNAPSHOT_DIR))?;
+        // the index has to store what the log compaction below may remove.
+        let batch = self
+            .state
+            .read()
+            .index
+            .unflushed(snapshot.revision);
+
+        if let Some(batch) = batch {
+            batch.write()?;
+            self.state
+                .write()
+                .index
+                .flushed(&batch);
+        }
+
+        let compacted_segments = match prune(dir.join(SNAPSHOT_DIR), policy.retain)? {
+            Some(oldest_revision) => log
+                .lock()
+                .compact(
+                    oldest_revision,
+                    policy
+                        .archive_log
+                        .then(|| dir.join(ARCHIVE_DIR))
+                        .as_deref(),
+                )?,
+            None => 0,
+        };
+
+        Ok(Some(Checkpoint {
+            snapshot: path,
+            revision: snapshot.revision,
+            compacted_segments,
+            duration: start.elapsed(),
+        }))
+    }
+}
@@ -0,0 +1,249 @@
+use std::fs::{File, create_dir_all, read_dir, remove_file, rename};
+use std::io::{
+    BufReader,
+    BufWriter,
+    Error as IoError,
+    ErrorKind as IoErrorKind,
+    Read,
+    Result as IoResult,
+    Write,
+};
+use std::path::{Path, PathBuf};
+
+use shared::canvas::{CanvasBounds, ChunkCoord};
+
+use crate::canvas::chunk::Chunk;
+use crate::utils::binary::ReadBinary;
+
+const SNAPSHOT_EXTENSION: &str = "snap";
+const MAGIC: &[u8; 8] = b"CDOTSNAP";
+const FORMAT_VERSION: u16 = 1;
+
+/// A point in time copy of every chunk of the canvas.
+///
+/// On disk it's a header with the chunk size, bounds, covered revision and
+/// chunk count, followed by every chunk as its coordinates, cells and the
+/// revision of every cell.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    pub chunk_size: u16,
+    pub bounds: CanvasBounds,
+    /// Every log record up to and including this revision is part of the
+    /// snapshot.
+    pub revision: u64,
+    pub chunks: Vec<(ChunkCoord, Chunk)>,
+}
+
+impl Snapshot {
+    /// Atomically writes the snapshot into `dir`, returning the path of the new
+    /// file.
+    pub fn write(&self, dir: impl AsRef<Path>) -> IoResult<PathBuf> {
+        let dir = dir.as_ref();
+        create_dir_all(dir)?;
+
+        let path = snapshot_path(dir, self.revision);
+        let partial = path.with_extension("partial");
+        let mut writer = BufWriter::new(File::create(&partial)?);
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        writer.write_all(
+            &self
+                .chunk_size
+                .to_le_bytes(),
+        )?;
+        writer.write_all(
+            &self
+                .bounds
+                .x
+                .to_le_bytes(),
+        )?;
+        writer.write_all(
+            &self
+                .bounds
+                .y
+                .to_le_bytes(),
+        )?;
+        writer.write_all(
+            &self
+                .bounds
+                .width
+                .to_le_bytes(),
+        )?;
+        writer.write_all(
+            &self
+                .bounds
+                .height
+                .to_le_bytes(),
+        )?;
+        writer.write_all(
+            &self
+                .revision
+                .to_le_bytes(),
+        )?;
+        writer.write_all(
+            &u64::try_from(
+                self.chunks
+                    .len(),
+            )
+            .unwrap_or(u64::MAX)
+            .to_le_bytes(),
+        )?;
+
+        for (coord, chunk) in &self.chunks {
+            writer.write_all(
+                &coord
+                    .x
+                    .to_le_bytes(),
+            )?;
+            writer.write_all(
+                &coord
+                    .y
+                    .to_le_bytes(),
+            )?;
+            writer.write_all(chunk.cells())?;
+
+            for version in chunk.cell_versions() {
+                writer.write_all(&version.to_le_bytes())?;
+            }
+        }
+
+        writer
+            .into_inner()
+            .map_err(|error| error.into_error())?
+            .sync_all()?;
+
+        rename(&partial, &path)?;
+        File::open(dir)?.sync_all()?;
+
+        Ok(path)
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> IoResult<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+
+        if &magic != MAGIC {
+            return Err(IoError::new(IoErrorKind::InvalidData, "Not a canvas snapshot."));
+        }
+
+        let version = reader.read_u16()?;
+
+        if version != FORMAT_VERSION {
+            return Err(IoError::new(
+                IoErrorKind::InvalidData,
+                format!("Unsupported snapshot format version {version}."),
+            ));
+        }
+
+        let chunk_size = reader.read_u16()?;
+        let bounds = CanvasBounds::new(
+            reader.read_i32()?,
+            reader.read_i32()?,
+            reader.read_u32()?,
+            reader.read_u32()?,
+        );
+        let revision = reader.read_u64()?;
+        let count = reader.read_u64()?;
+        let side = usize::from(chunk_size);
+        let mut chunks = Vec::new();
+
+        for _ in 0..count {
+            let coord = ChunkCoord::new(reader.read_i32()?, reader.read_i32()?);
+            let mut cells = vec![0; side * side * 3];
+            reader.read_exact(&mut cells)?;
+
+            let cell_versions = (0..side * side)
+                .map(|_| reader.read_u64())
+                .collect::<IoResult<_>>()?;
+
+            chunks.push((coord, Chunk::from_parts(chunk_size, cells, cell_versions)));
+        }
+
+        let mut trailing = [0; 1];
+
+        if reader.read(&mut trailing)? != 0 {
+            return Err(IoError::new(IoErrorKind::InvalidData, "Trailing snapshot data."));
+        }
+
+        Ok(Self { chunk_size, bounds, revision, chunks })
+    }
+
+    /// The newest snapshot in `dir` that can be read, if any.
+    pub fn latest(dir: impl AsRef<Path>) -> IoResult<Option<Self>> {
+        for (_, path) in list_snapshots(dir.as_ref())?
+            .into_iter()
+            .rev()
+        {
+            match Self::read(&path) {
+                Ok(snapshot) => return Ok(Some(snapshot)),
+                Err(error)
+                    if matches!(
+                        error.kind(),
+                        IoErrorKind::InvalidData | IoErrorKind::UnexpectedEof
+                    ) => {},
+                Err(error) => return Err(error),
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Deletes all but the `retain` newest snapshots in `dir`,
+/// returns the revision of the oldest snapshot left.
+pub fn prune(dir: impl AsRef<Path>, retain: usize) -> IoResult<Option<u64>> {
+    let snapshots = list_snapshots(dir.as_ref())?;
+    let excess = snapshots
+        .len()
+        .saturating_sub(retain.max(1));
+
+    for (_, path) in &snapshots[..excess] {
+        remove_file(path)?;
+    }
+
+    Ok(snapshots
+        .get(excess)
+        .map(|(revision, _)| *revision))
+}
+
+fn snapshot_path(dir: &Path, revision: u64) -> PathBuf {
+    dir.join(format!("{revision:020}.{SNAPSHOT_EXTENSION}"))
+}
+
+/// Snapshots in `dir` sorted by the revision they cover.
+pub(crate) fn list_snapshots(dir: &Path) -> IoResult<Vec<(u64, PathBuf)>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut snapshots = Vec::new();
+
+    for entry in read_dir(dir)? {
+        let path = entry?.path();
+
+        if path
+            .extension()
+            .is_none_or(|extension| extension != SNAPSHOT_EXTENSION)
+        {
+            continue;
+        }
+
+        if let Some(revision) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| {
+                stem.parse::<u64>()
+                    .ok()
+            })
+        {
+            snapshots.push((revision, path));
+        }
+    }
+
+    snapshots.sort_unstable();
+
+    Ok(snapshots)
+}
@@ -0,0 +1,664 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs::read_to_string;
+use std::io::{Error as IoError, ErrorKind};
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::de::{Error as DeError, Unexpected};
+use serde::{Deserialize, Deserializer};
+use shared::canvas::{CanvasBounds, DEFAULT_CHUNK_SIZE};
+use thiserror::Error;
+use toml::{Table, Value};
+
+use crate::accounts::AccountsConfig;
+use crate::accounts::oidc::OidcConfig;
+use crate::canvas::checkpoint::SnapshotPolicy;
+use crate::cooldown::CooldownPolicy;
+use crate::funding::GrowthPolicy;
+use crate::payouts::PayoutPolicy;
+use crate::realtime::RealtimeConfig;
+
+/// Read when neither `--config` nor `CANVADOT_CONFIG` point elsewhere, the
+/// defaults are used when it doesn't exist.
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+const CONFIG_ENV: &str = "CANVADOT_CONFIG";
+const CONFIG_FLAG: &str = "--config";
+
+/// How the text of a variable or flag becomes a value of the file.
+#[derive(Clone, Copy)]
+enum Kind {
+    Text,
+    Number,
+    Boolean,
+    /// Separated by commas.
+    List,
+}
+
+impl Kind {
+    fn value(self, text: &str) -> Option<Value> {
+        let text = text.trim();
+
+        match self {
+            Self::Text => Some(Value::String(text.to_owned())),
+            Self::Number => text
+                .parse()
+                .ok()
+                .map(Value::Integer),
+            Self::Boolean => text
+                .parse()
+                .ok()
+                .map(Value::Boolean),
+            Self::List => Some(Value::Array(
+                text.split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(|item| Value::String(item.to_owned()))
+                    .collect(),
+            )),
+        }
+    }
+
+    fn expected(self) -> &'static str {
+        match self {
+            Self::Text | Self::List => "text",
+            Self::Number => "a number",
+            Self::Boolean => "true or false",
+        }
+    }
+}
+
+/// A setting by its key in the file, its environment variable and its flag.
+struct Setting {
+    key: &'static str,
+    env: &'static str,
+    flag: &'static str,
+    kind: Kind,
+}
+
+impl Setting {
+    const fn new(key: &'static str, env: &'static str, flag: &'static str, kind: Kind) -> Self {
+        Self { key, env, flag, kind }
+    }
+}
+
+const SETTINGS: [Setting; 28] = [
+    Setting::new("server.bind", "CANVADOT_BIND", "--bind", Kind::Text),
+    Setting::new("server.workers", "CANVADOT_WORKERS", "--workers", Kind::Number),
+    Setting::new(
+        "server.trusted_proxies",
+        "CANVADOT_TRUSTED_PROXIES",
+        "--trusted-proxies",
+        Kind::List,
+    ),
+    Setting::new("storage.data_dir", "CANVADOT_DATA_DIR", "--data-dir", Kind::Text),
+    Setting::new("canvas.chunk_size", "CANVADOT_CHUNK_SIZE", "--chunk-size", Kind::Number),
+    Setting::new(
+        "cooldown.interval_ms",
+        "CANVADOT_COOLDOWN_INTERVAL_MS",
+        "--cooldown-interval-ms",
+        Kind::Number,
+    ),
+    Setting::new("cooldown.burst", "CANVADOT_COOLDOWN_BURST", "--cooldown-burst", Kind::Number),
+    Setting::new(
+        "snapshots.interval_secs",
+        "CANVADOT_SNAPSHOT_INTERVAL_SECS",
+        "--snapshot-interval-secs",
+        Kind::Number,
+    ),
+    Setting::new("snapshots.retain", "CANVADOT_SNAPSHOT_RETAIN", "--snapshot-retain", Kind::Number),
+    Setting::new(
+        "snapshots.archive_log",
+        "CANVADOT_SNAPSHOT_ARCHIVE_LOG",
+        "--snapshot-archive-log",
+        Kind::Boolean,
+    ),
+    Setting::new(
+        "growth.base_chunks",
+        "CANVADOT_GROWTH_BASE_CHUNKS",
+        "--growth-base-chunks",
+        Kind::Number,
+    ),
+    Setting::new(
+        "growth.ring_chunks",
+        "CANVADOT_GROWTH_RING_CHUNKS",
+        "--growth-ring-chunks",
+        Kind::Number,
+    ),
+    Setting::new(
+        "growth.tier_cents",
+        "CANVADOT_GROWTH_TIER_CENTS",
+        "--growth-tier-cents",
+        Kind::Number,
+    ),
+    Setting::new(
+        "growth.max_tiers",
+        "CANVADOT_GROWTH_MAX_TIERS",
+        "--growth-max-tiers",
+        Kind::Number,
+    ),
+    Setting::new(
+        "realtime.heartbeat_interval_ms",
+        "CANVADOT_REALTIME_HEARTBEAT_INTERVAL_MS",
+        "--realtime-heartbeat-interval-ms",
+        Kind::Number,
+    ),
+    Setting::new(
+        "realtime.client_timeout_ms",
+        "CANVADOT_REALTIME_CLIENT_TIMEOUT_MS",
+        "--realtime-client-timeout-ms",
+        Kind::Number,
+    ),
+    Setting::new(
+        "realtime.send_buffer",
+        "CANVADOT_REALTIME_SEND_BUFFER",
+        "--realtime-send-buffer",
+        Kind::Number,
+    ),
+    Setting::new(
+        "realtime.max_subscriptions",
+        "CANVADOT_REALTIME_MAX_SUBSCRIPTIONS",
+        "--realtime-max-subscriptions",
+        Kind::Number,
+    ),
+    Setting::new(
+        "payouts.winner_percent",
+        "CANVADOT_PAYOUT_WINNER_PERCENT",
+        "--payout-winner-percent",
+        Kind::Number,
+    ),
+    Setting::new(
+        "payouts.hold_secs",
+        "CANVADOT_PAYOUT_HOLD_SECS",
+        "--payout-hold-secs",
+        Kind::Number,
+    ),
+    Setting::new("accounts.admins", "CANVADOT_ADMINS", "--admins", Kind::List),
+    Setting::new("accounts.moderators", "CANVADOT_MODERATORS", "--moderators", Kind::List),
+    Setting::new("oidc.issuer", "CANVADOT_OIDC_ISSUER", "--oidc-issuer", Kind::Text),
+    Setting::new("oidc.client_id", "CANVADOT_OIDC_CLIENT_ID", "--oidc-client-id", Kind::Text),
+    Setting::new(
+        "oidc.client_secret",
+        "CANVADOT_OIDC_CLIENT_SECRET",
+        "--oidc-client-secret",
+        Kind::Text,
+    ),
+    Setting::new(
+        "oidc.redirect_uri",
+        "CANVADOT_OIDC_REDIRECT_URI",
+        "--oidc-redirect-uri",
+        Kind::Text,
+    ),
+    Setting::new(
+        "payments.fake_secret",
+        "CANVADOT_FAKE_PAYMENTS_SECRET",
+        "--fake-payments-secret",
+        Kind::Text,
+    ),
+    Setting::new(
+        "payments.dev_mode",
+        "CANVADOT_FAKE_PAYMENTS_DEV_MODE",
+        "--fake-payments-dev-mode",
+        Kind::Boolean,
+    ),
+];
+
+/// Where a setting overriding the file came from, to point at it when it's
+/// invalid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    Env(&'static str),
+    Flag(&'static str),
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Env(name) => write!(f, "{name}"),
+            Self::Flag(flag) => write!(f, "{flag}"),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("The config file {} couldn't be read: {error}", .path.display())]
+    Read { path: PathBuf, error: IoError },
+
+    #[error("The config file {} is invalid: {message}", .path.display())]
+    File { path: PathBuf, message: String },
+
+    #[error("Unknown argument {0}.")]
+    UnknownArgument(String),
+
+    #[error("The flag {0} needs a value.")]
+    MissingArgument(String),
+
+    #[error("{origin} is invalid: {message}")]
+    InvalidValue { origin: Origin, message: String },
+
+    #[error("The config is invalid: {0}")]
+    Invalid(String),
+}
+
+/// Everything the server is started with.
+///
+/// Loaded from a TOML file, then overridden by `CANVADOT_*` environment
+/// variables and then by command line flags. Every table is optional, the
+/// `oidc` and `payments` ones enable their feature.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub storage: StorageConfig,
+    pub canvas: CanvasConfig,
+    pub cooldown: CooldownPolicy,
+    pub snapshots: SnapshotPolicy,
+    pub growth: GrowthConfig,
+    pub realtime: RealtimeConfig,
+    pub payouts: PayoutPolicy,
+    pub accounts: RolesConfig,
+    pub oidc: Option<OidcConfig>,
+    pub payments: Option<PaymentsConfig>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: SocketAddr,
+    /// Worker threads, one per core without it.
+    pub workers: Option<NonZeroUsize>,
+    /// Reverse proxies trusted to forward the address of clients.
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([0, 0, 0, 0], 8081)),
+            workers: None,
+            trusted_proxies: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// Where every store keeps its files.
+    pub data_dir: PathBuf,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self { data_dir: PathBuf::from("data") }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct CanvasConfig {
+    #[serde(deserialize_with = "nonzero")]
+    pub chunk_size: u16,
+}
+
+impl Default for CanvasConfig {
+    fn default() -> Self {
+        Self { chunk_size: DEFAULT_CHUNK_SIZE }
+    }
+}
+
+/// The [`GrowthPolicy`] in chunks, as the chunk size is a setting of its
+/// own.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct GrowthConfig {
+    /// Chunks on every side of the canvas before any tier is unlocked.
+    #[serde(deserialize_with = "nonzero")]
+    pub base_chunks: u32,
+    /// Chunks added on every side per tier.
+    pub ring_chunks: u32,
+    pub tier_cents: u64,
+    pub max_tiers: Option<u32>,
+}
+
+impl GrowthConfig {
+    /// Fails when the base canvas is too large to measure in pixels.
+    pub fn policy(&self, chunk_size: u16) -> Result<GrowthPolicy, ConfigError> {
+        let base = CanvasBounds::from_chunks(self.base_chunks, self.base_chunks, chunk_size)
+            .ok_or_else(|| {
+                ConfigError::Invalid(format!(
+                    "growth.base_chunks of {} chunks with {chunk_size} pixels each is too large.",
+                    self.base_chunks
+                ))
+            })?;
+
+        Ok(GrowthPolicy {
+            base,
+            tier_cents: self.tier_cents,
+            ring: self
+                .ring_chunks
+                .saturating_mul(u32::from(chunk_size)),
+            max_tiers: self.max_tiers,
+        })
+    }
+}
+
+impl Default for GrowthConfig {
+    fn default() -> Self {
+        let policy = GrowthPolicy::default();
+
+        Self {
+            base_chunks: 4,
+            ring_chunks: 1,
+            tier_cents: policy.tier_cents,
+            max_tiers: policy.max_tiers,
+        }
+    }
+}
+
+/// Who runs the canvas, the rest of the [`AccountsConfig`] isn't configurable.
+///
+/// Every username has to be registered already, see [`AccountsConfig::admins`].
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RolesConfig {
+    /// Usernames allowed to run events.
+    pub admins: Vec<String>,
+    /// Usernames allowed to moderate the canvas, admins always are.
+    pub moderators: Vec<String>,
+}
+
+impl RolesConfig {
+    pub fn accounts(&self) -> AccountsConfig {
+        AccountsConfig {
+            admins: self
+                .admins
+                .clone(),
+            moderators: self
+                .moderators
+                .clone(),
+            ..AccountsConfig::default()
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct PaymentsConfig {
+    /// Secret of the fake payment provider.
+    pub fake_secret: String,
+    /// Has to be `true`, the fake provider moves no real money so it's only
+    /// run for development when asked for explicitly.
+    #[serde(deserialize_with = "confirmed")]
+    pub dev_mode: bool,
+}
+
+/// A switch that has to be turned on, like the dev mode of fake payments.
+fn confirmed<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    if !bool::deserialize(deserializer)? {
+        return Err(D::Error::invalid_value(Unexpected::Bool(false), &"true"));
+    }
+
+    Ok(true)
+}
+
+/// A number that can't be zero, like a chunk size.
+pub(crate) fn nonzero<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default + PartialEq,
+{
+    let value = T::deserialize(deserializer)?;
+
+    if value == T::default() {
+        return Err(D::Error::invalid_value(Unexpected::Unsigned(0), &"a number above zero"));
+    }
+
+    Ok(value)
+}
+
+/// A share of something, at most 100.
+pub(crate) fn percent<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    let value = u8::deserialize(deserializer)?;
+
+    if value > 100 {
+        return Err(D::Error::invalid_value(
+            Unexpected::Unsigned(u64::from(value)),
+            &"a percentage up to 100",
+        ));
+    }
+
+    Ok(value)
+}
+
+/// A duration given in milliseconds.
+pub(crate) fn millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    u64::deserialize(deserializer).map(Duration::from_millis)
+}
+
+/// A duration given in milliseconds that can't be zero, like an interval.
+pub(crate) fn nonzero_millis<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Duration, D::Error> {
+    nonzero::<D, u64>(deserializer).map(Duration::from_millis)
+}
+
+/// A duration given in seconds.
+pub(crate) fn seconds<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    u64::deserialize(deserializer).map(Duration::from_secs)
+}
+
+/// A duration given in seconds that can't be zero, like an interval.
+pub(crate) fn nonzero_seconds<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Duration, D::Error> {
+    nonzero::<D, u64>(deserializer).map(Duration::from_secs)
+}
+
+/// The config file as a table, an empty one when the file is optional and
+/// missing.
+fn read_file(path: PathBuf, required: bool) -> Result<Table, ConfigError> {
+    let text = match read_to_string(&path) {
+        Ok(text) => text,
+        Err(error) if error.kind() == ErrorKind::NotFound && !required => {
+            return Ok(Table::new());
+        },
+        Err(error) => return Err(ConfigError::Read { path, error }),
+    };
+
+    // parsed as a whole first so errors point at their line.
+    if let Err(error) = toml::from_str::<Config>(&text) {
+        return Err(ConfigError::File {
+            path,
+            message: error
+                .to_string()
+                .trim()
+                .to_owned(),
+        });
+    }
+
+    text.parse()
+        .map_err(|error: toml::de::Error| ConfigError::File {
+            path,
+            message: error
+                .message()
+                .to_owned(),
+        })
+}
+
+/// Sets the dotted `key` in `table`, creating the tables on the way.
+fn insert(table: &mut Table, key: &str, value: Value) {
+    let (tables, name) = key
+        .rsplit_once('.')
+        .unwrap_or(("", key));
+    let mut table = table;
+
+    for part in tables
+        .split('.')
+        .filter(|part| !part.is_empty())
+    {
+        let entry = table
+            .entry(part)
+            .or_insert_with(|| Value::Table(Table::new()));
+
+        if !entry.is_table() {
+            *entry = Value::Table(Table::new());
+        }
+
+        let Value::Table(inner) = entry else {
+            unreachable!("The entry was just made a table.");
+        };
+
+        table = inner;
+    }
+
+    table.insert(name.to_owned(), value);
+}
+
+/// Splits `--flag value` and `--flag=value` arguments into pairs.
+fn flag_pairs(
+    args: impl IntoIterator<Item = String>,
+) -> Result<Vec<(String, String)>, ConfigError> {
+    let mut args = args.into_iter();
+    let mut pairs = Vec::new();
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            return Err(ConfigError::UnknownArgument(arg));
+        }
+
+        let pair = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_owned(), value.to_owned()),
+            None => match args.next() {
+                Some(value) => (arg, value),
+                None => return Err(ConfigError::MissingArgument(arg)),
+            },
+        };
+
+        pairs.push(pair);
+    }
+
+    Ok(pairs)
+}
+
+impl Config {
+    /// Loads the config from the file given by `--config` or `CANVADOT_CONFIG`,
+    /// or from [`DEFAULT_CONFIG_PATH`] when it exists, then applies the
+    /// variables `env` knows about and then the flags in `args`.
+    pub fn load(
+        args: impl IntoIterator<Item = String>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let (paths, flags): (Vec<_>, Vec<_>) = flag_pairs(args)?
+            .into_iter()
+            .partition(|(flag, _)| flag == CONFIG_FLAG);
+
+        let file = match paths
+            .into_iter()
+            .last()
+            .map(|(_, path)| path)
+            .or_else(|| env(CONFIG_ENV))
+        {
+            Some(path) => read_file(PathBuf::from(path), true)?,
+            None => read_file(PathBuf::from(DEFAULT_CONFIG_PATH), false)?,
+        };
+
+        let mut overrides = Vec::new();
+
+        for setting in &SETTINGS {
+            if let Some(text) = env(setting.env) {
+                overrides.push((setting, text, Origin::Env(setting.env)));
+            }
+        }
+
+        for (flag, text) in flags {
+            let Some(setting) = SETTINGS
+                .iter()
+                .find(|setting| setting.flag == flag)
+            else {
+                return Err(ConfigError::UnknownArgument(flag));
+            };
+
+            overrides.push((setting, text, Origin::Flag(setting.flag)));
+        }
+
+        let overrides = overrides
+            .into_iter()
+            .map(|(setting, text, origin)| {
+                match setting
+                    .kind
+                    .value(&text)
+                {
+                    Some(value) => Ok((setting.key, value, origin)),
+                    None => Err(ConfigError::InvalidValue {
+                        origin,
+                        message: format!(
+                            "expected {}",
+                            setting
+                                .kind
+                                .expected()
+                        ),
+                    }),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // later overrides replace earlier ones, `skip` leaves one out.
+        let layered = |skip: Option<usize>| {
+            let mut table = file.clone();
+
+            for (index, (key, value, _)) in overrides
+                .iter()
+                .enumerate()
+            {
+                if skip != Some(index) {
+                    insert(&mut table, key, value.clone());
+                }
+            }
+
+            Self::deserialize(table)
+        };
+
+        let config = layered(None).map_err(|error| {
+            let message = error
+                .message()
+                .to_owned();
+
+            // the file is valid on its own, so the override it's valid without is to
+            // blame.
+            match (0..overrides.len())
+                .rev()
+                .find(|&index| layered(Some(index)).is_ok())
+            {
+                Some(index) => ConfigError::InvalidValue {
+                    origin: overrides[index]
+                        .2
+                        .clone(),
+                    message,
+                },
+                None => ConfigError::Invalid(
+                    error
+                        .to_string()
+                        .trim()
+                        .replace('\n', " "),
+                ),
+            }
+        })?;
+
+        // the growth settings only fit together with the chunk size.
+        config
+            .growth
+            .policy(
+                config
+                    .canvas
+                    .chunk_size,
+            )?;
+
+        Ok(config)
+    }
+}
@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::http::header::{HeaderValue, RETRY_AFTER};
+use actix_web::{HttpResponse, ResponseError};
+use parking_lot::Mutex;
+use serde::Deserialize;
+use shared::api::{ApiErrorBody, ApiErrorCode, Cooldown};
+use thiserror::Error;
+
+use crate::config::{millis, nonzero};
+use crate::utils::api_error::ApiError;
+use crate::utils::client::client_network;
+
+/// How often placements may be made by the same user or address.
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct CooldownPolicy {
+    /// Time it takes to earn one placement back.
+    #[serde(rename = "interval_ms", deserialize_with = "millis")]
+    pub interval: Duration,
+    /// Placements that can be made back to back after waiting long enough.
+    #[serde(deserialize_with = "nonzero")]
+    pub burst: u32,
+}
+
+impl Default for CooldownPolicy {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            burst: 3,
+        }
+    }
+}
+
+/// Who a cooldown applies to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CooldownKey {
+    User(u64),
+    /// Anonymous callers, IPv6 addresses share a cooldown per `/64` network as
+    /// that's what a single client usually gets.
+    Ip(IpAddr),
+}
+
+impl CooldownKey {
+    pub fn ip(address: IpAddr) -> Self {
+        Self::Ip(client_network(address))
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum CooldownError {
+    #[error("You can place your next pixel in {} seconds.", .wait_ms.div_ceil(1000))]
+    Active { retry_at: u64, wait_ms: u64 },
+
+    #[error("The address of the caller is unknown.")]
+    NoAddress,
+}
+
+impl ResponseError for CooldownError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Active { .. } => StatusCode::TOO_MANY_REQUESTS,
+            Self::NoAddress => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            Self::Active { retry_at, wait_ms } => {
+                let mut response = ApiError::new(
+                    self.status_code(),
+                    ApiErrorBody::new(ApiErrorCode::RateLimited, self.to_string())
+                        .with_retry_at(*retry_at),
+                )
+                .error_response();
+
+                response
+                    .headers_mut()
+                    .insert(RETRY_AFTER, HeaderValue::from(wait_ms.div_ceil(1000)));
+
+                response
+            },
+            Self::NoAddress => ApiError::new(
+                self.status_code(),
+                ApiErrorBody::new(ApiErrorCode::InvalidRequest, self.to_string()),
+            )
+            .error_response(),
+        }
+    }
+}
+
+struct CooldownState {
+    /// Per key the time at which its bucket is full again.
+    full_at: HashMap<CooldownKey, u64>,
+    next_prune: u64,
+}
+
+/// Placement cooldowns of every user and address.
+///
+/// A bucket of `burst` placements per key that refills one placement per
+/// `interval`, only the time at which it's full again is stored.
+pub struct Cooldowns {
+    policy: CooldownPolicy,
+    state: Mutex<CooldownState>,
+}
+
+impl Cooldowns {
+    pub fn new(policy: CooldownPolicy) -> Self {
+        Self {
+            policy,
+            state: Mutex::new(CooldownState { full_at: HashMap::new(), next_prune: 0 }),
+        }
+    }
+
+    #[inline]
+    pub fn policy(&self) -> &CooldownPolicy {
+        &self.policy
+    }
+
+    fn interval_ms(&self) -> u64 {
+        u64::try_from(
+            self.policy
+                .interval
+                .as_millis(),
+        )
+        .unwrap_or(u64::MAX)
+    }
+
+    fn window_ms(&self) -> u64 {
+        self.interval_ms()
+            .saturating_mul(u64::from(
+                self.policy
+                    .burst
+                    .max(1),
+            ))
+    }
+
+    /// The cooldown at `now` of a bucket that's full again at `full_at`, which
+    /// isn't before `now`.
+    fn cooldown(&self, full_at: u64, now: u64) -> Cooldown {
+        let interval = self
+            .interval_ms()
+            .max(1);
+        let missing = full_at - now;
+
+        Cooldown {
+            // bounded by `burst`.
+            remaining: u32::try_from(
+                self.window_ms()
+                    .saturating_sub(missing)
+                    / interval,
+            )
+            .unwrap_or(u32::MAX),
+            next_placement_at: now
+                + missing.saturating_sub(
+                    self.window_ms()
+                        .saturating_sub(interval),
+                ),
+        }
+    }
+
+    /// The cooldown of `key` without taking a placement.
+    pub fn status(&self, key: CooldownKey, now: u64) -> Cooldown {
+        let full_at = self
+            .state
+            .lock()
+            .full_at
+            .get(&key)
+            .copied()
+            .unwrap_or(0);
+
+        self.cooldown(full_at.max(now), now)
+    }
+
+    /// Takes a placement from the bucket of `key` at `now` unix milliseconds.
+    pub fn acquire(&self, key: CooldownKey, now: u64) -> Result<Cooldown, CooldownError> {
+        let mut state = self
+            .state
+            .lock();
+
+        if now >= state.next_prune {
+            state
+                .full_at
+                .retain(|_, full_at| *full_at > now);
+            state.next_prune = now.saturating_add(self.window_ms());
+        }
+
+        let full_at = state
+            .full_at
+            .get(&key)
+            .copied()
+            .unwrap_or(0)
+            .max(now);
+        let full_at = full_at.saturating_add(self.interval_ms());
+
+        // taking one more would need more than the whole burst.
+        if full_at.saturating_sub(now) > self.window_ms() {
+            let retry_at = full_at.saturating_sub(self.window_ms());
+
+            return Err(CooldownError::Active { retry_at, wait_ms: retry_at - now });
+        }
+
+        state
+            .full_at
+            .insert(key, full_at);
+
+        Ok(self.cooldown(full_at, now))
+    }
+
+    /// Gives back a placement [`Cooldowns::acquire`] took, for placements
+    /// that failed after it.
+    pub fn release(&self, key: CooldownKey) {
+        let interval = self.interval_ms();
+
+        if let Some(full_at) = self
+            .state
+            .lock()
+            .full_at
+            .get_mut(&key)
+        {
+            *full_at = full_at.saturating_sub(interval);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use shared::api::{EventResults, EventStanding, SubmissionInfo, TieBreak};
+use shared::canvas::CanvasBounds;
+
+use crate::events::Event;
+
+/// A participant's entry, the part of the event's region they claim.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Submission {
+    pub artist: u64,
+    pub region: CanvasBounds,
+    /// Unix milliseconds.
+    pub submitted_at: u64,
+    /// Position among the submissions of the event, the last resort when
+    /// everything else is tied.
+    pub order: u64,
+}
+
+impl Submission {
+    pub fn info(&self) -> SubmissionInfo {
+        SubmissionInfo {
+            artist: self.artist,
+            region: self.region,
+            submitted_at: self.submitted_at,
+        }
+    }
+}
+
+/// How many of `votes` went to each artist.
+fn tally(votes: &BTreeMap<u64, u64>) -> BTreeMap<u64, u64> {
+    let mut tally = BTreeMap::new();
+
+    for &artist in votes.values() {
+        *tally
+            .entry(artist)
+            .or_default() += 1;
+    }
+
+    tally
+}
+
+impl Event {
+    /// Ranks the submissions by their votes.
+    ///
+    /// Ties are broken by the event's [`TieBreak`]s in order and finally by
+    /// the order of submission, so the same votes always give the same
+    /// results. A winner picked by the jury takes first place regardless.
+    pub fn results(&self) -> EventResults {
+        let votes = tally(&self.votes);
+        let jury_votes = tally(&self.jury_votes);
+        let mut ranked = self
+            .submissions
+            .values()
+            .map(|submission| {
+                let count = |tally: &BTreeMap<u64, u64>| {
+                    tally
+                        .get(&submission.artist)
+                        .copied()
+                        .unwrap_or_default()
+                };
+
+                (
+                    submission.order,
+                    EventStanding {
+                        submission: submission.info(),
+                        votes: count(&votes),
+                        jury_votes: count(&jury_votes),
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+
+        ranked.sort_by(|(order, standing), (other_order, other)| {
+            other
+                .votes
+                .cmp(&standing.votes)
+                .then_with(|| {
+                    self.tie_breaks
+                        .iter()
+                        .fold(Ordering::Equal, |ordering, tie_break| {
+                            ordering.then_with(|| match tie_break {
+                                TieBreak::EarliestSubmission => standing
+                                    .submission
+                                    .submitted_at
+                                    .cmp(
+                                        &other
+                                            .submission
+                                            .submitted_at,
+                                    ),
+                                TieBreak::JuryVote => other
+                                    .jury_votes
+                                    .cmp(&standing.jury_votes),
+                            })
+                        })
+                })
+                .then_with(|| order.cmp(other_order))
+        });
+
+        let standings = ranked
+            .into_iter()
+            .map(|(_, standing)| standing)
+            .collect::<Vec<_>>();
+        let overridden = self
+            .jury_winner
+            .is_some_and(|artist| {
+                self.submissions
+                    .contains_key(&artist)
+            });
+
+        EventResults {
+            event: self.id,
+            winner: if overridden {
+                self.jury_winner
+            } else {
+                standings
+                    .first()
+                    .map(|standing| {
+                        standing
+                            .submission
+                            .artist
+                    })
+            },
+            overridden,
+            standings,
+        }
+    }
+}
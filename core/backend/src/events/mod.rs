@@ -0,0 +1,971 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Error as IoError;
+use std::path::Path;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use shared::api::{
+    ApiErrorBody,
+    ApiErrorCode,
+    EventInfo,
+    EventRequest,
+    EventResults,
+    EventState,
+    PaymentPurpose,
+    PaymentStatus,
+    SubmissionInfo,
+    TieBreak,
+    default_tie_breaks,
+};
+use shared::canvas::{CanvasBounds, PixelCoord};
+use thiserror::Error;
+
+use crate::events::judging::Submission;
+use crate::payments::Payment;
+use crate::utils::api_error::ApiError;
+use crate::utils::journal::Journal;
+
+pub mod judging;
+pub mod scheduler;
+
+const JOURNAL_FILE: &str = "events.jsonl";
+/// Longest event title in characters.
+pub const MAX_TITLE_LENGTH: usize = 80;
+
+#[derive(Error, Debug)]
+pub enum EventError {
+    #[error("The event {0} doesn't exist.")]
+    NotFound(u64),
+
+    #[error("Event titles are 1 to {MAX_TITLE_LENGTH} characters long.")]
+    InvalidTitle,
+
+    #[error(
+        "An event can't start in the past and has to end after it starts and before judging ends."
+    )]
+    InvalidSchedule,
+
+    #[error("The region of an event has to lie on the canvas.")]
+    InvalidRegion,
+
+    #[error("The region overlaps the one of event {0}.")]
+    Overlaps(u64),
+
+    #[error("This isn't possible while the event is {0:?}.")]
+    WrongState(EventState),
+
+    #[error("You are already registered for this event.")]
+    AlreadyRegistered,
+
+    #[error("The pixel is reserved for event {0}.")]
+    Reserved(u64),
+
+    #[error("Paid events can't be joined while payments are disabled.")]
+    PaymentsDisabled,
+
+    #[error("Only participants can submit to this event.")]
+    NotParticipant,
+
+    #[error("A submission has to lie in the region of its event.")]
+    InvalidSubmission,
+
+    #[error("The region overlaps the submission of artist {0}.")]
+    SubmissionTaken(u64),
+
+    #[error("You already submitted to this event.")]
+    AlreadySubmitted,
+
+    #[error("Artist {0} didn't submit to this event.")]
+    UnknownSubmission(u64),
+
+    #[error("Only accounts made before the event started can vote.")]
+    NotEligible,
+
+    #[error("You can't vote for your own submission.")]
+    OwnSubmission,
+
+    #[error("You already voted in this event.")]
+    AlreadyVoted,
+
+    #[error("The event storage failed: {0}")]
+    Storage(#[from] IoError),
+}
+
+impl ResponseError for EventError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound(_) | Self::UnknownSubmission(_) => StatusCode::NOT_FOUND,
+            Self::InvalidTitle
+            | Self::InvalidSchedule
+            | Self::InvalidRegion
+            | Self::InvalidSubmission => StatusCode::BAD_REQUEST,
+            Self::Overlaps(_)
+            | Self::WrongState(_)
+            | Self::AlreadyRegistered
+            | Self::SubmissionTaken(_)
+            | Self::AlreadySubmitted
+            | Self::AlreadyVoted => StatusCode::CONFLICT,
+            Self::Reserved(_) | Self::NotParticipant | Self::NotEligible | Self::OwnSubmission => {
+                StatusCode::FORBIDDEN
+            },
+            Self::PaymentsDisabled => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let body = match self {
+            Self::NotFound(_) | Self::UnknownSubmission(_) => {
+                ApiErrorBody::new(ApiErrorCode::NotFound, self.to_string())
+            },
+            Self::InvalidTitle
+            | Self::InvalidSchedule
+            | Self::InvalidRegion
+            | Self::InvalidSubmission => {
+                ApiErrorBody::new(ApiErrorCode::InvalidRequest, self.to_string())
+            },
+            Self::Overlaps(_)
+            | Self::WrongState(_)
+            | Self::AlreadyRegistered
+            | Self::SubmissionTaken(_)
+            | Self::AlreadySubmitted
+            | Self::AlreadyVoted => ApiErrorBody::new(ApiErrorCode::Conflict, self.to_string()),
+            Self::Reserved(_) | Self::NotParticipant | Self::NotEligible | Self::OwnSubmission => {
+                ApiErrorBody::new(ApiErrorCode::Forbidden, self.to_string())
+            },
+            Self::PaymentsDisabled => {
+                ApiErrorBody::new(ApiErrorCode::Unavailable, self.to_string())
+            },
+            Self::Storage(_) => {
+                ApiErrorBody::new(ApiErrorCode::Internal, "The event couldn't be updated.")
+            },
+        };
+
+        ApiError::new(self.status_code(), body).error_response()
+    }
+}
+
+/// An art event held on a reserved region of the canvas.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub id: u64,
+    pub title: String,
+    pub region: CanvasBounds,
+    pub state: EventState,
+    /// Unix milliseconds at which the event starts running.
+    pub starts_at: u64,
+    /// Unix milliseconds at which drawing stops and judging starts.
+    pub ends_at: u64,
+    /// Unix milliseconds at which the event is finished.
+    pub judging_ends_at: u64,
+    pub entry_fee_cents: u64,
+    #[serde(default = "default_tie_breaks")]
+    pub tie_breaks: Vec<TieBreak>,
+    /// Accounts registered for the event.
+    pub participants: BTreeSet<u64>,
+    /// Unix milliseconds.
+    pub created_at: u64,
+    /// Submissions by artist.
+    #[serde(default)]
+    pub submissions: BTreeMap<u64, Submission>,
+    /// The artist every voter picked.
+    #[serde(default)]
+    pub votes: BTreeMap<u64, u64>,
+    /// The artist every juror picked.
+    #[serde(default)]
+    pub jury_votes: BTreeMap<u64, u64>,
+    /// The winner picked by the jury regardless of the votes.
+    #[serde(default)]
+    pub jury_winner: Option<u64>,
+}
+
+impl Event {
+    pub fn info(&self) -> EventInfo {
+        EventInfo {
+            id: self.id,
+            title: self
+                .title
+                .clone(),
+            region: self.region,
+            state: self.state,
+            starts_at: self.starts_at,
+            ends_at: self.ends_at,
+            judging_ends_at: self.judging_ends_at,
+            entry_fee_cents: self.entry_fee_cents,
+            tie_breaks: self
+                .tie_breaks
+                .clone(),
+            participants: self
+                .participants
+                .len() as u64,
+        }
+    }
+
+    /// The state the schedule puts a published event in at `now`.
+    pub fn scheduled_state(&self, now: u64) -> EventState {
+        if now >= self.judging_ends_at {
+            EventState::Finished
+        } else if now >= self.ends_at {
+            EventState::Judging
+        } else if now >= self.starts_at {
+            EventState::Running
+        } else {
+            EventState::Registration
+        }
+    }
+
+    /// Fails unless `account` can register right now.
+    fn check_registration(&self, account: u64) -> Result<(), EventError> {
+        if !self.open_for(false) {
+            return Err(EventError::WrongState(self.state));
+        }
+
+        if self
+            .participants
+            .contains(&account)
+        {
+            return Err(EventError::AlreadyRegistered);
+        }
+
+        Ok(())
+    }
+
+    /// Whether the event finished or was cancelled.
+    pub fn is_over(&self) -> bool {
+        matches!(self.state, EventState::Finished | EventState::Cancelled)
+    }
+
+    /// Fails unless `voter` may vote for `artist` right now.
+    fn check_vote(&self, voter: u64, artist: u64) -> Result<(), EventError> {
+        if self.state != EventState::Judging {
+            return Err(EventError::WrongState(self.state));
+        }
+
+        if !self
+            .submissions
+            .contains_key(&artist)
+        {
+            return Err(EventError::UnknownSubmission(artist));
+        }
+
+        if voter == artist {
+            return Err(EventError::OwnSubmission);
+        }
+
+        Ok(())
+    }
+
+    /// Whether accounts can still join, payments that settle after the
+    /// event started are honoured.
+    fn open_for(&self, late: bool) -> bool {
+        match self.state {
+            EventState::Registration => true,
+            EventState::Running => late,
+            EventState::Draft
+            | EventState::Judging
+            | EventState::Finished
+            | EventState::Cancelled => false,
+        }
+    }
+}
+
+/// A change to the events, as stored in the journal.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum EventEntry {
+    Created(Event),
+    Transitioned {
+        event: u64,
+        state: EventState,
+        at: u64,
+    },
+    Joined {
+        event: u64,
+        account: u64,
+        at: u64,
+    },
+    Left {
+        event: u64,
+        account: u64,
+        at: u64,
+    },
+    Submitted {
+        event: u64,
+        submission: Submission,
+    },
+    Voted {
+        event: u64,
+        voter: u64,
+        artist: u64,
+        at: u64,
+    },
+    JuryVoted {
+        event: u64,
+        juror: u64,
+        artist: u64,
+        at: u64,
+    },
+    Overridden {
+        event: u64,
+        artist: u64,
+        at: u64,
+    },
+}
+
+#[derive(Default)]
+struct EventsState {
+    events: BTreeMap<u64, Event>,
+}
+
+impl EventsState {
+    fn apply(&mut self, entry: EventEntry) {
+        match entry {
+            EventEntry::Created(event) => {
+                self.events
+                    .insert(event.id, event);
+            },
+            EventEntry::Transitioned { event, state, .. } => {
+                if let Some(event) = self
+                    .events
+                    .get_mut(&event)
+                {
+                    event.state = state;
+                }
+            },
+            EventEntry::Joined { event, account, .. } => {
+                if let Some(event) = self
+                    .events
+                    .get_mut(&event)
+                {
+                    event
+                        .participants
+                        .insert(account);
+                }
+            },
+            EventEntry::Left { event, account, .. } => {
+                if let Some(event) = self
+                    .events
+                    .get_mut(&event)
+                {
+                    event
+                        .participants
+                        .remove(&account);
+                }
+            },
+            EventEntry::Submitted { event, submission } => {
+                if let Some(event) = self
+                    .events
+                    .get_mut(&event)
+                {
+                    event
+                        .submissions
+                        .insert(submission.artist, submission);
+                }
+            },
+            EventEntry::Voted { event, voter, artist, .. } => {
+                if let Some(event) = self
+                    .events
+                    .get_mut(&event)
+                {
+                    event
+                        .votes
+                        .insert(voter, artist);
+                }
+            },
+            EventEntry::JuryVoted { event, juror, artist, .. } => {
+                if let Some(event) = self
+                    .events
+                    .get_mut(&event)
+                {
+                    event
+                        .jury_votes
+                        .insert(juror, artist);
+                }
+            },
+            EventEntry::Overridden { event, artist, .. } => {
+                if let Some(event) = self
+                    .events
+                    .get_mut(&event)
+                {
+                    event.jury_winner = Some(artist);
+                }
+            },
+        }
+    }
+
+    fn event(&self, id: u64) -> Result<&Event, EventError> {
+        self.events
+            .get(&id)
+            .ok_or(EventError::NotFound(id))
+    }
+}
+
+/// The art events and who takes part in them.
+///
+/// When opened from a directory every change is journaled before it's
+/// applied.
+pub struct Events {
+    state: Mutex<EventsState>,
+    journal: Option<Mutex<Journal<EventEntry>>>,
+}
+
+impl Events {
+    /// Events that only live in memory.
+    pub fn new() -> Self {
+        Self { state: Mutex::default(), journal: None }
+    }
+
+    /// Loads the events stored in `dir`, or starts recording them there.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, EventError> {
+        let (journal, entries) = Journal::open(
+            dir.as_ref()
+                .join(JOURNAL_FILE),
+        )?;
+        let mut state = EventsState::default();
+
+        for entry in entries {
+            state.apply(entry);
+        }
+
+        Ok(Self {
+            state: Mutex::new(state),
+            journal: Some(Mutex::new(journal)),
+        })
+    }
+
+    fn append(&self, entry: &EventEntry) -> Result<(), EventError> {
+        if let Some(journal) = &self.journal {
+            journal
+                .lock()
+                .append(entry)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn event(&self, id: u64) -> Option<Event> {
+        self.state
+            .lock()
+            .events
+            .get(&id)
+            .cloned()
+    }
+
+    /// Every event in the order they were created, drafts only if asked for.
+    pub fn events(&self, drafts: bool) -> Vec<Event> {
+        self.state
+            .lock()
+            .events
+            .values()
+            .filter(|event| drafts || event.state != EventState::Draft)
+            .cloned()
+            .collect()
+    }
+
+    /// Creates a draft event on `region` of a canvas currently spanning
+    /// `bounds`.
+    ///
+    /// The region can't overlap the one of another event that isn't over.
+    pub fn create(
+        &self,
+        bounds: CanvasBounds,
+        request: EventRequest,
+        now: u64,
+    ) -> Result<Event, EventError> {
+        let EventRequest {
+            title,
+            region,
+            starts_at,
+            ends_at,
+            judging_ends_at,
+            entry_fee_cents,
+            tie_breaks,
+        } = request;
+        let title = title
+            .trim()
+            .to_owned();
+
+        if title.is_empty()
+            || title
+                .chars()
+                .count()
+                > MAX_TITLE_LENGTH
+        {
+            return Err(EventError::InvalidTitle);
+        }
+
+        if starts_at < now || ends_at <= starts_at || judging_ends_at < ends_at {
+            return Err(EventError::InvalidSchedule);
+        }
+
+        if region.area() == 0 || !bounds.contains_bounds(&region) {
+            return Err(EventError::InvalidRegion);
+        }
+
+        let mut state = self
+            .state
+            .lock();
+
+        if let Some(other) = state
+            .events
+            .values()
+            .find(|other| {
+                !other.is_over()
+                    && other
+                        .region
+                        .intersects(&region)
+            })
+        {
+            return Err(EventError::Overlaps(other.id));
+        }
+
+        let event = Event {
+            id: state
+                .events
+                .last_key_value()
+                .map_or(0, |(&id, _)| id)
+                + 1,
+            title,
+            region,
+            state: EventState::Draft,
+            starts_at,
+            ends_at,
+            judging_ends_at,
+            entry_fee_cents,
+            tie_breaks,
+            participants: BTreeSet::new(),
+            created_at: now,
+            submissions: BTreeMap::new(),
+            votes: BTreeMap::new(),
+            jury_votes: BTreeMap::new(),
+            jury_winner: None,
+        };
+        let entry = EventEntry::Created(event.clone());
+
+        self.append(&entry)?;
+        state.apply(entry);
+
+        Ok(event)
+    }
+
+    /// Opens the registration of a draft.
+    ///
+    /// A draft whose start already passed goes straight to the state its
+    /// schedule puts it in on the next [`Events::advance`].
+    pub fn publish(&self, id: u64, now: u64) -> Result<Event, EventError> {
+        let mut state = self
+            .state
+            .lock();
+        let event = state.event(id)?;
+
+        if event.state != EventState::Draft {
+            return Err(EventError::WrongState(event.state));
+        }
+
+        let entry = EventEntry::Transitioned {
+            event: id,
+            state: EventState::Registration,
+            at: now,
+        };
+
+        self.append(&entry)?;
+        state.apply(entry);
+
+        state
+            .event(id)
+            .cloned()
+    }
+
+    /// Moves every published event to the state its schedule puts it in at
+    /// `now`, returns the events that changed.
+    ///
+    /// Events only ever move forward, an event the server missed several
+    /// transitions of skips right to the current one.
+    pub fn advance(&self, now: u64) -> Result<Vec<Event>, EventError> {
+        let mut state = self
+            .state
+            .lock();
+        let due = state
+            .events
+            .values()
+            .filter(|event| event.state != EventState::Draft)
+            .filter_map(|event| {
+                let next = event.scheduled_state(now);
+
+                (next > event.state).then_some((event.id, next))
+            })
+            .collect::<Vec<_>>();
+        let mut changed = Vec::new();
+
+        for (id, next) in due {
+            let entry = EventEntry::Transitioned { event: id, state: next, at: now };
+
+            self.append(&entry)?;
+            state.apply(entry);
+            changed.extend(
+                state
+                    .event(id)
+                    .cloned(),
+            );
+        }
+
+        Ok(changed)
+    }
+
+    /// Calls off an event that isn't over yet, its region is free again.
+    ///
+    /// Entry fees are refunded by the payout worker, see
+    /// [`Payouts::process`](crate::payouts::Payouts::process).
+    pub fn cancel(&self, id: u64, now: u64) -> Result<Event, EventError> {
+        let mut state = self
+            .state
+            .lock();
+        let event = state.event(id)?;
+
+        if event.is_over() {
+            return Err(EventError::WrongState(event.state));
+        }
+
+        let entry = EventEntry::Transitioned {
+            event: id,
+            state: EventState::Cancelled,
+            at: now,
+        };
+
+        self.append(&entry)?;
+        state.apply(entry);
+
+        state
+            .event(id)
+            .cloned()
+    }
+
+    /// Registers `account` for a free event, paid events are joined through
+    /// [`Events::apply_payment`] once their entry fee is paid.
+    pub fn join(&self, id: u64, account: u64, now: u64) -> Result<Event, EventError> {
+        let mut state = self
+            .state
+            .lock();
+        let event = state.event(id)?;
+
+        event.check_registration(account)?;
+
+        let entry = EventEntry::Joined { event: id, account, at: now };
+
+        self.append(&entry)?;
+        state.apply(entry);
+
+        state
+            .event(id)
+            .cloned()
+    }
+
+    /// Checks that `account` may register for the event, before it's asked
+    /// to pay the entry fee.
+    pub fn check_registration(&self, id: u64, account: u64) -> Result<Event, EventError> {
+        let state = self
+            .state
+            .lock();
+        let event = state.event(id)?;
+
+        event.check_registration(account)?;
+
+        Ok(event.clone())
+    }
+
+    /// Registers the payer of a succeeded entry fee and drops the one of a
+    /// refunded one.
+    ///
+    /// Returns the event if its participants changed. Handing the same
+    /// payment over again changes nothing, so payments can be replayed after
+    /// a crash. Fees that settle once the event stopped running stay in its
+    /// prize pool without registering anyone, as do ones short of the entry
+    /// fee.
+    pub fn apply_payment(&self, payment: &Payment, now: u64) -> Result<Option<Event>, EventError> {
+        let (PaymentPurpose::EventEntry { event: id }, Some(account)) =
+            (&payment.purpose, payment.account)
+        else {
+            return Ok(None);
+        };
+        let mut state = self
+            .state
+            .lock();
+        let Some(event) = state
+            .events
+            .get(id)
+        else {
+            return Ok(None);
+        };
+        let registered = event
+            .participants
+            .contains(&account);
+
+        let entry = match payment.status {
+            PaymentStatus::Succeeded
+                if !registered
+                    && event.open_for(true)
+                    && payment.amount_cents >= event.entry_fee_cents =>
+            {
+                EventEntry::Joined { event: *id, account, at: now }
+            },
+            PaymentStatus::Refunded if registered => {
+                EventEntry::Left { event: *id, account, at: now }
+            },
+            _ => return Ok(None),
+        };
+
+        self.append(&entry)?;
+        state.apply(entry);
+
+        Ok(state
+            .events
+            .get(id)
+            .cloned())
+    }
+
+    /// Enters the part `region` of the event's region as the submission of
+    /// `artist`, only while the event runs.
+    ///
+    /// Every participant submits once and submissions can't overlap.
+    pub fn submit(
+        &self,
+        id: u64,
+        artist: u64,
+        region: CanvasBounds,
+        now: u64,
+    ) -> Result<Submission, EventError> {
+        let mut state = self
+            .state
+            .lock();
+        let event = state.event(id)?;
+
+        if event.state != EventState::Running {
+            return Err(EventError::WrongState(event.state));
+        }
+
+        if !event
+            .participants
+            .contains(&artist)
+        {
+            return Err(EventError::NotParticipant);
+        }
+
+        if event
+            .submissions
+            .contains_key(&artist)
+        {
+            return Err(EventError::AlreadySubmitted);
+        }
+
+        if region.area() == 0
+            || !event
+                .region
+                .contains_bounds(&region)
+        {
+            return Err(EventError::InvalidSubmission);
+        }
+
+        if let Some(other) = event
+            .submissions
+            .values()
+            .find(|other| {
+                other
+                    .region
+                    .intersects(&region)
+            })
+        {
+            return Err(EventError::SubmissionTaken(other.artist));
+        }
+
+        let submission = Submission {
+            artist,
+            region,
+            submitted_at: now,
+            order: event
+                .submissions
+                .len() as u64
+                + 1,
+        };
+        let entry = EventEntry::Submitted { event: id, submission };
+
+        self.append(&entry)?;
+        state.apply(entry);
+
+        Ok(submission)
+    }
+
+    /// The submissions of an event in the order they were made.
+    pub fn submissions(&self, id: u64) -> Result<Vec<SubmissionInfo>, EventError> {
+        let state = self
+            .state
+            .lock();
+        let mut submissions = state
+            .event(id)?
+            .submissions
+            .values()
+            .copied()
+            .collect::<Vec<_>>();
+
+        submissions.sort_by_key(|submission| submission.order);
+
+        Ok(submissions
+            .iter()
+            .map(Submission::info)
+            .collect())
+    }
+
+    /// Casts the one community vote of `voter` for the submission of
+    /// `artist`, only while the event is judged.
+    ///
+    /// Only accounts made before the event started, `voter_since`, may vote
+    /// so nobody can sign up just to vote for themselves.
+    pub fn vote(
+        &self,
+        id: u64,
+        voter: u64,
+        voter_since: u64,
+        artist: u64,
+        now: u64,
+    ) -> Result<(), EventError> {
+        let mut state = self
+            .state
+            .lock();
+        let event = state.event(id)?;
+
+        event.check_vote(voter, artist)?;
+
+        if voter_since >= event.starts_at {
+            return Err(EventError::NotEligible);
+        }
+
+        if event
+            .votes
+            .contains_key(&voter)
+        {
+            return Err(EventError::AlreadyVoted);
+        }
+
+        let entry = EventEntry::Voted { event: id, voter, artist, at: now };
+
+        self.append(&entry)?;
+        state.apply(entry);
+
+        Ok(())
+    }
+
+    /// Casts the one jury vote of `juror`, jury votes only break ties.
+    pub fn jury_vote(&self, id: u64, juror: u64, artist: u64, now: u64) -> Result<(), EventError> {
+        let mut state = self
+            .state
+            .lock();
+        let event = state.event(id)?;
+
+        event.check_vote(juror, artist)?;
+
+        if event
+            .jury_votes
+            .contains_key(&juror)
+        {
+            return Err(EventError::AlreadyVoted);
+        }
+
+        let entry = EventEntry::JuryVoted { event: id, juror, artist, at: now };
+
+        self.append(&entry)?;
+        state.apply(entry);
+
+        Ok(())
+    }
+
+    /// Makes `artist` the winner regardless of the votes, picking again
+    /// replaces the earlier pick.
+    pub fn override_winner(&self, id: u64, artist: u64, now: u64) -> Result<(), EventError> {
+        let mut state = self
+            .state
+            .lock();
+        let event = state.event(id)?;
+
+        if event.state != EventState::Judging {
+            return Err(EventError::WrongState(event.state));
+        }
+
+        if !event
+            .submissions
+            .contains_key(&artist)
+        {
+            return Err(EventError::UnknownSubmission(artist));
+        }
+
+        let entry = EventEntry::Overridden { event: id, artist, at: now };
+
+        self.append(&entry)?;
+        state.apply(entry);
+
+        Ok(())
+    }
+
+    /// The results of a finished event, computed from the stored votes.
+    pub fn results(&self, id: u64) -> Result<EventResults, EventError> {
+        let state = self
+            .state
+            .lock();
+        let event = state.event(id)?;
+
+        if event.state != EventState::Finished {
+            return Err(EventError::WrongState(event.state));
+        }
+
+        Ok(event.results())
+    }
+
+    /// Fails if the region `coord` lies in is reserved from `account`.
+    ///
+    /// While an event runs only its participants may draw in its region,
+    /// while it's judged nobody may.
+    pub fn check_placement(
+        &self,
+        coord: PixelCoord,
+        account: Option<u64>,
+    ) -> Result<(), EventError> {
+        let state = self
+            .state
+            .lock();
+
+        for event in state
+            .events
+            .values()
+            .filter(|event| {
+                event
+                    .region
+                    .contains(coord)
+            })
+        {
+            let allowed = match event.state {
+                EventState::Running => account.is_some_and(|account| {
+                    event
+                        .participants
+                        .contains(&account)
+                }),
+                EventState::Judging => false,
+                EventState::Draft
+                | EventState::Registration
+                | EventState::Finished
+                | EventState::Cancelled => true,
+            };
+
+            if !allowed {
+                return Err(EventError::Reserved(event.id));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Self::new()
+    }
+}
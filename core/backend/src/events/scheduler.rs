@@ -0,0 +1,33 @@
+use std::time::Duration;
+
+use actix_web::rt::spawn;
+use actix_web::rt::task::JoinHandle;
+use actix_web::rt::time::interval;
+use actix_web::web::Data;
+use log::{error, info};
+
+use crate::events::Events;
+use crate::utils::time::unix_millis;
+
+/// Moves `events` along their schedules every `period` until the runtime
+/// stops, events are at most `period` late.
+pub fn spawn_event_scheduler(events: Data<Events>, period: Duration) -> JoinHandle<()> {
+    spawn(async move {
+        let mut ticker = interval(period);
+
+        loop {
+            ticker
+                .tick()
+                .await;
+
+            match events.advance(unix_millis()) {
+                Ok(changed) => {
+                    for event in changed {
+                        info!("Event {} is now {:?}.", event.id, event.state);
+                    }
+                },
+                Err(event_error) => error!("Advancing the events failed: {event_error}"),
+            }
+        }
+    })
+}
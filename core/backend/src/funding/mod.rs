@@ -0,0 +1,337 @@
+use std::collections::HashSet;
+use std::io::Error as IoError;
+use std::path::Path;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use shared::api::{ApiErrorBody, ApiErrorCode, FundingStatus, PaymentPurpose, PaymentStatus};
+use shared::canvas::{BoundsChange, CanvasBounds, DEFAULT_CHUNK_SIZE};
+use shared::realtime::ServerMessage;
+use thiserror::Error;
+
+use crate::canvas::{CanvasError, CanvasStore};
+use crate::payments::Payment;
+use crate::realtime::hub::RealtimeHub;
+use crate::utils::api_error::ApiError;
+use crate::utils::journal::Journal;
+
+const JOURNAL_FILE: &str = "contributions.jsonl";
+
+/// How contributions grow the canvas.
+///
+/// Every `tier_cents` contributed in total unlock another tier, which adds a
+/// ring of `ring` pixels on every side of `base`.
+#[derive(Clone, Copy, Debug)]
+pub struct GrowthPolicy {
+    /// The canvas before any tier is unlocked.
+    pub base: CanvasBounds,
+    pub tier_cents: u64,
+    /// Pixels added on every side per tier, a multiple of the chunk size keeps
+    /// the canvas on chunk borders.
+    pub ring: u32,
+    /// Tiers past this one don't grow the canvas any further.
+    pub max_tiers: Option<u32>,
+}
+
+impl GrowthPolicy {
+    /// Tiers unlocked by `total_cents`.
+    pub fn tier(&self, total_cents: u64) -> u32 {
+        let tier = total_cents
+            .checked_div(self.tier_cents)
+            .map_or(0, |tier| u32::try_from(tier).unwrap_or(u32::MAX));
+
+        match self.max_tiers {
+            Some(max_tiers) => tier.min(max_tiers),
+            None => tier,
+        }
+    }
+
+    /// Total at which the tier after `tier` unlocks.
+    pub fn next_tier_cents(&self, tier: u32) -> Option<u64> {
+        if self.tier_cents == 0
+            || self
+                .max_tiers
+                .is_some_and(|max_tiers| tier >= max_tiers)
+        {
+            return None;
+        }
+
+        (u64::from(tier) + 1).checked_mul(self.tier_cents)
+    }
+
+    /// The canvas bounds once `tier` is unlocked.
+    pub fn bounds(&self, tier: u32) -> CanvasBounds {
+        self.base
+            .expanded(tier.saturating_mul(self.ring))
+    }
+}
+
+impl Default for GrowthPolicy {
+    fn default() -> Self {
+        Self {
+            base: CanvasBounds::new(
+                0,
+                0,
+                4 * u32::from(DEFAULT_CHUNK_SIZE),
+                4 * u32::from(DEFAULT_CHUNK_SIZE),
+            ),
+            tier_cents: 50_00,
+            ring: u32::from(DEFAULT_CHUNK_SIZE),
+            max_tiers: None,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum FundingError {
+    #[error("A contribution has to be at least one cent.")]
+    InvalidAmount,
+
+    #[error("The funding storage failed: {0}")]
+    Storage(#[from] IoError),
+
+    #[error(transparent)]
+    Canvas(#[from] CanvasError),
+}
+
+impl ResponseError for FundingError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InvalidAmount => StatusCode::BAD_REQUEST,
+            Self::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Canvas(error) => error.status_code(),
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            Self::InvalidAmount => ApiError::new(
+                self.status_code(),
+                ApiErrorBody::new(ApiErrorCode::InvalidRequest, self.to_string()),
+            )
+            .error_response(),
+            Self::Storage(_) => ApiError::new(
+                self.status_code(),
+                ApiErrorBody::new(ApiErrorCode::Internal, "The contribution couldn't be recorded."),
+            )
+            .error_response(),
+            Self::Canvas(error) => error.error_response(),
+        }
+    }
+}
+
+/// A recorded contribution towards growing the canvas.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Contribution {
+    pub id: u64,
+    /// `None` for anonymous contributions.
+    pub account: Option<u64>,
+    pub amount_cents: u64,
+    /// Unix milliseconds.
+    pub contributed_at: u64,
+    /// The payment it was made through, `None` when it was recorded directly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payment: Option<u64>,
+}
+
+#[derive(Default)]
+struct FundingState {
+    total_cents: u64,
+    contributions: u64,
+    /// Payments already counted.
+    payments: HashSet<u64>,
+}
+
+impl FundingState {
+    fn apply(&mut self, contribution: &Contribution) {
+        self.total_cents = self
+            .total_cents
+            .saturating_add(contribution.amount_cents);
+        self.contributions = contribution.id;
+
+        if let Some(payment) = contribution.payment {
+            self.payments
+                .insert(payment);
+        }
+    }
+}
+
+/// The contributions made towards growing the canvas.
+///
+/// Only totals are kept in memory, when opened from a directory every
+/// contribution is journaled before it counts.
+pub struct Funding {
+    policy: GrowthPolicy,
+    state: Mutex<FundingState>,
+    journal: Option<Mutex<Journal<Contribution>>>,
+}
+
+impl Funding {
+    /// Funding that only lives in memory.
+    pub fn new(policy: GrowthPolicy) -> Self {
+        Self {
+            policy,
+            state: Mutex::default(),
+            journal: None,
+        }
+    }
+
+    /// Loads the contributions stored in `dir`, or starts recording them there.
+    pub fn open(dir: impl AsRef<Path>, policy: GrowthPolicy) -> Result<Self, FundingError> {
+        let (journal, contributions) = Journal::open(
+            dir.as_ref()
+                .join(JOURNAL_FILE),
+        )?;
+        let mut state = FundingState::default();
+
+        for contribution in &contributions {
+            state.apply(contribution);
+        }
+
+        Ok(Self {
+            policy,
+            state: Mutex::new(state),
+            journal: Some(Mutex::new(journal)),
+        })
+    }
+
+    #[inline]
+    pub fn policy(&self) -> &GrowthPolicy {
+        &self.policy
+    }
+
+    pub fn status(&self, canvas: &CanvasStore) -> FundingStatus {
+        let state = self
+            .state
+            .lock();
+        let tier = self
+            .policy
+            .tier(state.total_cents);
+
+        FundingStatus {
+            total_cents: state.total_cents,
+            contributions: state.contributions,
+            tier,
+            next_tier_cents: self
+                .policy
+                .next_tier_cents(tier),
+            bounds: canvas.bounds(),
+        }
+    }
+
+    /// Counts a succeeded contribution payment and grows the canvas if it
+    /// crossed a tier, connected clients are told about the new bounds.
+    ///
+    /// Returns `None` for any other payment and for payments counted before,
+    /// so payments can be handed over again after a crash. Refunds don't take
+    /// anything back as the canvas never shrinks.
+    pub fn contribute_payment(
+        &self,
+        canvas: &CanvasStore,
+        hub: &RealtimeHub,
+        payment: &Payment,
+        now: u64,
+    ) -> Result<Option<Contribution>, FundingError> {
+        let mut state = self
+            .state
+            .lock();
+
+        if payment.purpose != PaymentPurpose::Contribution
+            || payment.status != PaymentStatus::Succeeded
+            || state
+                .payments
+                .contains(&payment.id)
+        {
+            return Ok(None);
+        }
+
+        self.record(
+            &mut state,
+            canvas,
+            hub,
+            Contribution {
+                id: 0,
+                account: payment.account,
+                amount_cents: payment.amount_cents,
+                contributed_at: now,
+                payment: Some(payment.id),
+            },
+        )
+        .map(Some)
+    }
+
+    /// Journals and counts `contribution` under the next id.
+    fn record(
+        &self,
+        state: &mut FundingState,
+        canvas: &CanvasStore,
+        hub: &RealtimeHub,
+        mut contribution: Contribution,
+    ) -> Result<Contribution, FundingError> {
+        if contribution.amount_cents == 0 {
+            return Err(FundingError::InvalidAmount);
+        }
+
+        contribution.id = state.contributions + 1;
+
+        if let Some(journal) = &self.journal {
+            journal
+                .lock()
+                .append(&contribution)?;
+        }
+
+        state.apply(&contribution);
+
+        // the canvas grows while the state is locked so concurrent
+        // contributions can't log their growth out of order.
+        self.grow(state, canvas, hub, contribution.contributed_at)?;
+
+        Ok(contribution)
+    }
+
+    /// Grows the canvas to the tier already funded, needed on start in case
+    /// the server stopped between recording a contribution and growing the
+    /// canvas.
+    pub fn sync_canvas(
+        &self,
+        canvas: &CanvasStore,
+        hub: &RealtimeHub,
+        now: u64,
+    ) -> Result<Option<BoundsChange>, FundingError> {
+        let state = self
+            .state
+            .lock();
+
+        self.grow(&state, canvas, hub, now)
+    }
+
+    fn grow(
+        &self,
+        state: &FundingState,
+        canvas: &CanvasStore,
+        hub: &RealtimeHub,
+        now: u64,
+    ) -> Result<Option<BoundsChange>, FundingError> {
+        let funded = self
+            .policy
+            .bounds(
+                self.policy
+                    .tier(state.total_cents),
+            );
+        // a canvas that started out larger than the base keeps its extra space.
+        let change = canvas.grow(
+            canvas
+                .bounds()
+                .union(&funded),
+            now,
+        )?;
+
+        if let Some(change) = change {
+            hub.broadcast(&ServerMessage::Bounds(change));
+        }
+
+        Ok(change)
+    }
+}
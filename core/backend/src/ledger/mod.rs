@@ -0,0 +1,530 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::Error as IoError;
+use std::path::Path;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use shared::api::{
+    ApiErrorBody,
+    ApiErrorCode,
+    LedgerAccount,
+    LedgerBalance,
+    LedgerReport,
+    PaymentPurpose,
+    PaymentStatus,
+};
+use thiserror::Error;
+
+use crate::payments::Payment;
+use crate::utils::api_error::ApiError;
+use crate::utils::journal::Journal;
+
+const JOURNAL_FILE: &str = "ledger.jsonl";
+
+#[derive(Error, Debug)]
+pub enum LedgerError {
+    #[error("A transaction needs at least two postings of non zero amounts.")]
+    Empty,
+
+    #[error("The postings of a transaction add up to {0} instead of zero.")]
+    Unbalanced(i64),
+
+    #[error("The amount doesn't fit the ledger.")]
+    Overflow,
+
+    #[error("The transaction would overdraw {0:?}.")]
+    Overdrawn(LedgerAccount),
+
+    #[error("The ledger is inconsistent: {0}")]
+    Invariant(String),
+
+    #[error("The ledger storage failed: {0}")]
+    Storage(#[from] IoError),
+}
+
+impl ResponseError for LedgerError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Overdrawn(_) => StatusCode::CONFLICT,
+            Self::Empty
+            | Self::Unbalanced(_)
+            | Self::Overflow
+            | Self::Invariant(_)
+            | Self::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let body = match self {
+            Self::Overdrawn(_) => ApiErrorBody::new(ApiErrorCode::Conflict, self.to_string()),
+            Self::Empty
+            | Self::Unbalanced(_)
+            | Self::Overflow
+            | Self::Invariant(_)
+            | Self::Storage(_) => {
+                ApiErrorBody::new(ApiErrorCode::Internal, "The ledger couldn't be updated.")
+            },
+        };
+
+        ApiError::new(self.status_code(), body).error_response()
+    }
+}
+
+/// Whether an account grows with debits, every other account grows with
+/// credits.
+fn debit_normal(account: LedgerAccount) -> bool {
+    matches!(account, LedgerAccount::Cash)
+}
+
+/// Whether an account may fall below zero on its normal side, only the
+/// platform can cover a loss.
+fn may_overdraw(account: LedgerAccount) -> bool {
+    matches!(account, LedgerAccount::Platform)
+}
+
+/// One side of a transaction, debits are positive and credits negative.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Posting {
+    pub account: LedgerAccount,
+    pub amount_cents: i64,
+}
+
+impl Posting {
+    pub fn debit(account: LedgerAccount, amount_cents: u64) -> Result<Self, LedgerError> {
+        Ok(Self {
+            account,
+            amount_cents: i64::try_from(amount_cents).map_err(|_| LedgerError::Overflow)?,
+        })
+    }
+
+    pub fn credit(account: LedgerAccount, amount_cents: u64) -> Result<Self, LedgerError> {
+        Ok(Self {
+            account,
+            amount_cents: -i64::try_from(amount_cents).map_err(|_| LedgerError::Overflow)?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionKind {
+    Contribution,
+    EntryFee,
+    Refund,
+    /// A prize pool split between the winner and the platform.
+    PrizeAward,
+    Payout,
+    /// A rejected prize award going back into its prize pool.
+    PayoutReversal,
+}
+
+/// A balanced set of postings.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    pub id: u64,
+    /// Unique per transaction, posting again under the same key returns the
+    /// first transaction instead.
+    pub key: String,
+    pub kind: TransactionKind,
+    pub postings: Vec<Posting>,
+    /// Unix milliseconds.
+    pub posted_at: u64,
+}
+
+fn check_balanced(postings: &[Posting]) -> Result<(), LedgerError> {
+    if postings.len() < 2
+        || postings
+            .iter()
+            .any(|posting| posting.amount_cents == 0)
+    {
+        return Err(LedgerError::Empty);
+    }
+
+    let sum = postings
+        .iter()
+        .try_fold(0i64, |sum, posting| sum.checked_add(posting.amount_cents))
+        .ok_or(LedgerError::Overflow)?;
+
+    if sum != 0 {
+        return Err(LedgerError::Unbalanced(sum));
+    }
+
+    Ok(())
+}
+
+/// The sum of `sums`, `None` when it overflows.
+fn total<'a>(sums: impl IntoIterator<Item = &'a i64>) -> Option<i64> {
+    sums.into_iter()
+        .try_fold(0i64, |total, &sum| total.checked_add(sum))
+}
+
+#[derive(Default)]
+struct LedgerState {
+    transactions: Vec<Transaction>,
+    by_key: HashMap<String, usize>,
+    /// Raw sums of every account, debits positive.
+    sums: BTreeMap<LedgerAccount, i64>,
+}
+
+impl LedgerState {
+    /// The balance of `account` on its normal side.
+    fn balance(&self, account: LedgerAccount) -> i64 {
+        let sum = self
+            .sums
+            .get(&account)
+            .copied()
+            .unwrap_or_default();
+
+        if debit_normal(account) { sum } else { -sum }
+    }
+
+    /// The sums of the accounts `postings` touch once they are applied.
+    fn summed(&self, postings: &[Posting]) -> Result<BTreeMap<LedgerAccount, i64>, LedgerError> {
+        let mut changed = BTreeMap::new();
+
+        for posting in postings {
+            let sum = changed
+                .entry(posting.account)
+                .or_insert_with(|| {
+                    self.sums
+                        .get(&posting.account)
+                        .copied()
+                        .unwrap_or_default()
+                });
+
+            *sum = sum
+                .checked_add(posting.amount_cents)
+                .ok_or(LedgerError::Overflow)?;
+        }
+
+        Ok(changed)
+    }
+
+    /// Fails if `postings` would leave an account overdrawn.
+    fn check_overdraw(&self, postings: &[Posting]) -> Result<(), LedgerError> {
+        for (account, sum) in self.summed(postings)? {
+            let balance = if debit_normal(account) { sum } else { -sum };
+
+            if balance < 0 && !may_overdraw(account) {
+                return Err(LedgerError::Overdrawn(account));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fails without applying anything when a sum would overflow.
+    fn apply(&mut self, transaction: Transaction) -> Result<(), LedgerError> {
+        let changed = self.summed(&transaction.postings)?;
+
+        self.sums
+            .extend(changed);
+
+        self.by_key
+            .insert(
+                transaction
+                    .key
+                    .clone(),
+                self.transactions
+                    .len(),
+            );
+        self.transactions
+            .push(transaction);
+
+        Ok(())
+    }
+
+    /// Whether the running sums add up to zero without leaving an account
+    /// overdrawn, which every post keeps true.
+    fn holds(&self) -> bool {
+        total(
+            self.sums
+                .values(),
+        ) == Some(0)
+            && self
+                .sums
+                .keys()
+                .all(|&account| self.balance(account) >= 0 || may_overdraw(account))
+    }
+
+    /// Recomputes every balance from the transactions and checks it against
+    /// the running sums.
+    fn verify(&self) -> Result<(), LedgerError> {
+        let mut sums = BTreeMap::<LedgerAccount, i64>::new();
+
+        for transaction in &self.transactions {
+            check_balanced(&transaction.postings).map_err(|error| {
+                LedgerError::Invariant(format!("transaction {}: {error}", transaction.id))
+            })?;
+
+            for posting in &transaction.postings {
+                let sum = sums
+                    .entry(posting.account)
+                    .or_default();
+
+                *sum = sum
+                    .checked_add(posting.amount_cents)
+                    .ok_or_else(|| {
+                        LedgerError::Invariant(format!(
+                            "transaction {} overflows {:?}",
+                            transaction.id, posting.account
+                        ))
+                    })?;
+            }
+        }
+
+        sums.retain(|_, sum| *sum != 0);
+
+        let mut running = self
+            .sums
+            .clone();
+        running.retain(|_, sum| *sum != 0);
+
+        if sums != running {
+            return Err(LedgerError::Invariant(
+                "the balances don't match the transactions".to_owned(),
+            ));
+        }
+
+        if total(sums.values()) != Some(0) {
+            return Err(LedgerError::Invariant("the balances don't add up to zero".to_owned()));
+        }
+
+        if let Some(account) = sums
+            .keys()
+            .find(|&&account| self.balance(account) < 0 && !may_overdraw(account))
+        {
+            return Err(LedgerError::Invariant(format!("{account:?} is overdrawn")));
+        }
+
+        Ok(())
+    }
+}
+
+/// Double entry bookkeeping of every cent that moves through CanvaDot.
+///
+/// Transactions are only ever appended, mistakes are corrected by posting
+/// the opposite transaction. When opened from a directory every transaction
+/// is journaled before it counts.
+pub struct Ledger {
+    state: Mutex<LedgerState>,
+    journal: Option<Mutex<Journal<Transaction>>>,
+}
+
+impl Ledger {
+    /// A ledger that only lives in memory.
+    pub fn new() -> Self {
+        Self { state: Mutex::default(), journal: None }
+    }
+
+    /// Loads the ledger stored in `dir` and checks its invariants, or starts
+    /// a new one there.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, LedgerError> {
+        let (journal, transactions) = Journal::open(
+            dir.as_ref()
+                .join(JOURNAL_FILE),
+        )?;
+        let mut state = LedgerState::default();
+
+        for transaction in transactions {
+            state
+                .apply(transaction)
+                .map_err(|error| LedgerError::Invariant(error.to_string()))?;
+        }
+
+        state.verify()?;
+
+        Ok(Self {
+            state: Mutex::new(state),
+            journal: Some(Mutex::new(journal)),
+        })
+    }
+
+    /// Posts a balanced transaction, or returns the one already posted under
+    /// `key`.
+    pub fn post(
+        &self,
+        key: impl Into<String>,
+        kind: TransactionKind,
+        postings: Vec<Posting>,
+        now: u64,
+    ) -> Result<Transaction, LedgerError> {
+        check_balanced(&postings)?;
+
+        self.post_with(key, kind, |_| Ok(postings), now)
+    }
+
+    /// Like [`Ledger::post`], with postings that depend on the balances they
+    /// are posted against.
+    fn post_with(
+        &self,
+        key: impl Into<String>,
+        kind: TransactionKind,
+        postings: impl FnOnce(&LedgerState) -> Result<Vec<Posting>, LedgerError>,
+        now: u64,
+    ) -> Result<Transaction, LedgerError> {
+        let key = key.into();
+        let mut state = self
+            .state
+            .lock();
+
+        if let Some(&index) = state
+            .by_key
+            .get(&key)
+        {
+            return Ok(state.transactions[index].clone());
+        }
+
+        let postings = postings(&state)?;
+
+        check_balanced(&postings)?;
+        state.check_overdraw(&postings)?;
+
+        let transaction = Transaction {
+            id: state
+                .transactions
+                .len() as u64
+                + 1,
+            key,
+            kind,
+            postings,
+            posted_at: now,
+        };
+
+        if let Some(journal) = &self.journal {
+            journal
+                .lock()
+                .append(&transaction)?;
+        }
+
+        state.apply(transaction.clone())?;
+
+        Ok(transaction)
+    }
+
+    /// Posts what a payment did to the books so far.
+    ///
+    /// Money received is credited to the account the payment was for, a
+    /// refund takes it back. What the account no longer holds, like an entry
+    /// fee already awarded as a prize, is covered by the platform. Each step
+    /// is keyed by the payment so handing the same payment over again posts
+    /// nothing new.
+    pub fn record_payment(
+        &self,
+        payment: &Payment,
+        now: u64,
+    ) -> Result<Vec<Transaction>, LedgerError> {
+        let (kind, account) = match payment.purpose {
+            PaymentPurpose::Contribution => {
+                (TransactionKind::Contribution, LedgerAccount::GrowthFund)
+            },
+            PaymentPurpose::EventEntry { event } => {
+                (TransactionKind::EntryFee, LedgerAccount::PrizePool { event })
+            },
+        };
+        let mut transactions = Vec::new();
+
+        if matches!(payment.status, PaymentStatus::Succeeded | PaymentStatus::Refunded) {
+            transactions.push(self.post(
+                format!("payment:{}:received", payment.id),
+                kind,
+                vec![
+                    Posting::debit(LedgerAccount::Cash, payment.amount_cents)?,
+                    Posting::credit(account, payment.amount_cents)?,
+                ],
+                now,
+            )?);
+        }
+
+        if payment.status == PaymentStatus::Refunded {
+            transactions.push(self.post_with(
+                format!("payment:{}:refunded", payment.id),
+                TransactionKind::Refund,
+                |state| {
+                    let held_cents = u64::try_from(state.balance(account))
+                        .unwrap_or_default()
+                        .min(payment.amount_cents);
+                    let covered_cents = payment.amount_cents - held_cents;
+                    let mut postings = Vec::new();
+
+                    if held_cents > 0 {
+                        postings.push(Posting::debit(account, held_cents)?);
+                    }
+
+                    if covered_cents > 0 {
+                        postings.push(Posting::debit(LedgerAccount::Platform, covered_cents)?);
+                    }
+
+                    postings.push(Posting::credit(LedgerAccount::Cash, payment.amount_cents)?);
+
+                    Ok(postings)
+                },
+                now,
+            )?);
+        }
+
+        Ok(transactions)
+    }
+
+    /// The balance of `account` on its normal side.
+    pub fn balance(&self, account: LedgerAccount) -> i64 {
+        self.state
+            .lock()
+            .balance(account)
+    }
+
+    /// Every transaction in the order they were posted.
+    pub fn transactions(&self) -> Vec<Transaction> {
+        self.state
+            .lock()
+            .transactions
+            .clone()
+    }
+
+    /// Checks that every transaction is balanced, that the balances match
+    /// them and that no account is overdrawn.
+    pub fn verify(&self) -> Result<(), LedgerError> {
+        self.state
+            .lock()
+            .verify()
+    }
+
+    /// The running balances, cheap enough for anyone to ask for. The full
+    /// replay of [`Ledger::verify`] only runs when the ledger is opened.
+    pub fn report(&self) -> LedgerReport {
+        let state = self
+            .state
+            .lock();
+        let mut balances = Vec::new();
+        let mut payables_cents = 0;
+
+        for &account in state
+            .sums
+            .keys()
+        {
+            let balance_cents = state.balance(account);
+
+            match account {
+                LedgerAccount::Payable { .. } => payables_cents += balance_cents,
+                _ => balances.push(LedgerBalance { account, balance_cents }),
+            }
+        }
+
+        LedgerReport {
+            balances,
+            payables_cents,
+            transactions: state
+                .transactions
+                .len() as u64,
+            balanced: state.holds(),
+        }
+    }
+}
+
+impl Default for Ledger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
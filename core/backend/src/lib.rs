@@ -1,5 +1,19 @@
 #[cfg(test)]
 mod tests;
 
+pub mod accounts;
+pub mod canvas;
+pub mod config;
+pub mod cooldown;
+pub mod events;
+pub mod funding;
+pub mod ledger;
+pub mod moderation;
+pub mod monitoring;
+pub mod payments;
+pub mod payouts;
+pub mod realtime;
 // Export routes for tests.
 pub mod routes;
+pub mod timelapse;
+pub mod utils;
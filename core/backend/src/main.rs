@@ -2,33 +2,376 @@
 #![deny(clippy::unwrap_used)]
 #![deny(clippy::match_like_matches_macro)]
 
+#[cfg(not(feature = "coverage"))]
+use std::env::{args, var};
 #[cfg(not(feature = "coverage"))]
 use std::io::Error as IoError;
+#[cfg(not(feature = "coverage"))]
+use std::time::Duration;
 
 #[cfg(not(feature = "coverage"))]
 use ::{
+    actix_web::middleware::from_fn,
+    actix_web::rt::signal::ctrl_c,
+    actix_web::rt::signal::unix::{SignalKind, signal},
+    actix_web::web::{Data, scope},
     actix_web::{App, HttpServer, main},
+    backend::accounts::oidc::OidcProvider,
+    backend::accounts::{AccountError, Accounts},
+    backend::canvas::checkpoint::spawn_snapshots,
+    backend::canvas::export::ExportLimits,
+    backend::canvas::{CanvasError, CanvasStore},
+    backend::config::{Config, ConfigError},
+    backend::cooldown::Cooldowns,
+    backend::events::scheduler::spawn_event_scheduler,
+    backend::events::{EventError, Events},
+    backend::funding::{Funding, FundingError},
+    backend::ledger::{Ledger, LedgerError},
+    backend::moderation::{Moderation, ModerationError},
+    backend::monitoring::Readiness,
+    backend::monitoring::metrics::{Metrics, track_requests},
+    backend::payments::fake::FakePaymentProvider,
+    backend::payments::{PaymentError, Payments},
+    backend::payouts::worker::spawn_payout_worker,
+    backend::payouts::{PayoutError, Payouts},
+    backend::realtime::hub::RealtimeHub,
+    backend::routes::accounts::{login, logout, me, oidc_callback, oidc_login, register},
+    backend::routes::canvas::{chunk, export_png, pixel_history, place_pixel},
+    backend::routes::events::{
+        cancel_event,
+        create_event,
+        event_info,
+        event_results,
+        event_submissions,
+        jury_vote_in_event,
+        list_events,
+        override_event_winner,
+        publish_event,
+        register_for_event,
+        submit_to_event,
+        vote_in_event,
+    },
+    backend::routes::funding::funding_status,
+    backend::routes::ledger::ledger_report,
+    backend::routes::moderation::{
+        ban_account,
+        list_bans,
+        list_reports,
+        report_pixel,
+        review_report,
+        rollback_placements,
+        unban_account,
+    },
+    backend::routes::monitoring::{health_check, metrics_report, readiness_check},
+    backend::routes::openapi::openapi_spec,
+    backend::routes::payments::{create_checkout, payment_status, payment_webhook},
+    backend::routes::payouts::{approve_payout, list_payouts, payout_audit, reject_payout},
+    backend::routes::realtime::canvas_live,
+    backend::routes::timelapse::{create_timelapse, timelapse_output, timelapse_status},
+    backend::timelapse::TimelapseLimits,
+    backend::timelapse::jobs::TimelapseJobs,
+    backend::utils::api_error::api_errors,
+    backend::utils::client::TrustedProxies,
+    backend::utils::http::TlsHttpClient,
+    backend::utils::time::unix_millis,
+    env_logger::Env,
+    log::{error, info},
+    std::future::pending,
     thiserror::Error,
+    tokio::select,
 };
-#[cfg(not(feature = "coverage"))]
-use routes::test_route::test_route;
 
-mod routes;
+/// How long clients are told to wait before reconnecting once the server
+/// goes down.
+#[cfg(not(feature = "coverage"))]
+const RECONNECT_AFTER: Duration = Duration::from_secs(5);
+/// How long open connections get to finish once the server goes down.
+#[cfg(not(feature = "coverage"))]
+const SHUTDOWN_TIMEOUT: u64 = 10;
 
 #[derive(Error, Debug)]
 #[cfg(not(feature = "coverage"))]
 enum AppError {
+    #[error("Error loading the config: {0}")]
+    Config(#[from] ConfigError),
+
     #[error("Error starting the server.")]
     Server(#[from] IoError),
+
+    #[error("Error loading the canvas: {0}")]
+    Canvas(#[from] CanvasError),
+
+    #[error("Error loading the accounts: {0}")]
+    Accounts(#[from] AccountError),
+
+    #[error("Error loading the funding: {0}")]
+    Funding(#[from] FundingError),
+
+    #[error("Error loading the payments: {0}")]
+    Payments(#[from] PaymentError),
+
+    #[error("Error loading the ledger: {0}")]
+    Ledger(#[from] LedgerError),
+
+    #[error("Error loading the events: {0}")]
+    Events(#[from] EventError),
+
+    #[error("Error loading the payouts: {0}")]
+    Payouts(#[from] PayoutError),
+
+    #[error("Error loading the moderation: {0}")]
+    Moderation(#[from] ModerationError),
+}
+
+/// Payments through the fake provider when the config has a secret for it,
+/// which only loads in dev mode. `None` keeps payments disabled.
+#[cfg(not(feature = "coverage"))]
+fn payments_from_config(config: &Config) -> Result<Option<Payments>, PaymentError> {
+    let Some(settings) = &config.payments else {
+        return Ok(None);
+    };
+
+    Payments::open(
+        config
+            .storage
+            .data_dir
+            .join("payments"),
+        FakePaymentProvider::new(
+            settings
+                .fake_secret
+                .clone(),
+        ),
+    )
+    .map(Some)
+}
+
+/// Resolves once the process is asked to stop, through SIGINT or SIGTERM.
+#[cfg(not(feature = "coverage"))]
+async fn stop_requested() {
+    let terminate = async {
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate
+                    .recv()
+                    .await;
+            },
+            Err(signal_error) => {
+                error!("Couldn't listen for SIGTERM: {signal_error}");
+                pending::<()>().await;
+            },
+        }
+    };
+
+    select! {
+        _ = ctrl_c() => {},
+        () = terminate => {},
+    }
 }
 
 #[main]
 #[cfg(not(feature = "coverage"))]
 async fn main() -> Result<(), AppError> {
-    HttpServer::new(|| App::new().service(test_route))
-        .bind(("0.0.0.0", 8081))?
-        .run()
-        .await?;
+    env_logger::init_from_env(Env::default().default_filter_or("info"));
+
+    let config = Config::load(args().skip(1), |name| var(name).ok())?;
+    let data = |store: &str| {
+        config
+            .storage
+            .data_dir
+            .join(store)
+    };
+
+    let chunk_size = config
+        .canvas
+        .chunk_size;
+    let growth = config
+        .growth
+        .policy(chunk_size)?;
+    let canvas = Data::new(CanvasStore::open(
+        &config
+            .storage
+            .data_dir,
+        chunk_size,
+        growth.base,
+    )?);
+    let metrics = Data::new(Metrics::new());
+    let readiness = Data::new(Readiness::new());
+    let snapshot_policy = config
+        .snapshots
+        .clone();
+    spawn_snapshots(canvas.clone(), snapshot_policy.clone(), metrics.clone());
+
+    let accounts = Data::new(Accounts::open(
+        data("accounts"),
+        config
+            .accounts
+            .accounts(),
+    )?);
+    let oidc = config
+        .oidc
+        .clone()
+        .map(|oidc| TlsHttpClient::new().map(|client| Data::new(OidcProvider::new(oidc, client))))
+        .transpose()?;
+    let hub = Data::new(RealtimeHub::new(config.realtime));
+    let funding = Data::new(Funding::open(data("funding"), growth)?);
+    funding.sync_canvas(&canvas, &hub, unix_millis())?;
+
+    let ledger = Data::new(Ledger::open(data("ledger"))?);
+    let payments = payments_from_config(&config)?.map(Data::new);
+    let events = Data::new(Events::open(data("events"))?);
+    let payouts = Data::new(Payouts::open(data("payouts"), config.payouts)?);
+    let moderation = Data::new(Moderation::open(data("moderation"))?);
+
+    // payments that changed right before a restart may not be counted yet,
+    // one that can't be booked is reported rather than keeping the server down.
+    for payment in payments
+        .iter()
+        .flat_map(|payments| payments.payments())
+    {
+        if let Err(ledger_error) = ledger.record_payment(&payment, unix_millis()) {
+            error!("Booking payment {} failed: {ledger_error}", payment.id);
+        }
+
+        funding.contribute_payment(&canvas, &hub, &payment, unix_millis())?;
+        events.apply_payment(&payment, unix_millis())?;
+    }
+    payouts.sync_ledger(&ledger, unix_millis())?;
+    spawn_event_scheduler(events.clone(), Duration::from_secs(5));
+    spawn_payout_worker(
+        payouts.clone(),
+        events.clone(),
+        ledger.clone(),
+        payments.clone(),
+        Duration::from_mins(1),
+    );
+
+    let cooldowns = Data::new(Cooldowns::new(config.cooldown));
+    let proxies = Data::new(TrustedProxies::new(
+        config
+            .server
+            .trusted_proxies
+            .clone(),
+    ));
+    let export_limits = Data::new(ExportLimits::default());
+    let timelapses = Data::new(TimelapseJobs::new(TimelapseLimits::default()));
+
+    let server_readiness = readiness.clone();
+    let final_canvas = canvas.clone();
+    let shutdown = {
+        let (canvas, hub, readiness) = (canvas.clone(), hub.clone(), readiness.clone());
+
+        async move {
+            stop_requested().await;
+            info!("Shutting down, placements are refused from now on.");
+
+            readiness.set_ready(false);
+            canvas.close();
+            hub.shut_down(RECONNECT_AFTER);
+        }
+    };
+    let mut server = HttpServer::new(move || {
+        App::new()
+            .wrap(from_fn(api_errors))
+            .wrap(from_fn(track_requests))
+            .app_data(canvas.clone())
+            .app_data(accounts.clone())
+            .app_data(hub.clone())
+            .app_data(cooldowns.clone())
+            .app_data(proxies.clone())
+            .app_data(export_limits.clone())
+            .app_data(timelapses.clone())
+            .app_data(funding.clone())
+            .app_data(ledger.clone())
+            .app_data(events.clone())
+            .app_data(payouts.clone())
+            .app_data(moderation.clone())
+            .app_data(metrics.clone())
+            .app_data(readiness.clone())
+            .service(health_check)
+            .service(readiness_check)
+            .service(metrics_report)
+            .service(
+                scope("/api")
+                    .service(openapi_spec)
+                    .service(register)
+                    .service(login)
+                    .service(logout)
+                    .service(me)
+                    .service(place_pixel)
+                    .service(pixel_history)
+                    .service(chunk)
+                    .service(export_png)
+                    .service(canvas_live)
+                    .service(create_timelapse)
+                    .service(timelapse_status)
+                    .service(timelapse_output)
+                    .service(funding_status)
+                    .service(ledger_report)
+                    .service(list_events)
+                    .service(create_event)
+                    .service(event_info)
+                    .service(publish_event)
+                    .service(register_for_event)
+                    .service(event_submissions)
+                    .service(submit_to_event)
+                    .service(vote_in_event)
+                    .service(jury_vote_in_event)
+                    .service(override_event_winner)
+                    .service(event_results)
+                    .service(cancel_event)
+                    .service(list_payouts)
+                    .service(payout_audit)
+                    .service(approve_payout)
+                    .service(reject_payout)
+                    .service(rollback_placements)
+                    .service(list_bans)
+                    .service(ban_account)
+                    .service(unban_account)
+                    .service(report_pixel)
+                    .service(list_reports)
+                    .service(review_report)
+                    .configure(|api| {
+                        if let Some(oidc) = &oidc {
+                            api.app_data(oidc.clone())
+                                .service(oidc_login)
+                                .service(oidc_callback);
+                        }
+                    })
+                    .configure(|api| {
+                        if let Some(payments) = &payments {
+                            api.app_data(payments.clone())
+                                .service(create_checkout)
+                                .service(payment_webhook)
+                                .service(payment_status);
+                        }
+                    }),
+            )
+    });
+
+    if let Some(workers) = config
+        .server
+        .workers
+    {
+        server = server.workers(workers.get());
+    }
+
+    let server = server
+        .shutdown_signal(shutdown)
+        .shutdown_timeout(SHUTDOWN_TIMEOUT)
+        .bind(
+            config
+                .server
+                .bind,
+        )?
+        .run();
+    server_readiness.set_ready(true);
+    server.await?;
+
+    // nothing changes the canvas anymore, the snapshot covers every placement.
+    final_canvas.close();
+    if let Some(checkpoint) = final_canvas.checkpoint(&snapshot_policy)? {
+        info!("Final canvas snapshot written at revision {}.", checkpoint.revision);
+    }
 
     Ok(())
 }
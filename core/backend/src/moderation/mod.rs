@@ -0,0 +1,573 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::Error as IoError;
+use std::net::IpAddr;
+use std::path::Path;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use shared::api::{
+    ApiErrorBody,
+    ApiErrorCode,
+    BanInfo,
+    BanKind,
+    BanRequest,
+    ReportInfo,
+    ReportRequest,
+    ReportStatus,
+    RollbackRequest,
+};
+use shared::canvas::PixelCoord;
+use thiserror::Error;
+
+use crate::canvas::history::IndexedPlacement;
+use crate::utils::api_error::ApiError;
+use crate::utils::client::client_network;
+use crate::utils::journal::Journal;
+
+const JOURNAL_FILE: &str = "moderation.jsonl";
+/// Longest report or ban reason in characters.
+pub const MAX_REASON_LENGTH: usize = 500;
+
+#[derive(Error, Debug)]
+pub enum ModerationError {
+    #[error("This account is banned.")]
+    Banned,
+
+    #[error("Placements from this address need a login.")]
+    AddressBanned,
+
+    #[error("The account {0} doesn't exist.")]
+    UnknownAccount(u64),
+
+    #[error("Only accounts of a lower role can be banned.")]
+    Outranked,
+
+    #[error("The account {0} isn't banned.")]
+    NotBanned(u64),
+
+    #[error("The report {0} doesn't exist.")]
+    ReportNotFound(u64),
+
+    #[error("Reasons are 1 to {MAX_REASON_LENGTH} characters long.")]
+    InvalidReason,
+
+    #[error("A rollback needs an account, a region or both.")]
+    InvalidRollback,
+
+    #[error("Reports can only be resolved or dismissed.")]
+    InvalidReview,
+
+    #[error("The report {0} was already reviewed.")]
+    AlreadyReviewed(u64),
+
+    #[error("The moderation storage failed: {0}")]
+    Storage(#[from] IoError),
+}
+
+impl ResponseError for ModerationError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Banned | Self::AddressBanned | Self::Outranked => StatusCode::FORBIDDEN,
+            Self::UnknownAccount(_) | Self::NotBanned(_) | Self::ReportNotFound(_) => {
+                StatusCode::NOT_FOUND
+            },
+            Self::InvalidReason | Self::InvalidRollback | Self::InvalidReview => {
+                StatusCode::BAD_REQUEST
+            },
+            Self::AlreadyReviewed(_) => StatusCode::CONFLICT,
+            Self::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let body = match self {
+            Self::Banned | Self::AddressBanned | Self::Outranked => {
+                ApiErrorBody::new(ApiErrorCode::Forbidden, self.to_string())
+            },
+            Self::UnknownAccount(_) | Self::NotBanned(_) | Self::ReportNotFound(_) => {
+                ApiErrorBody::new(ApiErrorCode::NotFound, self.to_string())
+            },
+            Self::InvalidReason | Self::InvalidRollback | Self::InvalidReview => {
+                ApiErrorBody::new(ApiErrorCode::InvalidRequest, self.to_string())
+            },
+            Self::AlreadyReviewed(_) => ApiErrorBody::new(ApiErrorCode::Conflict, self.to_string()),
+            Self::Storage(_) => {
+                ApiErrorBody::new(ApiErrorCode::Internal, "The moderation couldn't be updated.")
+            },
+        };
+
+        ApiError::new(self.status_code(), body).error_response()
+    }
+}
+
+/// Trims a reason and fails unless it's 1 to [`MAX_REASON_LENGTH`]
+/// characters long.
+fn check_reason(reason: &str) -> Result<String, ModerationError> {
+    let reason = reason.trim();
+
+    if reason.is_empty()
+        || reason
+            .chars()
+            .count()
+            > MAX_REASON_LENGTH
+    {
+        return Err(ModerationError::InvalidReason);
+    }
+
+    Ok(reason.to_owned())
+}
+
+/// Which placements a [`RollbackRequest`] reverts, for
+/// [`CanvasStore::rollback`].
+///
+/// [`CanvasStore::rollback`]: crate::canvas::CanvasStore::rollback
+pub fn reverted_by(
+    request: &RollbackRequest,
+) -> Result<impl Fn(PixelCoord, &IndexedPlacement) -> bool + use<>, ModerationError> {
+    let RollbackRequest { account, region, since } = *request;
+
+    if account.is_none() && region.is_none() {
+        return Err(ModerationError::InvalidRollback);
+    }
+
+    Ok(move |coord: PixelCoord, placement: &IndexedPlacement| {
+        placement.placed_at >= since
+            && account.is_none_or(|account| placement.author == Some(account))
+            && region.is_none_or(|region| region.contains(coord))
+    })
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Ban {
+    pub account: u64,
+    pub kind: BanKind,
+    pub reason: Option<String>,
+    pub banned_by: u64,
+    /// Unix milliseconds.
+    pub banned_at: u64,
+    /// The networks, as grouped by [`client_network`], the account placed
+    /// from last before the ban and since. Nobody places from there without
+    /// logging in, shadow bans cover none.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<IpAddr>,
+}
+
+impl Ban {
+    pub fn info(&self) -> BanInfo {
+        BanInfo {
+            account: self.account,
+            kind: self.kind,
+            reason: self
+                .reason
+                .clone(),
+            banned_by: self.banned_by,
+            banned_at: self.banned_at,
+        }
+    }
+}
+
+/// A pixel a user flagged for the moderators.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub id: u64,
+    pub coord: PixelCoord,
+    pub reason: String,
+    pub reporter: u64,
+    pub status: ReportStatus,
+    /// Unix milliseconds.
+    pub created_at: u64,
+    pub reviewed_by: Option<u64>,
+    /// Unix milliseconds.
+    pub reviewed_at: Option<u64>,
+}
+
+impl Report {
+    pub fn info(&self) -> ReportInfo {
+        ReportInfo {
+            id: self.id,
+            coord: self.coord,
+            reason: self
+                .reason
+                .clone(),
+            reporter: self.reporter,
+            status: self.status,
+            created_at: self.created_at,
+            reviewed_by: self.reviewed_by,
+            reviewed_at: self.reviewed_at,
+        }
+    }
+}
+
+/// A change to the moderation state, as stored in the journal.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum ModerationEntry {
+    /// Wrapped since the kind of the ban would clash with the tag.
+    Banned {
+        ban: Ban,
+    },
+    Unbanned {
+        account: u64,
+        by: u64,
+        at: u64,
+    },
+    Reported(Report),
+    Reviewed {
+        report: u64,
+        status: ReportStatus,
+        by: u64,
+        at: u64,
+    },
+}
+
+#[derive(Default)]
+struct ModerationState {
+    bans: BTreeMap<u64, Ban>,
+    /// The banned accounts behind each network of a ban.
+    by_address: HashMap<IpAddr, BTreeSet<u64>>,
+    /// The network each account placed from last, only kept in memory.
+    last_addresses: HashMap<u64, IpAddr>,
+    reports: BTreeMap<u64, Report>,
+}
+
+impl ModerationState {
+    fn apply(&mut self, entry: ModerationEntry) {
+        match entry {
+            ModerationEntry::Banned { ban } => {
+                self.lift(ban.account);
+
+                if ban.kind == BanKind::Ban {
+                    for address in &ban.addresses {
+                        self.by_address
+                            .entry(client_network(*address))
+                            .or_default()
+                            .insert(ban.account);
+                    }
+                }
+
+                self.bans
+                    .insert(ban.account, ban);
+            },
+            ModerationEntry::Unbanned { account, .. } => self.lift(account),
+            ModerationEntry::Reported(report) => {
+                self.reports
+                    .insert(report.id, report);
+            },
+            ModerationEntry::Reviewed { report, status, by, at } => {
+                if let Some(report) = self
+                    .reports
+                    .get_mut(&report)
+                {
+                    report.status = status;
+                    report.reviewed_by = Some(by);
+                    report.reviewed_at = Some(at);
+                }
+            },
+        }
+    }
+
+    /// Takes the ban of `account` and its addresses away.
+    fn lift(&mut self, account: u64) {
+        let Some(ban) = self
+            .bans
+            .remove(&account)
+        else {
+            return;
+        };
+
+        for address in ban.addresses {
+            let network = client_network(address);
+
+            if let Some(accounts) = self
+                .by_address
+                .get_mut(&network)
+            {
+                accounts.remove(&account);
+
+                if accounts.is_empty() {
+                    self.by_address
+                        .remove(&network);
+                }
+            }
+        }
+    }
+}
+
+/// Bans and the pixel report queue.
+///
+/// Kept in memory, when opened from a directory every change is appended
+/// to a journal before it's applied.
+pub struct Moderation {
+    state: Mutex<ModerationState>,
+    journal: Option<Mutex<Journal<ModerationEntry>>>,
+}
+
+impl Moderation {
+    /// Moderation that only lives in memory.
+    pub fn new() -> Self {
+        Self { state: Mutex::default(), journal: None }
+    }
+
+    /// Loads the moderation state stored in `dir`, or starts recording it
+    /// there.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, ModerationError> {
+        let (journal, entries) = Journal::open(
+            dir.as_ref()
+                .join(JOURNAL_FILE),
+        )?;
+        let mut state = ModerationState::default();
+
+        for entry in entries {
+            state.apply(entry);
+        }
+
+        Ok(Self {
+            state: Mutex::new(state),
+            journal: Some(Mutex::new(journal)),
+        })
+    }
+
+    fn append(&self, entry: &ModerationEntry) -> Result<(), ModerationError> {
+        if let Some(journal) = &self.journal {
+            journal
+                .lock()
+                .append(entry)?;
+        }
+
+        Ok(())
+    }
+
+    /// How `account` is banned, `None` if it isn't.
+    pub fn ban_of(&self, account: u64) -> Option<BanKind> {
+        self.state
+            .lock()
+            .bans
+            .get(&account)
+            .map(|ban| ban.kind)
+    }
+
+    /// How anonymous placements from `address` are banned, `None` if no ban
+    /// covers its network.
+    pub fn ban_of_address(&self, address: IpAddr) -> Option<BanKind> {
+        self.state
+            .lock()
+            .by_address
+            .contains_key(&client_network(address))
+            .then_some(BanKind::Ban)
+    }
+
+    /// Remembers that `account` placed from `address`, a banned account has
+    /// its network added to its ban.
+    pub fn placed_from(&self, account: u64, address: IpAddr) -> Result<(), ModerationError> {
+        let network = client_network(address);
+        let mut state = self
+            .state
+            .lock();
+
+        state
+            .last_addresses
+            .insert(account, network);
+
+        let Some(ban) = state
+            .bans
+            .get(&account)
+            .filter(|ban| {
+                ban.kind == BanKind::Ban
+                    && !ban
+                        .addresses
+                        .contains(&network)
+            })
+        else {
+            return Ok(());
+        };
+        let mut ban = ban.clone();
+
+        ban.addresses
+            .push(network);
+
+        self.append(&ModerationEntry::Banned { ban: ban.clone() })?;
+        state.apply(ModerationEntry::Banned { ban });
+
+        Ok(())
+    }
+
+    pub fn bans(&self) -> Vec<Ban> {
+        self.state
+            .lock()
+            .bans
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Bans `account`, replacing an earlier ban of it. A ban covers the
+    /// networks of the earlier one and the network the account placed from
+    /// last, a shadow ban covers none so it stays unnoticed.
+    pub fn ban(
+        &self,
+        account: u64,
+        request: BanRequest,
+        by: u64,
+        now: u64,
+    ) -> Result<Ban, ModerationError> {
+        let reason = request
+            .reason
+            .as_deref()
+            .map(check_reason)
+            .transpose()?;
+        let mut state = self
+            .state
+            .lock();
+        let mut addresses = state
+            .bans
+            .get(&account)
+            .map(|ban| {
+                ban.addresses
+                    .clone()
+            })
+            .unwrap_or_default();
+
+        if let Some(&address) = state
+            .last_addresses
+            .get(&account)
+            && !addresses.contains(&address)
+        {
+            addresses.push(address);
+        }
+
+        if request.kind == BanKind::ShadowBan {
+            addresses.clear();
+        }
+
+        let ban = Ban {
+            account,
+            kind: request.kind,
+            reason,
+            banned_by: by,
+            banned_at: now,
+            addresses,
+        };
+
+        self.append(&ModerationEntry::Banned { ban: ban.clone() })?;
+        state.apply(ModerationEntry::Banned { ban: ban.clone() });
+
+        Ok(ban)
+    }
+
+    pub fn unban(&self, account: u64, by: u64, now: u64) -> Result<(), ModerationError> {
+        let mut state = self
+            .state
+            .lock();
+
+        if !state
+            .bans
+            .contains_key(&account)
+        {
+            return Err(ModerationError::NotBanned(account));
+        }
+
+        let entry = ModerationEntry::Unbanned { account, by, at: now };
+
+        self.append(&entry)?;
+        state.apply(entry);
+
+        Ok(())
+    }
+
+    /// Files a report by `reporter` into the review queue.
+    ///
+    /// Banned accounts can't report, shadow banned ones can.
+    pub fn report(
+        &self,
+        reporter: u64,
+        request: ReportRequest,
+        now: u64,
+    ) -> Result<Report, ModerationError> {
+        let reason = check_reason(&request.reason)?;
+        let mut state = self
+            .state
+            .lock();
+
+        if state
+            .bans
+            .get(&reporter)
+            .is_some_and(|ban| ban.kind == BanKind::Ban)
+        {
+            return Err(ModerationError::Banned);
+        }
+
+        let report = Report {
+            id: state
+                .reports
+                .last_key_value()
+                .map_or(1, |(id, _)| id + 1),
+            coord: request.coord,
+            reason,
+            reporter,
+            status: ReportStatus::Open,
+            created_at: now,
+            reviewed_by: None,
+            reviewed_at: None,
+        };
+
+        self.append(&ModerationEntry::Reported(report.clone()))?;
+        state.apply(ModerationEntry::Reported(report.clone()));
+
+        Ok(report)
+    }
+
+    /// Reports with `status`, or all of them without it, oldest first.
+    pub fn reports(&self, status: Option<ReportStatus>) -> Vec<Report> {
+        self.state
+            .lock()
+            .reports
+            .values()
+            .filter(|report| status.is_none_or(|status| report.status == status))
+            .cloned()
+            .collect()
+    }
+
+    /// Takes an open report out of the queue as resolved or dismissed.
+    pub fn review(
+        &self,
+        id: u64,
+        status: ReportStatus,
+        by: u64,
+        now: u64,
+    ) -> Result<Report, ModerationError> {
+        if status == ReportStatus::Open {
+            return Err(ModerationError::InvalidReview);
+        }
+
+        let mut state = self
+            .state
+            .lock();
+        let report = state
+            .reports
+            .get(&id)
+            .ok_or(ModerationError::ReportNotFound(id))?;
+
+        if report.status != ReportStatus::Open {
+            return Err(ModerationError::AlreadyReviewed(id));
+        }
+
+        let entry = ModerationEntry::Reviewed { report: id, status, by, at: now };
+
+        self.append(&entry)?;
+        state.apply(entry);
+
+        state
+            .reports
+            .get(&id)
+            .cloned()
+            .ok_or(ModerationError::ReportNotFound(id))
+    }
+}
+
+impl Default for Moderation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,294 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use actix_web::Error;
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::web::Data;
+use parking_lot::Mutex;
+use shared::api::ApiErrorCode;
+
+/// Route placements are made through.
+const PLACEMENT_ROUTE: &str = "/api/canvas/pixels";
+/// Label of the requests that matched no route, their paths would make for
+/// endless series.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Upper bounds of the HTTP latency buckets in seconds.
+const LATENCY_BUCKETS: [f64; 11] =
+    [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5];
+/// Upper bounds of the snapshot duration buckets in seconds.
+const SNAPSHOT_BUCKETS: [f64; 9] = [0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Observations counted into buckets, as a Prometheus histogram.
+#[derive(Clone, Debug)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    /// Observations per bucket, not cumulative, the last one past every
+    /// bound.
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len() + 1],
+            sum: 0.0,
+        }
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|&bound| value <= bound)
+            .unwrap_or(
+                self.bounds
+                    .len(),
+            );
+
+        if let Some(count) = self
+            .counts
+            .get_mut(bucket)
+        {
+            *count += 1;
+        }
+        self.sum += value;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts
+            .iter()
+            .sum()
+    }
+
+    /// Appends the bucket, sum and count series of `name` with `labels`
+    /// put before the bucket bound.
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+
+        for (bound, count) in self
+            .bounds
+            .iter()
+            .map(ToString::to_string)
+            .chain(["+Inf".to_owned()])
+            .zip(&self.counts)
+        {
+            cumulative += count;
+            let _ = writeln!(out, "{name}_bucket{{{labels}le=\"{bound}\"}} {cumulative}");
+        }
+
+        let labels = labels.trim_end_matches(',');
+        let labels = if labels.is_empty() { String::new() } else { format!("{{{labels}}}") };
+
+        let _ = writeln!(out, "{name}_sum{labels} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{labels} {}", self.count());
+    }
+}
+
+/// Escapes a label value for the text format.
+fn label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+struct MetricsState {
+    /// Per machine readable reason.
+    rejections: BTreeMap<String, u64>,
+    snapshots: Histogram,
+    /// Per method and route pattern.
+    requests: BTreeMap<(String, String), Histogram>,
+}
+
+/// What the server did since it started, scraped through `/metrics`.
+pub struct Metrics {
+    placements: AtomicU64,
+    state: Mutex<MetricsState>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            placements: AtomicU64::new(0),
+            state: Mutex::new(MetricsState {
+                rejections: BTreeMap::new(),
+                snapshots: Histogram::new(&SNAPSHOT_BUCKETS),
+                requests: BTreeMap::new(),
+            }),
+        }
+    }
+
+    pub fn record_placement(&self) {
+        self.placements
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn placements(&self) -> u64 {
+        self.placements
+            .load(Ordering::Relaxed)
+    }
+
+    pub fn record_rejection(&self, reason: ApiErrorCode) {
+        *self
+            .state
+            .lock()
+            .rejections
+            .entry(reason.to_string())
+            .or_default() += 1;
+    }
+
+    pub fn rejections(&self, reason: ApiErrorCode) -> u64 {
+        self.state
+            .lock()
+            .rejections
+            .get(&reason.to_string())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn record_snapshot(&self, duration: Duration) {
+        self.state
+            .lock()
+            .snapshots
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn snapshots(&self) -> Histogram {
+        self.state
+            .lock()
+            .snapshots
+            .clone()
+    }
+
+    pub fn record_request(&self, method: &str, route: &str, duration: Duration) {
+        self.state
+            .lock()
+            .requests
+            .entry((method.to_owned(), route.to_owned()))
+            .or_insert_with(|| Histogram::new(&LATENCY_BUCKETS))
+            .observe(duration.as_secs_f64());
+    }
+
+    /// Every metric in the Prometheus text format, along with the realtime
+    /// `sockets` connected right now.
+    pub fn render(&self, sockets: usize) -> String {
+        let state = self
+            .state
+            .lock();
+        let mut out = String::new();
+
+        // a rate over this counter gives the placements per second.
+        header(&mut out, "canvadot_placements_total", "counter", "Pixels placed.");
+        let _ = writeln!(out, "canvadot_placements_total {}", self.placements());
+
+        header(
+            &mut out,
+            "canvadot_placement_rejections_total",
+            "counter",
+            "Placements refused, by reason.",
+        );
+        for (reason, count) in &state.rejections {
+            let _ = writeln!(
+                out,
+                "canvadot_placement_rejections_total{{reason=\"{}\"}} {count}",
+                label(reason)
+            );
+        }
+
+        header(&mut out, "canvadot_connected_sockets", "gauge", "Realtime sessions connected.");
+        let _ = writeln!(out, "canvadot_connected_sockets {sockets}");
+
+        header(
+            &mut out,
+            "canvadot_snapshot_duration_seconds",
+            "histogram",
+            "Time taken to write canvas snapshots.",
+        );
+        state
+            .snapshots
+            .render(&mut out, "canvadot_snapshot_duration_seconds", "");
+
+        header(
+            &mut out,
+            "canvadot_http_request_duration_seconds",
+            "histogram",
+            "Time taken to answer HTTP requests, by method and route.",
+        );
+        for ((method, route), histogram) in &state.requests {
+            histogram.render(
+                &mut out,
+                "canvadot_http_request_duration_seconds",
+                &format!("method=\"{}\",route=\"{}\",", label(method), label(route)),
+            );
+        }
+
+        out
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Times every request by its route and counts placements along with the
+/// reasons they were refused for, into the [`Metrics`] of the app.
+pub async fn track_requests(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let Some(metrics) = request
+        .app_data::<Data<Metrics>>()
+        .cloned()
+    else {
+        return next
+            .call(request)
+            .await;
+    };
+    let started = Instant::now();
+    let method = request
+        .method()
+        .clone();
+    let route = request
+        .match_pattern()
+        .unwrap_or_else(|| UNMATCHED_ROUTE.to_owned());
+
+    let response = next
+        .call(request)
+        .await?;
+
+    metrics.record_request(method.as_str(), &route, started.elapsed());
+
+    if method == Method::POST && route == PLACEMENT_ROUTE {
+        let status = response.status();
+
+        if status.is_success() {
+            metrics.record_placement();
+        } else {
+            metrics.record_rejection(
+                response
+                    .response()
+                    .extensions()
+                    .get::<ApiErrorCode>()
+                    .copied()
+                    .unwrap_or_else(|| ApiErrorCode::from_status(status.as_u16())),
+            );
+        }
+    }
+
+    Ok(response)
+}
@@ -0,0 +1,31 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub mod metrics;
+
+/// Whether the server should get traffic, off until every store is loaded
+/// and again once it starts shutting down.
+pub struct Readiness {
+    ready: AtomicBool,
+}
+
+impl Readiness {
+    pub fn new() -> Self {
+        Self { ready: AtomicBool::new(false) }
+    }
+
+    pub fn set_ready(&self, ready: bool) {
+        self.ready
+            .store(ready, Ordering::Release);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready
+            .load(Ordering::Acquire)
+    }
+}
+
+impl Default for Readiness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
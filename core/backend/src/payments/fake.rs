@@ -0,0 +1,224 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use actix_web::http::header::HeaderMap;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use futures_util::FutureExt;
+use futures_util::future::LocalBoxFuture;
+use serde::{Deserialize, Serialize};
+use shared::api::PaymentStatus;
+use url::Url;
+
+use crate::payments::signature::{sign, verify};
+use crate::payments::{
+    Payment,
+    PaymentError,
+    PaymentProvider,
+    PayoutOrder,
+    ProviderCheckout,
+    WebhookEvent,
+};
+use crate::utils::http::FetchRequest;
+
+/// Header the fake provider signs its webhooks in.
+pub const SIGNATURE_HEADER: &str = "Fake-Signature";
+
+/// The webhook body of the fake provider.
+#[derive(Serialize, Deserialize, Debug)]
+struct FakeEvent {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    checkout: String,
+}
+
+fn event_type(status: PaymentStatus) -> &'static str {
+    match status {
+        PaymentStatus::Pending => "checkout.pending",
+        PaymentStatus::Succeeded => "checkout.completed",
+        PaymentStatus::Failed => "checkout.failed",
+        PaymentStatus::Refunded => "charge.refunded",
+    }
+}
+
+fn event_status(kind: &str) -> Option<PaymentStatus> {
+    match kind {
+        "checkout.pending" => Some(PaymentStatus::Pending),
+        "checkout.completed" => Some(PaymentStatus::Succeeded),
+        "checkout.failed" | "checkout.expired" => Some(PaymentStatus::Failed),
+        "charge.refunded" => Some(PaymentStatus::Refunded),
+        _ => None,
+    }
+}
+
+/// A payment provider that never talks to anyone, for development and tests.
+///
+/// Checkouts get a `fake://` URL nobody can open, payments only move on once
+/// a [`WebhookSimulator`] sends the webhooks a real provider would.
+pub struct FakePaymentProvider {
+    secret: Vec<u8>,
+    unavailable: AtomicBool,
+}
+
+impl FakePaymentProvider {
+    /// A provider signing its webhooks with `secret`.
+    pub fn new(secret: impl Into<Vec<u8>>) -> Self {
+        Self {
+            secret: secret.into(),
+            unavailable: AtomicBool::new(false),
+        }
+    }
+
+    /// Makes opening checkouts, refunds and payouts fail as if the provider
+    /// was down.
+    pub fn set_unavailable(&self, unavailable: bool) {
+        self.unavailable
+            .store(unavailable, Ordering::Relaxed);
+    }
+
+    /// Sends webhooks signed like the ones of this provider.
+    pub fn simulator(&self) -> WebhookSimulator {
+        WebhookSimulator {
+            secret: self
+                .secret
+                .clone(),
+        }
+    }
+}
+
+impl FakePaymentProvider {
+    fn check_available(&self) -> Result<(), PaymentError> {
+        if self
+            .unavailable
+            .load(Ordering::Relaxed)
+        {
+            return Err(PaymentError::Provider("the fake provider is unavailable".to_owned()));
+        }
+
+        Ok(())
+    }
+}
+
+impl PaymentProvider for FakePaymentProvider {
+    fn create_checkout<'a>(
+        &'a self,
+        _payment: &'a Payment,
+    ) -> LocalBoxFuture<'a, Result<ProviderCheckout, PaymentError>> {
+        async move {
+            self.check_available()?;
+
+            let reference = format!("cs_fake_{:016x}", OsRng.next_u64());
+
+            Ok(ProviderCheckout {
+                url: format!("fake://checkout/{reference}"),
+                reference,
+            })
+        }
+        .boxed_local()
+    }
+
+    fn parse_webhook(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+        now: u64,
+    ) -> Result<WebhookEvent, PaymentError> {
+        let signature = headers
+            .get(SIGNATURE_HEADER)
+            .and_then(|value| {
+                value
+                    .to_str()
+                    .ok()
+            })
+            .ok_or(PaymentError::InvalidSignature)?;
+
+        if !verify(&self.secret, signature, body, now / 1000) {
+            return Err(PaymentError::InvalidSignature);
+        }
+
+        let event = serde_json::from_slice::<FakeEvent>(body)
+            .map_err(|_| PaymentError::InvalidWebhook("it isn't a fake provider event"))?;
+
+        Ok(WebhookEvent {
+            status: event_status(&event.kind)
+                .ok_or(PaymentError::InvalidWebhook("the event type is unknown"))?,
+            id: event.id,
+            reference: event.checkout,
+        })
+    }
+
+    fn refund<'a>(
+        &'a self,
+        _payment: &'a Payment,
+        _idempotency_key: &'a str,
+    ) -> LocalBoxFuture<'a, Result<(), PaymentError>> {
+        async move { self.check_available() }.boxed_local()
+    }
+
+    fn send_payout<'a>(
+        &'a self,
+        _order: &'a PayoutOrder,
+    ) -> LocalBoxFuture<'a, Result<String, PaymentError>> {
+        async move {
+            self.check_available()?;
+
+            Ok(format!("po_fake_{:016x}", OsRng.next_u64()))
+        }
+        .boxed_local()
+    }
+}
+
+/// A webhook as the fake provider would deliver it.
+#[derive(Clone, Debug)]
+pub struct SimulatedWebhook {
+    pub id: String,
+    pub body: Vec<u8>,
+    /// Value of the [`SIGNATURE_HEADER`].
+    pub signature: String,
+}
+
+impl SimulatedWebhook {
+    /// The delivery of this webhook to `url`, to be sent with any
+    /// [`HttpClient`](crate::utils::http::HttpClient).
+    pub fn request(&self, url: Url) -> FetchRequest {
+        FetchRequest {
+            method: "POST",
+            url,
+            headers: Vec::new(),
+            body: self
+                .body
+                .clone(),
+        }
+        .header("Content-Type", "application/json")
+        .header(
+            SIGNATURE_HEADER,
+            self.signature
+                .as_str(),
+        )
+    }
+}
+
+/// Plays the part of the fake provider's servers, builds the signed webhooks
+/// it sends when a payer finishes, abandons or gets back a payment.
+pub struct WebhookSimulator {
+    secret: Vec<u8>,
+}
+
+impl WebhookSimulator {
+    /// A webhook moving the checkout `reference` to `status`, signed at `now`
+    /// in unix milliseconds.
+    pub fn event(&self, reference: &str, status: PaymentStatus, now: u64) -> SimulatedWebhook {
+        let event = FakeEvent {
+            id: format!("evt_fake_{:016x}", OsRng.next_u64()),
+            kind: event_type(status).to_owned(),
+            checkout: reference.to_owned(),
+        };
+        // a struct of strings always serializes.
+        let body = serde_json::to_vec(&event).unwrap_or_default();
+
+        SimulatedWebhook {
+            signature: sign(&self.secret, now / 1000, &body),
+            id: event.id,
+            body,
+        }
+    }
+}
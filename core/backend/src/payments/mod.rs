@@ -0,0 +1,654 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Error as IoError;
+use std::path::Path;
+use std::sync::Arc;
+
+use actix_web::http::StatusCode;
+use actix_web::http::header::HeaderMap;
+use actix_web::{HttpResponse, ResponseError};
+use futures_util::future::LocalBoxFuture;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use shared::api::{
+    ApiErrorBody,
+    ApiErrorCode,
+    CheckoutRequest,
+    PaymentInfo,
+    PaymentPurpose,
+    PaymentStatus,
+};
+use thiserror::Error;
+
+use crate::utils::api_error::ApiError;
+use crate::utils::journal::Journal;
+
+pub mod fake;
+pub mod signature;
+
+const JOURNAL_FILE: &str = "payments.jsonl";
+/// Largest single payment, anything above is most likely a typo.
+pub const MAX_AMOUNT_CENTS: u64 = 10_000 * 100;
+
+#[derive(Error, Debug)]
+pub enum PaymentError {
+    #[error("A payment has to be between 1 and {MAX_AMOUNT_CENTS} cents.")]
+    InvalidAmount,
+
+    #[error("Entry fees are paid by registering for the event.")]
+    EntryOutsideRegistration,
+
+    #[error("The payment {0} doesn't exist.")]
+    NotFound(u64),
+
+    #[error("The idempotency key was already used for another payment.")]
+    IdempotencyConflict,
+
+    #[error("The webhook signature doesn't match.")]
+    InvalidSignature,
+
+    #[error("The webhook couldn't be read: {0}")]
+    InvalidWebhook(&'static str),
+
+    #[error("The webhook is about the unknown checkout {0}.")]
+    UnknownCheckout(String),
+
+    #[error("The payment provider failed: {0}")]
+    Provider(String),
+
+    #[error("The payment storage failed: {0}")]
+    Storage(#[from] IoError),
+}
+
+impl ResponseError for PaymentError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InvalidAmount | Self::EntryOutsideRegistration | Self::InvalidWebhook(_) => {
+                StatusCode::BAD_REQUEST
+            },
+            Self::NotFound(_) | Self::UnknownCheckout(_) => StatusCode::NOT_FOUND,
+            Self::IdempotencyConflict => StatusCode::CONFLICT,
+            Self::InvalidSignature => StatusCode::UNAUTHORIZED,
+            Self::Provider(_) => StatusCode::BAD_GATEWAY,
+            Self::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let body = match self {
+            Self::InvalidAmount | Self::EntryOutsideRegistration | Self::InvalidWebhook(_) => {
+                ApiErrorBody::new(ApiErrorCode::InvalidRequest, self.to_string())
+            },
+            Self::NotFound(_) | Self::UnknownCheckout(_) => {
+                ApiErrorBody::new(ApiErrorCode::NotFound, self.to_string())
+            },
+            Self::IdempotencyConflict => {
+                ApiErrorBody::new(ApiErrorCode::Conflict, self.to_string())
+            },
+            Self::InvalidSignature => {
+                ApiErrorBody::new(ApiErrorCode::Unauthorized, self.to_string())
+            },
+            Self::Provider(_) => ApiErrorBody::new(
+                ApiErrorCode::Unavailable,
+                "The payment provider can't be reached right now.",
+            ),
+            Self::Storage(_) => {
+                ApiErrorBody::new(ApiErrorCode::Internal, "The payment couldn't be recorded.")
+            },
+        };
+
+        ApiError::new(self.status_code(), body).error_response()
+    }
+}
+
+/// A checkout opened with the provider.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProviderCheckout {
+    /// How the provider refers to the checkout in its webhooks.
+    pub reference: String,
+    pub url: String,
+}
+
+/// A verified webhook, mapped to our payment states.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WebhookEvent {
+    /// Unique per event, providers deliver the same event again until they
+    /// see it accepted.
+    pub id: String,
+    /// The checkout the event is about.
+    pub reference: String,
+    pub status: PaymentStatus,
+}
+
+/// Money sent to an account, like the prize of an event.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PayoutOrder {
+    /// Unique per payout, providers use it to not send the same payout twice.
+    pub id: u64,
+    pub account: u64,
+    pub amount_cents: u64,
+}
+
+/// A payment service contributions and entries are paid through and prizes
+/// are paid out with.
+pub trait PaymentProvider: Send + Sync {
+    /// Opens a hosted checkout for `payment`.
+    fn create_checkout<'a>(
+        &'a self,
+        payment: &'a Payment,
+    ) -> LocalBoxFuture<'a, Result<ProviderCheckout, PaymentError>>;
+
+    /// Verifies the signature of a webhook and reads the event it carries,
+    /// `now` is in unix milliseconds.
+    fn parse_webhook(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+        now: u64,
+    ) -> Result<WebhookEvent, PaymentError>;
+
+    /// Returns the money of a succeeded payment to the payer. Retries carry
+    /// the same `idempotency_key`, the provider refunds it only once.
+    fn refund<'a>(
+        &'a self,
+        payment: &'a Payment,
+        idempotency_key: &'a str,
+    ) -> LocalBoxFuture<'a, Result<(), PaymentError>>;
+
+    /// Sends money to an account, returns how the provider refers to the
+    /// transfer.
+    fn send_payout<'a>(
+        &'a self,
+        order: &'a PayoutOrder,
+    ) -> LocalBoxFuture<'a, Result<String, PaymentError>>;
+}
+
+/// A shared provider, lets the owner keep a handle to it after handing it to
+/// [`Payments`].
+impl<P: PaymentProvider + ?Sized> PaymentProvider for Arc<P> {
+    fn create_checkout<'a>(
+        &'a self,
+        payment: &'a Payment,
+    ) -> LocalBoxFuture<'a, Result<ProviderCheckout, PaymentError>> {
+        (**self).create_checkout(payment)
+    }
+
+    fn parse_webhook(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+        now: u64,
+    ) -> Result<WebhookEvent, PaymentError> {
+        (**self).parse_webhook(headers, body, now)
+    }
+
+    fn refund<'a>(
+        &'a self,
+        payment: &'a Payment,
+        idempotency_key: &'a str,
+    ) -> LocalBoxFuture<'a, Result<(), PaymentError>> {
+        (**self).refund(payment, idempotency_key)
+    }
+
+    fn send_payout<'a>(
+        &'a self,
+        order: &'a PayoutOrder,
+    ) -> LocalBoxFuture<'a, Result<String, PaymentError>> {
+        (**self).send_payout(order)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Payment {
+    pub id: u64,
+    /// `None` for anonymous payments.
+    pub account: Option<u64>,
+    pub amount_cents: u64,
+    pub purpose: PaymentPurpose,
+    pub status: PaymentStatus,
+    /// Set once the checkout is opened.
+    pub reference: String,
+    pub checkout_url: String,
+    /// Retries of a checkout with the same key get the same payment back.
+    pub idempotency_key: Option<String>,
+    /// Unix milliseconds.
+    pub created_at: u64,
+    /// Unix milliseconds.
+    pub updated_at: u64,
+}
+
+impl Payment {
+    pub fn info(&self) -> PaymentInfo {
+        PaymentInfo {
+            id: self.id,
+            amount_cents: self.amount_cents,
+            purpose: self
+                .purpose
+                .clone(),
+            status: self.status,
+            checkout_url: self
+                .checkout_url
+                .clone(),
+        }
+    }
+}
+
+/// Whether a payment may move from `from` to `to`, events arriving out of
+/// order or for settled payments are ignored.
+fn allowed(from: PaymentStatus, to: PaymentStatus) -> bool {
+    matches!(
+        (from, to),
+        (PaymentStatus::Pending, PaymentStatus::Succeeded | PaymentStatus::Failed)
+            | (PaymentStatus::Failed, PaymentStatus::Succeeded)
+            | (PaymentStatus::Succeeded, PaymentStatus::Refunded)
+    )
+}
+
+/// A change to the payments, as stored in the journal.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum PaymentEntry {
+    Created(Payment),
+    /// A webhook event was handled or a refund issued, `status` is `None`
+    /// when it didn't change anything.
+    Event {
+        event: String,
+        payment: u64,
+        status: Option<PaymentStatus>,
+        at: u64,
+    },
+    /// A refund is being asked of the provider, it's done once the matching
+    /// `refund:` event is recorded.
+    RefundStarted {
+        payment: u64,
+        at: u64,
+    },
+}
+
+/// A payment that changed status through a webhook or a refund.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaymentUpdate {
+    pub payment: Payment,
+    pub previous: PaymentStatus,
+}
+
+impl PaymentUpdate {
+    #[inline]
+    pub fn changed(&self) -> bool {
+        self.previous
+            != self
+                .payment
+                .status
+    }
+}
+
+#[derive(Default)]
+struct PaymentsState {
+    /// Ids of checkouts the provider failed to open are skipped.
+    payments: BTreeMap<u64, Payment>,
+    by_reference: HashMap<String, u64>,
+    by_key: HashMap<(Option<u64>, String), u64>,
+    events: HashSet<String>,
+    /// Payments a refund was asked for, finished or not.
+    refunds_started: HashSet<u64>,
+    /// Payments a refund is being asked for right now, not journaled.
+    refunds_in_flight: HashSet<u64>,
+    /// The last id handed out, checkouts still being opened included.
+    next_id: u64,
+}
+
+impl PaymentsState {
+    fn apply(&mut self, entry: PaymentEntry) {
+        match entry {
+            PaymentEntry::Created(payment) => {
+                self.by_reference
+                    .insert(
+                        payment
+                            .reference
+                            .clone(),
+                        payment.id,
+                    );
+
+                if let Some(key) = &payment.idempotency_key {
+                    self.by_key
+                        .insert((payment.account, key.clone()), payment.id);
+                }
+
+                self.next_id = self
+                    .next_id
+                    .max(payment.id);
+                self.payments
+                    .insert(payment.id, payment);
+            },
+            PaymentEntry::Event { event, payment, status, at } => {
+                if let (Some(status), Some(payment)) = (
+                    status,
+                    self.payments
+                        .get_mut(&payment),
+                ) {
+                    payment.status = status;
+                    payment.updated_at = at;
+                }
+
+                self.events
+                    .insert(event);
+            },
+            PaymentEntry::RefundStarted { payment, .. } => {
+                self.refunds_started
+                    .insert(payment);
+            },
+        }
+    }
+}
+
+/// Every payment made through the configured provider.
+///
+/// Kept in memory, when opened from a directory every change is journaled
+/// before it's applied.
+pub struct Payments {
+    provider: Box<dyn PaymentProvider>,
+    state: Mutex<PaymentsState>,
+    journal: Option<Mutex<Journal<PaymentEntry>>>,
+}
+
+impl Payments {
+    /// Payments that only live in memory.
+    pub fn new(provider: impl PaymentProvider + 'static) -> Self {
+        Self {
+            provider: Box::new(provider),
+            state: Mutex::default(),
+            journal: None,
+        }
+    }
+
+    /// Loads the payments stored in `dir`, or starts recording them there.
+    pub fn open(
+        dir: impl AsRef<Path>,
+        provider: impl PaymentProvider + 'static,
+    ) -> Result<Self, PaymentError> {
+        let (journal, entries) = Journal::open(
+            dir.as_ref()
+                .join(JOURNAL_FILE),
+        )?;
+        let mut state = PaymentsState::default();
+
+        for entry in entries {
+            state.apply(entry);
+        }
+
+        Ok(Self {
+            provider: Box::new(provider),
+            state: Mutex::new(state),
+            journal: Some(Mutex::new(journal)),
+        })
+    }
+
+    fn append(&self, entry: &PaymentEntry) -> Result<(), PaymentError> {
+        if let Some(journal) = &self.journal {
+            journal
+                .lock()
+                .append(entry)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn payment(&self, id: u64) -> Option<Payment> {
+        self.state
+            .lock()
+            .payments
+            .get(&id)
+            .cloned()
+    }
+
+    /// Every payment in the order they were made.
+    pub fn payments(&self) -> Vec<Payment> {
+        self.state
+            .lock()
+            .payments
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Opens a checkout with the provider for a new payment.
+    ///
+    /// With an idempotency key, retries by the same account get the payment
+    /// made by the first request back instead of a new one.
+    pub async fn checkout(
+        &self,
+        account: Option<u64>,
+        request: CheckoutRequest,
+        idempotency_key: Option<String>,
+        now: u64,
+    ) -> Result<Payment, PaymentError> {
+        let CheckoutRequest { amount_cents, purpose } = request;
+
+        if !(1..=MAX_AMOUNT_CENTS).contains(&amount_cents) {
+            return Err(PaymentError::InvalidAmount);
+        }
+
+        let existing = |state: &PaymentsState| {
+            let key = idempotency_key.clone()?;
+            let id = state
+                .by_key
+                .get(&(account, key))?;
+
+            state
+                .payments
+                .get(id)
+                .cloned()
+        };
+        let same = |payment: Payment| {
+            if payment.amount_cents == amount_cents && payment.purpose == purpose {
+                Ok(payment)
+            } else {
+                Err(PaymentError::IdempotencyConflict)
+            }
+        };
+
+        let mut payment = {
+            let mut state = self
+                .state
+                .lock();
+
+            if let Some(payment) = existing(&state) {
+                return same(payment);
+            }
+
+            state.next_id += 1;
+
+            Payment {
+                id: state.next_id,
+                account,
+                amount_cents,
+                purpose: purpose.clone(),
+                status: PaymentStatus::Pending,
+                reference: String::new(),
+                checkout_url: String::new(),
+                idempotency_key: idempotency_key.clone(),
+                created_at: now,
+                updated_at: now,
+            }
+        };
+
+        // the provider is called without holding the lock.
+        let checkout = self
+            .provider
+            .create_checkout(&payment)
+            .await?;
+        payment.reference = checkout.reference;
+        payment.checkout_url = checkout.url;
+
+        let mut state = self
+            .state
+            .lock();
+
+        // a retry with the same key may have won the race.
+        if let Some(existing) = existing(&state) {
+            return same(existing);
+        }
+
+        let entry = PaymentEntry::Created(payment.clone());
+
+        self.append(&entry)?;
+        state.apply(entry);
+
+        Ok(payment)
+    }
+
+    /// Verifies a webhook and applies the status it reports.
+    ///
+    /// Returns the payment the webhook is about, along with its status
+    /// before. Events seen before and changes that don't fit the current
+    /// status are accepted without changing anything, so the provider stops
+    /// delivering them, but still return the payment so whatever depends on
+    /// its status can catch up on a retry.
+    pub fn handle_webhook(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+        now: u64,
+    ) -> Result<PaymentUpdate, PaymentError> {
+        let event = self
+            .provider
+            .parse_webhook(headers, body, now)?;
+        let mut state = self
+            .state
+            .lock();
+        let id = *state
+            .by_reference
+            .get(&event.reference)
+            .ok_or(PaymentError::UnknownCheckout(event.reference))?;
+        let previous = state
+            .payments
+            .get(&id)
+            .map(|payment| payment.status)
+            .ok_or(PaymentError::NotFound(id))?;
+
+        if !state
+            .events
+            .contains(&event.id)
+        {
+            let status = allowed(previous, event.status).then_some(event.status);
+            let entry = PaymentEntry::Event {
+                event: event.id,
+                payment: id,
+                status,
+                at: now,
+            };
+
+            self.append(&entry)?;
+            state.apply(entry);
+        }
+
+        Ok(PaymentUpdate {
+            payment: state
+                .payments
+                .get(&id)
+                .cloned()
+                .ok_or(PaymentError::NotFound(id))?,
+            previous,
+        })
+    }
+
+    /// Refunds a succeeded payment through the provider.
+    ///
+    /// The refund is journaled as started before the provider is asked, and
+    /// the provider gets the same idempotency key on every retry. Returns
+    /// `None` for any other payment and while the same refund is underway,
+    /// so refunds can be retried without paying back twice.
+    pub async fn refund(&self, id: u64, now: u64) -> Result<Option<PaymentUpdate>, PaymentError> {
+        let event = format!("refund:{id}");
+        let payment = {
+            let mut state = self
+                .state
+                .lock();
+            let payment = state
+                .payments
+                .get(&id)
+                .cloned()
+                .ok_or(PaymentError::NotFound(id))?;
+
+            if payment.status != PaymentStatus::Succeeded
+                || !state
+                    .refunds_in_flight
+                    .insert(id)
+            {
+                return Ok(None);
+            }
+
+            if !state
+                .refunds_started
+                .contains(&id)
+            {
+                let entry = PaymentEntry::RefundStarted { payment: id, at: now };
+
+                if let Err(error) = self.append(&entry) {
+                    state
+                        .refunds_in_flight
+                        .remove(&id);
+
+                    return Err(error);
+                }
+
+                state.apply(entry);
+            }
+
+            payment
+        };
+
+        // the provider is called without holding the lock.
+        let refunded = self
+            .provider
+            .refund(&payment, &event)
+            .await;
+        let mut state = self
+            .state
+            .lock();
+
+        state
+            .refunds_in_flight
+            .remove(&id);
+        refunded?;
+
+        let previous = state
+            .payments
+            .get(&id)
+            .map(|payment| payment.status)
+            .ok_or(PaymentError::NotFound(id))?;
+
+        if state
+            .events
+            .contains(&event)
+        {
+            return Ok(None);
+        }
+
+        let status = allowed(previous, PaymentStatus::Refunded).then_some(PaymentStatus::Refunded);
+        let entry = PaymentEntry::Event { event, payment: id, status, at: now };
+
+        self.append(&entry)?;
+        state.apply(entry);
+
+        Ok(status.and_then(|_| {
+            Some(PaymentUpdate {
+                payment: state
+                    .payments
+                    .get(&id)?
+                    .clone(),
+                previous,
+            })
+        }))
+    }
+
+    /// Sends a payout through the provider, returns its reference.
+    pub async fn send_payout(&self, order: &PayoutOrder) -> Result<String, PaymentError> {
+        if !(1..=MAX_AMOUNT_CENTS).contains(&order.amount_cents) {
+            return Err(PaymentError::InvalidAmount);
+        }
+
+        self.provider
+            .send_payout(order)
+            .await
+    }
+}
@@ -0,0 +1,74 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Seconds a signed webhook stays valid, older ones are treated as replays.
+pub const TOLERANCE_SECS: u64 = 5 * 60;
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn mac(secret: &[u8], timestamp: u64, body: &[u8]) -> HmacSha256 {
+    // HMAC takes keys of any length.
+    let mut mac = HmacSha256::new_from_slice(secret).unwrap_or_else(|_| unreachable!());
+
+    mac.update(
+        timestamp
+            .to_string()
+            .as_bytes(),
+    );
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+/// The signature header of `body` sent at `timestamp` in unix seconds, in the
+/// form `t=<timestamp>,v1=<hex HMAC-SHA256 of "<timestamp>.<body>">`.
+pub fn sign(secret: &[u8], timestamp: u64, body: &[u8]) -> String {
+    format!(
+        "t={timestamp},v1={}",
+        hex::encode(
+            mac(secret, timestamp, body)
+                .finalize()
+                .into_bytes()
+        )
+    )
+}
+
+/// Whether `header` is a signature of `body` made with `secret` at most
+/// [`TOLERANCE_SECS`] away from `now` in unix seconds.
+///
+/// Any of several `v1` signatures may match, so secrets can be rotated.
+pub fn verify(secret: &[u8], header: &str, body: &[u8], now: u64) -> bool {
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+
+    for part in header.split(',') {
+        match part
+            .trim()
+            .split_once('=')
+        {
+            Some(("t", value)) => {
+                timestamp = value
+                    .parse::<u64>()
+                    .ok()
+            },
+            Some(("v1", value)) => signatures.extend(hex::decode(value).ok()),
+            _ => {},
+        }
+    }
+
+    let Some(timestamp) = timestamp else {
+        return false;
+    };
+
+    if timestamp.abs_diff(now) > TOLERANCE_SECS {
+        return false;
+    }
+
+    signatures
+        .iter()
+        .any(|signature| {
+            mac(secret, timestamp, body)
+                .verify_slice(signature)
+                .is_ok()
+        })
+}
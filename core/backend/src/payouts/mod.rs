@@ -0,0 +1,785 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Error as IoError;
+use std::path::Path;
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use shared::api::{
+    ApiErrorBody,
+    ApiErrorCode,
+    EventState,
+    LedgerAccount,
+    PaymentPurpose,
+    PaymentStatus,
+    PayoutAction,
+    PayoutAuditRecord,
+    PayoutInfo,
+    PayoutStatus,
+};
+use thiserror::Error;
+
+use crate::config::{percent, seconds};
+use crate::events::{EventError, Events};
+use crate::ledger::{Ledger, LedgerError, Posting, TransactionKind};
+use crate::payments::{PaymentError, Payments, PayoutOrder};
+use crate::utils::api_error::ApiError;
+use crate::utils::journal::Journal;
+
+pub mod worker;
+
+const JOURNAL_FILE: &str = "payouts.jsonl";
+
+/// How prize pools are paid out.
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct PayoutPolicy {
+    /// Part of the prize pool going to the winner, the rest goes to the
+    /// platform.
+    #[serde(deserialize_with = "percent")]
+    pub winner_percent: u8,
+    /// Time after the end of an event before its payout is sent, leaves room
+    /// for disputes and chargebacks.
+    #[serde(rename = "hold_secs", deserialize_with = "seconds")]
+    pub hold: Duration,
+}
+
+impl Default for PayoutPolicy {
+    fn default() -> Self {
+        Self {
+            winner_percent: 50,
+            hold: Duration::from_secs(3 * 24 * 60 * 60),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum PayoutError {
+    #[error("The payout {0} doesn't exist.")]
+    NotFound(u64),
+
+    #[error("This isn't possible while the payout is {0:?}.")]
+    WrongStatus(PayoutStatus),
+
+    #[error("The payout storage failed: {0}")]
+    Storage(#[from] IoError),
+
+    #[error(transparent)]
+    Ledger(#[from] LedgerError),
+
+    #[error(transparent)]
+    Payment(#[from] PaymentError),
+
+    #[error(transparent)]
+    Event(#[from] EventError),
+}
+
+impl ResponseError for PayoutError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::WrongStatus(_) => StatusCode::CONFLICT,
+            Self::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Ledger(error) => error.status_code(),
+            Self::Payment(error) => error.status_code(),
+            Self::Event(error) => error.status_code(),
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            Self::NotFound(_) => ApiError::new(
+                self.status_code(),
+                ApiErrorBody::new(ApiErrorCode::NotFound, self.to_string()),
+            )
+            .error_response(),
+            Self::WrongStatus(_) => ApiError::new(
+                self.status_code(),
+                ApiErrorBody::new(ApiErrorCode::Conflict, self.to_string()),
+            )
+            .error_response(),
+            Self::Storage(_) => ApiError::new(
+                self.status_code(),
+                ApiErrorBody::new(ApiErrorCode::Internal, "The payout couldn't be recorded."),
+            )
+            .error_response(),
+            Self::Ledger(error) => error.error_response(),
+            Self::Payment(error) => error.error_response(),
+            Self::Event(error) => error.error_response(),
+        }
+    }
+}
+
+/// The winner's share of a prize pool.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Payout {
+    pub id: u64,
+    pub event: u64,
+    /// The winning artist.
+    pub account: u64,
+    pub amount_cents: u64,
+    /// The whole prize pool when the payout was made, the rest of it goes to
+    /// the platform.
+    pub pool_cents: u64,
+    pub status: PayoutStatus,
+    /// Unix milliseconds.
+    pub created_at: u64,
+    /// Unix milliseconds before which the payout isn't sent.
+    pub release_at: u64,
+    pub approved_by: Option<u64>,
+    pub reference: Option<String>,
+}
+
+impl Payout {
+    pub fn info(&self) -> PayoutInfo {
+        PayoutInfo {
+            id: self.id,
+            event: self.event,
+            account: self.account,
+            amount_cents: self.amount_cents,
+            status: self.status,
+            created_at: self.created_at,
+            release_at: self.release_at,
+            approved_by: self.approved_by,
+            reference: self
+                .reference
+                .clone(),
+        }
+    }
+
+    /// Posts what the payout did to the books so far, each step is keyed by
+    /// the payout so posting again changes nothing.
+    fn post(&self, ledger: &Ledger, now: u64) -> Result<(), LedgerError> {
+        let pool = LedgerAccount::PrizePool { event: self.event };
+        let payable = LedgerAccount::Payable { user: self.account };
+        let platform_cents = self.pool_cents - self.amount_cents;
+        let mut award = vec![
+            Posting::debit(pool, self.pool_cents)?,
+            Posting::credit(payable, self.amount_cents)?,
+        ];
+        let mut reversal = vec![
+            Posting::debit(payable, self.amount_cents)?,
+            Posting::credit(pool, self.pool_cents)?,
+        ];
+
+        if platform_cents > 0 {
+            award.push(Posting::credit(LedgerAccount::Platform, platform_cents)?);
+            reversal.push(Posting::debit(LedgerAccount::Platform, platform_cents)?);
+        }
+
+        ledger.post(
+            format!("payout:{}:awarded", self.id),
+            TransactionKind::PrizeAward,
+            award,
+            now,
+        )?;
+
+        match self.status {
+            PayoutStatus::Paid => {
+                ledger.post(
+                    format!("payout:{}:paid", self.id),
+                    TransactionKind::Payout,
+                    vec![
+                        Posting::debit(payable, self.amount_cents)?,
+                        Posting::credit(LedgerAccount::Cash, self.amount_cents)?,
+                    ],
+                    now,
+                )?;
+            },
+            PayoutStatus::Rejected => {
+                ledger.post(
+                    format!("payout:{}:rejected", self.id),
+                    TransactionKind::PayoutReversal,
+                    reversal,
+                    now,
+                )?;
+            },
+            PayoutStatus::Pending | PayoutStatus::Approved => {},
+        }
+
+        Ok(())
+    }
+}
+
+/// A step of the workflow, as stored in the journal. The journal doubles as
+/// the audit trail.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum PayoutEntry {
+    Created(Payout),
+    Approved {
+        payout: u64,
+        by: u64,
+        at: u64,
+    },
+    Rejected {
+        payout: u64,
+        by: u64,
+        at: u64,
+    },
+    Paid {
+        payout: u64,
+        reference: String,
+        at: u64,
+    },
+    Failed {
+        payout: u64,
+        reason: String,
+        at: u64,
+    },
+    Refunded {
+        event: u64,
+        payment: u64,
+        amount_cents: u64,
+        at: u64,
+    },
+    RefundFailed {
+        event: u64,
+        payment: u64,
+        reason: String,
+        at: u64,
+    },
+}
+
+#[derive(Default)]
+struct PayoutsState {
+    payouts: BTreeMap<u64, Payout>,
+    by_event: HashMap<u64, u64>,
+    /// Payouts whose last transfer failed.
+    failed_payouts: HashSet<u64>,
+    /// Entry fees whose last refund failed.
+    failed_refunds: HashSet<u64>,
+    audit: Vec<PayoutAuditRecord>,
+}
+
+impl PayoutsState {
+    fn apply(&mut self, entry: PayoutEntry) {
+        let record = self.record(&entry);
+
+        match entry {
+            PayoutEntry::Created(payout) => {
+                self.by_event
+                    .insert(payout.event, payout.id);
+                self.payouts
+                    .insert(payout.id, payout);
+            },
+            PayoutEntry::Approved { payout, by, .. } => {
+                if let Some(payout) = self
+                    .payouts
+                    .get_mut(&payout)
+                {
+                    payout.status = PayoutStatus::Approved;
+                    payout.approved_by = Some(by);
+                }
+            },
+            PayoutEntry::Rejected { payout, .. } => {
+                if let Some(payout) = self
+                    .payouts
+                    .get_mut(&payout)
+                {
+                    payout.status = PayoutStatus::Rejected;
+
+                    // the pool is awarded again on the next run.
+                    if self
+                        .by_event
+                        .get(&payout.event)
+                        == Some(&payout.id)
+                    {
+                        self.by_event
+                            .remove(&payout.event);
+                    }
+                }
+            },
+            PayoutEntry::Paid { payout, reference, .. } => {
+                self.failed_payouts
+                    .remove(&payout);
+
+                if let Some(payout) = self
+                    .payouts
+                    .get_mut(&payout)
+                {
+                    payout.status = PayoutStatus::Paid;
+                    payout.reference = Some(reference);
+                }
+            },
+            PayoutEntry::Failed { payout, .. } => {
+                self.failed_payouts
+                    .insert(payout);
+            },
+            PayoutEntry::Refunded { payment, .. } => {
+                self.failed_refunds
+                    .remove(&payment);
+            },
+            PayoutEntry::RefundFailed { payment, .. } => {
+                self.failed_refunds
+                    .insert(payment);
+            },
+        }
+
+        self.audit
+            .extend(record);
+    }
+
+    /// How `entry` shows up in the audit trail.
+    fn record(&self, entry: &PayoutEntry) -> Option<PayoutAuditRecord> {
+        let payout = |id: &u64| {
+            self.payouts
+                .get(id)
+        };
+        let record = |at, action, event, payout| PayoutAuditRecord {
+            at,
+            action,
+            event,
+            payout,
+            payment: None,
+            actor: None,
+            amount_cents: None,
+            detail: None,
+        };
+
+        Some(match entry {
+            PayoutEntry::Created(created) => PayoutAuditRecord {
+                amount_cents: Some(created.amount_cents),
+                ..record(
+                    created.created_at,
+                    PayoutAction::PayoutCreated,
+                    created.event,
+                    Some(created.id),
+                )
+            },
+            PayoutEntry::Approved { payout: id, by, at } => PayoutAuditRecord {
+                actor: Some(*by),
+                ..record(*at, PayoutAction::PayoutApproved, payout(id)?.event, Some(*id))
+            },
+            PayoutEntry::Rejected { payout: id, by, at } => PayoutAuditRecord {
+                actor: Some(*by),
+                ..record(*at, PayoutAction::PayoutRejected, payout(id)?.event, Some(*id))
+            },
+            PayoutEntry::Paid { payout: id, reference, at } => PayoutAuditRecord {
+                amount_cents: Some(payout(id)?.amount_cents),
+                detail: Some(reference.clone()),
+                ..record(*at, PayoutAction::PayoutPaid, payout(id)?.event, Some(*id))
+            },
+            PayoutEntry::Failed { payout: id, reason, at } => PayoutAuditRecord {
+                detail: Some(reason.clone()),
+                ..record(*at, PayoutAction::PayoutFailed, payout(id)?.event, Some(*id))
+            },
+            PayoutEntry::Refunded { event, payment, amount_cents, at } => PayoutAuditRecord {
+                payment: Some(*payment),
+                amount_cents: Some(*amount_cents),
+                ..record(*at, PayoutAction::EntryRefunded, *event, None)
+            },
+            PayoutEntry::RefundFailed { event, payment, reason, at } => PayoutAuditRecord {
+                payment: Some(*payment),
+                detail: Some(reason.clone()),
+                ..record(*at, PayoutAction::RefundFailed, *event, None)
+            },
+        })
+    }
+
+    /// Whether a payout of `account` for `event` was rejected before.
+    fn was_rejected(&self, event: u64, account: u64) -> bool {
+        self.payouts
+            .values()
+            .any(|payout| {
+                payout.event == event
+                    && payout.account == account
+                    && payout.status == PayoutStatus::Rejected
+            })
+    }
+
+    fn payout(&self, id: u64) -> Result<&Payout, PayoutError> {
+        self.payouts
+            .get(&id)
+            .ok_or(PayoutError::NotFound(id))
+    }
+}
+
+/// Pays the winners of events their share of the prize pool and refunds the
+/// entries of cancelled events.
+///
+/// Payouts wait for an admin to approve them and for their hold period to
+/// pass. When opened from a directory every step is journaled before it's
+/// applied, the journal is the audit trail of the workflow.
+pub struct Payouts {
+    policy: PayoutPolicy,
+    state: Mutex<PayoutsState>,
+    journal: Option<Mutex<Journal<PayoutEntry>>>,
+}
+
+impl Payouts {
+    /// Payouts that only live in memory.
+    pub fn new(policy: PayoutPolicy) -> Self {
+        Self {
+            policy,
+            state: Mutex::default(),
+            journal: None,
+        }
+    }
+
+    /// Loads the payouts stored in `dir`, or starts recording them there.
+    pub fn open(dir: impl AsRef<Path>, policy: PayoutPolicy) -> Result<Self, PayoutError> {
+        let (journal, entries) = Journal::open(
+            dir.as_ref()
+                .join(JOURNAL_FILE),
+        )?;
+        let mut state = PayoutsState::default();
+
+        for entry in entries {
+            state.apply(entry);
+        }
+
+        Ok(Self {
+            policy,
+            state: Mutex::new(state),
+            journal: Some(Mutex::new(journal)),
+        })
+    }
+
+    #[inline]
+    pub fn policy(&self) -> &PayoutPolicy {
+        &self.policy
+    }
+
+    fn append(&self, state: &mut PayoutsState, entry: PayoutEntry) -> Result<(), PayoutError> {
+        if let Some(journal) = &self.journal {
+            journal
+                .lock()
+                .append(&entry)?;
+        }
+
+        state.apply(entry);
+
+        Ok(())
+    }
+
+    pub fn payout(&self, id: u64) -> Option<Payout> {
+        self.state
+            .lock()
+            .payouts
+            .get(&id)
+            .cloned()
+    }
+
+    /// Every payout in the order they were made.
+    pub fn payouts(&self) -> Vec<Payout> {
+        self.state
+            .lock()
+            .payouts
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Every step taken so far, oldest first.
+    pub fn audit(&self) -> Vec<PayoutAuditRecord> {
+        self.state
+            .lock()
+            .audit
+            .clone()
+    }
+
+    /// Approves a pending payout, it's sent once its hold period is over.
+    pub fn approve(&self, id: u64, admin: u64, now: u64) -> Result<Payout, PayoutError> {
+        let mut state = self
+            .state
+            .lock();
+        let payout = state.payout(id)?;
+
+        if payout.status != PayoutStatus::Pending {
+            return Err(PayoutError::WrongStatus(payout.status));
+        }
+
+        self.append(&mut state, PayoutEntry::Approved { payout: id, by: admin, at: now })?;
+
+        state
+            .payout(id)
+            .cloned()
+    }
+
+    /// Rejects a pending payout, the whole award goes back into the prize
+    /// pool, the platform's share included. The next run awards the pool to
+    /// the best placed artist whose payout wasn't rejected yet.
+    pub fn reject(
+        &self,
+        ledger: &Ledger,
+        id: u64,
+        admin: u64,
+        now: u64,
+    ) -> Result<Payout, PayoutError> {
+        let mut state = self
+            .state
+            .lock();
+        let payout = state.payout(id)?;
+
+        if payout.status != PayoutStatus::Pending {
+            return Err(PayoutError::WrongStatus(payout.status));
+        }
+
+        self.append(&mut state, PayoutEntry::Rejected { payout: id, by: admin, at: now })?;
+
+        let payout = state
+            .payout(id)?
+            .clone();
+
+        payout.post(ledger, now)?;
+
+        Ok(payout)
+    }
+
+    /// Posts every step of every payout to the ledger, needed on start in
+    /// case the server stopped between recording a step and posting it.
+    pub fn sync_ledger(&self, ledger: &Ledger, now: u64) -> Result<(), PayoutError> {
+        for payout in self.payouts() {
+            payout.post(ledger, now)?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves the workflow along, returns the steps it took.
+    ///
+    /// Finished events with a winner and a prize pool get a pending payout,
+    /// approved payouts past their hold are sent and the succeeded entry fees
+    /// of cancelled events are refunded. Failed transfers are recorded once
+    /// and tried again on every call.
+    pub async fn process(
+        &self,
+        events: &Events,
+        ledger: &Ledger,
+        payments: Option<&Payments>,
+        now: u64,
+    ) -> Result<Vec<PayoutAuditRecord>, PayoutError> {
+        let start = self
+            .state
+            .lock()
+            .audit
+            .len();
+
+        self.create_payouts(events, ledger, now)?;
+
+        if let Some(payments) = payments {
+            self.send_payouts(ledger, payments, now)
+                .await?;
+            self.refund_cancelled(events, ledger, payments, now)
+                .await?;
+        }
+
+        Ok(self
+            .state
+            .lock()
+            .audit[start..]
+            .to_vec())
+    }
+
+    fn create_payouts(
+        &self,
+        events: &Events,
+        ledger: &Ledger,
+        now: u64,
+    ) -> Result<(), PayoutError> {
+        for event in events.events(false) {
+            if event.state != EventState::Finished {
+                continue;
+            }
+
+            let mut state = self
+                .state
+                .lock();
+
+            if state
+                .by_event
+                .contains_key(&event.id)
+            {
+                continue;
+            }
+
+            let results = event.results();
+            let Some(winner) = results
+                .winner
+                .into_iter()
+                .chain(
+                    results
+                        .standings
+                        .iter()
+                        .map(|standing| {
+                            standing
+                                .submission
+                                .artist
+                        }),
+                )
+                .find(|artist| !state.was_rejected(event.id, *artist))
+            else {
+                continue;
+            };
+            let pool_cents =
+                u64::try_from(ledger.balance(LedgerAccount::PrizePool { event: event.id }))
+                    .unwrap_or_default();
+            let amount_cents = pool_cents
+                * u64::from(
+                    self.policy
+                        .winner_percent
+                        .min(100),
+                )
+                / 100;
+
+            if amount_cents == 0 {
+                continue;
+            }
+
+            let payout = Payout {
+                id: state
+                    .payouts
+                    .len() as u64
+                    + 1,
+                event: event.id,
+                account: winner,
+                amount_cents,
+                pool_cents,
+                status: PayoutStatus::Pending,
+                created_at: now,
+                release_at: event
+                    .judging_ends_at
+                    .saturating_add(
+                        u64::try_from(
+                            self.policy
+                                .hold
+                                .as_millis(),
+                        )
+                        .unwrap_or(u64::MAX),
+                    ),
+                approved_by: None,
+                reference: None,
+            };
+
+            self.append(&mut state, PayoutEntry::Created(payout.clone()))?;
+            payout.post(ledger, now)?;
+        }
+
+        Ok(())
+    }
+
+    async fn send_payouts(
+        &self,
+        ledger: &Ledger,
+        payments: &Payments,
+        now: u64,
+    ) -> Result<(), PayoutError> {
+        let due = self
+            .payouts()
+            .into_iter()
+            .filter(|payout| payout.status == PayoutStatus::Approved && now >= payout.release_at);
+
+        for payout in due {
+            let order = PayoutOrder {
+                id: payout.id,
+                account: payout.account,
+                amount_cents: payout.amount_cents,
+            };
+            // the provider is called without holding the lock.
+            let sent = payments
+                .send_payout(&order)
+                .await;
+            let mut state = self
+                .state
+                .lock();
+
+            match sent {
+                Ok(reference) => {
+                    self.append(
+                        &mut state,
+                        PayoutEntry::Paid { payout: payout.id, reference, at: now },
+                    )?;
+                    state
+                        .payout(payout.id)?
+                        .post(ledger, now)?;
+                },
+                // a transfer that keeps failing is recorded once.
+                Err(_)
+                    if state
+                        .failed_payouts
+                        .contains(&payout.id) => {},
+                Err(error) => self.append(
+                    &mut state,
+                    PayoutEntry::Failed {
+                        payout: payout.id,
+                        reason: error.to_string(),
+                        at: now,
+                    },
+                )?,
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn refund_cancelled(
+        &self,
+        events: &Events,
+        ledger: &Ledger,
+        payments: &Payments,
+        now: u64,
+    ) -> Result<(), PayoutError> {
+        let cancelled = |event: u64| {
+            events
+                .event(event)
+                .is_some_and(|event| event.state == EventState::Cancelled)
+        };
+        let due = payments
+            .payments()
+            .into_iter()
+            .filter(|payment| {
+                payment.status == PaymentStatus::Succeeded
+                    && matches!(
+                        payment.purpose,
+                        PaymentPurpose::EventEntry { event } if cancelled(event)
+                    )
+            });
+
+        for payment in due {
+            let PaymentPurpose::EventEntry { event } = payment.purpose else {
+                continue;
+            };
+            let refunded = payments
+                .refund(payment.id, now)
+                .await;
+            let mut state = self
+                .state
+                .lock();
+
+            match refunded {
+                Ok(Some(update)) => {
+                    ledger.record_payment(&update.payment, now)?;
+                    events.apply_payment(&update.payment, now)?;
+                    self.append(
+                        &mut state,
+                        PayoutEntry::Refunded {
+                            event,
+                            payment: payment.id,
+                            amount_cents: payment.amount_cents,
+                            at: now,
+                        },
+                    )?;
+                },
+                Ok(None) => {},
+                Err(_)
+                    if state
+                        .failed_refunds
+                        .contains(&payment.id) => {},
+                Err(error) => self.append(
+                    &mut state,
+                    PayoutEntry::RefundFailed {
+                        event,
+                        payment: payment.id,
+                        reason: error.to_string(),
+                        at: now,
+                    },
+                )?,
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use actix_web::rt::spawn;
+use actix_web::rt::task::JoinHandle;
+use actix_web::rt::time::interval;
+use actix_web::web::Data;
+use log::{error, info};
+
+use crate::events::Events;
+use crate::ledger::Ledger;
+use crate::payments::Payments;
+use crate::payouts::Payouts;
+use crate::utils::time::unix_millis;
+
+/// Runs [`Payouts::process`] every `period` until the runtime stops.
+pub fn spawn_payout_worker(
+    payouts: Data<Payouts>,
+    events: Data<Events>,
+    ledger: Data<Ledger>,
+    payments: Option<Data<Payments>>,
+    period: Duration,
+) -> JoinHandle<()> {
+    spawn(async move {
+        let mut ticker = interval(period);
+
+        loop {
+            ticker
+                .tick()
+                .await;
+
+            match payouts
+                .process(
+                    &events,
+                    &ledger,
+                    payments
+                        .as_ref()
+                        .map(|payments| payments.get_ref()),
+                    unix_millis(),
+                )
+                .await
+            {
+                Ok(records) => {
+                    for record in records {
+                        info!("Payout step {:?} for event {}.", record.action, record.event);
+                    }
+                },
+                Err(payout_error) => error!("Processing the payouts failed: {payout_error}"),
+            }
+        }
+    })
+}
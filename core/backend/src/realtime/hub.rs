@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use bytestring::ByteString;
+use parking_lot::Mutex;
+use shared::canvas::ChunkCoord;
+use shared::realtime::ServerMessage;
+use tokio::sync::mpsc::{Receiver, Sender, channel};
+
+use crate::realtime::RealtimeConfig;
+use crate::realtime::subscriptions::{SubscriptionError, SubscriptionRegistry};
+
+pub type SessionId = u64;
+
+#[derive(Default)]
+struct HubState {
+    sessions: HashMap<SessionId, Sender<ByteString>>,
+    subscriptions: SubscriptionRegistry,
+    /// Set once the server shuts down, sessions connecting afterwards end
+    /// right away.
+    shutting_down: bool,
+}
+
+impl HubState {
+    fn drop_session(&mut self, id: SessionId) {
+        self.sessions
+            .remove(&id);
+        self.subscriptions
+            .remove_session(id);
+    }
+}
+
+/// Fans out server messages to connected realtime sessions.
+///
+/// Each session gets a bounded queue, a session that can't keep up
+/// is disconnected instead of slowing down everyone else.
+pub struct RealtimeHub {
+    config: RealtimeConfig,
+    next_id: AtomicU64,
+    state: Mutex<HubState>,
+}
+
+impl RealtimeHub {
+    pub fn new(config: RealtimeConfig) -> Self {
+        Self {
+            config,
+            next_id: AtomicU64::new(1),
+            state: Mutex::default(),
+        }
+    }
+
+    #[inline]
+    pub fn config(&self) -> RealtimeConfig {
+        self.config
+    }
+
+    /// Registers a session, the receiver yields every message sent to it.
+    pub fn connect(&self) -> (SessionId, Receiver<ByteString>) {
+        let id = self
+            .next_id
+            .fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = channel(
+            self.config
+                .send_buffer,
+        );
+
+        let mut state = self
+            .state
+            .lock();
+
+        // dropping the sender ends the session once it starts.
+        if !state.shutting_down {
+            state
+                .sessions
+                .insert(id, sender);
+        }
+
+        (id, receiver)
+    }
+
+    pub fn disconnect(&self, id: SessionId) {
+        self.state
+            .lock()
+            .drop_session(id);
+    }
+
+    pub fn session_count(&self) -> usize {
+        self.state
+            .lock()
+            .sessions
+            .len()
+    }
+
+    pub fn subscribe(&self, id: SessionId, chunks: &[ChunkCoord]) -> Result<(), SubscriptionError> {
+        self.state
+            .lock()
+            .subscriptions
+            .subscribe(
+                id,
+                chunks,
+                self.config
+                    .max_subscriptions,
+            )
+    }
+
+    pub fn unsubscribe(&self, id: SessionId, chunks: &[ChunkCoord]) {
+        self.state
+            .lock()
+            .subscriptions
+            .unsubscribe(id, chunks);
+    }
+
+    pub fn subscriber_count(&self, chunk: ChunkCoord) -> usize {
+        self.state
+            .lock()
+            .subscriptions
+            .subscribers(chunk)
+            .count()
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.state
+            .lock()
+            .shutting_down
+    }
+
+    /// Tells every session the server is going down and to reconnect after
+    /// `reconnect_after`, then ends them once that message is sent.
+    ///
+    /// Returns how many sessions were told.
+    pub fn shut_down(&self, reconnect_after: Duration) -> usize {
+        let message = ServerMessage::ShuttingDown {
+            reconnect_after_ms: u64::try_from(reconnect_after.as_millis()).unwrap_or(u64::MAX),
+        };
+        let mut state = self
+            .state
+            .lock();
+        let ids = state
+            .sessions
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+
+        state.shutting_down = true;
+
+        let delivered = match Self::encode(&message) {
+            Some(text) => Self::deliver(&mut state, ids.clone(), &text),
+            None => 0,
+        };
+
+        // the message queued before is still sent once the sender is gone.
+        for id in ids {
+            state.drop_session(id);
+        }
+
+        delivered
+    }
+
+    /// Queues a message for every session, returns how many sessions it
+    /// reached.
+    pub fn broadcast(&self, message: &ServerMessage) -> usize {
+        let Some(text) = Self::encode(message) else {
+            return 0;
+        };
+
+        let mut state = self
+            .state
+            .lock();
+        let ids = state
+            .sessions
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+
+        Self::deliver(&mut state, ids, &text)
+    }
+
+    /// Queues a message for the sessions subscribed to `chunk`,
+    /// returns how many sessions it reached.
+    pub fn publish(&self, chunk: ChunkCoord, message: &ServerMessage) -> usize {
+        let Some(text) = Self::encode(message) else {
+            return 0;
+        };
+
+        let mut state = self
+            .state
+            .lock();
+        let ids = state
+            .subscriptions
+            .subscribers(chunk)
+            .collect::<Vec<_>>();
+
+        Self::deliver(&mut state, ids, &text)
+    }
+
+    fn encode(message: &ServerMessage) -> Option<ByteString> {
+        serde_json::to_string(message)
+            .ok()
+            .map(ByteString::from)
+    }
+
+    fn deliver(state: &mut HubState, ids: Vec<SessionId>, text: &ByteString) -> usize {
+        let mut delivered = 0;
+
+        for id in ids {
+            let sent = state
+                .sessions
+                .get(&id)
+                .is_some_and(|sender| {
+                    sender
+                        .try_send(text.clone())
+                        .is_ok()
+                });
+
+            if sent {
+                delivered += 1;
+            } else {
+                // dropping the sender of a full queue ends its session.
+                state.drop_session(id);
+            }
+        }
+
+        delivered
+    }
+}
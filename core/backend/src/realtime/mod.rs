@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::config::{nonzero, nonzero_millis};
+
+pub mod hub;
+pub mod session;
+pub mod subscriptions;
+
+/// Tuning of the realtime channel.
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct RealtimeConfig {
+    /// How often sessions are pinged.
+    #[serde(rename = "heartbeat_interval_ms", deserialize_with = "nonzero_millis")]
+    pub heartbeat_interval: Duration,
+    /// How long a session may stay silent before it's considered dead.
+    #[serde(rename = "client_timeout_ms", deserialize_with = "nonzero_millis")]
+    pub client_timeout: Duration,
+    /// Messages queued per session before the session is dropped as too slow.
+    #[serde(deserialize_with = "nonzero")]
+    pub send_buffer: usize,
+    /// Chunks a single session may be subscribed to at once.
+    pub max_subscriptions: usize,
+}
+
+impl Default for RealtimeConfig {
+    fn default() -> Self {
+        Self {
+            heartbeat_interval: Duration::from_secs(5),
+            client_timeout: Duration::from_secs(15),
+            send_buffer: 256,
+            max_subscriptions: 1024,
+        }
+    }
+}
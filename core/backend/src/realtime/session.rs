@@ -0,0 +1,116 @@
+use std::time::Instant;
+
+use actix_web::web::Data;
+use actix_ws::{CloseCode, CloseReason, Message, MessageStream, Session};
+use bytestring::ByteString;
+use futures_util::StreamExt;
+use shared::realtime::ClientMessage;
+use tokio::select;
+use tokio::sync::mpsc::Receiver;
+use tokio::time::interval;
+
+use crate::realtime::hub::{RealtimeHub, SessionId};
+
+/// Drives a realtime session until the client leaves, goes idle, falls
+/// behind or the server shuts down.
+pub async fn run(
+    hub: Data<RealtimeHub>,
+    id: SessionId,
+    mut session: Session,
+    mut stream: MessageStream,
+    mut outbound: Receiver<ByteString>,
+) {
+    let config = hub.config();
+    let mut heartbeat = interval(config.heartbeat_interval);
+    let mut last_seen = Instant::now();
+
+    let reason = loop {
+        select! {
+            _ = heartbeat.tick() => {
+                if last_seen.elapsed() > config.client_timeout {
+                    break Some(CloseReason {
+                        code: CloseCode::Away,
+                        description: Some("Heartbeat timed out.".into()),
+                    });
+                }
+
+                if session.ping(b"").await.is_err() {
+                    break None;
+                }
+            },
+
+            message = outbound.recv() => {
+                let Some(text) = message else {
+                    if hub.is_shutting_down() {
+                        break Some(CloseReason {
+                            code: CloseCode::Restart,
+                            description: Some("The server is shutting down.".into()),
+                        });
+                    }
+
+                    break Some(CloseReason {
+                        code: CloseCode::Policy,
+                        description: Some("Too slow to keep up with the canvas.".into()),
+                    });
+                };
+
+                if session.text(text).await.is_err() {
+                    break None;
+                }
+            },
+
+            message = stream.next() => match message {
+                Some(Ok(Message::Ping(bytes))) => {
+                    last_seen = Instant::now();
+
+                    if session.pong(&bytes).await.is_err() {
+                        break None;
+                    }
+                },
+
+                Some(Ok(Message::Text(text))) => {
+                    last_seen = Instant::now();
+
+                    if let Err(reason) = handle_client_message(&hub, id, &text) {
+                        break Some(reason);
+                    }
+                },
+
+                Some(Ok(Message::Close(reason))) => break reason,
+
+                Some(Ok(_)) => {
+                    last_seen = Instant::now();
+                },
+
+                Some(Err(_)) | None => break None,
+            },
+        }
+    };
+
+    hub.disconnect(id);
+    // the client may already be gone.
+    let _ = session
+        .close(reason)
+        .await;
+}
+
+fn handle_client_message(hub: &RealtimeHub, id: SessionId, text: &str) -> Result<(), CloseReason> {
+    let message = serde_json::from_str::<ClientMessage>(text).map_err(|_| CloseReason {
+        code: CloseCode::Invalid,
+        description: Some("Unknown realtime message.".into()),
+    })?;
+
+    match message {
+        ClientMessage::Subscribe { chunks } => hub
+            .subscribe(id, &chunks)
+            .map_err(|error| CloseReason {
+                code: CloseCode::Policy,
+                description: Some(error.to_string()),
+            }),
+
+        ClientMessage::Unsubscribe { chunks } => {
+            hub.unsubscribe(id, &chunks);
+            Ok(())
+        },
+    }
+}
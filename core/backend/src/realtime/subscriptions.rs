@@ -0,0 +1,113 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+use shared::canvas::ChunkCoord;
+use thiserror::Error;
+
+use crate::realtime::hub::SessionId;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SubscriptionError {
+    #[error("A session can't be subscribed to more than {0} chunks.")]
+    TooManyChunks(usize),
+}
+
+/// Which sessions want to hear about which chunks.
+#[derive(Default, Debug)]
+pub struct SubscriptionRegistry {
+    by_chunk: HashMap<ChunkCoord, HashSet<SessionId>>,
+    by_session: HashMap<SessionId, HashSet<ChunkCoord>>,
+}
+
+impl SubscriptionRegistry {
+    /// Subscribes a session to `chunks`, nothing is subscribed if that would
+    /// exceed `limit`.
+    pub fn subscribe(
+        &mut self,
+        id: SessionId,
+        chunks: &[ChunkCoord],
+        limit: usize,
+    ) -> Result<(), SubscriptionError> {
+        let current = self
+            .by_session
+            .entry(id)
+            .or_default();
+        let added = chunks
+            .iter()
+            .filter(|chunk| !current.contains(chunk))
+            .collect::<HashSet<_>>();
+
+        if current.len() + added.len() > limit {
+            return Err(SubscriptionError::TooManyChunks(limit));
+        }
+
+        for chunk in added {
+            current.insert(*chunk);
+            self.by_chunk
+                .entry(*chunk)
+                .or_default()
+                .insert(id);
+        }
+
+        Ok(())
+    }
+
+    pub fn unsubscribe(&mut self, id: SessionId, chunks: &[ChunkCoord]) {
+        let Some(current) = self
+            .by_session
+            .get_mut(&id)
+        else {
+            return;
+        };
+
+        for chunk in chunks {
+            if current.remove(chunk) {
+                Self::forget(&mut self.by_chunk, *chunk, id);
+            }
+        }
+    }
+
+    /// Drops every subscription of a session.
+    pub fn remove_session(&mut self, id: SessionId) {
+        for chunk in self
+            .by_session
+            .remove(&id)
+            .unwrap_or_default()
+        {
+            Self::forget(&mut self.by_chunk, chunk, id);
+        }
+    }
+
+    pub fn subscribers(&self, chunk: ChunkCoord) -> impl Iterator<Item = SessionId> + '_ {
+        self.by_chunk
+            .get(&chunk)
+            .into_iter()
+            .flatten()
+            .copied()
+    }
+
+    pub fn subscriptions(&self, id: SessionId) -> usize {
+        self.by_session
+            .get(&id)
+            .map_or(0, HashSet::len)
+    }
+
+    fn forget(
+        by_chunk: &mut HashMap<ChunkCoord, HashSet<SessionId>>,
+        chunk: ChunkCoord,
+        id: SessionId,
+    ) {
+        if let Entry::Occupied(mut entry) = by_chunk.entry(chunk) {
+            entry
+                .get_mut()
+                .remove(&id);
+
+            if entry
+                .get()
+                .is_empty()
+            {
+                entry.remove();
+            }
+        }
+    }
+}
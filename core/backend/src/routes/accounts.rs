@@ -0,0 +1,232 @@
+use actix_web::cookie::time::Duration as CookieDuration;
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::http::header::LOCATION;
+use actix_web::web::{Data, Json, Query, block};
+use actix_web::{HttpRequest, HttpResponse, Result as ActixResult, get, post};
+use serde::Deserialize;
+use shared::api::{AccountInfo, ApiErrorBody, Credentials};
+use utoipa::IntoParams;
+
+use crate::accounts::oidc::{LOGIN_TTL_MS, OidcError, OidcProvider, STATE_COOKIE};
+use crate::accounts::session::AuthenticatedUser;
+use crate::accounts::{AccountError, Accounts};
+use crate::utils::time::unix_millis;
+
+/// What the identity provider sends back to the callback.
+#[derive(Deserialize, IntoParams)]
+pub struct OidcCallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+fn state_cookie(accounts: &Accounts, value: String, max_age: CookieDuration) -> Cookie<'static> {
+    Cookie::build(STATE_COOKIE, value)
+        .path("/api/auth/oidc")
+        .http_only(true)
+        .secure(
+            accounts
+                .config()
+                .secure_cookies,
+        )
+        .same_site(SameSite::Lax)
+        .max_age(max_age)
+        .finish()
+}
+
+/// Creates an account and logs it in.
+#[utoipa::path(
+    tag = "accounts",
+    request_body = Credentials,
+    responses(
+        (status = 201, description = "The account, logged in with a session cookie.", body = AccountInfo),
+        (status = 400, description = "The username or password isn't valid.", body = ApiErrorBody),
+        (status = 409, description = "The username is taken.", body = ApiErrorBody),
+    ),
+)]
+#[post("/auth/register")]
+pub async fn register(
+    accounts: Data<Accounts>,
+    request: Json<Credentials>,
+) -> ActixResult<HttpResponse> {
+    let Credentials { username, password } = request.into_inner();
+    let now = unix_millis();
+    let account = {
+        let accounts = accounts.clone();
+
+        block(move || accounts.register(&username, &password, now)).await??
+    };
+    let (_, cookie) = accounts.start_session(account.id, now);
+
+    Ok(HttpResponse::Created()
+        .cookie(cookie)
+        .json(account.info()))
+}
+
+#[utoipa::path(
+    tag = "accounts",
+    request_body = Credentials,
+    responses(
+        (status = 200, description = "The account, logged in with a session cookie.", body = AccountInfo),
+        (status = 401, description = "The credentials are wrong.", body = ApiErrorBody),
+    ),
+)]
+#[post("/auth/login")]
+pub async fn login(
+    accounts: Data<Accounts>,
+    request: Json<Credentials>,
+) -> ActixResult<HttpResponse> {
+    let Credentials { username, password } = request.into_inner();
+    let account = {
+        let accounts = accounts.clone();
+
+        block(move || accounts.verify(&username, &password)).await??
+    };
+    let (_, cookie) = accounts.start_session(account.id, unix_millis());
+
+    Ok(HttpResponse::Ok()
+        .cookie(cookie)
+        .json(account.info()))
+}
+
+/// Revokes the current session and clears its cookie.
+#[utoipa::path(
+    tag = "accounts",
+    responses(
+        (status = 204, description = "The session is over."),
+        (status = 401, description = "The caller isn't logged in.", body = ApiErrorBody),
+    ),
+    security(("session" = [])),
+)]
+#[post("/auth/logout")]
+pub async fn logout(
+    accounts: Data<Accounts>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, AccountError> {
+    let cookie = accounts.end_session(&user.session, unix_millis())?;
+
+    Ok(HttpResponse::NoContent()
+        .cookie(cookie)
+        .finish())
+}
+
+#[utoipa::path(
+    tag = "accounts",
+    responses(
+        (status = 200, body = AccountInfo),
+        (status = 401, description = "The caller isn't logged in.", body = ApiErrorBody),
+    ),
+    security(("session" = [])),
+)]
+#[get("/auth/me")]
+pub async fn me(user: AuthenticatedUser) -> Json<AccountInfo> {
+    Json(AccountInfo { id: user.id, username: user.username })
+}
+
+/// Sends the browser to the identity provider.
+#[utoipa::path(
+    tag = "accounts",
+    responses(
+        (status = 302, description = "Redirects to the identity provider."),
+        (status = 502, description = "The identity provider couldn't be reached.", body = ApiErrorBody),
+    ),
+)]
+#[get("/auth/oidc/login")]
+pub async fn oidc_login(
+    accounts: Data<Accounts>,
+    provider: Data<OidcProvider>,
+) -> Result<HttpResponse, OidcError> {
+    let redirect = provider
+        .start_login(unix_millis())
+        .await?;
+    let max_age = CookieDuration::milliseconds(
+        LOGIN_TTL_MS
+            .try_into()
+            .unwrap_or(i64::MAX),
+    );
+
+    Ok(HttpResponse::Found()
+        .insert_header((
+            LOCATION,
+            redirect
+                .url
+                .as_str(),
+        ))
+        .cookie(state_cookie(&accounts, redirect.state, max_age))
+        .finish())
+}
+
+/// Where the identity provider sends the browser back to, logs the user in
+/// and registers them on their first visit.
+#[utoipa::path(
+    tag = "accounts",
+    params(OidcCallbackQuery),
+    responses(
+        (status = 302, description = "Logged in, redirects back to the canvas."),
+        (status = 401, description = "The login was denied or couldn't be verified.", body = ApiErrorBody),
+        (status = 502, description = "The identity provider couldn't be reached.", body = ApiErrorBody),
+    ),
+)]
+#[get("/auth/oidc/callback")]
+pub async fn oidc_callback(
+    accounts: Data<Accounts>,
+    provider: Data<OidcProvider>,
+    request: HttpRequest,
+    query: Query<OidcCallbackQuery>,
+) -> Result<HttpResponse, OidcError> {
+    let query = query.into_inner();
+
+    if let Some(error) = query.error {
+        return Err(OidcError::Denied(match query.error_description {
+            Some(description) => format!("{error} ({description})"),
+            None => error,
+        }));
+    }
+
+    let (Some(code), Some(state)) = (query.code, query.state) else {
+        return Err(OidcError::InvalidState);
+    };
+
+    // the state has to come back to the browser that started the login.
+    if request
+        .cookie(STATE_COOKIE)
+        .is_none_or(|cookie| cookie.value() != state)
+    {
+        return Err(OidcError::InvalidState);
+    }
+
+    let now = unix_millis();
+    let claims = provider
+        .finish_login(&state, &code, now)
+        .await?;
+    let hint = claims
+        .preferred_username
+        .as_deref()
+        .or(claims
+            .name
+            .as_deref())
+        .or(claims
+            .email
+            .as_deref()
+            .and_then(|email| {
+                email
+                    .split('@')
+                    .next()
+            }))
+        .unwrap_or_default();
+    let account = accounts.external(&claims.iss, &claims.sub, hint, now)?;
+    let (_, cookie) = accounts.start_session(account.id, now);
+
+    Ok(HttpResponse::Found()
+        .insert_header((
+            LOCATION,
+            provider
+                .config()
+                .post_login_redirect
+                .as_str(),
+        ))
+        .cookie(cookie)
+        .cookie(state_cookie(&accounts, String::new(), CookieDuration::ZERO))
+        .finish())
+}
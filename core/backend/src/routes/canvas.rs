@@ -0,0 +1,322 @@
+use std::time::SystemTime;
+
+use actix_web::http::header::{
+    CacheControl,
+    CacheDirective,
+    ContentDisposition,
+    ContentType,
+    ETag,
+    EntityTag,
+    Header,
+    IfNoneMatch,
+};
+use actix_web::web::{Data, Json, Path, Query, block};
+use actix_web::{HttpRequest, HttpResponse, Result as ActixResult, get, post};
+use serde::Deserialize;
+use shared::api::{
+    ApiErrorBody,
+    BanKind,
+    PixelHistory,
+    PixelHistoryEntry,
+    PlacePixelRequest,
+    PlacePixelResponse,
+};
+use shared::canvas::{CanvasBounds, ChunkCoord, PixelCoord, Placement};
+use shared::realtime::ServerMessage;
+use utoipa::IntoParams;
+
+use crate::accounts::Accounts;
+use crate::accounts::session::AuthenticatedUser;
+use crate::canvas::export::{ExportLimits, render_png, validate};
+use crate::canvas::{CanvasError, CanvasStore};
+use crate::cooldown::{CooldownError, CooldownKey, Cooldowns};
+use crate::events::Events;
+use crate::moderation::{Moderation, ModerationError};
+use crate::realtime::hub::RealtimeHub;
+use crate::routes::openapi::Binary;
+use crate::utils::client::TrustedProxies;
+use crate::utils::time::unix_millis;
+
+/// Placements listed per page of a pixel's history unless fewer are asked for.
+const HISTORY_PAGE_LIMIT: usize = 50;
+
+#[derive(Deserialize, IntoParams)]
+pub struct HistoryQuery {
+    /// Only placements before this revision, to continue from `next_before`.
+    before: Option<u64>,
+    /// At most 50.
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct ChunkQuery {
+    /// Version the client already has, only newer cells are sent.
+    since: Option<u64>,
+}
+
+/// Region of an export, everything left out defaults to the whole canvas.
+#[derive(Deserialize, IntoParams)]
+pub struct ExportQuery {
+    x: Option<i32>,
+    y: Option<i32>,
+    w: Option<u32>,
+    h: Option<u32>,
+    scale: Option<u32>,
+}
+
+impl ExportQuery {
+    fn region(&self, bounds: CanvasBounds) -> CanvasBounds {
+        let x = self
+            .x
+            .unwrap_or(bounds.x);
+        let y = self
+            .y
+            .unwrap_or(bounds.y);
+        let rest = |start: i32, length: u32, from: i32| {
+            u32::try_from(i64::from(start) + i64::from(length) - i64::from(from)).unwrap_or(0)
+        };
+
+        CanvasBounds::new(
+            x,
+            y,
+            self.w
+                .unwrap_or_else(|| rest(bounds.x, bounds.width, x)),
+            self.h
+                .unwrap_or_else(|| rest(bounds.y, bounds.height, y)),
+        )
+    }
+}
+
+/// Places a pixel unless the caller is banned, their cooldown is still running
+/// or the pixel is reserved for an event.
+///
+/// Logged in users have their own cooldown, anyone else shares one per
+/// address, the one forwarded by a trusted proxy. Placements of shadow banned
+/// users are answered like any other but never reach the canvas. Addresses
+/// banned users placed from are banned the same way for anonymous callers.
+// every service a placement is checked against is extracted separately.
+#[allow(clippy::too_many_arguments)]
+#[utoipa::path(
+    tag = "canvas",
+    request_body = PlacePixelRequest,
+    responses(
+        (status = 200, body = PlacePixelResponse),
+        (status = 400, description = "The pixel is outside of the canvas or the address of the caller is unknown.", body = ApiErrorBody),
+        (status = 403, description = "The caller or their address is banned, or the pixel is reserved for an event.", body = ApiErrorBody),
+        (status = 429, description = "The cooldown is still running.", body = ApiErrorBody),
+        (status = 503, description = "The server is shutting down.", body = ApiErrorBody),
+    ),
+    security((), ("session" = [])),
+)]
+#[post("/canvas/pixels")]
+pub async fn place_pixel(
+    canvas: Data<CanvasStore>,
+    hub: Data<RealtimeHub>,
+    cooldowns: Data<Cooldowns>,
+    events: Data<Events>,
+    moderation: Data<Moderation>,
+    proxies: Data<TrustedProxies>,
+    user: Option<AuthenticatedUser>,
+    http_request: HttpRequest,
+    request: Json<PlacePixelRequest>,
+) -> ActixResult<Json<PlacePixelResponse>> {
+    let PlacePixelRequest { coord, color } = request.into_inner();
+
+    // rejected placements shouldn't cost a cooldown.
+    if !canvas
+        .bounds()
+        .contains(coord)
+    {
+        return Err(CanvasError::OutOfBounds(coord).into());
+    }
+
+    let author = user.map(|user| user.id);
+    let address = proxies.client_ip(&http_request);
+    let ban = match author {
+        Some(author) => {
+            if let Some(address) = address {
+                moderation.placed_from(author, address)?;
+            }
+
+            moderation.ban_of(author)
+        },
+        None => address.and_then(|address| moderation.ban_of_address(address)),
+    };
+
+    if ban == Some(BanKind::Ban) {
+        return Err(if author.is_some() {
+            ModerationError::Banned
+        } else {
+            ModerationError::AddressBanned
+        }
+        .into());
+    }
+
+    events.check_placement(coord, author)?;
+
+    let key = match author {
+        Some(author) => CooldownKey::User(author),
+        None => CooldownKey::ip(address.ok_or(CooldownError::NoAddress)?),
+    };
+    let now = unix_millis();
+    let cooldown = cooldowns.acquire(key, now)?;
+
+    if ban == Some(BanKind::ShadowBan) {
+        // numbered like the next placement would be.
+        let placement = Placement {
+            seq: canvas.revision() + 1,
+            coord,
+            color,
+            placed_at: now,
+            author,
+        };
+
+        return Ok(Json(PlacePixelResponse { placement, cooldown }));
+    }
+
+    let placement = {
+        let canvas = canvas.clone();
+
+        block(move || canvas.place(coord, color, author, now))
+            .await?
+            .inspect_err(|_| cooldowns.release(key))?
+    };
+
+    hub.publish(
+        placement
+            .coord
+            .chunk(canvas.chunk_size()),
+        &ServerMessage::Placement(placement.clone()),
+    );
+
+    Ok(Json(PlacePixelResponse { placement, cooldown }))
+}
+
+/// Lists who placed what on a pixel, newest first.
+#[utoipa::path(
+    tag = "canvas",
+    params(HistoryQuery),
+    responses(
+        (status = 200, body = PixelHistory),
+        (status = 400, description = "The pixel is outside of the canvas.", body = ApiErrorBody),
+    ),
+)]
+#[get("/canvas/pixels/{x}/{y}/history")]
+pub async fn pixel_history(
+    canvas: Data<CanvasStore>,
+    accounts: Data<Accounts>,
+    coord: Path<(i32, i32)>,
+    query: Query<HistoryQuery>,
+) -> ActixResult<Json<PixelHistory>> {
+    let (x, y) = coord.into_inner();
+    let coord = PixelCoord::new(x, y);
+    let before = query.before;
+    let limit = query
+        .limit
+        .map_or(HISTORY_PAGE_LIMIT, |limit| limit.clamp(1, HISTORY_PAGE_LIMIT));
+    // the placements of the chunk may have to be read from disk.
+    let page = block(move || canvas.pixel_history(coord, before, limit)).await??;
+
+    Ok(Json(PixelHistory {
+        coord,
+        placements: page
+            .placements
+            .into_iter()
+            .map(|placement| PixelHistoryEntry {
+                seq: placement.seq,
+                author: placement
+                    .author
+                    .and_then(|author| accounts.account(author))
+                    .map(|account| account.info()),
+                color: placement.color,
+                placed_at: placement.placed_at,
+            })
+            .collect(),
+        next_before: page.next_before,
+    }))
+}
+
+/// Serves a chunk as a binary [`shared::encoding::ChunkPayload`] tagged with
+/// its version, a matching `If-None-Match` gets a `304 Not Modified`.
+#[utoipa::path(
+    tag = "canvas",
+    params(
+        ChunkQuery,
+        ("If-None-Match" = Option<String>, Header, description = "Version the client already has."),
+    ),
+    responses(
+        (status = 200, description = "The encoded chunk.", content_type = "application/octet-stream", body = Binary),
+        (status = 304, description = "The chunk didn't change."),
+        (status = 404, description = "The chunk is outside of the canvas.", body = ApiErrorBody),
+    ),
+)]
+#[get("/canvas/chunks/{cx}/{cy}")]
+pub async fn chunk(
+    canvas: Data<CanvasStore>,
+    request: HttpRequest,
+    coord: Path<(i32, i32)>,
+    query: Query<ChunkQuery>,
+) -> Result<HttpResponse, CanvasError> {
+    let (cx, cy) = coord.into_inner();
+    let payload = canvas.chunk_payload(ChunkCoord::new(cx, cy), query.since)?;
+    let tag = EntityTag::new_strong(
+        payload
+            .version()
+            .to_string(),
+    );
+
+    let not_modified = match IfNoneMatch::parse(&request) {
+        Ok(IfNoneMatch::Any) => true,
+        Ok(IfNoneMatch::Items(tags)) => tags
+            .iter()
+            .any(|other| other.weak_eq(&tag)),
+        Err(_) => false,
+    };
+
+    let mut response = if not_modified { HttpResponse::NotModified() } else { HttpResponse::Ok() };
+
+    response
+        .insert_header(ETag(tag))
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]));
+
+    if not_modified {
+        return Ok(response.finish());
+    }
+
+    Ok(response
+        .content_type(ContentType::octet_stream())
+        .body(payload.encode()))
+}
+
+/// Renders the canvas, or a region of it, as a PNG.
+#[utoipa::path(
+    tag = "canvas",
+    params(ExportQuery),
+    responses(
+        (status = 200, content_type = "image/png", body = Binary),
+        (status = 400, description = "The region is empty, too large or outside of the canvas.", body = ApiErrorBody),
+    ),
+)]
+#[get("/canvas/export.png")]
+pub async fn export_png(
+    canvas: Data<CanvasStore>,
+    limits: Data<ExportLimits>,
+    query: Query<ExportQuery>,
+) -> ActixResult<HttpResponse> {
+    let region = query.region(canvas.bounds());
+    let scale = query
+        .scale
+        .unwrap_or(1);
+
+    // rejected before moving to the blocking pool so oversized requests stay cheap.
+    validate(&canvas, region, scale, &limits)?;
+
+    let export =
+        block(move || render_png(&canvas, region, scale, &limits, SystemTime::now())).await??;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::png())
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        .insert_header(ContentDisposition::attachment(format!("canvas-{}.png", export.revision)))
+        .body(export.bytes))
+}
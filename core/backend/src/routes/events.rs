@@ -0,0 +1,336 @@
+use actix_web::web::{Data, Json, Path};
+use actix_web::{HttpRequest, HttpResponse, Result as ActixResult, get, post};
+use shared::api::{
+    ApiErrorBody,
+    CheckoutRequest,
+    EventInfo,
+    EventRegistration,
+    EventRequest,
+    EventResults,
+    EventState,
+    PaymentPurpose,
+    SubmissionInfo,
+    SubmissionRequest,
+    VoteRequest,
+};
+
+use crate::accounts::Accounts;
+use crate::accounts::session::{AdminUser, AuthenticatedUser};
+use crate::canvas::CanvasStore;
+use crate::events::{EventError, Events};
+use crate::payments::Payments;
+use crate::routes::payments::idempotency_key;
+use crate::utils::time::unix_millis;
+
+/// Every published event, admins also see the drafts.
+#[utoipa::path(
+    tag = "events",
+    responses((status = 200, body = [EventInfo])),
+    security((), ("session" = [])),
+)]
+#[get("/events")]
+pub async fn list_events(events: Data<Events>, admin: Option<AdminUser>) -> Json<Vec<EventInfo>> {
+    Json(
+        events
+            .events(admin.is_some())
+            .iter()
+            .map(|event| event.info())
+            .collect(),
+    )
+}
+
+/// A published event, drafts are only shown to admins.
+#[utoipa::path(
+    tag = "events",
+    responses(
+        (status = 200, body = EventInfo),
+        (status = 404, description = "The event doesn't exist.", body = ApiErrorBody),
+    ),
+    security((), ("session" = [])),
+)]
+#[get("/events/{id}")]
+pub async fn event_info(
+    events: Data<Events>,
+    admin: Option<AdminUser>,
+    path: Path<u64>,
+) -> Result<Json<EventInfo>, EventError> {
+    let id = path.into_inner();
+
+    events
+        .event(id)
+        .filter(|event| admin.is_some() || event.state != EventState::Draft)
+        .map(|event| Json(event.info()))
+        .ok_or(EventError::NotFound(id))
+}
+
+/// Creates a draft event, only for admins.
+#[utoipa::path(
+    tag = "events",
+    request_body = EventRequest,
+    responses(
+        (status = 201, body = EventInfo),
+        (status = 400, description = "The title, schedule or region isn't valid.", body = ApiErrorBody),
+        (status = 401, description = "The caller isn't logged in.", body = ApiErrorBody),
+        (status = 403, description = "The caller isn't an admin.", body = ApiErrorBody),
+        (status = 409, description = "The region overlaps another event.", body = ApiErrorBody),
+    ),
+    security(("session" = [])),
+)]
+#[post("/events")]
+pub async fn create_event(
+    events: Data<Events>,
+    canvas: Data<CanvasStore>,
+    _admin: AdminUser,
+    request: Json<EventRequest>,
+) -> Result<HttpResponse, EventError> {
+    let event = events.create(canvas.bounds(), request.into_inner(), unix_millis())?;
+
+    Ok(HttpResponse::Created().json(event.info()))
+}
+
+/// Opens the registration of a draft, only for admins.
+#[utoipa::path(
+    tag = "events",
+    responses(
+        (status = 200, body = EventInfo),
+        (status = 401, description = "The caller isn't logged in.", body = ApiErrorBody),
+        (status = 403, description = "The caller isn't an admin.", body = ApiErrorBody),
+        (status = 404, description = "The event doesn't exist.", body = ApiErrorBody),
+        (status = 409, description = "The event isn't a draft.", body = ApiErrorBody),
+    ),
+    security(("session" = [])),
+)]
+#[post("/events/{id}/publish")]
+pub async fn publish_event(
+    events: Data<Events>,
+    _admin: AdminUser,
+    path: Path<u64>,
+) -> Result<Json<EventInfo>, EventError> {
+    let event = events.publish(path.into_inner(), unix_millis())?;
+
+    Ok(Json(event.info()))
+}
+
+/// Registers the caller for an event.
+///
+/// Free events are joined right away, for paid ones a checkout for the entry
+/// fee is opened and the caller joins once it's paid.
+#[utoipa::path(
+    tag = "events",
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries the checkout without paying twice."),
+    ),
+    responses(
+        (status = 200, description = "The caller joined, or has to pay the entry fee first.", body = EventRegistration),
+        (status = 401, description = "The caller isn't logged in.", body = ApiErrorBody),
+        (status = 404, description = "The event doesn't exist.", body = ApiErrorBody),
+        (status = 409, description = "The registration is closed or the caller already joined.", body = ApiErrorBody),
+        (status = 503, description = "Payments are disabled.", body = ApiErrorBody),
+    ),
+    security(("session" = [])),
+)]
+#[post("/events/{id}/registrations")]
+pub async fn register_for_event(
+    events: Data<Events>,
+    payments: Option<Data<Payments>>,
+    user: AuthenticatedUser,
+    http_request: HttpRequest,
+    path: Path<u64>,
+) -> ActixResult<Json<EventRegistration>> {
+    let id = path.into_inner();
+    let event = events.check_registration(id, user.id)?;
+
+    if event.entry_fee_cents == 0 {
+        let event = events.join(id, user.id, unix_millis())?;
+
+        return Ok(Json(EventRegistration { event: event.info(), payment: None }));
+    }
+
+    let payments = payments.ok_or(EventError::PaymentsDisabled)?;
+    let payment = payments
+        .checkout(
+            Some(user.id),
+            CheckoutRequest {
+                amount_cents: event.entry_fee_cents,
+                purpose: PaymentPurpose::EventEntry { event: id },
+            },
+            idempotency_key(&http_request),
+            unix_millis(),
+        )
+        .await?;
+
+    Ok(Json(EventRegistration {
+        event: event.info(),
+        payment: Some(payment.info()),
+    }))
+}
+
+/// The submissions to an event in the order they were made.
+#[utoipa::path(
+    tag = "events",
+    responses(
+        (status = 200, body = [SubmissionInfo]),
+        (status = 404, description = "The event doesn't exist.", body = ApiErrorBody),
+    ),
+)]
+#[get("/events/{id}/submissions")]
+pub async fn event_submissions(
+    events: Data<Events>,
+    path: Path<u64>,
+) -> Result<Json<Vec<SubmissionInfo>>, EventError> {
+    Ok(Json(events.submissions(path.into_inner())?))
+}
+
+/// Enters a part of the event's region as the caller's submission.
+#[utoipa::path(
+    tag = "events",
+    request_body = SubmissionRequest,
+    responses(
+        (status = 201, body = SubmissionInfo),
+        (status = 400, description = "The region isn't inside the event's.", body = ApiErrorBody),
+        (status = 401, description = "The caller isn't logged in.", body = ApiErrorBody),
+        (status = 403, description = "The caller didn't join the event.", body = ApiErrorBody),
+        (status = 404, description = "The event doesn't exist.", body = ApiErrorBody),
+        (status = 409, description = "The event isn't running or the region is taken.", body = ApiErrorBody),
+    ),
+    security(("session" = [])),
+)]
+#[post("/events/{id}/submissions")]
+pub async fn submit_to_event(
+    events: Data<Events>,
+    user: AuthenticatedUser,
+    path: Path<u64>,
+    request: Json<SubmissionRequest>,
+) -> Result<HttpResponse, EventError> {
+    let submission = events.submit(path.into_inner(), user.id, request.region, unix_millis())?;
+
+    Ok(HttpResponse::Created().json(submission.info()))
+}
+
+/// Casts the caller's vote while the event is judged.
+#[utoipa::path(
+    tag = "events",
+    request_body = VoteRequest,
+    responses(
+        (status = 204, description = "The vote is counted."),
+        (status = 401, description = "The caller isn't logged in.", body = ApiErrorBody),
+        (status = 403, description = "The caller may not vote for that submission.", body = ApiErrorBody),
+        (status = 404, description = "The event doesn't exist.", body = ApiErrorBody),
+        (status = 409, description = "The event isn't judged or the caller already voted.", body = ApiErrorBody),
+    ),
+    security(("session" = [])),
+)]
+#[post("/events/{id}/votes")]
+pub async fn vote_in_event(
+    events: Data<Events>,
+    accounts: Data<Accounts>,
+    user: AuthenticatedUser,
+    path: Path<u64>,
+    request: Json<VoteRequest>,
+) -> Result<HttpResponse, EventError> {
+    let voter_since = accounts
+        .account(user.id)
+        .map_or(u64::MAX, |account| account.created_at);
+
+    events.vote(path.into_inner(), user.id, voter_since, request.artist, unix_millis())?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Casts the jury vote of an admin, jury votes break ties.
+#[utoipa::path(
+    tag = "events",
+    request_body = VoteRequest,
+    responses(
+        (status = 204, description = "The vote is counted."),
+        (status = 401, description = "The caller isn't logged in.", body = ApiErrorBody),
+        (status = 403, description = "The caller isn't an admin.", body = ApiErrorBody),
+        (status = 404, description = "The event doesn't exist.", body = ApiErrorBody),
+        (status = 409, description = "The event isn't judged or the admin already voted.", body = ApiErrorBody),
+    ),
+    security(("session" = [])),
+)]
+#[post("/events/{id}/jury-votes")]
+pub async fn jury_vote_in_event(
+    events: Data<Events>,
+    admin: AdminUser,
+    path: Path<u64>,
+    request: Json<VoteRequest>,
+) -> Result<HttpResponse, EventError> {
+    events.jury_vote(
+        path.into_inner(),
+        admin
+            .0
+            .id,
+        request.artist,
+        unix_millis(),
+    )?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Lets the jury pick the winner regardless of the votes, only for admins.
+#[utoipa::path(
+    tag = "events",
+    request_body = VoteRequest,
+    responses(
+        (status = 204, description = "The winner is set."),
+        (status = 401, description = "The caller isn't logged in.", body = ApiErrorBody),
+        (status = 403, description = "The caller isn't an admin.", body = ApiErrorBody),
+        (status = 404, description = "The event doesn't exist.", body = ApiErrorBody),
+        (status = 409, description = "The event isn't judged.", body = ApiErrorBody),
+    ),
+    security(("session" = [])),
+)]
+#[post("/events/{id}/winner")]
+pub async fn override_event_winner(
+    events: Data<Events>,
+    _admin: AdminUser,
+    path: Path<u64>,
+    request: Json<VoteRequest>,
+) -> Result<HttpResponse, EventError> {
+    events.override_winner(path.into_inner(), request.artist, unix_millis())?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// The results of a finished event.
+#[utoipa::path(
+    tag = "events",
+    responses(
+        (status = 200, body = EventResults),
+        (status = 404, description = "The event doesn't exist.", body = ApiErrorBody),
+        (status = 409, description = "The event isn't finished.", body = ApiErrorBody),
+    ),
+)]
+#[get("/events/{id}/results")]
+pub async fn event_results(
+    events: Data<Events>,
+    path: Path<u64>,
+) -> Result<Json<EventResults>, EventError> {
+    Ok(Json(events.results(path.into_inner())?))
+}
+
+/// Calls off an event, only for admins. The entry fees are refunded by the
+/// payout worker.
+#[utoipa::path(
+    tag = "events",
+    responses(
+        (status = 200, body = EventInfo),
+        (status = 401, description = "The caller isn't logged in.", body = ApiErrorBody),
+        (status = 403, description = "The caller isn't an admin.", body = ApiErrorBody),
+        (status = 404, description = "The event doesn't exist.", body = ApiErrorBody),
+        (status = 409, description = "The event is already over.", body = ApiErrorBody),
+    ),
+    security(("session" = [])),
+)]
+#[post("/events/{id}/cancel")]
+pub async fn cancel_event(
+    events: Data<Events>,
+    _admin: AdminUser,
+    path: Path<u64>,
+) -> Result<Json<EventInfo>, EventError> {
+    let event = events.cancel(path.into_inner(), unix_millis())?;
+
+    Ok(Json(event.info()))
+}
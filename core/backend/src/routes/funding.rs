@@ -0,0 +1,19 @@
+use actix_web::get;
+use actix_web::web::{Data, Json};
+use shared::api::FundingStatus;
+
+use crate::canvas::CanvasStore;
+use crate::funding::Funding;
+
+/// How much was contributed and how far that grew the canvas.
+#[utoipa::path(
+    tag = "funding",
+    responses((status = 200, body = FundingStatus)),
+)]
+#[get("/funding")]
+pub async fn funding_status(
+    canvas: Data<CanvasStore>,
+    funding: Data<Funding>,
+) -> Json<FundingStatus> {
+    Json(funding.status(&canvas))
+}
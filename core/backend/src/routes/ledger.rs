@@ -0,0 +1,15 @@
+use actix_web::get;
+use actix_web::web::{Data, Json};
+use shared::api::LedgerReport;
+
+use crate::ledger::Ledger;
+
+/// The balance of every pool, for anyone to audit where the money went.
+#[utoipa::path(
+    tag = "ledger",
+    responses((status = 200, body = LedgerReport)),
+)]
+#[get("/ledger")]
+pub async fn ledger_report(ledger: Data<Ledger>) -> Json<LedgerReport> {
+    Json(ledger.report())
+}
@@ -1 +1,12 @@
-pub mod test_route;
+pub mod accounts;
+pub mod canvas;
+pub mod events;
+pub mod funding;
+pub mod ledger;
+pub mod moderation;
+pub mod monitoring;
+pub mod openapi;
+pub mod payments;
+pub mod payouts;
+pub mod realtime;
+pub mod timelapse;
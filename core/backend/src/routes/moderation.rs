@@ -0,0 +1,271 @@
+use actix_web::web::{Data, Json, Path, Query, block};
+use actix_web::{HttpResponse, Result as ActixResult, delete, get, post};
+use serde::Deserialize;
+use shared::api::{
+    ApiErrorBody,
+    BanInfo,
+    BanRequest,
+    ReportInfo,
+    ReportRequest,
+    ReportReview,
+    ReportStatus,
+    RollbackInfo,
+    RollbackRequest,
+};
+use shared::realtime::ServerMessage;
+use utoipa::IntoParams;
+
+use crate::accounts::Accounts;
+use crate::accounts::session::{AuthenticatedUser, ModeratorUser};
+use crate::canvas::{CanvasError, CanvasStore};
+use crate::moderation::{Moderation, ModerationError, reverted_by};
+use crate::realtime::hub::RealtimeHub;
+use crate::utils::time::unix_millis;
+
+#[derive(Deserialize, IntoParams)]
+pub struct ReportQuery {
+    /// Only reports with this status, every report without it.
+    status: Option<ReportStatus>,
+}
+
+/// Reverts the placements of an account or inside a region since a point in
+/// time, with new placements by the moderator.
+#[utoipa::path(
+    tag = "moderation",
+    request_body = RollbackRequest,
+    responses(
+        (status = 200, body = RollbackInfo),
+        (status = 400, description = "The rollback selects no account or region.", body = ApiErrorBody),
+        (status = 401, description = "The caller isn't logged in.", body = ApiErrorBody),
+        (status = 403, description = "The caller isn't a moderator.", body = ApiErrorBody),
+    ),
+    security(("session" = [])),
+)]
+#[post("/moderation/rollbacks")]
+pub async fn rollback_placements(
+    canvas: Data<CanvasStore>,
+    hub: Data<RealtimeHub>,
+    moderator: ModeratorUser,
+    request: Json<RollbackRequest>,
+) -> ActixResult<Json<RollbackInfo>> {
+    let reverted = reverted_by(&request)?;
+    let author = Some(
+        moderator
+            .0
+            .id,
+    );
+    let placements = {
+        let canvas = canvas.clone();
+
+        block(move || canvas.rollback(reverted, author, unix_millis())).await??
+    };
+
+    for placement in &placements {
+        hub.publish(
+            placement
+                .coord
+                .chunk(canvas.chunk_size()),
+            &ServerMessage::Placement(placement.clone()),
+        );
+    }
+
+    Ok(Json(RollbackInfo {
+        restored: placements.len() as u64,
+        revision: canvas.revision(),
+    }))
+}
+
+/// Every banned account, only for moderators.
+#[utoipa::path(
+    tag = "moderation",
+    responses(
+        (status = 200, body = [BanInfo]),
+        (status = 401, description = "The caller isn't logged in.", body = ApiErrorBody),
+        (status = 403, description = "The caller isn't a moderator.", body = ApiErrorBody),
+    ),
+    security(("session" = [])),
+)]
+#[get("/moderation/bans")]
+pub async fn list_bans(
+    moderation: Data<Moderation>,
+    _moderator: ModeratorUser,
+) -> Json<Vec<BanInfo>> {
+    Json(
+        moderation
+            .bans()
+            .iter()
+            .map(|ban| ban.info())
+            .collect(),
+    )
+}
+
+/// Bans or shadow bans an account, replacing an earlier ban of it. Only
+/// accounts of a lower role than the caller's can be banned.
+#[utoipa::path(
+    tag = "moderation",
+    request_body = BanRequest,
+    responses(
+        (status = 200, body = BanInfo),
+        (status = 400, description = "The reason isn't valid.", body = ApiErrorBody),
+        (status = 401, description = "The caller isn't logged in.", body = ApiErrorBody),
+        (status = 403, description = "The caller isn't a moderator or doesn't outrank the account.", body = ApiErrorBody),
+        (status = 404, description = "The account doesn't exist.", body = ApiErrorBody),
+    ),
+    security(("session" = [])),
+)]
+#[post("/moderation/bans/{account}")]
+pub async fn ban_account(
+    moderation: Data<Moderation>,
+    accounts: Data<Accounts>,
+    moderator: ModeratorUser,
+    path: Path<u64>,
+    request: Json<BanRequest>,
+) -> Result<Json<BanInfo>, ModerationError> {
+    let account = path.into_inner();
+
+    if accounts
+        .account(account)
+        .is_none()
+    {
+        return Err(ModerationError::UnknownAccount(account));
+    }
+
+    if !accounts.outranks(
+        moderator
+            .0
+            .id,
+        account,
+    ) {
+        return Err(ModerationError::Outranked);
+    }
+
+    let ban = moderation.ban(
+        account,
+        request.into_inner(),
+        moderator
+            .0
+            .id,
+        unix_millis(),
+    )?;
+
+    Ok(Json(ban.info()))
+}
+
+/// Lifts the ban of an account.
+#[utoipa::path(
+    tag = "moderation",
+    responses(
+        (status = 204, description = "The ban is lifted."),
+        (status = 401, description = "The caller isn't logged in.", body = ApiErrorBody),
+        (status = 403, description = "The caller isn't a moderator.", body = ApiErrorBody),
+        (status = 404, description = "The account isn't banned.", body = ApiErrorBody),
+    ),
+    security(("session" = [])),
+)]
+#[delete("/moderation/bans/{account}")]
+pub async fn unban_account(
+    moderation: Data<Moderation>,
+    moderator: ModeratorUser,
+    path: Path<u64>,
+) -> Result<HttpResponse, ModerationError> {
+    moderation.unban(
+        path.into_inner(),
+        moderator
+            .0
+            .id,
+        unix_millis(),
+    )?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Flags a pixel for the moderators.
+#[utoipa::path(
+    tag = "moderation",
+    request_body = ReportRequest,
+    responses(
+        (status = 201, body = ReportInfo),
+        (status = 400, description = "The pixel is outside of the canvas or the reason isn't valid.", body = ApiErrorBody),
+        (status = 401, description = "The caller isn't logged in.", body = ApiErrorBody),
+    ),
+    security(("session" = [])),
+)]
+#[post("/reports")]
+pub async fn report_pixel(
+    canvas: Data<CanvasStore>,
+    moderation: Data<Moderation>,
+    user: AuthenticatedUser,
+    request: Json<ReportRequest>,
+) -> ActixResult<HttpResponse> {
+    let request = request.into_inner();
+
+    if !canvas
+        .bounds()
+        .contains(request.coord)
+    {
+        return Err(CanvasError::OutOfBounds(request.coord).into());
+    }
+
+    let report = moderation.report(user.id, request, unix_millis())?;
+
+    Ok(HttpResponse::Created().json(report.info()))
+}
+
+/// The report queue, only for moderators.
+#[utoipa::path(
+    tag = "moderation",
+    params(ReportQuery),
+    responses(
+        (status = 200, body = [ReportInfo]),
+        (status = 401, description = "The caller isn't logged in.", body = ApiErrorBody),
+        (status = 403, description = "The caller isn't a moderator.", body = ApiErrorBody),
+    ),
+    security(("session" = [])),
+)]
+#[get("/moderation/reports")]
+pub async fn list_reports(
+    moderation: Data<Moderation>,
+    _moderator: ModeratorUser,
+    query: Query<ReportQuery>,
+) -> Json<Vec<ReportInfo>> {
+    Json(
+        moderation
+            .reports(query.status)
+            .iter()
+            .map(|report| report.info())
+            .collect(),
+    )
+}
+
+/// Resolves or dismisses an open report.
+#[utoipa::path(
+    tag = "moderation",
+    request_body = ReportReview,
+    responses(
+        (status = 200, body = ReportInfo),
+        (status = 400, description = "Reports can only be resolved or dismissed.", body = ApiErrorBody),
+        (status = 401, description = "The caller isn't logged in.", body = ApiErrorBody),
+        (status = 403, description = "The caller isn't a moderator.", body = ApiErrorBody),
+        (status = 404, description = "The report doesn't exist.", body = ApiErrorBody),
+        (status = 409, description = "The report was already reviewed.", body = ApiErrorBody),
+    ),
+    security(("session" = [])),
+)]
+#[post("/moderation/reports/{id}/review")]
+pub async fn review_report(
+    moderation: Data<Moderation>,
+    moderator: ModeratorUser,
+    path: Path<u64>,
+    request: Json<ReportReview>,
+) -> Result<Json<ReportInfo>, ModerationError> {
+    let report = moderation.review(
+        path.into_inner(),
+        request.status,
+        moderator
+            .0
+            .id,
+        unix_millis(),
+    )?;
+
+    Ok(Json(report.info()))
+}
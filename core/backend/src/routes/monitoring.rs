@@ -0,0 +1,55 @@
+use actix_web::http::StatusCode;
+use actix_web::web::Data;
+use actix_web::{HttpResponse, get};
+use shared::api::{ApiErrorBody, ApiErrorCode};
+
+use crate::monitoring::Readiness;
+use crate::monitoring::metrics::Metrics;
+use crate::realtime::hub::RealtimeHub;
+use crate::utils::api_error::ApiError;
+
+/// The Prometheus text format.
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Answers as long as the server runs.
+#[utoipa::path(
+    tag = "monitoring",
+    responses((status = 200, content_type = "text/plain", body = String)),
+)]
+#[get("/healthz")]
+pub async fn health_check() -> HttpResponse {
+    HttpResponse::Ok().body("ok")
+}
+
+/// Answers once every store is loaded, until the server starts shutting
+/// down.
+#[utoipa::path(
+    tag = "monitoring",
+    responses(
+        (status = 200, content_type = "text/plain", body = String),
+        (status = 503, description = "The server is starting or shutting down.", body = ApiErrorBody),
+    ),
+)]
+#[get("/readyz")]
+pub async fn readiness_check(readiness: Data<Readiness>) -> Result<HttpResponse, ApiError> {
+    if !readiness.is_ready() {
+        return Err(ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            ApiErrorBody::new(ApiErrorCode::Unavailable, "The server isn't ready."),
+        ));
+    }
+
+    Ok(HttpResponse::Ok().body("ok"))
+}
+
+/// Every metric in the Prometheus text format.
+#[utoipa::path(
+    tag = "monitoring",
+    responses((status = 200, content_type = "text/plain; version=0.0.4", body = String)),
+)]
+#[get("/metrics")]
+pub async fn metrics_report(metrics: Data<Metrics>, hub: Data<RealtimeHub>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(METRICS_CONTENT_TYPE)
+        .body(metrics.render(hub.session_count()))
+}
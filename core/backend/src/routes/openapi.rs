@@ -0,0 +1,130 @@
+use actix_web::get;
+use actix_web::web::Json;
+use utoipa::openapi::OpenApi as OpenApiDocument;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
+use utoipa::{Modify, OpenApi, ToSchema};
+
+use crate::accounts::session::SESSION_COOKIE;
+use crate::routes::{
+    accounts,
+    canvas,
+    events,
+    funding,
+    ledger,
+    moderation,
+    monitoring,
+    payments,
+    payouts,
+    realtime,
+    timelapse,
+};
+
+/// Raw bytes, the body of the responses that aren't JSON.
+#[derive(ToSchema)]
+#[schema(value_type = String, format = Binary)]
+pub struct Binary(pub Vec<u8>);
+
+/// Declares the session cookie operations ask for through the `session`
+/// security requirement, and drops the license the derive takes from the
+/// manifest since it declares none.
+struct Extras;
+
+impl Modify for Extras {
+    fn modify(&self, openapi: &mut OpenApiDocument) {
+        openapi
+            .info
+            .license = None;
+        openapi
+            .components
+            .get_or_insert_default()
+            .add_security_scheme(
+                "session",
+                SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new(SESSION_COOKIE))),
+            );
+    }
+}
+
+/// The routes mounted under `/api`.
+#[derive(OpenApi)]
+#[openapi(paths(
+    openapi_spec,
+    accounts::register,
+    accounts::login,
+    accounts::logout,
+    accounts::me,
+    accounts::oidc_login,
+    accounts::oidc_callback,
+    canvas::place_pixel,
+    canvas::pixel_history,
+    canvas::chunk,
+    canvas::export_png,
+    realtime::canvas_live,
+    timelapse::create_timelapse,
+    timelapse::timelapse_status,
+    timelapse::timelapse_output,
+    funding::funding_status,
+    ledger::ledger_report,
+    events::list_events,
+    events::create_event,
+    events::event_info,
+    events::publish_event,
+    events::register_for_event,
+    events::event_submissions,
+    events::submit_to_event,
+    events::vote_in_event,
+    events::jury_vote_in_event,
+    events::override_event_winner,
+    events::event_results,
+    events::cancel_event,
+    payouts::list_payouts,
+    payouts::payout_audit,
+    payouts::approve_payout,
+    payouts::reject_payout,
+    moderation::rollback_placements,
+    moderation::list_bans,
+    moderation::ban_account,
+    moderation::unban_account,
+    moderation::report_pixel,
+    moderation::list_reports,
+    moderation::review_report,
+    payments::create_checkout,
+    payments::payment_webhook,
+    payments::payment_status,
+))]
+struct ApiRoutes;
+
+/// The OpenAPI document of every route, served at `/api/openapi.json`.
+///
+/// Failures are answered with an `ApiErrorBody`, operations asking for the
+/// `session` cookie are the ones that need a logged in caller.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "CanvaDot", description = "A collaborative pixel canvas that grows as it's funded."),
+    paths(monitoring::health_check, monitoring::readiness_check, monitoring::metrics_report),
+    nest((path = "/api", api = ApiRoutes)),
+    modifiers(&Extras),
+    tags(
+        (name = "accounts", description = "Registration, logins and sessions."),
+        (name = "canvas", description = "Placing pixels and reading the canvas back."),
+        (name = "realtime", description = "Placements as they happen."),
+        (name = "timelapses", description = "Rendering the history of a region."),
+        (name = "funding", description = "Contributions and how far they grew the canvas."),
+        (name = "ledger", description = "Where the money went."),
+        (name = "events", description = "Timed contests on a region of the canvas."),
+        (name = "payouts", description = "Prizes and refunds, approved by admins."),
+        (name = "moderation", description = "Rollbacks, bans and reports."),
+        (name = "payments", description = "Checkouts and the provider's webhook."),
+        (name = "monitoring", description = "Probes and metrics."),
+        (name = "openapi", description = "This document."),
+    ),
+)]
+pub struct ApiDoc;
+
+#[utoipa::path(
+    tag = "openapi",
+    responses((status = 200, description = "The OpenAPI 3 document of the API.", body = Object)),
+)]
+#[get("/openapi.json")]
+pub async fn openapi_spec() -> Json<OpenApiDocument> {
+    Json(ApiDoc::openapi())
+}
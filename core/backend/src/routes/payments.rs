@@ -0,0 +1,142 @@
+use actix_web::web::{Bytes, Data, Json, Path, block};
+use actix_web::{HttpRequest, HttpResponse, Result as ActixResult, get, post};
+use shared::api::{ApiErrorBody, CheckoutRequest, PaymentInfo, PaymentPurpose};
+
+use crate::accounts::session::AuthenticatedUser;
+use crate::canvas::CanvasStore;
+use crate::events::Events;
+use crate::funding::Funding;
+use crate::ledger::Ledger;
+use crate::payments::{PaymentError, Payments};
+use crate::realtime::hub::RealtimeHub;
+use crate::utils::time::unix_millis;
+
+/// Header clients set to retry a checkout without paying twice.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// The [`IDEMPOTENCY_KEY_HEADER`] of a request.
+pub(crate) fn idempotency_key(request: &HttpRequest) -> Option<String> {
+    request
+        .headers()
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| {
+            value
+                .to_str()
+                .ok()
+        })
+        .map(str::to_owned)
+}
+
+/// Starts a payment, the payer finishes it at the returned checkout URL.
+///
+/// Entry fees are charged through the registration of their event, which
+/// takes the amount from the event.
+#[utoipa::path(
+    tag = "payments",
+    request_body = CheckoutRequest,
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries the checkout without paying twice."),
+    ),
+    responses(
+        (status = 201, body = PaymentInfo),
+        (status = 400, description = "The amount isn't valid or the checkout is an entry fee.", body = ApiErrorBody),
+        (status = 409, description = "The idempotency key was used for another checkout.", body = ApiErrorBody),
+        (status = 502, description = "The payment provider couldn't be reached.", body = ApiErrorBody),
+    ),
+    security((), ("session" = [])),
+)]
+#[post("/payments")]
+pub async fn create_checkout(
+    payments: Data<Payments>,
+    user: Option<AuthenticatedUser>,
+    http_request: HttpRequest,
+    request: Json<CheckoutRequest>,
+) -> Result<HttpResponse, PaymentError> {
+    let request = request.into_inner();
+
+    if let PaymentPurpose::EventEntry { .. } = request.purpose {
+        return Err(PaymentError::EntryOutsideRegistration);
+    }
+
+    let payment = payments
+        .checkout(user.map(|user| user.id), request, idempotency_key(&http_request), unix_millis())
+        .await?;
+
+    Ok(HttpResponse::Created().json(payment.info()))
+}
+
+/// A payment of the caller, anonymous payments can be looked up by anyone.
+#[utoipa::path(
+    tag = "payments",
+    responses(
+        (status = 200, body = PaymentInfo),
+        (status = 404, description = "The payment doesn't exist or belongs to someone else.", body = ApiErrorBody),
+    ),
+    security((), ("session" = [])),
+)]
+#[get("/payments/{id}")]
+pub async fn payment_status(
+    payments: Data<Payments>,
+    user: Option<AuthenticatedUser>,
+    path: Path<u64>,
+) -> Result<Json<PaymentInfo>, PaymentError> {
+    let id = path.into_inner();
+
+    payments
+        .payment(id)
+        .filter(|payment| {
+            payment
+                .account
+                .is_none_or(|account| {
+                    user.as_ref()
+                        .is_some_and(|user| user.id == account)
+                })
+        })
+        .map(|payment| Json(payment.info()))
+        .ok_or(PaymentError::NotFound(id))
+}
+
+/// Where the payment provider reports what happened to a checkout.
+///
+/// Anything but a bad signature or an unknown checkout is acknowledged, even
+/// events seen before, so the provider stops delivering them. The payment is
+/// booked, counted and applied to its event every time, which changes
+/// nothing once it's done, so a delivery that failed halfway is finished by
+/// the retry.
+#[utoipa::path(
+    tag = "payments",
+    request_body(content = Object, description = "The event, as signed by the provider.", content_type = "application/json"),
+    responses(
+        (status = 200, description = "The event is acknowledged."),
+        (status = 400, description = "The event isn't valid.", body = ApiErrorBody),
+        (status = 401, description = "The signature is wrong.", body = ApiErrorBody),
+        (status = 404, description = "The checkout doesn't exist.", body = ApiErrorBody),
+    ),
+)]
+#[post("/payments/webhook")]
+// every service a payment can affect is extracted separately.
+#[allow(clippy::too_many_arguments)]
+pub async fn payment_webhook(
+    payments: Data<Payments>,
+    funding: Data<Funding>,
+    ledger: Data<Ledger>,
+    events: Data<Events>,
+    canvas: Data<CanvasStore>,
+    hub: Data<RealtimeHub>,
+    request: HttpRequest,
+    body: Bytes,
+) -> ActixResult<HttpResponse> {
+    let now = unix_millis();
+
+    let payment = payments
+        .handle_webhook(request.headers(), &body, now)?
+        .payment;
+
+    ledger.record_payment(&payment, now)?;
+    events.apply_payment(&payment, now)?;
+
+    // growing the canvas writes to disk.
+    block(move || funding.contribute_payment(&canvas, &hub, &payment, now)).await??;
+
+    Ok(HttpResponse::Ok().finish())
+}
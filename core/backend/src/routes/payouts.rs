@@ -0,0 +1,107 @@
+use actix_web::web::{Data, Json, Path};
+use actix_web::{get, post};
+use shared::api::{ApiErrorBody, PayoutAuditRecord, PayoutInfo};
+
+use crate::accounts::session::AdminUser;
+use crate::ledger::Ledger;
+use crate::payouts::{PayoutError, Payouts};
+use crate::utils::time::unix_millis;
+
+/// Every payout, only for admins.
+#[utoipa::path(
+    tag = "payouts",
+    responses(
+        (status = 200, body = [PayoutInfo]),
+        (status = 401, description = "The caller isn't logged in.", body = ApiErrorBody),
+        (status = 403, description = "The caller isn't an admin.", body = ApiErrorBody),
+    ),
+    security(("session" = [])),
+)]
+#[get("/payouts")]
+pub async fn list_payouts(payouts: Data<Payouts>, _admin: AdminUser) -> Json<Vec<PayoutInfo>> {
+    Json(
+        payouts
+            .payouts()
+            .iter()
+            .map(|payout| payout.info())
+            .collect(),
+    )
+}
+
+/// Every step of the payout workflow, only for admins.
+#[utoipa::path(
+    tag = "payouts",
+    responses(
+        (status = 200, body = [PayoutAuditRecord]),
+        (status = 401, description = "The caller isn't logged in.", body = ApiErrorBody),
+        (status = 403, description = "The caller isn't an admin.", body = ApiErrorBody),
+    ),
+    security(("session" = [])),
+)]
+#[get("/payouts/audit")]
+pub async fn payout_audit(
+    payouts: Data<Payouts>,
+    _admin: AdminUser,
+) -> Json<Vec<PayoutAuditRecord>> {
+    Json(payouts.audit())
+}
+
+/// Approves a pending payout, it's sent once its hold period is over.
+#[utoipa::path(
+    tag = "payouts",
+    responses(
+        (status = 200, body = PayoutInfo),
+        (status = 401, description = "The caller isn't logged in.", body = ApiErrorBody),
+        (status = 403, description = "The caller isn't an admin.", body = ApiErrorBody),
+        (status = 404, description = "The payout doesn't exist.", body = ApiErrorBody),
+        (status = 409, description = "The payout isn't pending.", body = ApiErrorBody),
+    ),
+    security(("session" = [])),
+)]
+#[post("/payouts/{id}/approve")]
+pub async fn approve_payout(
+    payouts: Data<Payouts>,
+    admin: AdminUser,
+    path: Path<u64>,
+) -> Result<Json<PayoutInfo>, PayoutError> {
+    let payout = payouts.approve(
+        path.into_inner(),
+        admin
+            .0
+            .id,
+        unix_millis(),
+    )?;
+
+    Ok(Json(payout.info()))
+}
+
+/// Rejects a pending payout, its amount goes back into the prize pool.
+#[utoipa::path(
+    tag = "payouts",
+    responses(
+        (status = 200, body = PayoutInfo),
+        (status = 401, description = "The caller isn't logged in.", body = ApiErrorBody),
+        (status = 403, description = "The caller isn't an admin.", body = ApiErrorBody),
+        (status = 404, description = "The payout doesn't exist.", body = ApiErrorBody),
+        (status = 409, description = "The payout isn't pending.", body = ApiErrorBody),
+    ),
+    security(("session" = [])),
+)]
+#[post("/payouts/{id}/reject")]
+pub async fn reject_payout(
+    payouts: Data<Payouts>,
+    ledger: Data<Ledger>,
+    admin: AdminUser,
+    path: Path<u64>,
+) -> Result<Json<PayoutInfo>, PayoutError> {
+    let payout = payouts.reject(
+        &ledger,
+        path.into_inner(),
+        admin
+            .0
+            .id,
+        unix_millis(),
+    )?;
+
+    Ok(Json(payout.info()))
+}
@@ -0,0 +1,26 @@
+use actix_web::rt::spawn;
+use actix_web::web::{Data, Payload};
+use actix_web::{Error as ActixError, HttpRequest, HttpResponse, get};
+
+use crate::realtime::hub::RealtimeHub;
+use crate::realtime::session;
+
+#[utoipa::path(
+    tag = "realtime",
+    responses(
+        (status = 101, description = "Upgrades to a websocket carrying `ServerMessage`s and `ClientMessage`s."),
+    ),
+)]
+#[get("/canvas/live")]
+pub async fn canvas_live(
+    request: HttpRequest,
+    body: Payload,
+    hub: Data<RealtimeHub>,
+) -> Result<HttpResponse, ActixError> {
+    let (response, ws_session, stream) = actix_ws::handle(&request, body)?;
+    let (id, outbound) = hub.connect();
+
+    spawn(session::run(hub, id, ws_session, stream, outbound));
+
+    Ok(response)
+}
@@ -0,0 +1,78 @@
+use actix_web::http::header::ContentDisposition;
+use actix_web::web::{Data, Json, Path};
+use actix_web::{HttpResponse, get, post};
+use shared::api::{ApiErrorBody, TimelapseFormat, TimelapseJob, TimelapseRequest};
+
+use crate::canvas::CanvasStore;
+use crate::routes::openapi::Binary;
+use crate::timelapse::TimelapseError;
+use crate::timelapse::jobs::{JobId, TimelapseJobs, spawn_timelapse};
+
+#[utoipa::path(
+    tag = "timelapses",
+    request_body = TimelapseRequest,
+    responses(
+        (status = 202, description = "The render is queued.", body = TimelapseJob),
+        (status = 400, description = "The region, range or scale isn't valid.", body = ApiErrorBody),
+        (status = 404, description = "The history of the range isn't kept.", body = ApiErrorBody),
+        (status = 503, description = "Too many renders are running.", body = ApiErrorBody),
+    ),
+)]
+#[post("/canvas/timelapses")]
+pub async fn create_timelapse(
+    canvas: Data<CanvasStore>,
+    jobs: Data<TimelapseJobs>,
+    request: Json<TimelapseRequest>,
+) -> Result<HttpResponse, TimelapseError> {
+    let job = spawn_timelapse(jobs, &canvas, &request)?;
+
+    Ok(HttpResponse::Accepted().json(job))
+}
+
+#[utoipa::path(
+    tag = "timelapses",
+    params(("id" = u64, Path, description = "The job.")),
+    responses(
+        (status = 200, body = TimelapseJob),
+        (status = 404, description = "The job doesn't exist.", body = ApiErrorBody),
+    ),
+)]
+#[get("/canvas/timelapses/{id}")]
+pub async fn timelapse_status(
+    jobs: Data<TimelapseJobs>,
+    id: Path<JobId>,
+) -> Result<Json<TimelapseJob>, TimelapseError> {
+    let id = id.into_inner();
+
+    jobs.job(id)
+        .map(Json)
+        .ok_or(TimelapseError::JobNotFound(id))
+}
+
+/// The rendered image, once the job finished.
+#[utoipa::path(
+    tag = "timelapses",
+    params(("id" = u64, Path, description = "The job.")),
+    responses(
+        (status = 200, content((Binary = "image/gif"), (Binary = "image/apng"))),
+        (status = 404, description = "The job doesn't exist.", body = ApiErrorBody),
+        (status = 409, description = "The render isn't finished.", body = ApiErrorBody),
+    ),
+)]
+#[get("/canvas/timelapses/{id}/output")]
+pub async fn timelapse_output(
+    jobs: Data<TimelapseJobs>,
+    id: Path<JobId>,
+) -> Result<HttpResponse, TimelapseError> {
+    let id = id.into_inner();
+    let (format, output) = jobs.output(id)?;
+    let (content_type, extension) = match format {
+        TimelapseFormat::Gif => ("image/gif", "gif"),
+        TimelapseFormat::Apng => ("image/apng", "png"),
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(ContentDisposition::attachment(format!("timelapse-{id}.{extension}")))
+        .body(output.to_vec()))
+}
@@ -0,0 +1,258 @@
+use shared::canvas::{BoundsChange, CanvasBounds, PixelColor, PixelCoord, Placement};
+
+use crate::canvas::CanvasStore;
+use crate::canvas::chunk::Chunk;
+use crate::canvas::log::{LogRecord, PlacementLog};
+use crate::canvas::snapshot::Snapshot;
+
+#[test]
+fn chunk_cells() {
+    let mut chunk = Chunk::new(4);
+
+    assert_eq!(
+        chunk
+            .cells()
+            .len(),
+        48
+    );
+    assert_eq!(chunk.get(3, 3), PixelColor::WHITE);
+    assert_eq!(chunk.version(), 0);
+
+    chunk.set(1, 2, PixelColor::new(1, 2, 3), 5);
+    chunk.set(0, 0, PixelColor::new(4, 5, 6), 3);
+
+    assert_eq!(chunk.get(1, 2), PixelColor::new(1, 2, 3));
+    assert_eq!(chunk.get(0, 0), PixelColor::new(4, 5, 6));
+    assert_eq!(&chunk.cells()[27..30], &[1, 2, 3]);
+    assert_eq!(chunk.version(), 5);
+    assert_eq!(
+        chunk.changes_since(0),
+        vec![(0, 0, PixelColor::new(4, 5, 6)), (1, 2, PixelColor::new(1, 2, 3))]
+    );
+    assert_eq!(chunk.changes_since(3), vec![(1, 2, PixelColor::new(1, 2, 3))]);
+    assert!(
+        chunk
+            .changes_since(5)
+            .is_empty()
+    );
+}
+
+#[test]
+fn store_pixels() {
+    let store = CanvasStore::new(4, CanvasBounds::new(-4, -4, 8, 8));
+
+    assert_eq!(store.pixel(PixelCoord::new(-4, -4)), Some(PixelColor::WHITE));
+    assert_eq!(store.pixel(PixelCoord::new(4, 0)), None);
+
+    let placement = store
+        .place(PixelCoord::new(-1, -1), PixelColor::new(9, 9, 9), None, 42)
+        .expect("Placement to be accepted.");
+
+    assert_eq!(placement.seq, 1);
+    assert_eq!(placement.placed_at, 42);
+    assert_eq!(store.pixel(PixelCoord::new(-1, -1)), Some(PixelColor::new(9, 9, 9)));
+    assert!(
+        store
+            .place(PixelCoord::new(-5, 0), PixelColor::WHITE, None, 43)
+            .is_err()
+    );
+    assert_eq!(store.revision(), 1);
+}
+
+#[test]
+fn log_reader_skips_older_records() {
+    let dir = tempfile::tempdir().expect("Temporary directory to be created.");
+    let mut log = PlacementLog::open(dir.path()).expect("Log to open.");
+
+    for seq in 1..=5 {
+        log.append(&LogRecord::Placement(Placement {
+            seq,
+            coord: PixelCoord::new(-1, 1),
+            color: PixelColor::new(1, 2, 3),
+            placed_at: seq * 10,
+            author: None,
+        }))
+        .expect("Record to be appended.");
+    }
+
+    assert!(
+        log.append(&LogRecord::Placement(Placement {
+            seq: 5,
+            coord: PixelCoord::new(0, 0),
+            color: PixelColor::WHITE,
+            placed_at: 0,
+            author: None,
+        }))
+        .is_err()
+    );
+
+    let records = log
+        .records(3)
+        .expect("Log to be readable.")
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Every record to be valid.");
+
+    assert_eq!(
+        records
+            .iter()
+            .map(LogRecord::seq)
+            .collect::<Vec<_>>(),
+        vec![4, 5]
+    );
+    assert_eq!(
+        records[0],
+        LogRecord::Placement(Placement {
+            seq: 4,
+            coord: PixelCoord::new(-1, 1),
+            color: PixelColor::new(1, 2, 3),
+            placed_at: 40,
+            author: None,
+        })
+    );
+}
+
+#[test]
+fn log_keeps_bounds_changes() {
+    let dir = tempfile::tempdir().expect("Temporary directory to be created.");
+    let change = BoundsChange {
+        seq: 2,
+        bounds: CanvasBounds::new(-8, -8, 32, 48),
+        changed_at: 20,
+    };
+
+    {
+        let mut log = PlacementLog::open(dir.path()).expect("Log to open.");
+
+        log.append(&LogRecord::Placement(Placement {
+            seq: 1,
+            coord: PixelCoord::new(0, 0),
+            color: PixelColor::WHITE,
+            placed_at: 10,
+            author: None,
+        }))
+        .expect("Record to be appended.");
+        log.append(&LogRecord::Bounds(change))
+            .expect("Record to be appended.");
+    }
+
+    let records = PlacementLog::open(dir.path())
+        .expect("Log to reopen.")
+        .records(1)
+        .expect("Log to be readable.")
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Every record to be valid.");
+
+    assert_eq!(records, vec![LogRecord::Bounds(change)]);
+}
+
+#[test]
+fn snapshot_round_trip() {
+    let dir = tempfile::tempdir().expect("Temporary directory to be created.");
+    let store = CanvasStore::new(4, CanvasBounds::new(-4, -4, 8, 8));
+
+    store
+        .place(PixelCoord::new(-1, -1), PixelColor::new(9, 9, 9), None, 1)
+        .expect("Placement to be accepted.");
+    store
+        .place(PixelCoord::new(3, 3), PixelColor::new(7, 7, 7), None, 2)
+        .expect("Placement to be accepted.");
+
+    let snapshot = store.snapshot();
+    let path = snapshot
+        .write(dir.path())
+        .expect("Snapshot to be written.");
+
+    assert_eq!(Snapshot::read(&path).expect("Snapshot to be read."), snapshot);
+    assert_eq!(Snapshot::latest(dir.path()).expect("Directory to be readable."), Some(snapshot));
+
+    std::fs::write(
+        dir.path()
+            .join("00000000000000000009.snap"),
+        b"CDOTSNAP",
+    )
+    .expect("Broken snapshot to be written.");
+
+    // a broken newer snapshot falls back to the previous one.
+    assert_eq!(
+        Snapshot::latest(dir.path())
+            .expect("Directory to be readable.")
+            .map(|snapshot| snapshot.revision),
+        Some(2)
+    );
+}
+
+#[test]
+fn log_keeps_authors() {
+    let dir = tempfile::tempdir().expect("Temporary directory to be created.");
+    let placements = [None, Some(7)].map(|author| Placement {
+        seq: author.map_or(1, |_| 2),
+        coord: PixelCoord::new(1, -1),
+        color: PixelColor::new(1, 2, 3),
+        placed_at: 10,
+        author,
+    });
+
+    {
+        let mut log = PlacementLog::open(dir.path()).expect("Log to open.");
+
+        for placement in &placements {
+            log.append(&LogRecord::Placement(placement.clone()))
+                .expect("Record to be appended.");
+        }
+    }
+
+    let records = PlacementLog::open(dir.path())
+        .expect("Log to reopen.")
+        .records(0)
+        .expect("Log to be readable.")
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Every record to be valid.");
+
+    assert_eq!(records, placements.map(LogRecord::Placement));
+}
+
+#[test]
+fn pixel_history_pages() {
+    let store = CanvasStore::new(4, CanvasBounds::new(0, 0, 8, 8));
+    let coord = PixelCoord::new(2, 3);
+
+    for author in 1..=5 {
+        store
+            .place(coord, PixelColor::new(0, 0, 0), Some(author), author * 10)
+            .expect("Placement to be accepted.");
+        store
+            .place(PixelCoord::new(0, 0), PixelColor::WHITE, None, author * 10)
+            .expect("Placement to be accepted.");
+    }
+
+    let page = |before: Option<u64>, limit: usize| {
+        let page = store
+            .pixel_history(coord, before, limit)
+            .expect("Pixel to be on the canvas.");
+
+        (
+            page.placements
+                .iter()
+                .map(|placement| placement.author)
+                .collect::<Vec<_>>(),
+            page.next_before,
+        )
+    };
+
+    assert_eq!(page(None, 2), (vec![Some(5), Some(4)], Some(7)));
+    assert_eq!(page(Some(7), 2), (vec![Some(3), Some(2)], Some(3)));
+    assert_eq!(page(Some(3), 2), (vec![Some(1)], None));
+    assert_eq!(page(None, 0), (vec![Some(5)], Some(9)));
+    assert_eq!(
+        store
+            .pixel_history(PixelCoord::new(5, 5), None, 10)
+            .expect("Pixel to be on the canvas.")
+            .placements,
+        vec![]
+    );
+    assert!(
+        store
+            .pixel_history(PixelCoord::new(8, 0), None, 10)
+            .is_err()
+    );
+}
@@ -0,0 +1,352 @@
+use std::collections::HashMap;
+use std::fs::write;
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tempfile::tempdir;
+
+use crate::config::{Config, ConfigError, Origin};
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter()
+        .map(|&arg| arg.to_owned())
+        .collect()
+}
+
+fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|&(name, value)| (name.to_owned(), value.to_owned()))
+        .collect();
+
+    move |name| {
+        vars.get(name)
+            .cloned()
+    }
+}
+
+fn config_file(dir: &Path, contents: &str) -> String {
+    let path = dir.join("config.toml");
+
+    write(&path, contents).expect("Config file to be written.");
+
+    path.display()
+        .to_string()
+}
+
+#[test]
+fn config_layers() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+    let path = config_file(
+        dir.path(),
+        r#"
+            [server]
+            bind = "127.0.0.1:9000"
+            workers = 2
+
+            [storage]
+            data_dir = "/var/lib/canvadot"
+
+            [cooldown]
+            interval_ms = 2000
+            burst = 5
+
+            [canvas]
+            chunk_size = 64
+
+            [snapshots]
+            interval_secs = 60
+
+            [growth]
+            base_chunks = 2
+
+            [accounts]
+            admins = ["alice"]
+
+            [oidc]
+            issuer = "https://id.example.com"
+            client_id = "canvadot"
+            redirect_uri = "https://canvadot.example.com/api/auth/oidc/callback"
+        "#,
+    );
+
+    let Ok(config) = Config::load(args(&["--config", &path]), env(&[])) else {
+        panic!("Config to load.");
+    };
+
+    assert_eq!(
+        config
+            .server
+            .bind,
+        "127.0.0.1:9000"
+            .parse::<SocketAddr>()
+            .expect("Address to parse.")
+    );
+    assert_eq!(
+        config
+            .server
+            .workers,
+        NonZeroUsize::new(2)
+    );
+    assert_eq!(
+        config
+            .storage
+            .data_dir,
+        PathBuf::from("/var/lib/canvadot")
+    );
+    assert_eq!(
+        config
+            .cooldown
+            .interval,
+        Duration::from_secs(2)
+    );
+    assert_eq!(
+        config
+            .cooldown
+            .burst,
+        5
+    );
+    assert_eq!(
+        config
+            .canvas
+            .chunk_size,
+        64
+    );
+    assert_eq!(
+        config
+            .snapshots
+            .interval,
+        Duration::from_mins(1)
+    );
+    assert_eq!(
+        config
+            .growth
+            .policy(64)
+            .expect("Policy to be valid.")
+            .base
+            .width,
+        128
+    );
+    assert_eq!(
+        config
+            .accounts
+            .admins,
+        ["alice"]
+    );
+    assert_eq!(
+        config
+            .oidc
+            .map(|oidc| oidc.scopes),
+        Some("openid profile email".to_owned())
+    );
+    assert!(
+        config
+            .payments
+            .is_none()
+    );
+
+    // the environment beats the file and flags beat both.
+    let Ok(config) = Config::load(
+        args(&["--workers", "8", "--chunk-size=128"]),
+        env(&[
+            ("CANVADOT_CONFIG", &path),
+            ("CANVADOT_WORKERS", "4"),
+            ("CANVADOT_COOLDOWN_BURST", "1"),
+            ("CANVADOT_FAKE_PAYMENTS_SECRET", "secret"),
+            ("CANVADOT_FAKE_PAYMENTS_DEV_MODE", "true"),
+            ("CANVADOT_MODERATORS", "bob, carol"),
+        ]),
+    ) else {
+        panic!("Config to load.");
+    };
+
+    assert_eq!(
+        config
+            .server
+            .workers,
+        NonZeroUsize::new(8)
+    );
+    assert_eq!(
+        config
+            .canvas
+            .chunk_size,
+        128
+    );
+    assert_eq!(
+        config
+            .cooldown
+            .burst,
+        1
+    );
+    assert_eq!(
+        config
+            .cooldown
+            .interval,
+        Duration::from_secs(2)
+    );
+    assert_eq!(
+        config
+            .payments
+            .map(|payments| payments.fake_secret)
+            .as_deref(),
+        Some("secret")
+    );
+    assert_eq!(
+        config
+            .accounts
+            .moderators,
+        ["bob", "carol"]
+    );
+}
+
+#[test]
+fn config_defaults() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+    let defaults = Config::default();
+
+    let Ok(config) = Config::load(args(&["--config", &config_file(dir.path(), "")]), env(&[]))
+    else {
+        panic!("An empty config to load.");
+    };
+
+    assert_eq!(
+        config
+            .server
+            .bind,
+        defaults
+            .server
+            .bind
+    );
+    assert_eq!(
+        config
+            .server
+            .workers,
+        None
+    );
+    assert_eq!(
+        config
+            .storage
+            .data_dir,
+        defaults
+            .storage
+            .data_dir
+    );
+    assert_eq!(
+        config
+            .canvas
+            .chunk_size,
+        defaults
+            .canvas
+            .chunk_size
+    );
+    assert!(
+        config
+            .oidc
+            .is_none()
+    );
+
+    // only the default file may be missing.
+    let missing = dir
+        .path()
+        .join("missing.toml")
+        .display()
+        .to_string();
+
+    assert!(matches!(
+        Config::load(args(&["--config", &missing]), env(&[])),
+        Err(ConfigError::Read { .. })
+    ));
+}
+
+#[test]
+fn config_errors() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+    let load = |contents: &str, flags: &[&str], vars: &[(&str, &str)]| {
+        let mut all = args(&["--config", &config_file(dir.path(), contents)]);
+
+        all.extend(args(flags));
+
+        Config::load(all, env(vars))
+    };
+
+    assert!(matches!(load("[server", &[], &[]), Err(ConfigError::File { .. })));
+    assert!(matches!(
+        load("[server]\nport = 8081", &[], &[]),
+        Err(ConfigError::File { message, .. }) if message.contains("port")
+    ));
+    assert!(matches!(
+        load("[payments]", &[], &[]),
+        Err(ConfigError::File { message, .. }) if message.contains("fake_secret")
+    ));
+
+    // fake payments only run in dev mode.
+    assert!(matches!(
+        load("[payments]\nfake_secret = \"secret\"", &[], &[]),
+        Err(ConfigError::File { message, .. }) if message.contains("dev_mode")
+    ));
+    assert!(matches!(
+        load(
+            "[payments]\nfake_secret = \"secret\"\ndev_mode = true",
+            &[],
+            &[("CANVADOT_FAKE_PAYMENTS_DEV_MODE", "false")]
+        ),
+        Err(ConfigError::InvalidValue {
+            origin: Origin::Env("CANVADOT_FAKE_PAYMENTS_DEV_MODE"),
+            ..
+        })
+    ));
+    assert!(matches!(load("[server]\nworkers = \"2\"", &[], &[]), Err(ConfigError::File { .. })));
+    assert!(matches!(
+        load("[payouts]\nwinner_percent = 150", &[], &[]),
+        Err(ConfigError::File { .. })
+    ));
+    assert!(matches!(
+        load("", &[], &[("CANVADOT_SNAPSHOT_ARCHIVE_LOG", "sometimes")]),
+        Err(ConfigError::InvalidValue {
+            origin: Origin::Env("CANVADOT_SNAPSHOT_ARCHIVE_LOG"),
+            ..
+        })
+    ));
+    assert!(matches!(
+        load("", &[], &[("CANVADOT_CHUNK_SIZE", "70000")]),
+        Err(ConfigError::InvalidValue {
+            origin: Origin::Env("CANVADOT_CHUNK_SIZE"),
+            ..
+        })
+    ));
+    assert!(matches!(
+        load("", &["--growth-base-chunks", "100000000"], &[]),
+        Err(ConfigError::Invalid(message)) if message.contains("base_chunks")
+    ));
+    assert!(matches!(
+        load("", &["--cooldown-burst", "0"], &[]),
+        Err(ConfigError::InvalidValue {
+            origin: Origin::Flag("--cooldown-burst"),
+            ..
+        })
+    ));
+    assert!(matches!(
+        load("", &["--bind", "somewhere"], &[]),
+        Err(ConfigError::InvalidValue { origin: Origin::Flag("--bind"), .. })
+    ));
+    assert!(matches!(
+        load("", &["--port", "80"], &[]),
+        Err(ConfigError::UnknownArgument(flag)) if flag == "--port"
+    ));
+    assert!(matches!(
+        load("", &["--workers"], &[]),
+        Err(ConfigError::MissingArgument(flag)) if flag == "--workers"
+    ));
+
+    let Err(error) = load("[server]\nworkers = 0", &[], &[]) else {
+        panic!("No workers to be refused.");
+    };
+
+    assert!(
+        error
+            .to_string()
+            .contains("workers = 0")
+    );
+}
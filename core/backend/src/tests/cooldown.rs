@@ -0,0 +1,93 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+use crate::cooldown::{CooldownError, CooldownKey, CooldownPolicy, Cooldowns};
+
+#[test]
+fn cooldown_burst_and_refill() {
+    let cooldowns = Cooldowns::new(CooldownPolicy {
+        interval: Duration::from_secs(10),
+        burst: 3,
+    });
+    let user = CooldownKey::User(1);
+
+    assert_eq!(
+        cooldowns
+            .status(user, 1_000)
+            .remaining,
+        3
+    );
+
+    for remaining in [2, 1, 0] {
+        let cooldown = cooldowns
+            .acquire(user, 1_000)
+            .expect("Placement to be in the burst.");
+
+        assert_eq!(cooldown.remaining, remaining);
+    }
+
+    assert_eq!(
+        cooldowns
+            .status(user, 1_000)
+            .next_placement_at,
+        11_000
+    );
+
+    let Err(CooldownError::Active { retry_at, wait_ms }) = cooldowns.acquire(user, 5_000) else {
+        panic!("Burst to be used up.");
+    };
+
+    assert_eq!((retry_at, wait_ms), (11_000, 6_000));
+
+    // a placement given back can be taken again right away.
+    cooldowns.release(user);
+
+    assert_eq!(
+        cooldowns
+            .acquire(user, 5_000)
+            .expect("Placement to be given back.")
+            .remaining,
+        0
+    );
+
+    // one placement comes back per interval.
+    let cooldown = cooldowns
+        .acquire(user, 11_000)
+        .expect("Placement to be refilled.");
+
+    assert_eq!(cooldown.remaining, 0);
+    assert_eq!(cooldown.next_placement_at, 21_000);
+    assert!(
+        cooldowns
+            .acquire(user, 20_999)
+            .is_err()
+    );
+
+    // after a long break the bucket is full but not fuller.
+    assert_eq!(
+        cooldowns
+            .status(user, 1_000_000)
+            .remaining,
+        3
+    );
+    assert_eq!(
+        cooldowns
+            .status(CooldownKey::User(2), 0)
+            .remaining,
+        3
+    );
+}
+
+#[test]
+fn cooldown_keys() {
+    let v4 = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let v6 = |last: u16| IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 1, 0, 0, 0, last));
+
+    assert_eq!(CooldownKey::ip(v4), CooldownKey::Ip(v4));
+    assert_eq!(CooldownKey::ip(v6(1)), CooldownKey::ip(v6(2)));
+    assert_ne!(CooldownKey::ip(v6(1)), CooldownKey::ip(IpAddr::V6(Ipv6Addr::LOCALHOST)));
+    assert_eq!(
+        CooldownKey::ip(IpAddr::V6(Ipv4Addr::new(192, 0, 2, 1).to_ipv6_mapped())),
+        CooldownKey::Ip(v4)
+    );
+}
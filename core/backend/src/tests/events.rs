@@ -0,0 +1,412 @@
+use shared::api::{
+    EventRequest,
+    EventState,
+    PaymentPurpose,
+    PaymentStatus,
+    TieBreak,
+    default_tie_breaks,
+};
+use shared::canvas::{CanvasBounds, PixelCoord};
+
+use crate::events::{EventError, Events};
+use crate::payments::Payment;
+
+const CANVAS: CanvasBounds = CanvasBounds::new(0, 0, 64, 64);
+
+fn request(region: CanvasBounds, entry_fee_cents: u64) -> EventRequest {
+    EventRequest {
+        title: "Pixel Sprint".to_owned(),
+        region,
+        starts_at: 100,
+        ends_at: 200,
+        judging_ends_at: 300,
+        entry_fee_cents,
+        tie_breaks: default_tie_breaks(),
+    }
+}
+
+fn entry(event: u64, account: u64, status: PaymentStatus) -> Payment {
+    Payment {
+        id: 1,
+        account: Some(account),
+        amount_cents: 5_00,
+        purpose: PaymentPurpose::EventEntry { event },
+        status,
+        reference: String::new(),
+        checkout_url: String::new(),
+        idempotency_key: None,
+        created_at: 0,
+        updated_at: 0,
+    }
+}
+
+#[test]
+fn event_lifecycle() {
+    let events = Events::new();
+    let region = CanvasBounds::new(0, 0, 16, 16);
+
+    assert!(matches!(
+        events.create(
+            CANVAS,
+            EventRequest {
+                title: " ".to_owned(),
+                ..request(region, 0)
+            },
+            0
+        ),
+        Err(EventError::InvalidTitle)
+    ));
+    assert!(matches!(
+        events.create(CANVAS, EventRequest { ends_at: 100, ..request(region, 0) }, 0),
+        Err(EventError::InvalidSchedule)
+    ));
+    assert!(matches!(
+        events.create(CANVAS, request(region, 0), 101),
+        Err(EventError::InvalidSchedule)
+    ));
+    assert!(matches!(
+        events.create(CANVAS, request(CanvasBounds::new(60, 60, 8, 8), 0), 0),
+        Err(EventError::InvalidRegion)
+    ));
+
+    let event = events
+        .create(CANVAS, request(region, 0), 0)
+        .expect("Event to be created.");
+
+    assert_eq!(event.state, EventState::Draft);
+    assert!(matches!(
+        events.create(CANVAS, request(CanvasBounds::new(8, 8, 16, 16), 0), 0),
+        Err(EventError::Overlaps(1))
+    ));
+
+    // drafts can't be joined and don't show up publicly.
+    assert!(matches!(events.join(event.id, 7, 0), Err(EventError::WrongState(EventState::Draft))));
+    assert!(
+        events
+            .events(false)
+            .is_empty()
+    );
+    assert_eq!(
+        events
+            .advance(150)
+            .expect("Events to advance.")
+            .len(),
+        0
+    );
+
+    events
+        .publish(event.id, 10)
+        .expect("Event to be published.");
+
+    assert!(matches!(
+        events.publish(event.id, 10),
+        Err(EventError::WrongState(EventState::Registration))
+    ));
+
+    events
+        .join(event.id, 7, 20)
+        .expect("Account to join.");
+
+    assert!(matches!(events.join(event.id, 7, 20), Err(EventError::AlreadyRegistered)));
+
+    // the schedule moves the event forward, skipping missed states.
+    let states = |now| {
+        events
+            .advance(now)
+            .expect("Events to advance.")
+            .iter()
+            .map(|event| event.state)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(states(99), vec![]);
+    assert_eq!(states(100), vec![EventState::Running]);
+    assert_eq!(states(150), vec![]);
+    assert!(matches!(events.join(event.id, 8, 150), Err(EventError::WrongState(_))));
+    assert_eq!(states(1_000), vec![EventState::Finished]);
+
+    // finished events free their region.
+    events
+        .create(
+            CANVAS,
+            EventRequest {
+                starts_at: 1_000,
+                ends_at: 1_100,
+                judging_ends_at: 1_200,
+                ..request(region, 0)
+            },
+            1_000,
+        )
+        .expect("Region to be free again.");
+}
+
+#[test]
+fn event_placements() {
+    let events = Events::new();
+    let event = events
+        .create(CANVAS, request(CanvasBounds::new(0, 0, 16, 16), 0), 0)
+        .expect("Event to be created.");
+    let inside = PixelCoord::new(4, 4);
+    let outside = PixelCoord::new(32, 32);
+
+    events
+        .publish(event.id, 0)
+        .expect("Event to be published.");
+    events
+        .join(event.id, 7, 0)
+        .expect("Account to join.");
+
+    // anyone may draw until the event starts.
+    assert!(
+        events
+            .check_placement(inside, None)
+            .is_ok()
+    );
+
+    events
+        .advance(100)
+        .expect("Events to advance.");
+
+    assert!(
+        events
+            .check_placement(inside, Some(7))
+            .is_ok()
+    );
+    assert!(matches!(events.check_placement(inside, Some(8)), Err(EventError::Reserved(1))));
+    assert!(matches!(events.check_placement(inside, None), Err(EventError::Reserved(1))));
+    assert!(
+        events
+            .check_placement(outside, None)
+            .is_ok()
+    );
+
+    events
+        .advance(200)
+        .expect("Events to advance.");
+
+    assert!(matches!(events.check_placement(inside, Some(7)), Err(EventError::Reserved(1))));
+
+    events
+        .advance(300)
+        .expect("Events to advance.");
+
+    assert!(
+        events
+            .check_placement(inside, None)
+            .is_ok()
+    );
+}
+
+#[test]
+fn event_payments() {
+    let events = Events::new();
+    let event = events
+        .create(CANVAS, request(CanvasBounds::new(0, 0, 16, 16), 5_00), 0)
+        .expect("Event to be created.");
+
+    events
+        .publish(event.id, 0)
+        .expect("Event to be published.");
+
+    let joined = |payment: &Payment| {
+        events
+            .apply_payment(payment, 0)
+            .expect("Payment to be applied.")
+            .map(|event| event.participants)
+    };
+
+    assert_eq!(joined(&entry(event.id, 7, PaymentStatus::Pending)), None);
+
+    // an entry paying less than the fee doesn't register anyone.
+    let underpaid = Payment {
+        amount_cents: 1,
+        ..entry(event.id, 7, PaymentStatus::Succeeded)
+    };
+
+    assert_eq!(joined(&underpaid), None);
+    assert_eq!(joined(&entry(event.id, 7, PaymentStatus::Succeeded)), Some([7].into()));
+    assert_eq!(joined(&entry(event.id, 7, PaymentStatus::Succeeded)), None);
+    assert_eq!(joined(&entry(2, 7, PaymentStatus::Succeeded)), None);
+    assert_eq!(joined(&entry(event.id, 7, PaymentStatus::Refunded)), Some([].into()));
+    assert_eq!(joined(&entry(event.id, 7, PaymentStatus::Refunded)), None);
+
+    // a fee settling after the start still counts.
+    events
+        .advance(100)
+        .expect("Events to advance.");
+
+    assert_eq!(joined(&entry(event.id, 8, PaymentStatus::Succeeded)), Some([8].into()));
+}
+
+/// An event whose artists 1 to 3 joined, judging at 200.
+fn judged_event(events: &Events, tie_breaks: Vec<TieBreak>) -> u64 {
+    let event = events
+        .create(
+            CANVAS,
+            EventRequest {
+                tie_breaks,
+                ..request(CanvasBounds::new(0, 0, 16, 16), 0)
+            },
+            0,
+        )
+        .expect("Event to be created.");
+
+    events
+        .publish(event.id, 0)
+        .expect("Event to be published.");
+
+    for artist in 1..=3 {
+        events
+            .join(event.id, artist, 0)
+            .expect("Artist to join.");
+    }
+
+    event.id
+}
+
+#[test]
+fn event_submissions() {
+    let events = Events::new();
+    let event = judged_event(&events, default_tie_breaks());
+    let left = CanvasBounds::new(0, 0, 8, 16);
+
+    assert!(matches!(
+        events.submit(event, 1, left, 50),
+        Err(EventError::WrongState(EventState::Registration))
+    ));
+
+    events
+        .advance(100)
+        .expect("Events to advance.");
+
+    assert!(matches!(events.submit(event, 9, left, 100), Err(EventError::NotParticipant)));
+    assert!(matches!(
+        events.submit(event, 1, CanvasBounds::new(8, 0, 16, 16), 100),
+        Err(EventError::InvalidSubmission)
+    ));
+
+    events
+        .submit(event, 1, left, 110)
+        .expect("Submission to be entered.");
+
+    assert!(matches!(
+        events.submit(event, 1, CanvasBounds::new(8, 0, 8, 8), 120),
+        Err(EventError::AlreadySubmitted)
+    ));
+    assert!(matches!(
+        events.submit(event, 2, CanvasBounds::new(4, 0, 8, 8), 120),
+        Err(EventError::SubmissionTaken(1))
+    ));
+
+    events
+        .submit(event, 2, CanvasBounds::new(8, 0, 8, 8), 120)
+        .expect("Submission to be entered.");
+
+    assert_eq!(
+        events
+            .submissions(event)
+            .expect("Submissions to be listed.")
+            .iter()
+            .map(|submission| submission.artist)
+            .collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+
+    // votes only count once the event is judged.
+    assert!(matches!(
+        events.vote(event, 5, 0, 1, 150),
+        Err(EventError::WrongState(EventState::Running))
+    ));
+
+    events
+        .advance(200)
+        .expect("Events to advance.");
+
+    assert!(matches!(events.vote(event, 1, 0, 1, 200), Err(EventError::OwnSubmission)));
+    assert!(matches!(events.vote(event, 5, 100, 1, 200), Err(EventError::NotEligible)));
+    assert!(matches!(events.vote(event, 5, 0, 3, 200), Err(EventError::UnknownSubmission(3))));
+
+    events
+        .vote(event, 5, 0, 1, 200)
+        .expect("Vote to be cast.");
+
+    assert!(matches!(events.vote(event, 5, 0, 2, 200), Err(EventError::AlreadyVoted)));
+    assert!(matches!(events.results(event), Err(EventError::WrongState(EventState::Judging))));
+}
+
+#[test]
+fn event_results() {
+    let events = Events::new();
+    let results = |tie_breaks: Vec<TieBreak>, jury: &[u64], winner: Option<u64>| {
+        let event = judged_event(&events, tie_breaks);
+
+        events
+            .advance(100)
+            .expect("Events to advance.");
+        events
+            .submit(event, 1, CanvasBounds::new(0, 0, 4, 4), 110)
+            .expect("Submission to be entered.");
+        events
+            .submit(event, 2, CanvasBounds::new(4, 0, 4, 4), 120)
+            .expect("Submission to be entered.");
+        events
+            .submit(event, 3, CanvasBounds::new(8, 0, 4, 4), 120)
+            .expect("Submission to be entered.");
+        events
+            .advance(200)
+            .expect("Events to advance.");
+
+        // artist 2 and 3 tie with two votes each.
+        for (voter, artist) in [(10, 1), (11, 2), (12, 2), (13, 3), (14, 3)] {
+            events
+                .vote(event, voter, 0, artist, 200)
+                .expect("Vote to be cast.");
+        }
+
+        for (juror, &artist) in jury
+            .iter()
+            .enumerate()
+        {
+            events
+                .jury_vote(event, 20 + juror as u64, artist, 200)
+                .expect("Jury vote to be cast.");
+        }
+
+        if let Some(artist) = winner {
+            events
+                .override_winner(event, artist, 250)
+                .expect("Winner to be picked.");
+        }
+
+        events
+            .advance(300)
+            .expect("Events to advance.");
+
+        let results = events
+            .results(event)
+            .expect("Results to be computed.");
+
+        (
+            results.winner,
+            results.overridden,
+            results
+                .standings
+                .iter()
+                .map(|standing| {
+                    standing
+                        .submission
+                        .artist
+                })
+                .collect::<Vec<_>>(),
+        )
+    };
+
+    // 2 and 3 submitted at the same time, the jury decides.
+    assert_eq!(results(default_tie_breaks(), &[3], None), (Some(3), false, vec![3, 2, 1]));
+    // without jury votes the order of submission does.
+    assert_eq!(results(default_tie_breaks(), &[], None), (Some(2), false, vec![2, 3, 1]));
+    assert_eq!(results(vec![TieBreak::JuryVote], &[3, 1], None), (Some(3), false, vec![3, 2, 1]));
+    assert_eq!(results(vec![], &[3], None), (Some(2), false, vec![2, 3, 1]));
+    // the jury can pick anyone regardless of the votes.
+    assert_eq!(results(default_tie_breaks(), &[], Some(1)), (Some(1), true, vec![2, 3, 1]));
+}
@@ -0,0 +1,31 @@
+use shared::canvas::CanvasBounds;
+
+use crate::funding::GrowthPolicy;
+
+#[test]
+fn growth_tiers() {
+    let policy = GrowthPolicy {
+        base: CanvasBounds::from_chunks(2, 2, 8).expect("Bounds to fit."),
+        tier_cents: 10_00,
+        ring: 8,
+        max_tiers: Some(2),
+    };
+
+    assert_eq!(policy.tier(0), 0);
+    assert_eq!(policy.tier(9_99), 0);
+    assert_eq!(policy.tier(10_00), 1);
+    assert_eq!(policy.tier(29_99), 2);
+    assert_eq!(policy.tier(u64::MAX), 2);
+
+    assert_eq!(policy.next_tier_cents(0), Some(10_00));
+    assert_eq!(policy.next_tier_cents(1), Some(20_00));
+    assert_eq!(policy.next_tier_cents(2), None);
+
+    assert_eq!(policy.bounds(0), policy.base);
+    assert_eq!(policy.bounds(2), CanvasBounds::new(-16, -16, 48, 48));
+
+    let free = GrowthPolicy { tier_cents: 0, ..policy };
+
+    assert_eq!(free.tier(u64::MAX), 0);
+    assert_eq!(free.next_tier_cents(0), None);
+}
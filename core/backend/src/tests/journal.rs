@@ -0,0 +1,63 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+
+use tempfile::tempdir;
+
+use crate::utils::journal::Journal;
+
+#[test]
+fn journal_replay() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+    let path = dir
+        .path()
+        .join("nested/journal.jsonl");
+
+    {
+        let (mut journal, entries) =
+            Journal::<(u64, String)>::open(&path).expect("Journal to open.");
+
+        assert!(entries.is_empty());
+
+        journal
+            .append(&(1, "one".to_owned()))
+            .expect("Entry to be appended.");
+        journal
+            .append(&(2, "two".to_owned()))
+            .expect("Entry to be appended.");
+    }
+
+    // a write cut short by a crash.
+    OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(b"[3,\"th"))
+        .expect("Torn entry to be written.");
+
+    {
+        let (mut journal, entries) =
+            Journal::<(u64, String)>::open(&path).expect("Journal to reopen.");
+
+        assert_eq!(entries, vec![(1, "one".to_owned()), (2, "two".to_owned())]);
+
+        journal
+            .append(&(3, "three".to_owned()))
+            .expect("Entry to be appended.");
+    }
+
+    let (_, entries) = Journal::<(u64, String)>::open(&path).expect("Journal to reopen.");
+
+    assert_eq!(
+        entries
+            .last()
+            .map(|(seq, _)| *seq),
+        Some(3)
+    );
+
+    OpenOptions::new()
+        .write(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(b"garbage\n"))
+        .expect("Journal to be corrupted.");
+
+    assert!(Journal::<(u64, String)>::open(&path).is_err());
+}
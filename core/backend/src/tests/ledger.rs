@@ -0,0 +1,158 @@
+use shared::api::{LedgerAccount, LedgerBalance, PaymentPurpose, PaymentStatus};
+
+use crate::ledger::{Ledger, LedgerError, Posting, TransactionKind};
+use crate::payments::Payment;
+
+fn posting(account: LedgerAccount, amount_cents: i64) -> Posting {
+    Posting { account, amount_cents }
+}
+
+#[test]
+fn ledger_postings() {
+    let ledger = Ledger::new();
+    let pool = LedgerAccount::PrizePool { event: 1 };
+
+    assert!(matches!(
+        ledger.post("a", TransactionKind::Contribution, vec![posting(LedgerAccount::Cash, 5)], 0),
+        Err(LedgerError::Empty)
+    ));
+    assert!(matches!(
+        ledger.post(
+            "a",
+            TransactionKind::Contribution,
+            vec![posting(LedgerAccount::Cash, 5), posting(pool, -4)],
+            0
+        ),
+        Err(LedgerError::Unbalanced(1))
+    ));
+
+    // pools can't pay out more than they hold, the platform can.
+    assert!(matches!(
+        ledger.post(
+            "a",
+            TransactionKind::Refund,
+            vec![posting(pool, 5), posting(LedgerAccount::Platform, -5)],
+            0
+        ),
+        Err(LedgerError::Overdrawn(LedgerAccount::PrizePool { event: 1 }))
+    ));
+
+    let first = ledger
+        .post(
+            "a",
+            TransactionKind::Contribution,
+            vec![posting(LedgerAccount::Cash, 5), posting(pool, -5)],
+            0,
+        )
+        .expect("Transaction to be posted.");
+    let again = ledger
+        .post(
+            "a",
+            TransactionKind::Contribution,
+            vec![posting(LedgerAccount::Cash, 7), posting(pool, -7)],
+            1,
+        )
+        .expect("Transaction to be found.");
+
+    assert_eq!(again, first);
+
+    ledger
+        .post(
+            "b",
+            TransactionKind::Refund,
+            vec![
+                posting(LedgerAccount::Platform, 3),
+                posting(LedgerAccount::Payable { user: 2 }, -1),
+                posting(LedgerAccount::Payable { user: 3 }, -2),
+            ],
+            2,
+        )
+        .expect("Transaction to be posted.");
+
+    assert_eq!(ledger.balance(LedgerAccount::Cash), 5);
+    assert_eq!(ledger.balance(pool), 5);
+    assert_eq!(ledger.balance(LedgerAccount::Platform), -3);
+    assert!(
+        ledger
+            .verify()
+            .is_ok()
+    );
+
+    let report = ledger.report();
+
+    assert_eq!(report.transactions, 2);
+    assert_eq!(report.payables_cents, 3);
+    assert!(report.balanced);
+    assert_eq!(
+        report.balances,
+        vec![
+            LedgerBalance {
+                account: LedgerAccount::Cash,
+                balance_cents: 5
+            },
+            LedgerBalance {
+                account: LedgerAccount::Platform,
+                balance_cents: -3
+            },
+            LedgerBalance { account: pool, balance_cents: 5 },
+        ]
+    );
+}
+
+#[test]
+fn ledger_payments() {
+    let ledger = Ledger::new();
+    let mut payment = Payment {
+        id: 4,
+        account: None,
+        amount_cents: 250,
+        purpose: PaymentPurpose::Contribution,
+        status: PaymentStatus::Pending,
+        reference: "cs_4".to_owned(),
+        checkout_url: String::new(),
+        idempotency_key: None,
+        created_at: 0,
+        updated_at: 0,
+    };
+
+    assert!(
+        ledger
+            .record_payment(&payment, 0)
+            .expect("Payment to be recorded.")
+            .is_empty()
+    );
+
+    payment.status = PaymentStatus::Succeeded;
+
+    for _ in 0..2 {
+        ledger
+            .record_payment(&payment, 1)
+            .expect("Payment to be recorded.");
+
+        assert_eq!(ledger.balance(LedgerAccount::GrowthFund), 250);
+        assert_eq!(ledger.balance(LedgerAccount::Cash), 250);
+    }
+
+    // a refund seen before the payment was recorded posts both.
+    payment.status = PaymentStatus::Refunded;
+
+    let transactions = ledger
+        .record_payment(&payment, 2)
+        .expect("Payment to be recorded.");
+
+    assert_eq!(
+        transactions
+            .iter()
+            .map(|transaction| transaction.kind)
+            .collect::<Vec<_>>(),
+        vec![TransactionKind::Contribution, TransactionKind::Refund]
+    );
+    assert_eq!(ledger.balance(LedgerAccount::GrowthFund), 0);
+    assert_eq!(ledger.balance(LedgerAccount::Cash), 0);
+    assert_eq!(
+        ledger
+            .transactions()
+            .len(),
+        2
+    );
+}
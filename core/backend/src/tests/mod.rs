@@ -1,2 +1,11 @@
-#[test]
-fn test_test() {}
+mod canvas;
+mod config;
+mod cooldown;
+mod events;
+mod funding;
+mod journal;
+mod ledger;
+mod moderation;
+mod monitoring;
+mod payments;
+mod realtime;
@@ -0,0 +1,181 @@
+use shared::api::{BanKind, BanRequest, ReportRequest, ReportStatus, RollbackRequest};
+use shared::canvas::{CanvasBounds, PixelColor, PixelCoord};
+
+use crate::canvas::CanvasStore;
+use crate::moderation::{Moderation, ModerationError, reverted_by};
+
+const RED: PixelColor = PixelColor::new(255, 0, 0);
+const BLUE: PixelColor = PixelColor::new(0, 0, 255);
+const GRIEF: PixelColor = PixelColor::new(0, 0, 0);
+
+fn rollback(store: &CanvasStore, request: &RollbackRequest) -> Vec<(PixelCoord, PixelColor)> {
+    store
+        .rollback(reverted_by(request).expect("Rollback to be valid."), Some(99), 1_000)
+        .expect("Rollback to succeed.")
+        .iter()
+        .map(|placement| {
+            assert_eq!(placement.author, Some(99));
+
+            (placement.coord, placement.color)
+        })
+        .collect()
+}
+
+#[test]
+fn rollback_of_an_account() {
+    let store = CanvasStore::new(4, CanvasBounds::new(0, 0, 8, 8));
+    let place = |x: i32, color: PixelColor, author: u64, placed_at: u64| {
+        store
+            .place(PixelCoord::new(x, 0), color, Some(author), placed_at)
+            .expect("Placement to be accepted.");
+    };
+
+    place(0, RED, 1, 10);
+    place(1, BLUE, 1, 10);
+    place(0, GRIEF, 2, 20);
+    place(1, GRIEF, 2, 20);
+    place(2, GRIEF, 2, 20);
+    place(3, GRIEF, 2, 5);
+    // painted over by someone else after the griefing.
+    place(1, RED, 3, 30);
+
+    let request = RollbackRequest {
+        account: Some(2),
+        region: None,
+        since: 10,
+    };
+
+    assert_eq!(
+        rollback(&store, &request),
+        vec![(PixelCoord::new(0, 0), RED), (PixelCoord::new(2, 0), PixelColor::WHITE)]
+    );
+    assert_eq!(store.pixel(PixelCoord::new(1, 0)), Some(RED));
+    assert_eq!(store.pixel(PixelCoord::new(3, 0)), Some(GRIEF));
+    assert_eq!(rollback(&store, &request), vec![]);
+
+    // the history keeps the reverted placement.
+    assert_eq!(
+        store
+            .pixel_history(PixelCoord::new(0, 0), None, 10)
+            .expect("Pixel to be on the canvas.")
+            .placements
+            .iter()
+            .map(|placement| placement.author)
+            .collect::<Vec<_>>(),
+        vec![Some(99), Some(2), Some(1)]
+    );
+}
+
+#[test]
+fn rollback_of_a_region() {
+    let store = CanvasStore::new(4, CanvasBounds::new(0, 0, 8, 8));
+
+    for (coord, color, placed_at) in [
+        (PixelCoord::new(1, 1), RED, 10),
+        (PixelCoord::new(1, 1), GRIEF, 20),
+        (PixelCoord::new(2, 2), GRIEF, 30),
+        (PixelCoord::new(1, 1), GRIEF, 40),
+        (PixelCoord::new(5, 5), GRIEF, 40),
+    ] {
+        store
+            .place(coord, color, None, placed_at)
+            .expect("Placement to be accepted.");
+    }
+
+    let request = RollbackRequest {
+        account: None,
+        region: Some(CanvasBounds::new(0, 0, 4, 4)),
+        since: 15,
+    };
+
+    assert_eq!(
+        rollback(&store, &request),
+        vec![(PixelCoord::new(1, 1), RED), (PixelCoord::new(2, 2), PixelColor::WHITE)]
+    );
+    assert_eq!(store.pixel(PixelCoord::new(5, 5)), Some(GRIEF));
+    // the restoring placements are inside the region too but change nothing.
+    assert_eq!(rollback(&store, &request), vec![]);
+    assert!(matches!(
+        reverted_by(&RollbackRequest { account: None, region: None, since: 0 }),
+        Err(ModerationError::InvalidRollback)
+    ));
+}
+
+#[test]
+fn bans_and_reports() {
+    let dir = tempfile::tempdir().expect("Temporary directory to be created.");
+    let moderation = Moderation::open(dir.path()).expect("Moderation to open.");
+    let report = |reporter: u64, reason: &str| {
+        moderation.report(
+            reporter,
+            ReportRequest {
+                coord: PixelCoord::new(1, 2),
+                reason: reason.to_owned(),
+            },
+            10,
+        )
+    };
+
+    moderation
+        .ban(
+            1,
+            BanRequest {
+                kind: BanKind::Ban,
+                reason: Some(" spam ".to_owned()),
+            },
+            9,
+            5,
+        )
+        .expect("Account to be banned.");
+    moderation
+        .ban(2, BanRequest { kind: BanKind::ShadowBan, reason: None }, 9, 5)
+        .expect("Account to be banned.");
+
+    assert_eq!(moderation.ban_of(1), Some(BanKind::Ban));
+    assert_eq!(moderation.ban_of(2), Some(BanKind::ShadowBan));
+    assert_eq!(moderation.ban_of(3), None);
+    assert!(matches!(report(1, "griefing"), Err(ModerationError::Banned)));
+    assert!(matches!(report(3, "  "), Err(ModerationError::InvalidReason)));
+
+    let first = report(2, "griefing").expect("Report to be filed.");
+    let second = report(3, " offensive ").expect("Report to be filed.");
+
+    assert_eq!((first.id, second.id), (1, 2));
+    assert_eq!(second.reason, "offensive");
+    assert!(matches!(
+        moderation.review(1, ReportStatus::Open, 9, 20),
+        Err(ModerationError::InvalidReview)
+    ));
+
+    let reviewed = moderation
+        .review(1, ReportStatus::Dismissed, 9, 20)
+        .expect("Report to be reviewed.");
+
+    assert_eq!(reviewed.reviewed_by, Some(9));
+    assert!(matches!(
+        moderation.review(1, ReportStatus::Resolved, 9, 20),
+        Err(ModerationError::AlreadyReviewed(1))
+    ));
+    assert!(matches!(
+        moderation.review(3, ReportStatus::Resolved, 9, 20),
+        Err(ModerationError::ReportNotFound(3))
+    ));
+    assert_eq!(moderation.reports(Some(ReportStatus::Open)), vec![second]);
+
+    moderation
+        .unban(2, 9, 30)
+        .expect("Account to be unbanned.");
+
+    assert!(matches!(moderation.unban(2, 9, 30), Err(ModerationError::NotBanned(2))));
+
+    let reopened = Moderation::open(dir.path()).expect("Moderation to reopen.");
+
+    assert_eq!(reopened.bans(), moderation.bans());
+    assert_eq!(reopened.reports(None), moderation.reports(None));
+    assert_eq!(
+        reopened.bans()[0]
+            .reason
+            .as_deref(),
+        Some("spam")
+    );
+}
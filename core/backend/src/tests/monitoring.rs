@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+use crate::monitoring::metrics::{Histogram, Metrics};
+
+#[test]
+fn histogram_buckets() {
+    let mut histogram = Histogram::new(&[0.1, 1.0]);
+
+    for value in [0.05, 0.1, 0.5, 3.0] {
+        histogram.observe(value);
+    }
+
+    assert_eq!(histogram.count(), 4);
+
+    let metrics = Metrics::new();
+
+    metrics.record_snapshot(Duration::from_millis(30));
+    metrics.record_snapshot(Duration::from_secs(60));
+    metrics.record_request("GET", "/api/say \"hi\"", Duration::from_millis(2));
+
+    let text = metrics.render(0);
+
+    for line in [
+        r#"canvadot_snapshot_duration_seconds_bucket{le="0.01"} 0"#,
+        r#"canvadot_snapshot_duration_seconds_bucket{le="0.05"} 1"#,
+        r#"canvadot_snapshot_duration_seconds_bucket{le="10"} 1"#,
+        r#"canvadot_snapshot_duration_seconds_bucket{le="+Inf"} 2"#,
+        "canvadot_snapshot_duration_seconds_sum 60.03",
+        "canvadot_snapshot_duration_seconds_count 2",
+        r#"canvadot_http_request_duration_seconds_bucket{method="GET",route="/api/say \"hi\"",le="0.0025"} 1"#,
+    ] {
+        assert!(
+            text.lines()
+                .any(|metric| metric == line),
+            "`{line}` to be in:\n{text}"
+        );
+    }
+}
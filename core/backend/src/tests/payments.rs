@@ -0,0 +1,29 @@
+use crate::payments::signature::{TOLERANCE_SECS, sign, verify};
+
+#[test]
+fn webhook_signatures() {
+    let body = br#"{"id":"evt_1"}"#;
+    let header = sign(b"secret", 1_000, body);
+
+    assert!(header.starts_with("t=1000,v1="));
+    assert!(verify(b"secret", &header, body, 1_000));
+    assert!(verify(b"secret", &header, body, 1_000 + TOLERANCE_SECS));
+
+    // replays, other secrets and edited bodies are rejected.
+    assert!(!verify(b"secret", &header, body, 1_001 + TOLERANCE_SECS));
+    assert!(!verify(b"other", &header, body, 1_000));
+    assert!(!verify(b"secret", &header, br#"{"id":"evt_2"}"#, 1_000));
+    assert!(!verify(b"secret", "v1=00", body, 1_000));
+    assert!(!verify(b"secret", "", body, 1_000));
+
+    // while a secret is rotated either signature is enough.
+    let rotated = format!(
+        "{header},v1={}",
+        sign(b"new", 1_000, body)
+            .split_once(",v1=")
+            .map_or("", |(_, signature)| signature)
+    );
+
+    assert!(verify(b"new", &rotated, body, 1_000));
+    assert!(verify(b"secret", &rotated, body, 1_000));
+}
@@ -0,0 +1,61 @@
+use shared::canvas::ChunkCoord;
+
+use crate::realtime::subscriptions::{SubscriptionError, SubscriptionRegistry};
+
+#[test]
+fn subscription_registry() {
+    let mut registry = SubscriptionRegistry::default();
+    let origin = ChunkCoord::new(0, 0);
+    let right = ChunkCoord::new(1, 0);
+
+    registry
+        .subscribe(1, &[origin, right], 2)
+        .expect("Subscription to fit the limit.");
+    registry
+        .subscribe(2, &[origin], 2)
+        .expect("Subscription to fit the limit.");
+    // subscribing twice to the same chunk doesn't count towards the limit.
+    registry
+        .subscribe(1, &[origin], 2)
+        .expect("Subscription to fit the limit.");
+
+    assert_eq!(
+        registry.subscribe(1, &[ChunkCoord::new(2, 0)], 2),
+        Err(SubscriptionError::TooManyChunks(2))
+    );
+    assert_eq!(registry.subscriptions(1), 2);
+
+    let mut subscribers = registry
+        .subscribers(origin)
+        .collect::<Vec<_>>();
+    subscribers.sort_unstable();
+
+    assert_eq!(subscribers, vec![1, 2]);
+
+    registry.unsubscribe(1, &[origin]);
+    registry.unsubscribe(3, &[origin]);
+
+    assert_eq!(
+        registry
+            .subscribers(origin)
+            .collect::<Vec<_>>(),
+        vec![2]
+    );
+
+    registry.remove_session(1);
+    registry.remove_session(2);
+
+    assert_eq!(
+        registry
+            .subscribers(right)
+            .count(),
+        0
+    );
+    assert_eq!(
+        registry
+            .subscribers(origin)
+            .count(),
+        0
+    );
+    assert_eq!(registry.subscriptions(1), 0);
+}
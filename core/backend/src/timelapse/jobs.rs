@@ -0,0 +1,183 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use actix_web::rt::task::spawn_blocking;
+use actix_web::web::Data;
+use parking_lot::Mutex;
+use shared::api::{TimelapseFormat, TimelapseJob, TimelapseRequest, TimelapseStatus};
+
+use crate::canvas::CanvasStore;
+use crate::timelapse::{TimelapseError, TimelapseLimits, TimelapsePlan, render};
+
+pub type JobId = u64;
+
+struct Job {
+    format: TimelapseFormat,
+    status: TimelapseStatus,
+    output: Option<Arc<Vec<u8>>>,
+}
+
+impl Job {
+    fn is_active(&self) -> bool {
+        matches!(self.status, TimelapseStatus::Queued | TimelapseStatus::Rendering)
+    }
+}
+
+/// Timelapses queued, rendering or ready for download.
+///
+/// Rendered images are kept in memory until enough newer jobs pushed them
+/// out.
+pub struct TimelapseJobs {
+    limits: TimelapseLimits,
+    next_id: AtomicU64,
+    jobs: Mutex<BTreeMap<JobId, Job>>,
+}
+
+impl TimelapseJobs {
+    pub fn new(limits: TimelapseLimits) -> Self {
+        Self {
+            limits,
+            next_id: AtomicU64::new(1),
+            jobs: Mutex::default(),
+        }
+    }
+
+    #[inline]
+    pub fn limits(&self) -> &TimelapseLimits {
+        &self.limits
+    }
+
+    pub fn job(&self, id: JobId) -> Option<TimelapseJob> {
+        self.jobs
+            .lock()
+            .get(&id)
+            .map(|job| TimelapseJob {
+                id,
+                format: job.format,
+                status: job
+                    .status
+                    .clone(),
+            })
+    }
+
+    /// The rendered image of a finished job.
+    pub fn output(&self, id: JobId) -> Result<(TimelapseFormat, Arc<Vec<u8>>), TimelapseError> {
+        let jobs = self
+            .jobs
+            .lock();
+        let job = jobs
+            .get(&id)
+            .ok_or(TimelapseError::JobNotFound(id))?;
+
+        job.output
+            .clone()
+            .map(|output| (job.format, output))
+            .ok_or(TimelapseError::NotFinished(id))
+    }
+
+    fn queue(&self, format: TimelapseFormat) -> Result<JobId, TimelapseError> {
+        let mut jobs = self
+            .jobs
+            .lock();
+
+        if jobs
+            .values()
+            .filter(|job| job.is_active())
+            .count()
+            >= self
+                .limits
+                .max_active
+        {
+            return Err(TimelapseError::Busy);
+        }
+
+        let id = self
+            .next_id
+            .fetch_add(1, Ordering::Relaxed);
+
+        jobs.insert(
+            id,
+            Job {
+                format,
+                status: TimelapseStatus::Queued,
+                output: None,
+            },
+        );
+
+        while jobs.len()
+            > self
+                .limits
+                .retain
+        {
+            let Some(oldest) = jobs
+                .iter()
+                .find(|(_, job)| !job.is_active())
+                .map(|(id, _)| *id)
+            else {
+                break;
+            };
+
+            jobs.remove(&oldest);
+        }
+
+        Ok(id)
+    }
+
+    fn update(&self, id: JobId, status: TimelapseStatus, output: Option<Vec<u8>>) {
+        if let Some(job) = self
+            .jobs
+            .lock()
+            .get_mut(&id)
+        {
+            job.status = status;
+            job.output = output.map(Arc::new);
+        }
+    }
+}
+
+/// Queues a timelapse of `store` and renders it on the blocking thread pool.
+pub fn spawn_timelapse(
+    jobs: Data<TimelapseJobs>,
+    store: &CanvasStore,
+    request: &TimelapseRequest,
+) -> Result<TimelapseJob, TimelapseError> {
+    let plan = TimelapsePlan::new(request, store.bounds(), jobs.limits())?;
+    let history = store
+        .history(0)?
+        .ok_or(TimelapseError::NoHistory)?;
+    let format = plan.format;
+    let id = jobs.queue(format)?;
+
+    spawn_blocking({
+        let jobs = jobs.clone();
+
+        move || {
+            jobs.update(id, TimelapseStatus::Rendering, None);
+
+            let mut output = Vec::new();
+
+            match render(&plan, history, &mut output) {
+                Ok(()) => {
+                    let size = output.len() as u64;
+
+                    jobs.update(
+                        id,
+                        TimelapseStatus::Finished { frames: plan.frames(), size },
+                        Some(output),
+                    );
+                },
+                Err(error) => {
+                    log::error!("Timelapse {id} failed: {error}");
+                    jobs.update(id, TimelapseStatus::Failed { message: error.to_string() }, None);
+                },
+            }
+        }
+    });
+
+    Ok(TimelapseJob {
+        id,
+        format,
+        status: TimelapseStatus::Queued,
+    })
+}
@@ -0,0 +1,429 @@
+use std::io::{Error as IoError, Result as IoResult, Write};
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use gif::{EncodingError as GifEncodingError, Frame, Repeat};
+use png::{BitDepth, ColorType, EncodingError as PngEncodingError};
+use shared::api::{ApiErrorBody, ApiErrorCode, TimelapseFormat, TimelapseRequest};
+use shared::canvas::{CanvasBounds, PixelColor};
+use thiserror::Error;
+
+use crate::canvas::CanvasError;
+use crate::canvas::log::LogRecord;
+use crate::timelapse::jobs::JobId;
+use crate::utils::api_error::ApiError;
+
+pub mod jobs;
+
+/// Playback time of a frame when the request doesn't set one.
+const DEFAULT_FRAME_DELAY: u16 = 100;
+/// Quantization speed for GIF frames with more than 256 colors.
+const GIF_QUANTIZE_SPEED: i32 = 10;
+
+/// Limits of timelapse rendering.
+#[derive(Clone, Copy, Debug)]
+pub struct TimelapseLimits {
+    /// Pixels a scaled frame may have at most.
+    pub max_area: u64,
+    pub max_frames: u32,
+    pub max_scale: u32,
+    /// Jobs that may be queued or rendering at once.
+    pub max_active: usize,
+    /// Jobs kept around for download, the oldest finished ones are dropped
+    /// first.
+    pub retain: usize,
+}
+
+impl Default for TimelapseLimits {
+    fn default() -> Self {
+        Self {
+            max_area: 1024 * 1024,
+            max_frames: 1000,
+            max_scale: 8,
+            max_active: 2,
+            retain: 16,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum TimelapseError {
+    #[error(
+        "The region {}x{} at ({}, {}) is empty or outside of the canvas.",
+        .0.width, .0.height, .0.x, .0.y
+    )]
+    InvalidRegion(CanvasBounds),
+
+    #[error("The scale must be between 1 and {max}, got {scale}.")]
+    InvalidScale { scale: u32, max: u32 },
+
+    #[error("The timelapse must end at or after {from}, got {to}.")]
+    InvalidRange { from: u64, to: u64 },
+
+    #[error("The frame interval must be at least one millisecond.")]
+    InvalidInterval,
+
+    #[error("The timelapse would have {frames} frames but at most {max} are allowed.")]
+    TooManyFrames { frames: u64, max: u32 },
+
+    #[error("Frames of {width}x{height} pixels are too large.")]
+    TooLarge { width: u64, height: u64 },
+
+    #[error("This canvas doesn't keep a placement history.")]
+    NoHistory,
+
+    #[error("The placement history before revision {first_seq} isn't kept.")]
+    HistoryCompacted { first_seq: u64 },
+
+    #[error("Too many timelapses are rendering, try again later.")]
+    Busy,
+
+    #[error("There's no timelapse {0}.")]
+    JobNotFound(JobId),
+
+    #[error("The timelapse {0} isn't finished.")]
+    NotFinished(JobId),
+
+    #[error(transparent)]
+    Canvas(#[from] CanvasError),
+
+    #[error("The placement history couldn't be read: {0}")]
+    Storage(#[from] IoError),
+
+    #[error("The timelapse couldn't be encoded: {0}")]
+    Gif(#[from] GifEncodingError),
+
+    #[error("The timelapse couldn't be encoded: {0}")]
+    Png(#[from] PngEncodingError),
+}
+
+impl ResponseError for TimelapseError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::InvalidRegion(_)
+            | Self::InvalidScale { .. }
+            | Self::InvalidRange { .. }
+            | Self::InvalidInterval
+            | Self::TooManyFrames { .. }
+            | Self::TooLarge { .. } => StatusCode::BAD_REQUEST,
+            Self::NoHistory | Self::HistoryCompacted { .. } | Self::JobNotFound(_) => {
+                StatusCode::NOT_FOUND
+            },
+            Self::NotFinished(_) => StatusCode::CONFLICT,
+            Self::Busy => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Canvas(error) => error.status_code(),
+            Self::Storage(_) | Self::Gif(_) | Self::Png(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let body = match self {
+            Self::InvalidRegion(_) => {
+                ApiErrorBody::new(ApiErrorCode::OutOfBounds, self.to_string())
+            },
+            Self::InvalidScale { .. }
+            | Self::InvalidRange { .. }
+            | Self::InvalidInterval
+            | Self::TooManyFrames { .. }
+            | Self::TooLarge { .. }
+            | Self::NotFinished(_) => {
+                ApiErrorBody::new(ApiErrorCode::InvalidRequest, self.to_string())
+            },
+            Self::NoHistory | Self::HistoryCompacted { .. } | Self::JobNotFound(_) => {
+                ApiErrorBody::new(ApiErrorCode::NotFound, self.to_string())
+            },
+            Self::Busy => ApiErrorBody::new(ApiErrorCode::Unavailable, self.to_string()),
+            Self::Canvas(error) => return error.error_response(),
+            Self::Storage(_) | Self::Gif(_) | Self::Png(_) => {
+                ApiErrorBody::new(ApiErrorCode::Internal, "The timelapse couldn't be rendered.")
+            },
+        };
+
+        ApiError::new(self.status_code(), body).error_response()
+    }
+}
+
+/// A validated [`TimelapseRequest`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimelapsePlan {
+    pub region: CanvasBounds,
+    pub scale: u32,
+    pub from: u64,
+    pub to: u64,
+    pub frame_interval: u64,
+    pub frame_delay: u16,
+    pub format: TimelapseFormat,
+    frames: u32,
+}
+
+impl TimelapsePlan {
+    /// Checks a request against the current canvas `bounds` and `limits`.
+    pub fn new(
+        request: &TimelapseRequest,
+        bounds: CanvasBounds,
+        limits: &TimelapseLimits,
+    ) -> Result<Self, TimelapseError> {
+        let region = request
+            .region
+            .unwrap_or(bounds);
+        let scale = request
+            .scale
+            .unwrap_or(1);
+
+        if region.area() == 0 || !bounds.contains_bounds(&region) {
+            return Err(TimelapseError::InvalidRegion(region));
+        }
+
+        if scale == 0 || scale > limits.max_scale {
+            return Err(TimelapseError::InvalidScale { scale, max: limits.max_scale });
+        }
+
+        if request.to < request.from {
+            return Err(TimelapseError::InvalidRange { from: request.from, to: request.to });
+        }
+
+        if request.frame_interval == 0 {
+            return Err(TimelapseError::InvalidInterval);
+        }
+
+        // one frame at `from` and one per interval after it, the last one is cut at
+        // `to`.
+        let frames = (request.to - request.from)
+            .div_ceil(request.frame_interval)
+            .saturating_add(1);
+        let frames = u32::try_from(frames)
+            .ok()
+            .filter(|frames| *frames <= limits.max_frames)
+            .ok_or(TimelapseError::TooManyFrames { frames, max: limits.max_frames })?;
+
+        let (width, height) = (
+            u64::from(region.width) * u64::from(scale),
+            u64::from(region.height) * u64::from(scale),
+        );
+
+        if width * height > limits.max_area
+            || (request.format == TimelapseFormat::Gif
+                && (width > u64::from(u16::MAX) || height > u64::from(u16::MAX)))
+        {
+            return Err(TimelapseError::TooLarge { width, height });
+        }
+
+        Ok(Self {
+            region,
+            scale,
+            from: request.from,
+            to: request.to,
+            frame_interval: request.frame_interval,
+            frame_delay: request
+                .frame_delay
+                .unwrap_or(DEFAULT_FRAME_DELAY),
+            format: request.format,
+            frames,
+        })
+    }
+
+    #[inline]
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// Unix milliseconds shown by `frame`.
+    fn frame_time(&self, frame: u32) -> u64 {
+        self.from
+            .saturating_add(u64::from(frame).saturating_mul(self.frame_interval))
+            .min(self.to)
+    }
+
+    /// Width and height of a scaled frame, both fit the frame area limit.
+    fn frame_size(&self) -> (u32, u32) {
+        (
+            self.region
+                .width
+                * self.scale,
+            self.region
+                .height
+                * self.scale,
+        )
+    }
+}
+
+/// Renders the timelapse described by `plan` into `output`.
+///
+/// `history` has to start at the very first placement of the canvas, only
+/// the region of a single frame is kept in memory while it's replayed. The
+/// output only depends on the plan and the history.
+pub fn render(
+    plan: &TimelapsePlan,
+    history: impl IntoIterator<Item = IoResult<LogRecord>>,
+    output: impl Write,
+) -> Result<(), TimelapseError> {
+    let region = plan.region;
+    let mut pixels = PixelColor::WHITE
+        .to_array()
+        .repeat((region.width * region.height) as usize);
+    let mut encoder = FrameEncoder::new(plan, output)?;
+    let mut frame = 0;
+
+    for (index, record) in history
+        .into_iter()
+        .enumerate()
+    {
+        let record = record?;
+
+        if index == 0 && record.seq() != 1 {
+            return Err(TimelapseError::HistoryCompacted { first_seq: record.seq() });
+        }
+
+        match record {
+            LogRecord::Placement(placement) => {
+                while frame < plan.frames && placement.placed_at > plan.frame_time(frame) {
+                    encoder.write(&pixels)?;
+                    frame += 1;
+                }
+
+                if frame == plan.frames {
+                    break;
+                }
+
+                if region.contains(placement.coord) {
+                    // inside the region both offsets are below its width and height.
+                    let (x, y) = (
+                        (i64::from(
+                            placement
+                                .coord
+                                .x,
+                        ) - i64::from(region.x)) as usize,
+                        (i64::from(
+                            placement
+                                .coord
+                                .y,
+                        ) - i64::from(region.y)) as usize,
+                    );
+                    let index = (y * region.width as usize + x) * 3;
+
+                    pixels[index..index + 3].copy_from_slice(
+                        &placement
+                            .color
+                            .to_array(),
+                    );
+                }
+            },
+            // growing the canvas leaves every pixel as it was.
+            LogRecord::Bounds(_) => {},
+        }
+    }
+
+    while frame < plan.frames {
+        encoder.write(&pixels)?;
+        frame += 1;
+    }
+
+    encoder.finish()
+}
+
+/// Writes frames of the region scaled up into the chosen format.
+enum FrameEncoder<W: Write> {
+    Gif {
+        encoder: gif::Encoder<W>,
+        plan: TimelapsePlan,
+        scaled: Vec<u8>,
+    },
+    Apng {
+        writer: png::Writer<W>,
+        plan: TimelapsePlan,
+        scaled: Vec<u8>,
+    },
+}
+
+impl<W: Write> FrameEncoder<W> {
+    fn new(plan: &TimelapsePlan, output: W) -> Result<Self, TimelapseError> {
+        let (width, height) = plan.frame_size();
+        let plan = plan.clone();
+        let scaled = Vec::with_capacity((width * height * 3) as usize);
+
+        Ok(match plan.format {
+            TimelapseFormat::Gif => {
+                // the plan keeps GIF frames within `u16` sides.
+                let mut encoder = gif::Encoder::new(output, width as u16, height as u16, &[])?;
+                encoder.set_repeat(Repeat::Infinite)?;
+
+                Self::Gif { encoder, plan, scaled }
+            },
+
+            TimelapseFormat::Apng => {
+                let mut encoder = png::Encoder::new(output, width, height);
+                encoder.set_color(ColorType::Rgb);
+                encoder.set_depth(BitDepth::Eight);
+                encoder.set_animated(plan.frames, 0)?;
+                encoder.set_frame_delay(plan.frame_delay, 1000)?;
+
+                Self::Apng {
+                    writer: encoder.write_header()?,
+                    plan,
+                    scaled,
+                }
+            },
+        })
+    }
+
+    /// Writes one frame from the unscaled RGB `pixels` of the region.
+    fn write(&mut self, pixels: &[u8]) -> Result<(), TimelapseError> {
+        match self {
+            Self::Gif { encoder, plan, scaled } => {
+                scale(plan, pixels, scaled);
+
+                let (width, height) = plan.frame_size();
+                let mut frame =
+                    Frame::from_rgb_speed(width as u16, height as u16, scaled, GIF_QUANTIZE_SPEED);
+                frame.delay = plan
+                    .frame_delay
+                    .div_ceil(10);
+
+                encoder.write_frame(&frame)?;
+            },
+
+            Self::Apng { writer, plan, scaled } => {
+                scale(plan, pixels, scaled);
+                writer.write_image_data(scaled)?;
+            },
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), TimelapseError> {
+        match self {
+            Self::Gif { encoder, .. } => {
+                encoder.into_inner()?;
+            },
+            Self::Apng { writer, .. } => writer.finish()?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Draws every pixel of the region as a `scale`x`scale` square into `scaled`.
+fn scale(plan: &TimelapsePlan, pixels: &[u8], scaled: &mut Vec<u8>) {
+    let scale = plan.scale as usize;
+
+    scaled.clear();
+
+    for row in pixels.chunks_exact(
+        plan.region
+            .width as usize
+            * 3,
+    ) {
+        let start = scaled.len();
+
+        for pixel in row.chunks_exact(3) {
+            for _ in 0..scale {
+                scaled.extend_from_slice(pixel);
+            }
+        }
+
+        for _ in 1..scale {
+            scaled.extend_from_within(start..start + row.len() * scale);
+        }
+    }
+}
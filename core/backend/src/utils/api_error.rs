@@ -0,0 +1,145 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use actix_web::body::{BoxBody, MessageBody, to_bytes};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::http::header::{CONTENT_TYPE, HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpResponse, ResponseError};
+use shared::api::{ApiErrorBody, ApiErrorCode};
+use thiserror::Error;
+
+use crate::utils::time::unix_millis;
+
+/// Carries the id of a request, both ways.
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+/// Longest request id accepted from a client.
+const MAX_REQUEST_ID_LENGTH: usize = 64;
+
+static NEXT_REQUEST: AtomicU64 = AtomicU64::new(0);
+
+/// A failed API request, answered with an [`ApiErrorBody`] that carries the
+/// status.
+///
+/// The error types of every module answer through it.
+#[derive(Error, Debug)]
+#[error("{}", .body.message)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub body: ApiErrorBody,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, body: ApiErrorBody) -> Self {
+        Self { status, body }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut body = self
+            .body
+            .clone();
+        body.status = Some(
+            self.status
+                .as_u16(),
+        );
+
+        let mut response = HttpResponse::build(self.status).json(body);
+
+        // for the middlewares that tell failures apart.
+        response
+            .extensions_mut()
+            .insert(
+                self.body
+                    .code,
+            );
+
+        response
+    }
+}
+
+/// The id a client sent along, if it's short and plain enough to be logged.
+fn client_request_id(request: &ServiceRequest) -> Option<String> {
+    request
+        .headers()
+        .get(REQUEST_ID_HEADER)?
+        .to_str()
+        .ok()
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LENGTH
+                && id
+                    .bytes()
+                    .all(|byte| byte.is_ascii_alphanumeric() || b"-_.".contains(&byte))
+        })
+        .map(str::to_owned)
+}
+
+/// Gives every request an id, taken from its `X-Request-Id` header when it
+/// has a valid one, and answers every failed request with an
+/// [`ApiErrorBody`] carrying it.
+///
+/// Errors that aren't one already, like the ones of the extractors or of
+/// unknown routes, are turned into one from their status.
+pub async fn api_errors(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let request_id = client_request_id(&request).unwrap_or_else(|| {
+        format!("{:x}-{:x}", unix_millis(), NEXT_REQUEST.fetch_add(1, Ordering::Relaxed))
+    });
+    let mut response = next
+        .call(request)
+        .await?
+        .map_into_boxed_body();
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response
+            .headers_mut()
+            .insert(REQUEST_ID_HEADER, value);
+    }
+
+    let status = response.status();
+
+    if !status.is_client_error() && !status.is_server_error() {
+        return Ok(response);
+    }
+
+    // internal errors aren't described to clients.
+    let fallback = match response
+        .response()
+        .error()
+    {
+        Some(error) if status.is_client_error() => error.to_string(),
+        _ => format!(
+            "{}.",
+            status
+                .canonical_reason()
+                .unwrap_or("Request failed")
+        ),
+    };
+    let (http_request, response) = response.into_parts();
+    let (mut head, body) = response.into_parts();
+
+    let mut envelope = to_bytes(body)
+        .await
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<ApiErrorBody>(&bytes).ok())
+        .unwrap_or_else(|| ApiErrorBody::new(ApiErrorCode::from_status(status.as_u16()), fallback));
+    envelope.status = Some(status.as_u16());
+    envelope.request_id = Some(request_id);
+
+    head.headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+    Ok(ServiceResponse::new(
+        http_request,
+        head.set_body(serde_json::to_vec(&envelope).unwrap_or_default())
+            .map_into_boxed_body(),
+    ))
+}
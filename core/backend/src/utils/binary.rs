@@ -0,0 +1,41 @@
+use std::io::{Read, Result as IoResult};
+
+/// Little endian primitive readers for the on disk formats.
+pub trait ReadBinary: Read {
+    fn read_u8(&mut self) -> IoResult<u8> {
+        let mut buffer = [0; 1];
+        self.read_exact(&mut buffer)?;
+
+        Ok(buffer[0])
+    }
+
+    fn read_u16(&mut self) -> IoResult<u16> {
+        let mut buffer = [0; 2];
+        self.read_exact(&mut buffer)?;
+
+        Ok(u16::from_le_bytes(buffer))
+    }
+
+    fn read_u32(&mut self) -> IoResult<u32> {
+        let mut buffer = [0; 4];
+        self.read_exact(&mut buffer)?;
+
+        Ok(u32::from_le_bytes(buffer))
+    }
+
+    fn read_i32(&mut self) -> IoResult<i32> {
+        let mut buffer = [0; 4];
+        self.read_exact(&mut buffer)?;
+
+        Ok(i32::from_le_bytes(buffer))
+    }
+
+    fn read_u64(&mut self) -> IoResult<u64> {
+        let mut buffer = [0; 8];
+        self.read_exact(&mut buffer)?;
+
+        Ok(u64::from_le_bytes(buffer))
+    }
+}
+
+impl<R: Read + ?Sized> ReadBinary for R {}
@@ -0,0 +1,67 @@
+use std::net::{IpAddr, SocketAddr};
+
+use actix_web::HttpRequest;
+
+/// The network a client address stands for, IPv6 addresses are grouped per
+/// `/64` network as that's what a single client usually gets.
+pub fn client_network(address: IpAddr) -> IpAddr {
+    match address {
+        IpAddr::V4(_) => address,
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => IpAddr::V4(v4),
+            None => IpAddr::V6((u128::from(v6) & !u128::from(u64::MAX)).into()),
+        },
+    }
+}
+
+/// Reverse proxies whose `Forwarded` and `X-Forwarded-For` headers are
+/// believed, anyone else could put any address in them.
+///
+/// The proxies have to replace those headers rather than append to them, the
+/// first address listed is taken as the client.
+#[derive(Clone, Debug, Default)]
+pub struct TrustedProxies {
+    proxies: Vec<IpAddr>,
+}
+
+impl TrustedProxies {
+    pub fn new(proxies: Vec<IpAddr>) -> Self {
+        Self { proxies }
+    }
+
+    /// Address of the client behind `request`, the forwarded one when it came
+    /// through a trusted proxy. `None` when the connection has no address, like
+    /// over a unix socket.
+    pub fn client_ip(&self, request: &HttpRequest) -> Option<IpAddr> {
+        let peer = request
+            .peer_addr()?
+            .ip();
+
+        if !self
+            .proxies
+            .contains(&peer)
+        {
+            return Some(peer);
+        }
+
+        let info = request.connection_info();
+        let forwarded = info
+            .realip_remote_addr()?
+            .trim();
+
+        forwarded
+            .parse::<IpAddr>()
+            .or_else(|_| {
+                forwarded
+                    .parse::<SocketAddr>()
+                    .map(|address| address.ip())
+            })
+            .or_else(|_| {
+                forwarded
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .parse::<IpAddr>()
+            })
+            .ok()
+    }
+}
@@ -0,0 +1,168 @@
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+use std::time::Duration;
+
+use futures_util::FutureExt;
+use futures_util::future::LocalBoxFuture;
+use reqwest::header::ACCEPT;
+use reqwest::redirect::Policy;
+use reqwest::{Client, Error as ReqwestError, Method};
+use url::Url;
+
+/// Longest a service may take to answer.
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Largest response accepted from a service.
+const MAX_RESPONSE: usize = 1024 * 1024;
+
+/// A request to an external service, like an identity or payment provider.
+#[derive(Clone, Debug)]
+pub struct FetchRequest {
+    pub method: &'static str,
+    pub url: Url,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl FetchRequest {
+    pub fn get(url: Url) -> Self {
+        Self {
+            method: "GET",
+            url,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// A `POST` of URL encoded `fields`.
+    pub fn form(url: Url, fields: &[(&str, &str)]) -> Self {
+        Self {
+            method: "POST",
+            url,
+            headers: vec![(
+                "Content-Type".to_owned(),
+                "application/x-www-form-urlencoded".to_owned(),
+            )],
+            body: url::form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(fields)
+                .finish()
+                .into_bytes(),
+        }
+    }
+
+    #[must_use]
+    pub fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers
+            .push((name.to_owned(), value.into()));
+        self
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FetchResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl FetchResponse {
+    /// The first header called `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// How the backend talks to external services.
+pub trait HttpClient: Send + Sync {
+    fn send(&self, request: FetchRequest) -> LocalBoxFuture<'_, IoResult<FetchResponse>>;
+}
+
+/// An HTTP/1.1 and HTTP/2 client speaking TLS through rustls, trusting the
+/// Mozilla root certificates.
+///
+/// Redirects are handed back rather than followed.
+#[derive(Clone, Debug)]
+pub struct TlsHttpClient {
+    client: Client,
+}
+
+fn transport(error: ReqwestError) -> IoError {
+    if error.is_timeout() {
+        return IoError::new(ErrorKind::TimedOut, "The service didn't answer.");
+    }
+
+    IoError::other(error)
+}
+
+impl TlsHttpClient {
+    pub fn new() -> IoResult<Self> {
+        let client = Client::builder()
+            .use_rustls_tls()
+            .redirect(Policy::none())
+            .timeout(TIMEOUT)
+            .build()
+            .map_err(IoError::other)?;
+
+        Ok(Self { client })
+    }
+
+    async fn fetch(&self, request: FetchRequest) -> IoResult<FetchResponse> {
+        let method = Method::from_bytes(
+            request
+                .method
+                .as_bytes(),
+        )
+        .map_err(IoError::other)?;
+        let mut builder = self
+            .client
+            .request(method, request.url)
+            .header(ACCEPT, "application/json")
+            .body(request.body);
+
+        for (name, value) in request.headers {
+            builder = builder.header(name, value);
+        }
+
+        let mut response = builder
+            .send()
+            .await
+            .map_err(transport)?;
+        let headers = response
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.as_str()
+                        .to_owned(),
+                    String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                )
+            })
+            .collect();
+        let status = response
+            .status()
+            .as_u16();
+        let mut body = Vec::new();
+
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(transport)?
+        {
+            if body.len() + chunk.len() > MAX_RESPONSE {
+                return Err(IoError::new(ErrorKind::InvalidData, "The response is too large."));
+            }
+
+            body.extend_from_slice(&chunk);
+        }
+
+        Ok(FetchResponse { status, headers, body })
+    }
+}
+
+impl HttpClient for TlsHttpClient {
+    fn send(&self, request: FetchRequest) -> LocalBoxFuture<'_, IoResult<FetchResponse>> {
+        self.fetch(request)
+            .boxed_local()
+    }
+}
@@ -0,0 +1,133 @@
+use std::fs::{File, OpenOptions, create_dir_all, rename};
+use std::io::{
+    BufRead,
+    BufReader,
+    BufWriter,
+    Error as IoError,
+    ErrorKind,
+    Result as IoResult,
+    Write,
+};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// An append only file of JSON lines, one entry per line.
+///
+/// Stores that are small enough to live in memory keep their changes here
+/// and replay them on start.
+pub struct Journal<T> {
+    path: PathBuf,
+    file: File,
+    entry: PhantomData<fn(T)>,
+}
+
+impl<T: Serialize + DeserializeOwned> Journal<T> {
+    /// Opens or creates the journal at `path` and reads every entry in it.
+    ///
+    /// A last line cut short by a crash is dropped, any other unreadable line
+    /// fails the whole journal.
+    pub fn open(path: impl AsRef<Path>) -> IoResult<(Self, Vec<T>)> {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+
+        let mut entries = Vec::new();
+        let mut reader = BufReader::new(&file);
+        let mut line = String::new();
+        let mut valid = 0;
+
+        loop {
+            line.clear();
+
+            let read = reader.read_line(&mut line)?;
+
+            if read == 0 {
+                break;
+            }
+
+            // only the last write can be torn, it never got its newline.
+            if !line.ends_with('\n') {
+                break;
+            }
+
+            entries.push(
+                serde_json::from_str(line.trim_end())
+                    .map_err(|error| IoError::new(ErrorKind::InvalidData, error))?,
+            );
+            valid += read as u64;
+        }
+
+        file.set_len(valid)?;
+
+        Ok((
+            Self {
+                path: path.to_owned(),
+                file,
+                entry: PhantomData,
+            },
+            entries,
+        ))
+    }
+
+    /// Appends an entry and waits for it to reach the disk.
+    pub fn append(&mut self, entry: &T) -> IoResult<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+
+        self.file
+            .write_all(&line)?;
+        self.file
+            .sync_data()
+    }
+
+    /// Replaces every entry with `entries`, for stores dropping the ones they
+    /// no longer need.
+    ///
+    /// The entries are written next to the journal and moved over it, a crash
+    /// leaves either the old or the new entries.
+    pub fn rewrite<'a>(&mut self, entries: impl IntoIterator<Item = &'a T>) -> IoResult<()>
+    where
+        T: 'a,
+    {
+        let temporary = self
+            .path
+            .with_extension("partial");
+        let file = File::create(&temporary)?;
+        let mut writer = BufWriter::new(&file);
+
+        for entry in entries {
+            serde_json::to_writer(&mut writer, entry)?;
+            writer.write_all(b"\n")?;
+        }
+
+        writer.flush()?;
+        drop(writer);
+        file.sync_all()?;
+        rename(&temporary, &self.path)?;
+
+        if let Some(parent) = self
+            .path
+            .parent()
+        {
+            File::open(parent)?.sync_all()?;
+        }
+
+        self.file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .open(&self.path)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,6 @@
+pub mod api_error;
+pub mod binary;
+pub mod client;
+pub mod http;
+pub mod journal;
+pub mod time;
@@ -0,0 +1,8 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Current server time in milliseconds since the unix epoch.
+pub fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX))
+}
@@ -0,0 +1,481 @@
+use std::fs::read_to_string;
+use std::time::Duration;
+
+use actix_web::cookie::Cookie;
+use actix_web::dev::ServiceResponse;
+use actix_web::test::{TestRequest, call_service, init_service, read_body_json};
+use actix_web::web::{Data, scope};
+use actix_web::{App, test};
+use argon2::Params;
+use backend::accounts::session::SESSION_COOKIE;
+use backend::accounts::{AccountError, Accounts, AccountsConfig};
+use backend::canvas::CanvasStore;
+use backend::cooldown::{CooldownPolicy, Cooldowns};
+use backend::events::Events;
+use backend::moderation::Moderation;
+use backend::realtime::RealtimeConfig;
+use backend::realtime::hub::RealtimeHub;
+use backend::routes::accounts::{login, logout, me, register};
+use backend::routes::canvas::place_pixel;
+use backend::utils::client::TrustedProxies;
+use shared::api::{AccountInfo, ApiErrorBody, ApiErrorCode, Credentials, PlacePixelRequest};
+use shared::canvas::{CanvasBounds, PixelColor, PixelCoord};
+use tempfile::tempdir;
+
+fn config(session_ttl: Duration) -> AccountsConfig {
+    AccountsConfig {
+        session_ttl,
+        secure_cookies: false,
+        // the default cost makes every test take seconds.
+        password_params: Params::new(Params::MIN_M_COST, 1, 1, None).expect("Params to be valid."),
+        ..AccountsConfig::default()
+    }
+}
+
+fn accounts(session_ttl: Duration) -> Data<Accounts> {
+    Data::new(Accounts::new(config(session_ttl)).expect("Accounts to be created."))
+}
+
+fn credentials(username: &str, password: &str) -> Credentials {
+    Credentials {
+        username: username.to_owned(),
+        password: password.to_owned(),
+    }
+}
+
+fn session_cookie(resp: &ServiceResponse) -> Cookie<'static> {
+    resp.response()
+        .cookies()
+        .find(|cookie| cookie.name() == SESSION_COOKIE)
+        .expect("Session cookie to be set.")
+        .into_owned()
+}
+
+#[test]
+async fn register_login_logout_test() {
+    let app = init_service(
+        App::new()
+            .app_data(accounts(Duration::from_secs(60)))
+            .service(
+                scope("/api")
+                    .service(register)
+                    .service(login)
+                    .service(logout)
+                    .service(me),
+            ),
+    )
+    .await;
+
+    let req = TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(credentials("ada", "correct horse"))
+        .to_request();
+    let resp = call_service(&app, req).await;
+
+    assert_eq!(
+        resp.status()
+            .as_u16(),
+        201u16
+    );
+
+    let cookie = session_cookie(&resp);
+
+    assert_eq!(cookie.http_only(), Some(true));
+    assert_ne!(cookie.value(), "");
+
+    let account: AccountInfo = read_body_json(resp).await;
+
+    assert_eq!(account, AccountInfo { id: 1, username: "ada".to_owned() });
+
+    let req = TestRequest::get()
+        .uri("/api/auth/me")
+        .cookie(cookie.clone())
+        .to_request();
+    let me_account: AccountInfo = read_body_json(call_service(&app, req).await).await;
+
+    assert_eq!(me_account, account);
+
+    let req = TestRequest::post()
+        .uri("/api/auth/login")
+        .set_json(credentials("ADA", "correct horse"))
+        .to_request();
+    let resp = call_service(&app, req).await;
+
+    assert_eq!(
+        resp.status()
+            .as_u16(),
+        200u16
+    );
+
+    let second = session_cookie(&resp);
+
+    let req = TestRequest::post()
+        .uri("/api/auth/logout")
+        .cookie(cookie.clone())
+        .to_request();
+    let resp = call_service(&app, req).await;
+
+    assert_eq!(
+        resp.status()
+            .as_u16(),
+        204u16
+    );
+    assert_eq!(session_cookie(&resp).value(), "");
+
+    let req = TestRequest::get()
+        .uri("/api/auth/me")
+        .cookie(cookie)
+        .to_request();
+
+    assert_eq!(
+        call_service(&app, req)
+            .await
+            .status()
+            .as_u16(),
+        401u16
+    );
+
+    // logging out ends only that session.
+    let req = TestRequest::get()
+        .uri("/api/auth/me")
+        .cookie(second)
+        .to_request();
+
+    assert_eq!(
+        call_service(&app, req)
+            .await
+            .status()
+            .as_u16(),
+        200u16
+    );
+}
+
+#[test]
+async fn wrong_password_test() {
+    let app = init_service(
+        App::new()
+            .app_data(accounts(Duration::from_secs(60)))
+            .service(
+                scope("/api")
+                    .service(register)
+                    .service(login),
+            ),
+    )
+    .await;
+
+    let req = TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(credentials("ada", "correct horse"))
+        .to_request();
+    call_service(&app, req).await;
+
+    for (username, password) in [("ada", "battery staple"), ("grace", "correct horse")] {
+        let req = TestRequest::post()
+            .uri("/api/auth/login")
+            .set_json(credentials(username, password))
+            .to_request();
+        let resp = call_service(&app, req).await;
+
+        assert_eq!(
+            resp.status()
+                .as_u16(),
+            401u16
+        );
+        assert!(
+            resp.response()
+                .cookies()
+                .next()
+                .is_none()
+        );
+
+        // unknown users can't be told apart from wrong passwords.
+        let body: ApiErrorBody = read_body_json(resp).await;
+
+        assert_eq!(body.code, ApiErrorCode::Unauthorized);
+        assert_eq!(body.message, AccountError::InvalidCredentials.to_string());
+    }
+}
+
+#[test]
+async fn expired_session_test() {
+    let app = init_service(
+        App::new()
+            .app_data(accounts(Duration::ZERO))
+            .service(
+                scope("/api")
+                    .service(register)
+                    .service(me),
+            ),
+    )
+    .await;
+
+    let req = TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(credentials("ada", "correct horse"))
+        .to_request();
+    let cookie = session_cookie(&call_service(&app, req).await);
+
+    let req = TestRequest::get()
+        .uri("/api/auth/me")
+        .cookie(cookie.clone())
+        .to_request();
+    let resp = call_service(&app, req).await;
+
+    assert_eq!(
+        resp.status()
+            .as_u16(),
+        401u16
+    );
+
+    let body: ApiErrorBody = read_body_json(resp).await;
+
+    assert_eq!(body.message, AccountError::SessionExpired.to_string());
+
+    // pushing the expiry back breaks the signature.
+    let value = cookie.value();
+    let (signed, _) = value
+        .rsplit_once('.')
+        .expect("Cookie to hold an expiry.");
+    let req = TestRequest::get()
+        .uri("/api/auth/me")
+        .cookie(Cookie::new(SESSION_COOKIE, format!("{signed}.{}", u64::MAX)))
+        .to_request();
+    let body: ApiErrorBody = read_body_json(call_service(&app, req).await).await;
+
+    assert_eq!(body.message, AccountError::Unauthenticated.to_string());
+}
+
+#[test]
+async fn duplicate_username_test() {
+    let app = init_service(
+        App::new()
+            .app_data(accounts(Duration::from_secs(60)))
+            .service(scope("/api").service(register)),
+    )
+    .await;
+
+    let req = TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(credentials("ada", "correct horse"))
+        .to_request();
+    call_service(&app, req).await;
+
+    let req = TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(credentials("Ada", "battery staple"))
+        .to_request();
+    let resp = call_service(&app, req).await;
+
+    assert_eq!(
+        resp.status()
+            .as_u16(),
+        409u16
+    );
+
+    let body: ApiErrorBody = read_body_json(resp).await;
+
+    assert_eq!(body.code, ApiErrorCode::Conflict);
+
+    for (username, password) in
+        [("a", "correct horse"), ("ada lovelace", "correct horse"), ("grace", "short")]
+    {
+        let req = TestRequest::post()
+            .uri("/api/auth/register")
+            .set_json(credentials(username, password))
+            .to_request();
+        let body: ApiErrorBody = read_body_json(call_service(&app, req).await).await;
+
+        assert_eq!(body.code, ApiErrorCode::InvalidRequest);
+    }
+}
+
+#[test]
+async fn user_cooldown_test() {
+    let app = init_service(
+        App::new()
+            .app_data(accounts(Duration::from_secs(60)))
+            .app_data(Data::new(CanvasStore::new(
+                16,
+                CanvasBounds::from_chunks(1, 1, 16).expect("Bounds to fit."),
+            )))
+            .app_data(Data::new(RealtimeHub::new(RealtimeConfig::default())))
+            .app_data(Data::new(Cooldowns::new(CooldownPolicy {
+                interval: Duration::from_secs(60),
+                burst: 1,
+            })))
+            .app_data(Data::new(TrustedProxies::default()))
+            .app_data(Data::new(Events::new()))
+            .app_data(Data::new(Moderation::new()))
+            .service(
+                scope("/api")
+                    .service(register)
+                    .service(place_pixel),
+            ),
+    )
+    .await;
+
+    let req = TestRequest::post()
+        .uri("/api/auth/register")
+        .set_json(credentials("ada", "correct horse"))
+        .to_request();
+    let cookie = session_cookie(&call_service(&app, req).await);
+
+    let place = |cookie: Option<Cookie<'static>>| {
+        let req = TestRequest::post()
+            .uri("/api/canvas/pixels")
+            .peer_addr(
+                "192.0.2.1:1000"
+                    .parse()
+                    .expect("Valid address."),
+            )
+            .set_json(PlacePixelRequest {
+                coord: PixelCoord::new(1, 1),
+                color: PixelColor::new(1, 2, 3),
+            });
+
+        match cookie {
+            Some(cookie) => req.cookie(cookie),
+            None => req,
+        }
+        .to_request()
+    };
+
+    // the anonymous and the logged in cooldown are separate.
+    for (cookie, status) in
+        [(None, 200u16), (None, 429u16), (Some(cookie.clone()), 200u16), (Some(cookie), 429u16)]
+    {
+        assert_eq!(
+            call_service(&app, place(cookie))
+                .await
+                .status()
+                .as_u16(),
+            status
+        );
+    }
+}
+
+#[test]
+async fn accounts_persist_test() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+    let ttl = Duration::from_secs(60);
+    let (kept, revoked) = {
+        let accounts = Accounts::open(dir.path(), config(ttl)).expect("Accounts to open.");
+        let account = accounts
+            .register("ada", "correct horse", 1000)
+            .expect("Account to be registered.");
+        let (_, kept) = accounts.start_session(account.id, 1000);
+        let (session, revoked) = accounts.start_session(account.id, 1000);
+
+        accounts
+            .end_session(&session, 1000)
+            .expect("Session to end.");
+
+        (kept, revoked)
+    };
+
+    let accounts = Accounts::open(dir.path(), config(ttl)).expect("Accounts to reopen.");
+
+    assert_eq!(
+        accounts
+            .verify("ada", "correct horse")
+            .expect("Password to match.")
+            .id,
+        1
+    );
+    assert!(
+        accounts
+            .session(kept.value(), 2000)
+            .is_ok()
+    );
+    assert!(matches!(accounts.session(revoked.value(), 2000), Err(AccountError::Unauthenticated)));
+    assert!(matches!(
+        accounts.register("Ada", "correct horse", 2000),
+        Err(AccountError::UsernameTaken(_))
+    ));
+}
+
+#[test]
+async fn roles_test() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+    let ttl = Duration::from_secs(60);
+    let roles = AccountsConfig {
+        admins: vec!["ADA".to_owned()],
+        moderators: vec!["grace".to_owned()],
+        ..config(ttl)
+    };
+
+    // a configured name nobody registered yet could be claimed by anyone.
+    assert!(matches!(
+        Accounts::open(dir.path(), roles.clone()),
+        Err(AccountError::UnknownRole(username)) if username == "ADA"
+    ));
+
+    {
+        let accounts = Accounts::open(dir.path(), config(ttl)).expect("Accounts to open.");
+
+        for username in ["ada", "grace", "linus"] {
+            accounts
+                .register(username, "correct horse", 1000)
+                .expect("Account to be registered.");
+        }
+    }
+
+    let accounts = Accounts::open(dir.path(), roles).expect("Accounts to reopen.");
+
+    assert!(accounts.is_admin(1));
+    assert!(accounts.is_moderator(1));
+    assert!(!accounts.is_admin(2));
+    assert!(accounts.is_moderator(2));
+    assert!(!accounts.is_moderator(3));
+}
+
+#[test]
+async fn revocations_compact_test() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+    let accounts =
+        Accounts::open(dir.path(), config(Duration::from_secs(60))).expect("Accounts to open.");
+    let account = accounts
+        .register("ada", "correct horse", 1000)
+        .expect("Account to be registered.");
+    let journal_lines = || {
+        read_to_string(
+            dir.path()
+                .join("accounts.jsonl"),
+        )
+        .expect("Journal to be read.")
+        .lines()
+        .count()
+    };
+
+    for _ in 0..1100 {
+        let (session, _) = accounts.start_session(account.id, 1000);
+
+        accounts
+            .end_session(&session, 1000)
+            .expect("Session to end.");
+    }
+
+    assert_eq!(journal_lines(), 1101);
+
+    // once they expired the revocations are dropped from the journal.
+    let (session, cookie) = accounts.start_session(account.id, 100_000);
+
+    accounts
+        .end_session(&session, 100_000)
+        .expect("Session to end.");
+
+    assert_eq!(journal_lines(), 2);
+
+    let accounts =
+        Accounts::open(dir.path(), config(Duration::from_secs(60))).expect("Accounts to reopen.");
+
+    assert!(matches!(
+        accounts.session(cookie.value(), 100_000),
+        Err(AccountError::Unauthenticated)
+    ));
+    assert!(
+        accounts
+            .verify("ada", "correct horse")
+            .is_ok()
+    );
+}
@@ -0,0 +1,113 @@
+use actix_web::dev::ServiceResponse;
+use actix_web::middleware::from_fn;
+use actix_web::test::{TestRequest, call_service, init_service, read_body_json};
+use actix_web::web::{Data, scope};
+use actix_web::{App, test};
+use backend::accounts::{Accounts, AccountsConfig};
+use backend::canvas::CanvasStore;
+use backend::routes::canvas::pixel_history;
+use backend::utils::api_error::{REQUEST_ID_HEADER, api_errors};
+use shared::api::{ApiErrorBody, ApiErrorCode};
+use shared::canvas::CanvasBounds;
+
+fn request_id(resp: &ServiceResponse) -> Option<String> {
+    resp.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| {
+            value
+                .to_str()
+                .ok()
+        })
+        .map(str::to_owned)
+}
+
+#[test]
+async fn api_errors_test() {
+    let app = init_service(
+        App::new()
+            .wrap(from_fn(api_errors))
+            .app_data(Data::new(CanvasStore::new(
+                8,
+                CanvasBounds::from_chunks(1, 1, 8).expect("Bounds to fit."),
+            )))
+            .app_data(Data::new(
+                Accounts::new(AccountsConfig::default()).expect("Accounts to be created."),
+            ))
+            .service(scope("/api").service(pixel_history)),
+    )
+    .await;
+    let error = async |uri: &str| -> (u16, Option<String>, ApiErrorBody) {
+        let resp = call_service(
+            &app,
+            TestRequest::get()
+                .uri(uri)
+                .to_request(),
+        )
+        .await;
+        let status = resp
+            .status()
+            .as_u16();
+        let id = request_id(&resp);
+
+        (status, id, read_body_json(resp).await)
+    };
+
+    // errors of the modules keep their code and message.
+    let (status, id, body) = error("/api/canvas/pixels/9/0/history").await;
+
+    assert_eq!(status, 400);
+    assert_eq!(body.code, ApiErrorCode::OutOfBounds);
+    assert_eq!(body.status, Some(400));
+    assert!(id.is_some());
+    assert_eq!(body.request_id, id);
+
+    // the ones of the extractors and of unknown routes become one too.
+    let (status, _, body) = error("/api/canvas/pixels/0/0/history?limit=many").await;
+
+    assert_eq!(status, 400);
+    assert_eq!(body.code, ApiErrorCode::InvalidRequest);
+    // with the reason of the extractor rather than the one of the status.
+    assert_ne!(body.message, "Bad Request.");
+
+    let (status, id, body) = error("/api/nowhere").await;
+
+    assert_eq!(status, 404);
+    assert_eq!(body.code, ApiErrorCode::NotFound);
+    assert_eq!(body.status, Some(404));
+    assert_eq!(body.request_id, id);
+
+    let (_, other_id, _) = error("/api/nowhere").await;
+
+    assert_ne!(id, other_id);
+
+    // successful responses only get the header.
+    let resp = call_service(
+        &app,
+        TestRequest::get()
+            .uri("/api/canvas/pixels/0/0/history")
+            .insert_header((REQUEST_ID_HEADER, "client-42"))
+            .to_request(),
+    )
+    .await;
+
+    assert!(
+        resp.status()
+            .is_success()
+    );
+    assert_eq!(request_id(&resp).as_deref(), Some("client-42"));
+
+    // ids that can't be logged as they are get replaced.
+    let resp = call_service(
+        &app,
+        TestRequest::get()
+            .uri("/api/nowhere")
+            .insert_header((REQUEST_ID_HEADER, "no spaces allowed"))
+            .to_request(),
+    )
+    .await;
+    let id = request_id(&resp);
+    let body: ApiErrorBody = read_body_json(resp).await;
+
+    assert_ne!(id.as_deref(), Some("no spaces allowed"));
+    assert_eq!(body.request_id, id);
+}
@@ -0,0 +1,676 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use actix_web::dev::ServiceResponse;
+use actix_web::test::{TestRequest, call_service, init_service, read_body, read_body_json};
+use actix_web::web::{Data, scope};
+use actix_web::{App, test};
+use argon2::Params;
+use backend::accounts::{Accounts, AccountsConfig};
+use backend::canvas::CanvasStore;
+use backend::cooldown::{CooldownPolicy, Cooldowns};
+use backend::events::Events;
+use backend::moderation::Moderation;
+use backend::realtime::RealtimeConfig;
+use backend::realtime::hub::RealtimeHub;
+use backend::routes::canvas::{chunk, pixel_history, place_pixel};
+use backend::utils::client::TrustedProxies;
+use backend::utils::time::unix_millis;
+use shared::api::{
+    ApiErrorBody,
+    ApiErrorCode,
+    PixelHistory,
+    PlacePixelRequest,
+    PlacePixelResponse,
+};
+use shared::canvas::{CanvasBounds, PixelColor, PixelCoord};
+use shared::encoding::ChunkPayload;
+
+fn canvas() -> Data<CanvasStore> {
+    Data::new(CanvasStore::new(16, CanvasBounds::from_chunks(2, 2, 16).expect("Bounds to fit.")))
+}
+
+fn hub() -> Data<RealtimeHub> {
+    Data::new(RealtimeHub::new(RealtimeConfig::default()))
+}
+
+fn cooldowns(interval: Duration, burst: u32) -> Data<Cooldowns> {
+    Data::new(Cooldowns::new(CooldownPolicy { interval, burst }))
+}
+
+fn header<'a>(resp: &'a ServiceResponse, name: &str) -> &'a str {
+    resp.headers()
+        .get(name)
+        .and_then(|value| {
+            value
+                .to_str()
+                .ok()
+        })
+        .unwrap_or_default()
+}
+
+#[test]
+async fn place_pixel_test() {
+    let canvas = canvas();
+    let app = init_service(
+        App::new()
+            .app_data(canvas.clone())
+            .app_data(hub())
+            .app_data(cooldowns(Duration::ZERO, 1))
+            .app_data(Data::new(TrustedProxies::default()))
+            .app_data(Data::new(Events::new()))
+            .app_data(Data::new(Moderation::new()))
+            .service(scope("/api").service(place_pixel)),
+    )
+    .await;
+
+    let color = PixelColor::new(10, 20, 30);
+    let req = TestRequest::post()
+        .uri("/api/canvas/pixels")
+        .peer_addr(
+            "192.0.2.1:1000"
+                .parse()
+                .expect("Valid address."),
+        )
+        .set_json(PlacePixelRequest { coord: PixelCoord::new(17, 3), color })
+        .to_request();
+    let resp = call_service(&app, req).await;
+
+    assert_eq!(
+        resp.status()
+            .as_u16(),
+        200u16
+    );
+
+    let PlacePixelResponse { placement, cooldown } = read_body_json(resp).await;
+
+    assert_eq!(placement.seq, 1);
+    assert_eq!(placement.coord, PixelCoord::new(17, 3));
+    assert_eq!(placement.color, color);
+    assert!(placement.placed_at > 0);
+    assert_eq!(cooldown.next_placement_at, placement.placed_at);
+    assert_eq!(canvas.pixel(PixelCoord::new(17, 3)), Some(color));
+    assert_eq!(canvas.revision(), 1);
+
+    let req = TestRequest::post()
+        .uri("/api/canvas/pixels")
+        .peer_addr(
+            "192.0.2.1:1000"
+                .parse()
+                .expect("Valid address."),
+        )
+        .set_json(PlacePixelRequest { coord: PixelCoord::new(0, 0), color })
+        .to_request();
+    let PlacePixelResponse { placement, .. } = read_body_json(call_service(&app, req).await).await;
+
+    assert_eq!(placement.seq, 2);
+}
+
+#[test]
+async fn place_pixel_out_of_bounds_test() {
+    let canvas = canvas();
+    let app = init_service(
+        App::new()
+            .app_data(canvas.clone())
+            .app_data(hub())
+            .app_data(cooldowns(Duration::ZERO, 1))
+            .app_data(Data::new(TrustedProxies::default()))
+            .app_data(Data::new(Events::new()))
+            .app_data(Data::new(Moderation::new()))
+            .service(scope("/api").service(place_pixel)),
+    )
+    .await;
+
+    for coord in [PixelCoord::new(32, 0), PixelCoord::new(0, -1)] {
+        let req = TestRequest::post()
+            .uri("/api/canvas/pixels")
+            .peer_addr(
+                "192.0.2.1:1000"
+                    .parse()
+                    .expect("Valid address."),
+            )
+            .set_json(PlacePixelRequest { coord, color: PixelColor::new(0, 0, 0) })
+            .to_request();
+        let resp = call_service(&app, req).await;
+
+        assert_eq!(
+            resp.status()
+                .as_u16(),
+            400u16
+        );
+
+        let body: ApiErrorBody = read_body_json(resp).await;
+
+        assert_eq!(body.code, ApiErrorCode::OutOfBounds);
+    }
+
+    assert_eq!(canvas.revision(), 0);
+}
+
+#[test]
+async fn place_pixel_malformed_test() {
+    let app = init_service(
+        App::new()
+            .app_data(canvas())
+            .app_data(hub())
+            .app_data(cooldowns(Duration::ZERO, 1))
+            .app_data(Data::new(TrustedProxies::default()))
+            .app_data(Data::new(Events::new()))
+            .app_data(Data::new(Moderation::new()))
+            .service(scope("/api").service(place_pixel)),
+    )
+    .await;
+
+    let req = TestRequest::post()
+        .uri("/api/canvas/pixels")
+        .insert_header(("content-type", "application/json"))
+        .set_payload(r#"{"coord":{"x":1,"y":1},"color":{"red":300,"green":0,"blue":0}}"#)
+        .to_request();
+    let resp = call_service(&app, req).await;
+
+    assert_eq!(
+        resp.status()
+            .as_u16(),
+        400u16
+    );
+}
+
+#[test]
+async fn place_pixel_cooldown_test() {
+    let app = init_service(
+        App::new()
+            .app_data(canvas())
+            .app_data(hub())
+            .app_data(cooldowns(Duration::from_secs(60), 2))
+            .app_data(Data::new(TrustedProxies::default()))
+            .app_data(Data::new(Events::new()))
+            .app_data(Data::new(Moderation::new()))
+            .service(scope("/api").service(place_pixel)),
+    )
+    .await;
+
+    let place = |x: i32, peer: &str| {
+        TestRequest::post()
+            .uri("/api/canvas/pixels")
+            .peer_addr(
+                peer.parse::<SocketAddr>()
+                    .expect("Valid address."),
+            )
+            .set_json(PlacePixelRequest {
+                coord: PixelCoord::new(x, 0),
+                color: PixelColor::new(0, 0, 0),
+            })
+            .to_request()
+    };
+
+    // out of bounds placements don't take from the burst.
+    assert_eq!(
+        call_service(&app, place(-1, "10.0.0.1:1000"))
+            .await
+            .status()
+            .as_u16(),
+        400u16
+    );
+
+    let first: PlacePixelResponse =
+        read_body_json(call_service(&app, place(0, "10.0.0.1:1000")).await).await;
+
+    assert_eq!(
+        first
+            .cooldown
+            .remaining,
+        1
+    );
+    assert_eq!(
+        first
+            .cooldown
+            .next_placement_at,
+        first
+            .placement
+            .placed_at
+    );
+
+    let second: PlacePixelResponse =
+        read_body_json(call_service(&app, place(1, "10.0.0.1:2000")).await).await;
+
+    assert_eq!(
+        second
+            .cooldown
+            .remaining,
+        0
+    );
+    assert!(
+        second
+            .cooldown
+            .next_placement_at
+            >= first
+                .placement
+                .placed_at
+                + 60_000
+    );
+
+    let resp = call_service(&app, place(2, "10.0.0.1:1000")).await;
+
+    assert_eq!(
+        resp.status()
+            .as_u16(),
+        429u16
+    );
+    assert_eq!(header(&resp, "retry-after"), "60");
+
+    let body: ApiErrorBody = read_body_json(resp).await;
+
+    assert_eq!(body.code, ApiErrorCode::RateLimited);
+    assert_eq!(
+        body.retry_at,
+        Some(
+            second
+                .cooldown
+                .next_placement_at
+        )
+    );
+
+    // another address has its own cooldown, IPv6 ones share it per /64.
+    for peer in ["10.0.0.2:1000", "[2001:db8::1]:1000"] {
+        assert_eq!(
+            call_service(&app, place(3, peer))
+                .await
+                .status()
+                .as_u16(),
+            200u16
+        );
+    }
+
+    for (peer, status) in [("[2001:db8::2]:1000", 200u16), ("[2001:db8::3]:1000", 429u16)] {
+        assert_eq!(
+            call_service(&app, place(4, peer))
+                .await
+                .status()
+                .as_u16(),
+            status
+        );
+    }
+}
+
+#[test]
+async fn place_pixel_address_test() {
+    let app = init_service(
+        App::new()
+            .app_data(canvas())
+            .app_data(hub())
+            .app_data(cooldowns(Duration::from_secs(60), 1))
+            .app_data(Data::new(TrustedProxies::new(vec![
+                "10.0.0.1"
+                    .parse()
+                    .expect("Valid address."),
+            ])))
+            .app_data(Data::new(Events::new()))
+            .app_data(Data::new(Moderation::new()))
+            .service(scope("/api").service(place_pixel)),
+    )
+    .await;
+
+    let place = |x: i32, peer: Option<&str>, forwarded: &str| {
+        let mut req = TestRequest::post()
+            .uri("/api/canvas/pixels")
+            .insert_header(("x-forwarded-for", forwarded))
+            .set_json(PlacePixelRequest {
+                coord: PixelCoord::new(x, 0),
+                color: PixelColor::new(0, 0, 0),
+            });
+
+        if let Some(peer) = peer {
+            req = req.peer_addr(
+                peer.parse::<SocketAddr>()
+                    .expect("Valid address."),
+            );
+        }
+
+        req.to_request()
+    };
+    let status = async |req| {
+        call_service(&app, req)
+            .await
+            .status()
+            .as_u16()
+    };
+
+    // without an address there's nothing to key the cooldown on.
+    let resp = call_service(&app, place(0, None, "192.0.2.1")).await;
+
+    assert_eq!(
+        resp.status()
+            .as_u16(),
+        400u16
+    );
+
+    let body: ApiErrorBody = read_body_json(resp).await;
+
+    assert_eq!(body.code, ApiErrorCode::InvalidRequest);
+
+    // clients behind the trusted proxy each have their own cooldown.
+    assert_eq!(status(place(0, Some("10.0.0.1:1000"), "192.0.2.1")).await, 200);
+    assert_eq!(status(place(1, Some("10.0.0.1:1000"), "192.0.2.2")).await, 200);
+    assert_eq!(status(place(2, Some("10.0.0.1:1000"), "192.0.2.1")).await, 429);
+
+    // anyone else can't pick their address.
+    assert_eq!(status(place(3, Some("10.0.0.2:1000"), "192.0.2.3")).await, 200);
+    assert_eq!(status(place(4, Some("10.0.0.2:1000"), "192.0.2.4")).await, 429);
+}
+
+#[test]
+async fn chunk_download_test() {
+    let canvas = canvas();
+    let app = init_service(
+        App::new()
+            .app_data(canvas.clone())
+            .service(scope("/api").service(chunk)),
+    )
+    .await;
+
+    let req = TestRequest::get()
+        .uri("/api/canvas/chunks/1/0")
+        .to_request();
+    let resp = call_service(&app, req).await;
+
+    assert_eq!(
+        resp.status()
+            .as_u16(),
+        200u16
+    );
+    assert_eq!(header(&resp, "etag"), "\"0\"");
+    assert_eq!(header(&resp, "content-type"), "application/octet-stream");
+
+    let payload = ChunkPayload::decode(&read_body(resp).await).expect("valid payload");
+
+    assert_eq!(
+        payload,
+        ChunkPayload::Full {
+            size: 16,
+            version: 0,
+            cells: vec![255; 16 * 16 * 3]
+        }
+    );
+
+    let color = PixelColor::new(1, 2, 3);
+
+    canvas
+        .place(PixelCoord::new(17, 3), color, None, 1)
+        .expect("placed");
+    canvas
+        .place(PixelCoord::new(18, 3), color, None, 2)
+        .expect("placed");
+
+    let req = TestRequest::get()
+        .uri("/api/canvas/chunks/1/0")
+        .to_request();
+    let resp = call_service(&app, req).await;
+
+    assert_eq!(header(&resp, "etag"), "\"2\"");
+
+    let Ok(ChunkPayload::Full { version: 2, cells, .. }) =
+        ChunkPayload::decode(&read_body(resp).await)
+    else {
+        panic!("expected a full chunk");
+    };
+
+    assert_eq!(&cells[(3 * 16 + 1) * 3..(3 * 16 + 3) * 3], &[1, 2, 3, 1, 2, 3]);
+}
+
+#[test]
+async fn chunk_not_modified_test() {
+    let canvas = canvas();
+    let app = init_service(
+        App::new()
+            .app_data(canvas.clone())
+            .service(scope("/api").service(chunk)),
+    )
+    .await;
+
+    canvas
+        .place(PixelCoord::new(0, 0), PixelColor::new(0, 0, 0), None, 1)
+        .expect("placed");
+
+    let req = TestRequest::get()
+        .uri("/api/canvas/chunks/0/0")
+        .insert_header(("if-none-match", "\"1\""))
+        .to_request();
+    let resp = call_service(&app, req).await;
+
+    assert_eq!(
+        resp.status()
+            .as_u16(),
+        304u16
+    );
+    assert_eq!(header(&resp, "etag"), "\"1\"");
+    assert!(
+        read_body(resp)
+            .await
+            .is_empty()
+    );
+
+    canvas
+        .place(PixelCoord::new(1, 0), PixelColor::new(0, 0, 0), None, 2)
+        .expect("placed");
+
+    let req = TestRequest::get()
+        .uri("/api/canvas/chunks/0/0")
+        .insert_header(("if-none-match", "\"1\""))
+        .to_request();
+    let resp = call_service(&app, req).await;
+
+    assert_eq!(
+        resp.status()
+            .as_u16(),
+        200u16
+    );
+}
+
+#[test]
+async fn chunk_since_test() {
+    let canvas = canvas();
+    let app = init_service(
+        App::new()
+            .app_data(canvas.clone())
+            .service(scope("/api").service(chunk)),
+    )
+    .await;
+
+    let (red, blue) = (PixelColor::new(255, 0, 0), PixelColor::new(0, 0, 255));
+
+    canvas
+        .place(PixelCoord::new(4, 5), red, None, 1)
+        .expect("placed");
+    canvas
+        .place(PixelCoord::new(6, 7), red, None, 2)
+        .expect("placed");
+    canvas
+        .place(PixelCoord::new(4, 5), blue, None, 3)
+        .expect("placed");
+
+    let req = TestRequest::get()
+        .uri("/api/canvas/chunks/0/0?since=1")
+        .to_request();
+    let payload = ChunkPayload::decode(&read_body(call_service(&app, req).await).await);
+
+    assert_eq!(
+        payload,
+        Ok(ChunkPayload::Delta {
+            size: 16,
+            version: 3,
+            changes: vec![(4, 5, blue), (6, 7, red)]
+        })
+    );
+
+    let req = TestRequest::get()
+        .uri("/api/canvas/chunks/0/0?since=3")
+        .to_request();
+    let payload = ChunkPayload::decode(&read_body(call_service(&app, req).await).await);
+
+    assert_eq!(payload, Ok(ChunkPayload::Delta { size: 16, version: 3, changes: vec![] }));
+}
+
+#[test]
+async fn chunk_out_of_bounds_test() {
+    let app = init_service(
+        App::new()
+            .app_data(canvas())
+            .service(scope("/api").service(chunk)),
+    )
+    .await;
+
+    for uri in [
+        "/api/canvas/chunks/2/0",
+        "/api/canvas/chunks/0/-1",
+        "/api/canvas/chunks/2147483647/0",
+        "/api/canvas/chunks/0/-2147483648",
+    ] {
+        let req = TestRequest::get()
+            .uri(uri)
+            .to_request();
+        let resp = call_service(&app, req).await;
+
+        assert_eq!(
+            resp.status()
+                .as_u16(),
+            404u16
+        );
+
+        let body: ApiErrorBody = read_body_json(resp).await;
+
+        assert_eq!(body.code, ApiErrorCode::NotFound);
+    }
+}
+
+#[test]
+async fn pixel_history_test() {
+    let accounts = Data::new(
+        Accounts::new(AccountsConfig {
+            session_ttl: Duration::from_secs(60),
+            secure_cookies: false,
+            password_params: Params::new(Params::MIN_M_COST, 1, 1, None)
+                .expect("Params to be valid."),
+            ..AccountsConfig::default()
+        })
+        .expect("Accounts to be created."),
+    );
+    let app = init_service(
+        App::new()
+            .app_data(canvas())
+            .app_data(hub())
+            .app_data(cooldowns(Duration::ZERO, 1))
+            .app_data(Data::new(TrustedProxies::default()))
+            .app_data(Data::new(Events::new()))
+            .app_data(Data::new(Moderation::new()))
+            .app_data(accounts.clone())
+            .service(
+                scope("/api")
+                    .service(place_pixel)
+                    .service(pixel_history),
+            ),
+    )
+    .await;
+    let account = accounts
+        .register("painter", "correct horse", 0)
+        .expect("Account to be registered.");
+    let (_, session) = accounts.start_session(account.id, unix_millis());
+    let coord = PixelCoord::new(4, 5);
+
+    for (index, logged_in) in [true, false, true]
+        .into_iter()
+        .enumerate()
+    {
+        let mut req = TestRequest::post()
+            .uri("/api/canvas/pixels")
+            .peer_addr(
+                "192.0.2.1:1000"
+                    .parse()
+                    .expect("Valid address."),
+            )
+            .set_json(PlacePixelRequest {
+                coord,
+                color: PixelColor::new(index as u8, 0, 0),
+            });
+
+        if logged_in {
+            req = req.cookie(session.clone());
+        }
+
+        let PlacePixelResponse { placement, .. } =
+            read_body_json(call_service(&app, req.to_request()).await).await;
+
+        assert_eq!(placement.author, logged_in.then_some(account.id));
+    }
+
+    let history = async |query: &str| -> PixelHistory {
+        let resp = call_service(
+            &app,
+            TestRequest::get()
+                .uri(&format!("/api/canvas/pixels/4/5/history{query}"))
+                .to_request(),
+        )
+        .await;
+
+        assert_eq!(
+            resp.status()
+                .as_u16(),
+            200u16
+        );
+
+        read_body_json(resp).await
+    };
+
+    let first = history("?limit=2").await;
+
+    assert_eq!(first.coord, coord);
+    assert_eq!(
+        first
+            .placements
+            .iter()
+            .map(|entry| (
+                entry.seq,
+                entry
+                    .author
+                    .clone(),
+                entry
+                    .color
+                    .red
+            ))
+            .collect::<Vec<_>>(),
+        vec![(3, Some(account.info()), 2), (2, None, 1)]
+    );
+    assert_eq!(first.next_before, Some(2));
+
+    let second = history("?limit=2&before=2").await;
+
+    assert_eq!(
+        second
+            .placements
+            .iter()
+            .map(|entry| entry.seq)
+            .collect::<Vec<_>>(),
+        vec![1]
+    );
+    assert_eq!(second.next_before, None);
+    assert_eq!(
+        history("")
+            .await
+            .placements
+            .len(),
+        3
+    );
+
+    let resp = call_service(
+        &app,
+        TestRequest::get()
+            .uri("/api/canvas/pixels/32/0/history")
+            .to_request(),
+    )
+    .await;
+
+    assert_eq!(
+        resp.status()
+            .as_u16(),
+        400u16
+    );
+}
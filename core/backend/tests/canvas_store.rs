@@ -0,0 +1,571 @@
+use std::fs::{OpenOptions, read, read_dir, remove_dir_all, write};
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use actix_web::rt::time::sleep;
+use actix_web::web::Data;
+use backend::canvas::checkpoint::{SnapshotPolicy, spawn_snapshots};
+use backend::canvas::log::{LogRecord, PlacementLog};
+use backend::canvas::{CanvasError, CanvasStore};
+use backend::monitoring::metrics::Metrics;
+use shared::canvas::{CanvasBounds, ChunkCoord, PixelColor, PixelCoord};
+use tempfile::tempdir;
+
+const CHUNK_SIZE: u16 = 8;
+
+fn bounds() -> CanvasBounds {
+    CanvasBounds::from_chunks(2, 2, CHUNK_SIZE).expect("Bounds to fit.")
+}
+
+fn fill(store: &CanvasStore, from: i32, to: i32) {
+    for i in from..to {
+        store
+            .place(
+                PixelCoord::new(i % 16, (i * 7) % 16),
+                PixelColor::new((i % 256) as u8, 0, 255),
+                None,
+                1_000 + i as u64,
+            )
+            .expect("Placement to be accepted.");
+    }
+}
+
+fn all_chunks(store: &CanvasStore) -> Vec<Option<Vec<u8>>> {
+    bounds()
+        .chunks(CHUNK_SIZE)
+        .map(|coord| {
+            store
+                .chunk(coord)
+                .map(|chunk| {
+                    chunk
+                        .cells()
+                        .to_vec()
+                })
+        })
+        .collect()
+}
+
+#[test]
+fn reopen_replays_log() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+
+    let expected = {
+        let store = CanvasStore::open(dir.path(), CHUNK_SIZE, bounds()).expect("Store to open.");
+        fill(&store, 0, 50);
+        all_chunks(&store)
+    };
+
+    let store = CanvasStore::open(dir.path(), CHUNK_SIZE, bounds()).expect("Store to reopen.");
+
+    assert_eq!(store.revision(), 50);
+    assert_eq!(all_chunks(&store), expected);
+    assert_eq!(
+        store
+            .place(PixelCoord::new(0, 0), PixelColor::WHITE, None, 0)
+            .expect("Placement to be accepted.")
+            .seq,
+        51
+    );
+}
+
+#[test]
+fn snapshot_and_tail_recovery() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+
+    let (expected, revision) = {
+        let store = CanvasStore::open(dir.path(), CHUNK_SIZE, bounds()).expect("Store to open.");
+
+        fill(&store, 0, 30);
+        store
+            .write_snapshot()
+            .expect("Snapshot to be written.")
+            .expect("Store to be on disk.");
+        fill(&store, 30, 45);
+
+        (all_chunks(&store), store.revision())
+    };
+
+    let store = CanvasStore::open(dir.path(), CHUNK_SIZE, bounds()).expect("Store to reopen.");
+
+    assert_eq!(store.revision(), revision);
+    assert_eq!(all_chunks(&store), expected);
+    assert_eq!(
+        store
+            .chunk(ChunkCoord::new(1, 1))
+            .map(|chunk| chunk.version()),
+        store
+            .snapshot()
+            .chunks
+            .iter()
+            .find(|(coord, _)| *coord == ChunkCoord::new(1, 1))
+            .map(|(_, chunk)| chunk.version())
+    );
+}
+
+#[test]
+fn torn_tail_is_discarded() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+
+    {
+        let store = CanvasStore::open(dir.path(), CHUNK_SIZE, bounds()).expect("Store to open.");
+        fill(&store, 0, 10);
+    }
+
+    let segment = read_dir(
+        dir.path()
+            .join("log"),
+    )
+    .expect("Log directory to exist.")
+    .next()
+    .expect("A log segment to exist.")
+    .expect("The segment entry to be readable.")
+    .path();
+    let clean_len = segment
+        .metadata()
+        .expect("Segment metadata.")
+        .len();
+
+    // half of a record header and payload, as left by a crash mid write.
+    OpenOptions::new()
+        .append(true)
+        .open(&segment)
+        .expect("Segment to open.")
+        .write_all(&[27, 0, 0, 0, 1, 2, 3, 4, 0, 11])
+        .expect("Garbage to be written.");
+
+    let store = CanvasStore::open(dir.path(), CHUNK_SIZE, bounds()).expect("Store to reopen.");
+
+    assert_eq!(store.revision(), 10);
+    assert_eq!(
+        segment
+            .metadata()
+            .expect("Segment metadata.")
+            .len(),
+        clean_len
+    );
+
+    fill(&store, 10, 12);
+    drop(store);
+
+    let log = PlacementLog::open(
+        dir.path()
+            .join("log"),
+    )
+    .expect("Log to open.");
+    let records = log
+        .records(0)
+        .expect("Log to be readable.")
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Every record to be valid.");
+
+    assert_eq!(records.len(), 12);
+    assert!(
+        records
+            .iter()
+            .enumerate()
+            .all(|(i, record)| record.seq() == i as u64 + 1)
+    );
+    assert!(matches!(records.last(), Some(LogRecord::Placement(_))));
+}
+
+#[test]
+fn corrupt_record_is_reported() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+
+    {
+        let store = CanvasStore::open(dir.path(), CHUNK_SIZE, bounds()).expect("Store to open.");
+        fill(&store, 0, 10);
+    }
+
+    let segment = read_dir(
+        dir.path()
+            .join("log"),
+    )
+    .expect("Log directory to exist.")
+    .next()
+    .expect("A log segment to exist.")
+    .expect("The segment entry to be readable.")
+    .path();
+    let mut bytes = read(&segment).expect("Segment to be readable.");
+    let len = bytes.len();
+
+    // a fully written last record whose payload never made it to disk is torn.
+    bytes[len - 1] ^= 0xff;
+    write(&segment, &bytes).expect("Segment to be written.");
+
+    let store = CanvasStore::open(dir.path(), CHUNK_SIZE, bounds()).expect("Store to reopen.");
+
+    assert_eq!(store.revision(), 9);
+    drop(store);
+
+    // while a damaged record with more after it is left alone.
+    let mut bytes = read(&segment).expect("Segment to be readable.");
+    let len = bytes.len();
+
+    bytes[len / 2] ^= 0xff;
+    write(&segment, &bytes).expect("Segment to be written.");
+
+    assert!(matches!(
+        CanvasStore::open(dir.path(), CHUNK_SIZE, bounds()),
+        Err(CanvasError::Storage(_))
+    ));
+    assert_eq!(
+        read(&segment)
+            .expect("Segment to be readable.")
+            .len(),
+        len
+    );
+}
+
+#[test]
+fn chunk_size_mismatch() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+
+    {
+        let store = CanvasStore::open(dir.path(), CHUNK_SIZE, bounds()).expect("Store to open.");
+        fill(&store, 0, 1);
+        store
+            .write_snapshot()
+            .expect("Snapshot to be written.");
+    }
+
+    assert!(matches!(
+        CanvasStore::open(dir.path(), CHUNK_SIZE * 2, bounds()),
+        Err(CanvasError::ChunkSizeMismatch { expected: 16, found: 8 })
+    ));
+}
+
+fn file_count(dir: &Path) -> usize {
+    read_dir(dir).map_or(0, Iterator::count)
+}
+
+#[test]
+fn checkpoint_compaction_and_retention() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+    let policy = SnapshotPolicy { retain: 2, ..SnapshotPolicy::default() };
+
+    let original = CanvasStore::open(dir.path(), CHUNK_SIZE, bounds()).expect("Store to open.");
+
+    for round in 0..3 {
+        fill(&original, round * 20, round * 20 + 20);
+
+        let checkpoint = original
+            .checkpoint(&policy)
+            .expect("Checkpoint to succeed.")
+            .expect("Store to be on disk.");
+
+        assert_eq!(checkpoint.revision, (round as u64 + 1) * 20);
+    }
+
+    fill(&original, 60, 70);
+
+    assert_eq!(
+        file_count(
+            &dir.path()
+                .join("snapshots")
+        ),
+        2
+    );
+    // segments up to the oldest kept snapshot at revision 40 are archived.
+    assert_eq!(
+        file_count(
+            &dir.path()
+                .join("archive")
+        ),
+        2
+    );
+    assert_eq!(
+        file_count(
+            &dir.path()
+                .join("log")
+        ),
+        2
+    );
+
+    let recovered = CanvasStore::open(dir.path(), CHUNK_SIZE, bounds()).expect("Store to reopen.");
+
+    assert_eq!(recovered.revision(), 70);
+    assert_eq!(all_chunks(&recovered), all_chunks(&original));
+
+    let original_dir = tempdir().expect("Temporary directory to be created.");
+    let recovered_dir = tempdir().expect("Temporary directory to be created.");
+    let original_path = original
+        .snapshot()
+        .write(original_dir.path())
+        .expect("Snapshot to be written.");
+    let recovered_path = recovered
+        .snapshot()
+        .write(recovered_dir.path())
+        .expect("Snapshot to be written.");
+
+    assert_eq!(
+        read(original_path).expect("Snapshot to be readable."),
+        read(recovered_path).expect("Snapshot to be readable.")
+    );
+}
+
+#[test]
+fn compaction_without_archive() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+    let policy = SnapshotPolicy {
+        retain: 1,
+        archive_log: false,
+        ..SnapshotPolicy::default()
+    };
+    let store = CanvasStore::open(dir.path(), CHUNK_SIZE, bounds()).expect("Store to open.");
+
+    fill(&store, 0, 5);
+    store
+        .checkpoint(&policy)
+        .expect("Checkpoint to succeed.");
+    fill(&store, 5, 10);
+    store
+        .checkpoint(&policy)
+        .expect("Checkpoint to succeed.");
+
+    assert_eq!(
+        file_count(
+            &dir.path()
+                .join("snapshots")
+        ),
+        1
+    );
+    assert_eq!(
+        file_count(
+            &dir.path()
+                .join("archive")
+        ),
+        0
+    );
+    assert_eq!(
+        file_count(
+            &dir.path()
+                .join("log")
+        ),
+        1
+    );
+    assert_eq!(
+        CanvasStore::open(dir.path(), CHUNK_SIZE, bounds())
+            .expect("Store to reopen.")
+            .revision(),
+        10
+    );
+}
+
+#[test]
+fn pixel_history_after_checkpoints() {
+    let history = |store: &CanvasStore| {
+        store
+            .pixel_history(PixelCoord::new(1, 1), None, 50)
+            .expect("Pixel to be on the canvas.")
+            .placements
+            .iter()
+            .map(|placement| (placement.seq, placement.author))
+            .collect::<Vec<_>>()
+    };
+
+    for archive_log in [true, false] {
+        let dir = tempdir().expect("Temporary directory to be created.");
+        let policy = SnapshotPolicy {
+            retain: 1,
+            archive_log,
+            ..SnapshotPolicy::default()
+        };
+        let store = CanvasStore::open(dir.path(), CHUNK_SIZE, bounds()).expect("Store to open.");
+
+        for author in [Some(1), None, Some(2)] {
+            store
+                .place(PixelCoord::new(1, 1), PixelColor::WHITE, author, 0)
+                .expect("Placement to be accepted.");
+            store
+                .checkpoint(&policy)
+                .expect("Checkpoint to succeed.");
+        }
+
+        store
+            .place(PixelCoord::new(1, 1), PixelColor::WHITE, Some(3), 0)
+            .expect("Placement to be accepted.");
+
+        let recovered =
+            CanvasStore::open(dir.path(), CHUNK_SIZE, bounds()).expect("Store to reopen.");
+
+        assert_eq!(history(&store), vec![(4, Some(3)), (3, Some(2)), (2, None), (1, Some(1))]);
+        // the index keeps what the compacted log no longer has.
+        assert_eq!(history(&recovered), history(&store));
+    }
+}
+
+#[test]
+fn pixel_index_is_stored() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+    let policy = SnapshotPolicy { retain: 1, ..SnapshotPolicy::default() };
+    let history = |store: &CanvasStore, coord: PixelCoord| {
+        store
+            .pixel_history(coord, None, 50)
+            .expect("Pixel to be on the canvas.")
+            .placements
+            .len()
+    };
+
+    {
+        let store = CanvasStore::open(dir.path(), CHUNK_SIZE, bounds()).expect("Store to open.");
+
+        fill(&store, 0, 10);
+        store
+            .checkpoint(&policy)
+            .expect("Checkpoint to succeed.");
+        fill(&store, 10, 12);
+    }
+
+    assert!(
+        file_count(
+            &dir.path()
+                .join("index")
+        ) > 1
+    );
+
+    // the archive is only needed until the index stores its placements.
+    remove_dir_all(
+        dir.path()
+            .join("archive"),
+    )
+    .expect("Archive to be removed.");
+
+    let recovered = CanvasStore::open(dir.path(), CHUNK_SIZE, bounds()).expect("Store to reopen.");
+
+    assert_eq!(recovered.revision(), 12);
+
+    for coord in [PixelCoord::new(0, 0), PixelCoord::new(11, 13)] {
+        assert_eq!(history(&recovered, coord), 1);
+    }
+
+    let restored = recovered
+        .rollback(|_, placement| placement.seq > 10, None, 0)
+        .expect("Rollback to succeed.");
+
+    assert_eq!(restored.len(), 2);
+}
+
+#[test]
+fn missing_log_is_detected() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+    let policy = SnapshotPolicy { retain: 1, ..SnapshotPolicy::default() };
+
+    {
+        let store = CanvasStore::open(dir.path(), CHUNK_SIZE, bounds()).expect("Store to open.");
+
+        fill(&store, 0, 5);
+        store
+            .checkpoint(&policy)
+            .expect("Checkpoint to succeed.");
+        fill(&store, 5, 6);
+        store
+            .checkpoint(&policy)
+            .expect("Checkpoint to succeed.");
+    }
+
+    remove_dir_all(
+        dir.path()
+            .join("snapshots"),
+    )
+    .expect("Snapshots to be removed.");
+
+    assert!(matches!(
+        CanvasStore::open(dir.path(), CHUNK_SIZE, bounds()),
+        Err(CanvasError::MissingLog { needed: 1, available: 7 })
+    ));
+}
+
+#[test]
+fn closed_canvas_refuses_changes() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+
+    {
+        let store = CanvasStore::open(dir.path(), CHUNK_SIZE, bounds()).expect("Store to open.");
+
+        fill(&store, 0, 5);
+        store.close();
+
+        assert!(store.is_closed());
+        assert!(matches!(
+            store.place(PixelCoord::new(0, 0), PixelColor::WHITE, None, 0),
+            Err(CanvasError::Closed)
+        ));
+        assert!(matches!(
+            store.grow(CanvasBounds::from_chunks(3, 3, CHUNK_SIZE).expect("Bounds to fit."), 0),
+            Err(CanvasError::Closed)
+        ));
+        assert!(matches!(store.rollback(|_, _| true, None, 0), Err(CanvasError::Closed)));
+
+        // the final snapshot still gets written.
+        let checkpoint = store
+            .checkpoint(&SnapshotPolicy::default())
+            .expect("Checkpoint to succeed.")
+            .expect("Store to be on disk.");
+
+        assert_eq!(checkpoint.revision, 5);
+    }
+
+    let store = CanvasStore::open(dir.path(), CHUNK_SIZE, bounds()).expect("Store to reopen.");
+
+    assert_eq!(store.revision(), 5);
+    assert!(!store.is_closed());
+}
+
+#[actix_web::test]
+async fn background_snapshots() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+    let store =
+        Data::new(CanvasStore::open(dir.path(), CHUNK_SIZE, bounds()).expect("Store to open."));
+
+    let metrics = Data::new(Metrics::new());
+    let task = spawn_snapshots(
+        store.clone(),
+        SnapshotPolicy {
+            interval: Duration::from_millis(50),
+            ..SnapshotPolicy::default()
+        },
+        metrics.clone(),
+    );
+
+    sleep(Duration::from_millis(120)).await;
+    // nothing changed, nothing to snapshot.
+    assert_eq!(
+        file_count(
+            &dir.path()
+                .join("snapshots")
+        ),
+        0
+    );
+
+    fill(&store, 0, 3);
+
+    // the snapshot is timed right after it's written.
+    for _ in 0..50 {
+        if metrics
+            .snapshots()
+            .count()
+            > 0
+        {
+            break;
+        }
+
+        sleep(Duration::from_millis(20)).await;
+    }
+
+    task.abort();
+
+    assert_eq!(
+        metrics
+            .snapshots()
+            .count(),
+        1
+    );
+    assert!(
+        dir.path()
+            .join("snapshots")
+            .join("00000000000000000003.snap")
+            .exists()
+    );
+}
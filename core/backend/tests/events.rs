@@ -0,0 +1,777 @@
+use std::time::Duration;
+
+use actix_web::cookie::Cookie;
+use actix_web::dev::ServiceResponse;
+use actix_web::rt::time::sleep;
+use actix_web::test::{TestRequest, call_service, init_service, read_body_json};
+use actix_web::web::{Data, ServiceConfig, scope};
+use actix_web::{App, test};
+use argon2::Params;
+use backend::accounts::{Accounts, AccountsConfig};
+use backend::canvas::CanvasStore;
+use backend::cooldown::{CooldownPolicy, Cooldowns};
+use backend::events::Events;
+use backend::events::scheduler::spawn_event_scheduler;
+use backend::funding::{Funding, GrowthPolicy};
+use backend::ledger::Ledger;
+use backend::moderation::Moderation;
+use backend::payments::Payments;
+use backend::payments::fake::{FakePaymentProvider, SIGNATURE_HEADER};
+use backend::realtime::RealtimeConfig;
+use backend::realtime::hub::RealtimeHub;
+use backend::routes::canvas::place_pixel;
+use backend::routes::events::{
+    create_event,
+    event_info,
+    event_results,
+    event_submissions,
+    jury_vote_in_event,
+    list_events,
+    override_event_winner,
+    publish_event,
+    register_for_event,
+    submit_to_event,
+    vote_in_event,
+};
+use backend::routes::payments::payment_webhook;
+use backend::utils::client::TrustedProxies;
+use backend::utils::time::unix_millis;
+use shared::api::{
+    ApiErrorBody,
+    ApiErrorCode,
+    EventInfo,
+    EventRegistration,
+    EventRequest,
+    EventResults,
+    EventState,
+    LedgerAccount,
+    PaymentPurpose,
+    PaymentStatus,
+    PlacePixelRequest,
+    SubmissionInfo,
+    SubmissionRequest,
+    VoteRequest,
+    default_tie_breaks,
+};
+use shared::canvas::{CanvasBounds, PixelColor, PixelCoord};
+use tempfile::tempdir;
+
+const SECRET: &str = "whsec_test";
+const HOUR: u64 = 60 * 60 * 1000;
+
+#[derive(Clone)]
+struct Services {
+    canvas: Data<CanvasStore>,
+    accounts: Data<Accounts>,
+    events: Data<Events>,
+    ledger: Data<Ledger>,
+    payments: Option<Data<Payments>>,
+}
+
+impl Services {
+    fn new(payments: Option<Payments>) -> Self {
+        Self {
+            canvas: Data::new(CanvasStore::new(
+                8,
+                CanvasBounds::from_chunks(4, 4, 8).expect("Bounds to fit."),
+            )),
+            accounts: Data::new(
+                Accounts::new(AccountsConfig {
+                    session_ttl: Duration::from_secs(60),
+                    secure_cookies: false,
+                    password_params: Params::new(Params::MIN_M_COST, 1, 1, None)
+                        .expect("Params to be valid."),
+                    admins: vec!["admin".to_owned()],
+                    ..AccountsConfig::default()
+                })
+                .expect("Accounts to be created."),
+            ),
+            events: Data::new(Events::new()),
+            ledger: Data::new(Ledger::new()),
+            payments: payments.map(Data::new),
+        }
+    }
+
+    fn configure(&self, config: &mut ServiceConfig) {
+        let policy = GrowthPolicy {
+            base: self
+                .canvas
+                .bounds(),
+            ..GrowthPolicy::default()
+        };
+
+        config
+            .app_data(
+                self.canvas
+                    .clone(),
+            )
+            .app_data(
+                self.accounts
+                    .clone(),
+            )
+            .app_data(
+                self.events
+                    .clone(),
+            )
+            .app_data(
+                self.ledger
+                    .clone(),
+            )
+            .app_data(Data::new(Funding::new(policy)))
+            .app_data(Data::new(Moderation::new()))
+            .app_data(Data::new(RealtimeHub::new(RealtimeConfig::default())))
+            .app_data(Data::new(Cooldowns::new(CooldownPolicy {
+                interval: Duration::ZERO,
+                burst: 1,
+            })))
+            .app_data(Data::new(TrustedProxies::default()))
+            .service(
+                scope("/api")
+                    .service(list_events)
+                    .service(create_event)
+                    .service(event_info)
+                    .service(publish_event)
+                    .service(register_for_event)
+                    .service(event_submissions)
+                    .service(submit_to_event)
+                    .service(vote_in_event)
+                    .service(jury_vote_in_event)
+                    .service(override_event_winner)
+                    .service(event_results)
+                    .service(place_pixel)
+                    .service(payment_webhook),
+            );
+
+        if let Some(payments) = &self.payments {
+            config.app_data(payments.clone());
+        }
+    }
+
+    fn session(&self, username: &str) -> Cookie<'static> {
+        let account = self
+            .accounts
+            .register(username, "correct horse", 0)
+            .expect("Account to be registered.");
+
+        self.accounts
+            .start_session(account.id, unix_millis())
+            .1
+    }
+}
+
+fn event_request(entry_fee_cents: u64) -> EventRequest {
+    let now = unix_millis();
+
+    EventRequest {
+        title: "Pixel Sprint".to_owned(),
+        region: CanvasBounds::new(0, 0, 8, 8),
+        starts_at: now + HOUR,
+        ends_at: now + 2 * HOUR,
+        judging_ends_at: now + 3 * HOUR,
+        entry_fee_cents,
+        tie_breaks: default_tie_breaks(),
+    }
+}
+
+fn place(coord: PixelCoord) -> TestRequest {
+    TestRequest::post()
+        .uri("/api/canvas/pixels")
+        .peer_addr(
+            "192.0.2.1:1000"
+                .parse()
+                .expect("Valid address."),
+        )
+        .set_json(PlacePixelRequest { coord, color: PixelColor::new(255, 0, 0) })
+}
+
+fn status(resp: &ServiceResponse) -> u16 {
+    resp.status()
+        .as_u16()
+}
+
+async fn error_code(resp: ServiceResponse) -> ApiErrorCode {
+    read_body_json::<ApiErrorBody, _>(resp)
+        .await
+        .code
+}
+
+#[test]
+async fn event_admin_test() {
+    let services = Services::new(None);
+    let app = init_service(App::new().configure(|config| services.configure(config))).await;
+    let admin = services.session("admin");
+    let artist = services.session("artist");
+    let create = || {
+        TestRequest::post()
+            .uri("/api/events")
+            .set_json(event_request(0))
+    };
+
+    let resp = call_service(&app, create().to_request()).await;
+
+    assert_eq!(status(&resp), 401u16);
+
+    let resp = call_service(
+        &app,
+        create()
+            .cookie(artist.clone())
+            .to_request(),
+    )
+    .await;
+
+    assert_eq!(status(&resp), 403u16);
+    assert_eq!(error_code(resp).await, ApiErrorCode::Forbidden);
+
+    let resp = call_service(
+        &app,
+        create()
+            .cookie(admin.clone())
+            .to_request(),
+    )
+    .await;
+
+    assert_eq!(status(&resp), 201u16);
+
+    let event: EventInfo = read_body_json(resp).await;
+
+    assert_eq!(event.state, EventState::Draft);
+
+    // the region is still taken by the draft.
+    let resp = call_service(
+        &app,
+        create()
+            .cookie(admin.clone())
+            .to_request(),
+    )
+    .await;
+
+    assert_eq!(status(&resp), 409u16);
+
+    // drafts are only shown to admins.
+    let list = |cookie: Cookie<'static>| {
+        TestRequest::get()
+            .uri("/api/events")
+            .cookie(cookie)
+            .to_request()
+    };
+    let listed: Vec<EventInfo> =
+        read_body_json(call_service(&app, list(artist.clone())).await).await;
+
+    assert!(listed.is_empty());
+
+    let listed: Vec<EventInfo> =
+        read_body_json(call_service(&app, list(admin.clone())).await).await;
+
+    assert_eq!(listed, vec![event.clone()]);
+
+    let resp = call_service(
+        &app,
+        TestRequest::get()
+            .uri(&format!("/api/events/{}", event.id))
+            .to_request(),
+    )
+    .await;
+
+    assert_eq!(status(&resp), 404u16);
+
+    let publish = |cookie: Cookie<'static>| {
+        TestRequest::post()
+            .uri(&format!("/api/events/{}/publish", event.id))
+            .cookie(cookie)
+            .to_request()
+    };
+
+    assert_eq!(status(&call_service(&app, publish(artist.clone())).await), 403u16);
+
+    let published: EventInfo =
+        read_body_json(call_service(&app, publish(admin.clone())).await).await;
+
+    assert_eq!(published.state, EventState::Registration);
+    assert_eq!(status(&call_service(&app, publish(admin)).await), 409u16);
+
+    let shown: EventInfo = read_body_json(
+        call_service(
+            &app,
+            TestRequest::get()
+                .uri(&format!("/api/events/{}", event.id))
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+
+    assert_eq!(shown, published);
+}
+
+#[test]
+async fn event_registration_test() {
+    let provider = FakePaymentProvider::new(SECRET);
+    let simulator = provider.simulator();
+    let services = Services::new(Some(Payments::new(provider)));
+    let app = init_service(App::new().configure(|config| services.configure(config))).await;
+    let artist = services.session("artist");
+    let now = unix_millis();
+    let free = services
+        .events
+        .create(
+            services
+                .canvas
+                .bounds(),
+            event_request(0),
+            now,
+        )
+        .expect("Event to be created.");
+    let paid = services
+        .events
+        .create(
+            services
+                .canvas
+                .bounds(),
+            EventRequest {
+                region: CanvasBounds::new(8, 8, 8, 8),
+                ..event_request(5_00)
+            },
+            now,
+        )
+        .expect("Event to be created.");
+    let register = |event: u64| {
+        TestRequest::post()
+            .uri(&format!("/api/events/{event}/registrations"))
+            .cookie(artist.clone())
+            .to_request()
+    };
+
+    // drafts can't be joined yet.
+    assert_eq!(status(&call_service(&app, register(free.id)).await), 409u16);
+
+    for event in [free.id, paid.id] {
+        services
+            .events
+            .publish(event, now)
+            .expect("Event to be published.");
+    }
+
+    let registration: EventRegistration =
+        read_body_json(call_service(&app, register(free.id)).await).await;
+
+    assert_eq!(
+        registration
+            .event
+            .participants,
+        1
+    );
+    assert_eq!(registration.payment, None);
+
+    let resp = call_service(&app, register(free.id)).await;
+
+    assert_eq!(status(&resp), 409u16);
+    assert_eq!(error_code(resp).await, ApiErrorCode::Conflict);
+
+    // paid events are only joined once the entry fee is paid.
+    let registration: EventRegistration =
+        read_body_json(call_service(&app, register(paid.id)).await).await;
+    let payment = registration
+        .payment
+        .expect("A payment to be started.");
+
+    assert_eq!(
+        registration
+            .event
+            .participants,
+        0
+    );
+    assert_eq!(payment.amount_cents, 5_00);
+    assert_eq!(payment.purpose, PaymentPurpose::EventEntry { event: paid.id });
+
+    let reference = services
+        .payments
+        .as_ref()
+        .and_then(|payments| payments.payment(payment.id))
+        .expect("Payment to exist.")
+        .reference;
+    let webhook = simulator.event(&reference, PaymentStatus::Succeeded, unix_millis());
+    let resp = call_service(
+        &app,
+        TestRequest::post()
+            .uri("/api/payments/webhook")
+            .insert_header((
+                SIGNATURE_HEADER,
+                webhook
+                    .signature
+                    .as_str(),
+            ))
+            .set_payload(webhook.body)
+            .to_request(),
+    )
+    .await;
+
+    assert_eq!(status(&resp), 200u16);
+    assert_eq!(
+        services
+            .events
+            .event(paid.id)
+            .expect("Event to exist.")
+            .participants
+            .len(),
+        1
+    );
+    assert_eq!(
+        services
+            .ledger
+            .balance(LedgerAccount::PrizePool { event: paid.id }),
+        5_00
+    );
+    assert_eq!(status(&call_service(&app, register(paid.id)).await), 409u16);
+}
+
+#[test]
+async fn event_paid_without_payments_test() {
+    let services = Services::new(None);
+    let app = init_service(App::new().configure(|config| services.configure(config))).await;
+    let artist = services.session("artist");
+    let event = services
+        .events
+        .create(
+            services
+                .canvas
+                .bounds(),
+            event_request(5_00),
+            unix_millis(),
+        )
+        .expect("Event to be created.");
+
+    services
+        .events
+        .publish(event.id, unix_millis())
+        .expect("Event to be published.");
+
+    let resp = call_service(
+        &app,
+        TestRequest::post()
+            .uri(&format!("/api/events/{}/registrations", event.id))
+            .cookie(artist)
+            .to_request(),
+    )
+    .await;
+
+    assert_eq!(status(&resp), 503u16);
+    assert_eq!(error_code(resp).await, ApiErrorCode::Unavailable);
+}
+
+#[test]
+async fn event_placement_test() {
+    let services = Services::new(None);
+    let app = init_service(App::new().configure(|config| services.configure(config))).await;
+    let artist = services.session("artist");
+    let other = services.session("other");
+    let now = unix_millis();
+    let event = services
+        .events
+        .create(
+            services
+                .canvas
+                .bounds(),
+            event_request(0),
+            now,
+        )
+        .expect("Event to be created.");
+    let inside = PixelCoord::new(2, 2);
+
+    services
+        .events
+        .publish(event.id, now)
+        .expect("Event to be published.");
+    call_service(
+        &app,
+        TestRequest::post()
+            .uri(&format!("/api/events/{}/registrations", event.id))
+            .cookie(artist.clone())
+            .to_request(),
+    )
+    .await;
+
+    assert_eq!(status(&call_service(&app, place(inside).to_request()).await), 200u16);
+
+    services
+        .events
+        .advance(now + HOUR)
+        .expect("Events to advance.");
+
+    let resp = call_service(
+        &app,
+        place(inside)
+            .cookie(other)
+            .to_request(),
+    )
+    .await;
+
+    assert_eq!(status(&resp), 403u16);
+    assert_eq!(error_code(resp).await, ApiErrorCode::Forbidden);
+    assert_eq!(status(&call_service(&app, place(inside).to_request()).await), 403u16);
+    assert_eq!(
+        status(&call_service(&app, place(PixelCoord::new(16, 16)).to_request()).await),
+        200u16
+    );
+    assert_eq!(
+        status(
+            &call_service(
+                &app,
+                place(inside)
+                    .cookie(artist.clone())
+                    .to_request()
+            )
+            .await
+        ),
+        200u16
+    );
+
+    // nobody draws while the entries are judged.
+    services
+        .events
+        .advance(now + 2 * HOUR)
+        .expect("Events to advance.");
+
+    assert_eq!(
+        status(
+            &call_service(
+                &app,
+                place(inside)
+                    .cookie(artist)
+                    .to_request()
+            )
+            .await
+        ),
+        403u16
+    );
+}
+
+#[test]
+async fn event_scheduler_test() {
+    let events = Data::new(Events::new());
+    let now = unix_millis();
+    let event = events
+        .create(
+            CanvasBounds::from_chunks(4, 4, 8).expect("Bounds to fit."),
+            EventRequest {
+                starts_at: now + 50,
+                ends_at: now + 800,
+                judging_ends_at: now + 850,
+                ..event_request(0)
+            },
+            now,
+        )
+        .expect("Event to be created.");
+
+    events
+        .publish(event.id, now)
+        .expect("Event to be published.");
+
+    let scheduler = spawn_event_scheduler(events.clone(), Duration::from_millis(10));
+    let state = || {
+        events
+            .event(event.id)
+            .expect("Event to exist.")
+            .state
+    };
+
+    assert_eq!(state(), EventState::Registration);
+
+    sleep(Duration::from_millis(200)).await;
+
+    assert_eq!(state(), EventState::Running);
+
+    sleep(Duration::from_millis(800)).await;
+
+    assert_eq!(state(), EventState::Finished);
+
+    scheduler.abort();
+}
+
+#[test]
+async fn event_persist_test() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+    let now = unix_millis();
+    let request = event_request(0);
+    let starts_at = request.starts_at;
+    let event = {
+        let events = Events::open(dir.path()).expect("Events to be opened.");
+        let event = events
+            .create(CanvasBounds::from_chunks(4, 4, 8).expect("Bounds to fit."), request, now)
+            .expect("Event to be created.");
+
+        events
+            .publish(event.id, now)
+            .expect("Event to be published.");
+        events
+            .join(event.id, 7, now)
+            .expect("Account to join.");
+        events
+            .advance(starts_at)
+            .expect("Events to advance.");
+
+        events
+            .event(event.id)
+            .expect("Event to exist.")
+    };
+
+    let events = Events::open(dir.path()).expect("Events to be reopened.");
+
+    assert_eq!(events.event(event.id), Some(event.clone()));
+    assert_eq!(event.state, EventState::Running);
+    assert!(
+        event
+            .participants
+            .contains(&7)
+    );
+}
+
+#[test]
+async fn event_judging_test() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+    let services = Services {
+        events: Data::new(Events::open(dir.path()).expect("Events to be opened.")),
+        ..Services::new(None)
+    };
+    let app = init_service(App::new().configure(|config| services.configure(config))).await;
+    let admin = services.session("admin");
+    let first = services.session("first");
+    let second = services.session("second");
+    let voter = services.session("voter");
+    let now = unix_millis();
+    let event = services
+        .events
+        .create(
+            services
+                .canvas
+                .bounds(),
+            event_request(0),
+            now,
+        )
+        .expect("Event to be created.");
+
+    services
+        .events
+        .publish(event.id, now)
+        .expect("Event to be published.");
+
+    for cookie in [&first, &second] {
+        call_service(
+            &app,
+            TestRequest::post()
+                .uri(&format!("/api/events/{}/registrations", event.id))
+                .cookie(cookie.clone())
+                .to_request(),
+        )
+        .await;
+    }
+
+    services
+        .events
+        .advance(now + HOUR)
+        .expect("Events to advance.");
+
+    let submit = |cookie: &Cookie<'static>, region: CanvasBounds| {
+        TestRequest::post()
+            .uri(&format!("/api/events/{}/submissions", event.id))
+            .cookie(cookie.clone())
+            .set_json(SubmissionRequest { region })
+            .to_request()
+    };
+
+    let resp = call_service(&app, submit(&first, CanvasBounds::new(0, 0, 4, 8))).await;
+
+    assert_eq!(status(&resp), 201u16);
+
+    let submitted: SubmissionInfo = read_body_json(resp).await;
+    let resp = call_service(&app, submit(&second, CanvasBounds::new(2, 0, 4, 8))).await;
+
+    assert_eq!(status(&resp), 409u16);
+
+    let resp = call_service(&app, submit(&voter, CanvasBounds::new(4, 0, 4, 8))).await;
+
+    assert_eq!(status(&resp), 403u16);
+    assert_eq!(
+        status(&call_service(&app, submit(&second, CanvasBounds::new(4, 0, 4, 8))).await),
+        201u16
+    );
+
+    let listed: Vec<SubmissionInfo> = read_body_json(
+        call_service(
+            &app,
+            TestRequest::get()
+                .uri(&format!("/api/events/{}/submissions", event.id))
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+
+    assert_eq!(listed.len(), 2);
+    assert_eq!(listed[0], submitted);
+
+    services
+        .events
+        .advance(now + 2 * HOUR)
+        .expect("Events to advance.");
+
+    let vote = |path: &str, cookie: &Cookie<'static>, artist: u64| {
+        TestRequest::post()
+            .uri(&format!("/api/events/{}/{path}", event.id))
+            .cookie(cookie.clone())
+            .set_json(VoteRequest { artist })
+            .to_request()
+    };
+
+    assert_eq!(status(&call_service(&app, vote("votes", &voter, submitted.artist)).await), 204u16);
+    assert_eq!(status(&call_service(&app, vote("votes", &voter, submitted.artist)).await), 409u16);
+    assert_eq!(status(&call_service(&app, vote("votes", &first, submitted.artist)).await), 403u16);
+    assert_eq!(
+        status(&call_service(&app, vote("jury-votes", &voter, submitted.artist)).await),
+        403u16
+    );
+    assert_eq!(
+        status(&call_service(&app, vote("jury-votes", &admin, submitted.artist)).await),
+        204u16
+    );
+
+    let results = || {
+        TestRequest::get()
+            .uri(&format!("/api/events/{}/results", event.id))
+            .to_request()
+    };
+
+    // results are only out once judging is over.
+    assert_eq!(status(&call_service(&app, results()).await), 409u16);
+
+    let second_artist = listed[1].artist;
+
+    assert_eq!(status(&call_service(&app, vote("winner", &voter, second_artist)).await), 403u16);
+    assert_eq!(status(&call_service(&app, vote("winner", &admin, second_artist)).await), 204u16);
+
+    services
+        .events
+        .advance(now + 3 * HOUR)
+        .expect("Events to advance.");
+
+    let resp = call_service(&app, results()).await;
+
+    assert_eq!(status(&resp), 200u16);
+
+    let published: EventResults = read_body_json(resp).await;
+
+    assert_eq!(published.winner, Some(second_artist));
+    assert!(published.overridden);
+    assert_eq!(published.standings[0].submission, submitted);
+    assert_eq!(published.standings[0].votes, 1);
+    assert_eq!(published.standings[0].jury_votes, 1);
+
+    // the same results come back from the stored votes.
+    let reopened = Events::open(dir.path()).expect("Events to be reopened.");
+
+    assert_eq!(
+        reopened
+            .results(event.id)
+            .ok(),
+        Some(published)
+    );
+}
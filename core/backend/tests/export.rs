@@ -0,0 +1,210 @@
+use std::io::Cursor;
+
+use actix_web::test::{TestRequest, call_service, init_service, read_body, read_body_json};
+use actix_web::web::{Data, scope};
+use actix_web::{App, test};
+use backend::canvas::CanvasStore;
+use backend::canvas::export::ExportLimits;
+use backend::routes::canvas::export_png;
+use png::Decoder;
+use shared::api::{ApiErrorBody, ApiErrorCode};
+use shared::canvas::{CanvasBounds, PixelColor, PixelCoord};
+
+struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+    text: Vec<(String, String)>,
+}
+
+impl Image {
+    fn pixel(&self, x: u32, y: u32) -> &[u8] {
+        let index = ((y * self.width + x) * 3) as usize;
+
+        &self.pixels[index..index + 3]
+    }
+}
+
+fn decode(bytes: &[u8]) -> Image {
+    let mut reader = Decoder::new(Cursor::new(bytes))
+        .read_info()
+        .expect("PNG header to be valid.");
+    let mut pixels = vec![
+        0;
+        reader
+            .output_buffer_size()
+            .expect("Image to fit in memory.")
+    ];
+    let info = reader
+        .next_frame(&mut pixels)
+        .expect("PNG frame to be valid.");
+    let text = reader
+        .info()
+        .uncompressed_latin1_text
+        .iter()
+        .map(|chunk| {
+            (
+                chunk
+                    .keyword
+                    .clone(),
+                chunk
+                    .text
+                    .clone(),
+            )
+        })
+        .collect();
+
+    assert_eq!(info.color_type, png::ColorType::Rgb);
+
+    Image {
+        width: info.width,
+        height: info.height,
+        pixels,
+        text,
+    }
+}
+
+fn canvas() -> Data<CanvasStore> {
+    let canvas = CanvasStore::new(16, CanvasBounds::new(-16, -16, 32, 32));
+
+    canvas
+        .place(PixelCoord::new(-16, -16), PixelColor::new(255, 0, 0), None, 1)
+        .expect("Placement to be accepted.");
+    canvas
+        .place(PixelCoord::new(3, 4), PixelColor::new(0, 0, 255), None, 2)
+        .expect("Placement to be accepted.");
+
+    Data::new(canvas)
+}
+
+#[test]
+async fn export_whole_canvas_test() {
+    let app = init_service(
+        App::new()
+            .app_data(canvas())
+            .app_data(Data::new(ExportLimits::default()))
+            .service(scope("/api").service(export_png)),
+    )
+    .await;
+
+    let req = TestRequest::get()
+        .uri("/api/canvas/export.png")
+        .to_request();
+    let resp = call_service(&app, req).await;
+
+    assert_eq!(
+        resp.status()
+            .as_u16(),
+        200u16
+    );
+    assert_eq!(
+        resp.headers()
+            .get("content-type")
+            .and_then(|value| {
+                value
+                    .to_str()
+                    .ok()
+            }),
+        Some("image/png")
+    );
+
+    let image = decode(&read_body(resp).await);
+
+    assert_eq!((image.width, image.height), (32, 32));
+    assert_eq!(image.pixel(0, 0), &[255, 0, 0]);
+    assert_eq!(image.pixel(19, 20), &[0, 0, 255]);
+    assert_eq!(image.pixel(31, 31), &[255, 255, 255]);
+    assert!(
+        image
+            .text
+            .contains(&("Revision".to_string(), "2".to_string()))
+    );
+    assert!(
+        image
+            .text
+            .iter()
+            .any(|(keyword, time)| keyword == "Creation Time" && time.ends_with("GMT"))
+    );
+}
+
+#[test]
+async fn export_scaled_region_test() {
+    let app = init_service(
+        App::new()
+            .app_data(canvas())
+            .app_data(Data::new(ExportLimits::default()))
+            .service(scope("/api").service(export_png)),
+    )
+    .await;
+
+    let req = TestRequest::get()
+        .uri("/api/canvas/export.png?x=2&y=4&w=3&h=2&scale=4")
+        .to_request();
+    let image = decode(&read_body(call_service(&app, req).await).await);
+
+    assert_eq!((image.width, image.height), (12, 8));
+
+    for (x, y) in [(4, 0), (7, 3)] {
+        assert_eq!(image.pixel(x, y), &[0, 0, 255]);
+    }
+
+    for (x, y) in [(3, 0), (8, 0), (4, 4)] {
+        assert_eq!(image.pixel(x, y), &[255, 255, 255]);
+    }
+
+    // a partial region reaches to the canvas edge.
+    let req = TestRequest::get()
+        .uri("/api/canvas/export.png?x=10&y=-16")
+        .to_request();
+    let image = decode(&read_body(call_service(&app, req).await).await);
+
+    assert_eq!((image.width, image.height), (6, 32));
+}
+
+#[test]
+async fn export_rejected_test() {
+    let app = init_service(
+        App::new()
+            .app_data(canvas())
+            .app_data(Data::new(ExportLimits { max_area: 32 * 32 * 4, max_scale: 4 }))
+            .service(scope("/api").service(export_png)),
+    )
+    .await;
+
+    for (query, code) in [
+        ("x=-17", ApiErrorCode::OutOfBounds),
+        ("x=0&w=17", ApiErrorCode::OutOfBounds),
+        ("w=0", ApiErrorCode::OutOfBounds),
+        ("scale=0", ApiErrorCode::InvalidRequest),
+        ("scale=5", ApiErrorCode::InvalidRequest),
+        ("scale=3", ApiErrorCode::InvalidRequest),
+    ] {
+        let req = TestRequest::get()
+            .uri(&format!("/api/canvas/export.png?{query}"))
+            .to_request();
+        let resp = call_service(&app, req).await;
+
+        assert_eq!(
+            resp.status()
+                .as_u16(),
+            400u16,
+            "{query}"
+        );
+
+        let body: ApiErrorBody = read_body_json(resp).await;
+
+        assert_eq!(body.code, code, "{query}");
+    }
+
+    let req = TestRequest::get()
+        .uri("/api/canvas/export.png?scale=2")
+        .to_request();
+
+    assert_eq!(
+        call_service(&app, req)
+            .await
+            .status()
+            .as_u16(),
+        200u16
+    );
+}
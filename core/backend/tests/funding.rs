@@ -0,0 +1,211 @@
+use actix_web::test::{TestRequest, call_service, init_service, read_body_json};
+use actix_web::web::{Data, scope};
+use actix_web::{App, test};
+use backend::canvas::{CanvasError, CanvasStore};
+use backend::funding::{Funding, FundingError, GrowthPolicy};
+use backend::payments::Payment;
+use backend::realtime::RealtimeConfig;
+use backend::realtime::hub::RealtimeHub;
+use backend::routes::funding::funding_status;
+use shared::api::{FundingStatus, PaymentPurpose, PaymentStatus};
+use shared::canvas::{CanvasBounds, PixelColor, PixelCoord};
+use shared::realtime::ServerMessage;
+use tempfile::tempdir;
+
+const CHUNK_SIZE: u16 = 8;
+
+fn policy() -> GrowthPolicy {
+    GrowthPolicy {
+        base: CanvasBounds::from_chunks(2, 2, CHUNK_SIZE).expect("Bounds to fit."),
+        tier_cents: 10_00,
+        ring: u32::from(CHUNK_SIZE),
+        max_tiers: Some(3),
+    }
+}
+
+/// A succeeded contribution payment.
+fn paid(id: u64, account: Option<u64>, amount_cents: u64) -> Payment {
+    Payment {
+        id,
+        account,
+        amount_cents,
+        purpose: PaymentPurpose::Contribution,
+        status: PaymentStatus::Succeeded,
+        reference: format!("cs_{id}"),
+        checkout_url: String::new(),
+        idempotency_key: None,
+        created_at: 0,
+        updated_at: 0,
+    }
+}
+
+#[test]
+async fn contributions_grow_canvas_test() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+    let canvas = Data::new(
+        CanvasStore::open(dir.path(), CHUNK_SIZE, policy().base).expect("Store to open."),
+    );
+    let funding = Data::new(
+        Funding::open(
+            dir.path()
+                .join("funding"),
+            policy(),
+        )
+        .expect("Funding to open."),
+    );
+    let hub = RealtimeHub::new(RealtimeConfig::default());
+    let (_, mut client) = hub.connect();
+    let outside = PixelCoord::new(-1, -1);
+
+    assert!(matches!(
+        funding.contribute_payment(&canvas, &hub, &paid(1, None, 0), 0),
+        Err(FundingError::InvalidAmount)
+    ));
+
+    funding
+        .contribute_payment(&canvas, &hub, &paid(2, Some(1), 9_99), 10)
+        .expect("Contribution to be recorded.");
+
+    assert_eq!(canvas.bounds(), policy().base);
+    assert!(
+        client
+            .try_recv()
+            .is_err()
+    );
+    assert!(matches!(
+        canvas.place(outside, PixelColor::WHITE, None, 20),
+        Err(CanvasError::OutOfBounds(_))
+    ));
+
+    // a single contribution can unlock more than one tier.
+    let contribution = funding
+        .contribute_payment(&canvas, &hub, &paid(3, None, 10_01), 30)
+        .expect("Contribution to be recorded.")
+        .expect("Payment to be counted.");
+    let grown = CanvasBounds::new(-16, -16, 48, 48);
+
+    assert_eq!(contribution.id, 2);
+    assert_eq!(canvas.bounds(), grown);
+    assert_eq!(canvas.revision(), 1);
+
+    let text = client
+        .recv()
+        .await
+        .expect("Clients to be told about the new bounds.");
+
+    assert!(matches!(
+        serde_json::from_str::<ServerMessage>(&text).expect("Message to be valid."),
+        ServerMessage::Bounds(change) if change.seq == 1 && change.bounds == grown && change.changed_at == 30
+    ));
+
+    canvas
+        .place(outside, PixelColor::new(1, 2, 3), None, 40)
+        .expect("Placement to be accepted.");
+
+    let app = init_service(
+        App::new()
+            .app_data(canvas.clone())
+            .app_data(funding.clone())
+            .service(scope("/api").service(funding_status)),
+    )
+    .await;
+    let status: FundingStatus = read_body_json(
+        call_service(
+            &app,
+            TestRequest::get()
+                .uri("/api/funding")
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+
+    assert_eq!(
+        status,
+        FundingStatus {
+            total_cents: 20_00,
+            contributions: 2,
+            tier: 2,
+            next_tier_cents: Some(30_00),
+            bounds: grown,
+        }
+    );
+
+    // past the last tier the canvas stays as it is.
+    funding
+        .contribute_payment(&canvas, &hub, &paid(4, None, 10_000), 50)
+        .expect("Contribution to be recorded.");
+
+    assert_eq!(canvas.bounds(), policy().bounds(3));
+    assert_eq!(
+        funding
+            .status(&canvas)
+            .next_tier_cents,
+        None
+    );
+
+    drop(app);
+    drop(canvas);
+    drop(funding);
+
+    let canvas =
+        CanvasStore::open(dir.path(), CHUNK_SIZE, policy().base).expect("Store to reopen.");
+    let funding = Funding::open(
+        dir.path()
+            .join("funding"),
+        policy(),
+    )
+    .expect("Funding to reopen.");
+
+    assert_eq!(canvas.bounds(), policy().bounds(3));
+    assert_eq!(canvas.pixel(outside), Some(PixelColor::new(1, 2, 3)));
+    assert_eq!(
+        funding
+            .status(&canvas)
+            .total_cents,
+        12_000
+    );
+    assert_eq!(
+        funding
+            .sync_canvas(&canvas, &hub, 60)
+            .expect("Canvas to be in sync."),
+        None
+    );
+}
+
+#[test]
+async fn sync_canvas_test() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+    let hub = RealtimeHub::new(RealtimeConfig::default());
+
+    {
+        let canvas = CanvasStore::new(CHUNK_SIZE, policy().base);
+        let funding = Funding::open(dir.path(), policy()).expect("Funding to open.");
+
+        funding
+            .contribute_payment(&canvas, &hub, &paid(1, None, 10_00), 0)
+            .expect("Contribution to be recorded.");
+    }
+
+    // the contribution was recorded but the canvas didn't keep its growth.
+    let canvas = CanvasStore::new(CHUNK_SIZE, policy().base);
+    let funding = Funding::open(dir.path(), policy()).expect("Funding to reopen.");
+    let change = funding
+        .sync_canvas(&canvas, &hub, 10)
+        .expect("Canvas to grow.")
+        .expect("Canvas to be behind.");
+
+    assert_eq!(change.bounds, policy().bounds(1));
+    assert_eq!(canvas.bounds(), policy().bounds(1));
+
+    // a larger canvas keeps the space it already had.
+    let larger = CanvasBounds::new(0, 0, 64, 8);
+    let canvas = CanvasStore::new(CHUNK_SIZE, larger);
+
+    funding
+        .sync_canvas(&canvas, &hub, 20)
+        .expect("Canvas to grow.");
+
+    assert_eq!(canvas.bounds(), larger.union(&policy().bounds(1)));
+    assert!(matches!(canvas.grow(policy().base, 30), Err(CanvasError::Shrink { .. })));
+}
@@ -0,0 +1,87 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+
+use backend::ledger::{Ledger, LedgerError, Posting, TransactionKind};
+use shared::api::LedgerAccount;
+use tempfile::tempdir;
+
+#[test]
+fn ledger_persist_test() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+
+    {
+        let ledger = Ledger::open(dir.path()).expect("Ledger to open.");
+
+        ledger
+            .post(
+                "payment:1:received",
+                TransactionKind::Contribution,
+                vec![
+                    Posting::debit(LedgerAccount::Cash, 300).expect("Amount to fit."),
+                    Posting::credit(LedgerAccount::GrowthFund, 300).expect("Amount to fit."),
+                ],
+                0,
+            )
+            .expect("Transaction to be posted.");
+    }
+
+    let ledger = Ledger::open(dir.path()).expect("Ledger to reopen.");
+
+    assert_eq!(ledger.balance(LedgerAccount::GrowthFund), 300);
+    assert_eq!(
+        ledger
+            .post(
+                "payment:1:received",
+                TransactionKind::Contribution,
+                vec![
+                    Posting::debit(LedgerAccount::Cash, 300).expect("Amount to fit."),
+                    Posting::credit(LedgerAccount::GrowthFund, 300).expect("Amount to fit."),
+                ],
+                1,
+            )
+            .expect("Transaction to be found.")
+            .posted_at,
+        0
+    );
+
+    drop(ledger);
+
+    // a transaction edited on disk doesn't balance anymore.
+    let mut journal = OpenOptions::new()
+        .append(true)
+        .open(
+            dir.path()
+                .join("ledger.jsonl"),
+        )
+        .expect("Journal to open.");
+
+    writeln!(
+        journal,
+        r#"{{"id":2,"key":"forged","kind":"refund","postings":[{{"account":{{"kind":"cash"}},"amount_cents":-300}},{{"account":{{"kind":"platform"}},"amount_cents":200}}],"posted_at":2}}"#
+    )
+    .expect("Journal to be written.");
+
+    assert!(matches!(Ledger::open(dir.path()), Err(LedgerError::Invariant(_))));
+
+    // balanced transactions that together overflow a balance.
+    let dir = tempdir().expect("Temporary directory to be created.");
+    let mut journal = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(
+            dir.path()
+                .join("ledger.jsonl"),
+        )
+        .expect("Journal to open.");
+
+    for id in 1..=2 {
+        writeln!(
+            journal,
+            r#"{{"id":{id},"key":"huge:{id}","kind":"contribution","postings":[{{"account":{{"kind":"cash"}},"amount_cents":{max}}},{{"account":{{"kind":"growth_fund"}},"amount_cents":-{max}}}],"posted_at":0}}"#,
+            max = i64::MAX
+        )
+        .expect("Journal to be written.");
+    }
+
+    assert!(matches!(Ledger::open(dir.path()), Err(LedgerError::Invariant(_))));
+}
@@ -0,0 +1,294 @@
+//! A small OpenID Connect provider running in the test process.
+//!
+//! Every authorization request is approved right away for the configured
+//! user, the tokens it issues can be tampered with to check they're rejected.
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, TcpListener};
+use std::sync::Arc;
+
+use actix_web::http::header::LOCATION;
+use actix_web::web::{Data, Form, Json, Query, get, post};
+use actix_web::{App, HttpResponse, HttpServer};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
+use parking_lot::Mutex;
+use rsa::pkcs1::EncodeRsaPrivateKey;
+use rsa::{BigUint, RsaPrivateKey};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+
+pub const CLIENT_ID: &str = "canvadot";
+pub const KEY_ID: &str = "test-key";
+
+/// A 2048 bit RSA key made for these tests only.
+const MODULUS: [&str; 8] = [
+    "c28d50f48db7ce7a35985bed264cdd7b7b4b18727be62a3e6e1b2d58bb9033f8",
+    "36fa8be0f2caa5aad9d8c3b09901447c2e65c89e97b6efd4caeb275ac5395147",
+    "c96dd65c795c56c4a04c05887170cd1bf35fe8ee261f83d5e4b9f8ae4ddaf9d9",
+    "8d09fcd98d167586264b735212b6e6c832357c35b918edd0285f1241f421f321",
+    "0d71129ebce67ee5017900b2a1e1eb072709541960e333c7c9c2d5ad855e570a",
+    "3ed439494ffd909c604ffa9dcf7cef6633214142ae37b7d742b30fada21e3c0c",
+    "952a0a89a68d264ab5cc68c318855309efa6f5166c8ed633637fed13518d5691",
+    "207312312b47893eb8384a5bf18310507f89304f033858ba4f5cd099c283960b",
+];
+const PRIVATE_EXPONENT: [&str; 8] = [
+    "294367c436baa16472319a6db60ddf7bed2e8444e3da7163445f274306a51320",
+    "b62875ba84444ed6e9d57bcbdce7d3eab2bc8bcc7749299cec5cebbfe8ccbb5c",
+    "8a706d62b7aaca0c72232d993ed639ea8fb7ebe376da804fc16f603b37f015c5",
+    "fa8fffbcf49baad9850dc3abc8a45a5663afcc646899ccf6b55954ba0059a303",
+    "24c65f4f5a1f8a5864f9ada1aa42b118a588c27bd7e149133678f85e7c561de1",
+    "877de91c6e1bb2522f51662ca55aaf0274956638f3a0f500bba674124c92c2e5",
+    "f482f84d476d3cf390b4c29cb7983e3448dd6c5329ce75abf4e57b82a2d491b9",
+    "39bcd653e15ce0e25b64bf5c4f4661a7c66380829ee29a79ffb05935a1818341",
+];
+
+fn hex(parts: &[&str]) -> Vec<u8> {
+    let hex = parts.concat();
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).expect("Key to be hex."))
+        .collect()
+}
+
+type ClaimsEdit = Box<dyn Fn(&mut Value) + Send + Sync>;
+
+struct Grant {
+    redirect_uri: String,
+    challenge: String,
+    nonce: String,
+}
+
+#[derive(Default)]
+struct MockState {
+    grants: HashMap<String, Grant>,
+    next_code: u64,
+    edit: Option<ClaimsEdit>,
+    corrupt_signature: bool,
+}
+
+struct Issuer {
+    url: String,
+    state: Mutex<MockState>,
+}
+
+#[derive(Deserialize)]
+struct AuthorizeQuery {
+    response_type: String,
+    client_id: String,
+    redirect_uri: String,
+    state: String,
+    nonce: String,
+    code_challenge: String,
+    code_challenge_method: String,
+}
+
+#[derive(Deserialize)]
+struct TokenForm {
+    grant_type: String,
+    code: String,
+    redirect_uri: String,
+    client_id: String,
+    code_verifier: String,
+}
+
+fn token_error(error: &str) -> HttpResponse {
+    HttpResponse::BadRequest().json(json!({ "error": error }))
+}
+
+async fn discovery(issuer: Data<Issuer>) -> Json<Value> {
+    Json(json!({
+        "issuer": issuer.url,
+        "authorization_endpoint": format!("{}/authorize", issuer.url),
+        "token_endpoint": format!("{}/token", issuer.url),
+        "jwks_uri": format!("{}/jwks", issuer.url),
+        "response_types_supported": ["code"],
+        "id_token_signing_alg_values_supported": ["RS256"],
+    }))
+}
+
+async fn jwks() -> Json<Value> {
+    Json(json!({
+        "keys": [{
+            "kty": "RSA",
+            "kid": KEY_ID,
+            "alg": "RS256",
+            "use": "sig",
+            "n": URL_SAFE_NO_PAD.encode(hex(&MODULUS)),
+            "e": URL_SAFE_NO_PAD.encode([1, 0, 1]),
+        }]
+    }))
+}
+
+async fn authorize(issuer: Data<Issuer>, query: Query<AuthorizeQuery>) -> HttpResponse {
+    let query = query.into_inner();
+
+    if query.response_type != "code"
+        || query.client_id != CLIENT_ID
+        || query.code_challenge_method != "S256"
+    {
+        return HttpResponse::BadRequest().finish();
+    }
+
+    let mut state = issuer
+        .state
+        .lock();
+    state.next_code += 1;
+
+    let code = format!("code-{}", state.next_code);
+    let location = format!("{}?code={code}&state={}", query.redirect_uri, query.state);
+
+    state
+        .grants
+        .insert(
+            code,
+            Grant {
+                redirect_uri: query.redirect_uri,
+                challenge: query.code_challenge,
+                nonce: query.nonce,
+            },
+        );
+
+    HttpResponse::Found()
+        .insert_header((LOCATION, location))
+        .finish()
+}
+
+async fn token(issuer: Data<Issuer>, form: Form<TokenForm>) -> HttpResponse {
+    let form = form.into_inner();
+    let state = &mut *issuer
+        .state
+        .lock();
+
+    if form.grant_type != "authorization_code" || form.client_id != CLIENT_ID {
+        return token_error("unauthorized_client");
+    }
+
+    // codes only work once.
+    let Some(grant) = state
+        .grants
+        .remove(&form.code)
+    else {
+        return token_error("invalid_grant");
+    };
+
+    if grant.redirect_uri != form.redirect_uri
+        || URL_SAFE_NO_PAD.encode(Sha256::digest(
+            form.code_verifier
+                .as_bytes(),
+        )) != grant.challenge
+    {
+        return token_error("invalid_grant");
+    }
+
+    let now = backend::utils::time::unix_millis() / 1000;
+    let mut claims = json!({
+        "iss": issuer.url,
+        "sub": "user-1",
+        "aud": CLIENT_ID,
+        "exp": now + 300,
+        "iat": now,
+        "nonce": grant.nonce,
+        "preferred_username": "ada lovelace",
+    });
+
+    if let Some(edit) = &state.edit {
+        edit(&mut claims);
+    }
+
+    let key = RsaPrivateKey::from_components(
+        BigUint::from_bytes_be(&hex(&MODULUS)),
+        BigUint::from(65_537u32),
+        BigUint::from_bytes_be(&hex(&PRIVATE_EXPONENT)),
+        Vec::new(),
+    )
+    .and_then(|key| {
+        key.to_pkcs1_der()
+            .map_err(Into::into)
+    })
+    .expect("Key to be valid.");
+    let header = Header {
+        kid: Some(KEY_ID.to_owned()),
+        ..Header::new(Algorithm::RS256)
+    };
+    let token = encode(&header, &claims, &EncodingKey::from_rsa_der(key.as_bytes()))
+        .expect("Token to be signed.");
+    let (signed, signature) = token
+        .rsplit_once('.')
+        .expect("Token to be a JWT.");
+    let mut signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .expect("Signature to be base64url.");
+
+    if state.corrupt_signature {
+        signature[0] ^= 1;
+    }
+
+    HttpResponse::Ok().json(json!({
+        "access_token": "unused",
+        "token_type": "Bearer",
+        "expires_in": 300,
+        "id_token": format!("{signed}.{}", URL_SAFE_NO_PAD.encode(signature)),
+    }))
+}
+
+/// A mock provider running until the end of the test.
+pub struct MockIssuer {
+    issuer: Arc<Issuer>,
+}
+
+impl MockIssuer {
+    pub fn start() -> Self {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).expect("Mock issuer to bind.");
+        let port = listener
+            .local_addr()
+            .expect("Mock issuer to have an address.")
+            .port();
+        let issuer = Arc::new(Issuer {
+            url: format!("http://127.0.0.1:{port}"),
+            state: Mutex::default(),
+        });
+        let data = Data::from(issuer.clone());
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(data.clone())
+                .route("/.well-known/openid-configuration", get().to(discovery))
+                .route("/jwks", get().to(jwks))
+                .route("/authorize", get().to(authorize))
+                .route("/token", post().to(token))
+        })
+        .workers(1)
+        .listen(listener)
+        .expect("Mock issuer to listen.")
+        .run();
+
+        actix_web::rt::spawn(server);
+
+        Self { issuer }
+    }
+
+    pub fn url(&self) -> &str {
+        &self
+            .issuer
+            .url
+    }
+
+    /// Changes the claims of every token issued from now on.
+    pub fn edit_claims(&self, edit: impl Fn(&mut Value) + Send + Sync + 'static) {
+        self.issuer
+            .state
+            .lock()
+            .edit = Some(Box::new(edit));
+    }
+
+    /// Breaks the signature of every token issued from now on.
+    pub fn corrupt_signatures(&self) {
+        self.issuer
+            .state
+            .lock()
+            .corrupt_signature = true;
+    }
+}
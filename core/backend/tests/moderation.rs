@@ -0,0 +1,491 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use actix_web::cookie::Cookie;
+use actix_web::dev::ServiceResponse;
+use actix_web::test::{TestRequest, call_service, init_service, read_body_json};
+use actix_web::web::{Data, ServiceConfig, scope};
+use actix_web::{App, test};
+use argon2::Params;
+use backend::accounts::{Accounts, AccountsConfig};
+use backend::canvas::CanvasStore;
+use backend::cooldown::{CooldownPolicy, Cooldowns};
+use backend::events::Events;
+use backend::moderation::Moderation;
+use backend::realtime::RealtimeConfig;
+use backend::realtime::hub::RealtimeHub;
+use backend::routes::canvas::place_pixel;
+use backend::routes::moderation::{
+    ban_account,
+    list_bans,
+    list_reports,
+    report_pixel,
+    review_report,
+    rollback_placements,
+    unban_account,
+};
+use backend::utils::client::TrustedProxies;
+use backend::utils::time::unix_millis;
+use shared::api::{
+    BanInfo,
+    BanKind,
+    BanRequest,
+    PlacePixelRequest,
+    PlacePixelResponse,
+    ReportInfo,
+    ReportRequest,
+    ReportReview,
+    ReportStatus,
+    RollbackInfo,
+    RollbackRequest,
+};
+use shared::canvas::{CanvasBounds, PixelColor, PixelCoord};
+
+const GRIEF: PixelColor = PixelColor::new(0, 0, 0);
+
+struct Services {
+    canvas: Data<CanvasStore>,
+    accounts: Data<Accounts>,
+    moderation: Data<Moderation>,
+}
+
+impl Services {
+    fn new() -> Self {
+        Self {
+            canvas: Data::new(CanvasStore::new(
+                8,
+                CanvasBounds::from_chunks(2, 2, 8).expect("Bounds to fit."),
+            )),
+            accounts: Data::new(
+                Accounts::new(AccountsConfig {
+                    session_ttl: Duration::from_secs(60),
+                    secure_cookies: false,
+                    password_params: Params::new(Params::MIN_M_COST, 1, 1, None)
+                        .expect("Params to be valid."),
+                    admins: vec!["admin".to_owned()],
+                    moderators: vec!["moderator".to_owned()],
+                })
+                .expect("Accounts to be created."),
+            ),
+            moderation: Data::new(Moderation::new()),
+        }
+    }
+
+    fn configure(&self, config: &mut ServiceConfig) {
+        config
+            .app_data(
+                self.canvas
+                    .clone(),
+            )
+            .app_data(
+                self.accounts
+                    .clone(),
+            )
+            .app_data(
+                self.moderation
+                    .clone(),
+            )
+            .app_data(Data::new(Events::new()))
+            .app_data(Data::new(RealtimeHub::new(RealtimeConfig::default())))
+            .app_data(Data::new(Cooldowns::new(CooldownPolicy {
+                interval: Duration::ZERO,
+                burst: 1,
+            })))
+            .app_data(Data::new(TrustedProxies::default()))
+            .service(
+                scope("/api")
+                    .service(place_pixel)
+                    .service(rollback_placements)
+                    .service(list_bans)
+                    .service(ban_account)
+                    .service(unban_account)
+                    .service(report_pixel)
+                    .service(list_reports)
+                    .service(review_report),
+            );
+    }
+
+    fn session(&self, username: &str) -> (u64, Cookie<'static>) {
+        let account = self
+            .accounts
+            .register(username, "correct horse", 0)
+            .expect("Account to be registered.");
+
+        (
+            account.id,
+            self.accounts
+                .start_session(account.id, unix_millis())
+                .1,
+        )
+    }
+}
+
+fn status(resp: &ServiceResponse) -> u16 {
+    resp.status()
+        .as_u16()
+}
+
+fn place(coord: PixelCoord, session: &Cookie<'static>) -> TestRequest {
+    anonymous(coord, "192.0.2.1").cookie(session.clone())
+}
+
+fn anonymous(coord: PixelCoord, address: &str) -> TestRequest {
+    TestRequest::post()
+        .uri("/api/canvas/pixels")
+        .peer_addr(SocketAddr::new(
+            address
+                .parse()
+                .expect("Valid address."),
+            1000,
+        ))
+        .set_json(PlacePixelRequest { coord, color: GRIEF })
+}
+
+fn ban(account: u64, kind: BanKind, session: &Cookie<'static>) -> TestRequest {
+    TestRequest::post()
+        .uri(&format!("/api/moderation/bans/{account}"))
+        .cookie(session.clone())
+        .set_json(BanRequest {
+            kind,
+            reason: Some("griefing".to_owned()),
+        })
+}
+
+#[test]
+async fn moderation_roles_test() {
+    let services = Services::new();
+    let app = init_service(App::new().configure(|config| services.configure(config))).await;
+    let (user, user_session) = services.session("painter");
+    let (moderator_id, moderator) = services.session("moderator");
+    let (admin_id, admin) = services.session("admin");
+
+    // nobody bans their own role or a higher one.
+    for (account, session) in
+        [(moderator_id, &moderator), (admin_id, &moderator), (admin_id, &admin)]
+    {
+        let resp = call_service(&app, ban(account, BanKind::Ban, session).to_request()).await;
+
+        assert_eq!(status(&resp), 403u16);
+    }
+
+    assert_eq!(
+        status(&call_service(&app, ban(moderator_id, BanKind::Ban, &admin).to_request()).await),
+        200u16
+    );
+    assert_eq!(
+        status(
+            &call_service(
+                &app,
+                TestRequest::delete()
+                    .uri(&format!("/api/moderation/bans/{moderator_id}"))
+                    .cookie(admin.clone())
+                    .to_request()
+            )
+            .await
+        ),
+        204u16
+    );
+
+    assert_eq!(
+        status(&call_service(&app, ban(user, BanKind::Ban, &user_session).to_request()).await),
+        403u16
+    );
+    assert_eq!(
+        status(
+            &call_service(
+                &app,
+                TestRequest::get()
+                    .uri("/api/moderation/reports")
+                    .to_request()
+            )
+            .await
+        ),
+        401u16
+    );
+    assert_eq!(
+        status(&call_service(&app, ban(user, BanKind::Ban, &moderator).to_request()).await),
+        200u16
+    );
+    assert_eq!(
+        status(&call_service(&app, ban(404, BanKind::Ban, &moderator).to_request()).await),
+        404u16
+    );
+
+    // admins are moderators too.
+    let bans: Vec<BanInfo> = read_body_json(
+        call_service(
+            &app,
+            TestRequest::get()
+                .uri("/api/moderation/bans")
+                .cookie(admin)
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+
+    assert_eq!(bans.len(), 1);
+    assert_eq!(bans[0].account, user);
+    assert_eq!(bans[0].kind, BanKind::Ban);
+}
+
+#[test]
+async fn bans_test() {
+    let services = Services::new();
+    let app = init_service(App::new().configure(|config| services.configure(config))).await;
+    let (banned, banned_session) = services.session("banned");
+    let (shadowed, shadowed_session) = services.session("shadowed");
+    let (_, moderator) = services.session("moderator");
+
+    // the network placed from before the ban is banned along with the
+    // account, shadow bans don't give themselves away like that.
+    for (session, address) in [(&banned_session, "2001:db8::7"), (&shadowed_session, "192.0.2.50")]
+    {
+        assert_eq!(
+            status(
+                &call_service(
+                    &app,
+                    anonymous(PixelCoord::new(0, 0), address)
+                        .cookie(session.clone())
+                        .to_request()
+                )
+                .await
+            ),
+            200u16
+        );
+    }
+
+    call_service(&app, ban(banned, BanKind::Ban, &moderator).to_request()).await;
+    call_service(&app, ban(shadowed, BanKind::ShadowBan, &moderator).to_request()).await;
+
+    assert_eq!(
+        status(
+            &call_service(&app, place(PixelCoord::new(1, 1), &banned_session).to_request()).await
+        ),
+        403u16
+    );
+
+    // shadow banned placements look accepted but never reach the canvas.
+    let resp =
+        call_service(&app, place(PixelCoord::new(2, 2), &shadowed_session).to_request()).await;
+
+    assert_eq!(status(&resp), 200u16);
+
+    let PlacePixelResponse { placement, .. } = read_body_json(resp).await;
+
+    assert_eq!(placement.color, GRIEF);
+    assert_eq!(placement.seq, 3);
+    assert_eq!(
+        services
+            .canvas
+            .pixel(PixelCoord::new(2, 2)),
+        Some(PixelColor::WHITE)
+    );
+    assert_eq!(
+        services
+            .canvas
+            .revision(),
+        2
+    );
+
+    // logging out doesn't get around a ban, the other callers are unaffected.
+    let anonymous_status = |address: &'static str| {
+        let app = &app;
+
+        async move {
+            status(&call_service(app, anonymous(PixelCoord::new(3, 3), address).to_request()).await)
+        }
+    };
+
+    assert_eq!(anonymous_status("2001:db8::1234").await, 403u16);
+    assert_eq!(anonymous_status("192.0.2.1").await, 403u16);
+    assert_eq!(anonymous_status("2001:db8:0:1::7").await, 200u16);
+    assert_eq!(anonymous_status("192.0.2.50").await, 200u16);
+
+    let unban = |account: u64| {
+        TestRequest::delete()
+            .uri(&format!("/api/moderation/bans/{account}"))
+            .cookie(moderator.clone())
+            .to_request()
+    };
+
+    assert_eq!(status(&call_service(&app, unban(banned)).await), 204u16);
+    assert_eq!(status(&call_service(&app, unban(banned)).await), 404u16);
+    assert_eq!(anonymous_status("2001:db8::7").await, 200u16);
+    assert_eq!(
+        status(
+            &call_service(&app, place(PixelCoord::new(1, 1), &banned_session).to_request()).await
+        ),
+        200u16
+    );
+}
+
+#[test]
+async fn reports_test() {
+    let services = Services::new();
+    let app = init_service(App::new().configure(|config| services.configure(config))).await;
+    let (_, reporter) = services.session("reporter");
+    let (_, moderator) = services.session("moderator");
+    let report = |coord: PixelCoord, reason: &str| {
+        TestRequest::post()
+            .uri("/api/reports")
+            .cookie(reporter.clone())
+            .set_json(ReportRequest { coord, reason: reason.to_owned() })
+            .to_request()
+    };
+
+    assert_eq!(
+        status(&call_service(&app, report(PixelCoord::new(99, 0), "griefing")).await),
+        400u16
+    );
+    assert_eq!(status(&call_service(&app, report(PixelCoord::new(3, 4), "")).await), 400u16);
+
+    let resp = call_service(&app, report(PixelCoord::new(3, 4), "griefing")).await;
+
+    assert_eq!(status(&resp), 201u16);
+
+    let filed: ReportInfo = read_body_json(resp).await;
+
+    assert_eq!(filed.status, ReportStatus::Open);
+
+    let queue = async |query: &str| -> Vec<ReportInfo> {
+        read_body_json(
+            call_service(
+                &app,
+                TestRequest::get()
+                    .uri(&format!("/api/moderation/reports{query}"))
+                    .cookie(moderator.clone())
+                    .to_request(),
+            )
+            .await,
+        )
+        .await
+    };
+
+    assert_eq!(queue("?status=open").await, vec![filed.clone()]);
+
+    let review = |status: ReportStatus| {
+        TestRequest::post()
+            .uri(&format!("/api/moderation/reports/{}/review", filed.id))
+            .cookie(moderator.clone())
+            .set_json(ReportReview { status })
+            .to_request()
+    };
+
+    assert_eq!(status(&call_service(&app, review(ReportStatus::Open)).await), 400u16);
+
+    let reviewed: ReportInfo =
+        read_body_json(call_service(&app, review(ReportStatus::Resolved)).await).await;
+
+    assert_eq!(reviewed.status, ReportStatus::Resolved);
+    assert!(
+        reviewed
+            .reviewed_at
+            .is_some()
+    );
+    assert_eq!(status(&call_service(&app, review(ReportStatus::Dismissed)).await), 409u16);
+    assert_eq!(queue("?status=open").await, vec![]);
+    assert_eq!(queue("").await, vec![reviewed]);
+}
+
+#[test]
+async fn rollback_test() {
+    let services = Services::new();
+    let app = init_service(App::new().configure(|config| services.configure(config))).await;
+    let (griefer, griefer_session) = services.session("griefer");
+    let (_, painter) = services.session("painter");
+    let (moderator_id, moderator) = services.session("moderator");
+    let art = PixelColor::new(10, 20, 30);
+
+    services
+        .canvas
+        .place(PixelCoord::new(0, 0), art, None, 0)
+        .expect("Placement to be accepted.");
+
+    for x in 0..3 {
+        call_service(&app, place(PixelCoord::new(x, 0), &griefer_session).to_request()).await;
+    }
+
+    call_service(&app, place(PixelCoord::new(5, 5), &painter).to_request()).await;
+
+    let rollback = |request: RollbackRequest| {
+        TestRequest::post()
+            .uri("/api/moderation/rollbacks")
+            .cookie(moderator.clone())
+            .set_json(request)
+            .to_request()
+    };
+
+    assert_eq!(
+        status(
+            &call_service(
+                &app,
+                rollback(RollbackRequest { account: None, region: None, since: 0 })
+            )
+            .await
+        ),
+        400u16
+    );
+
+    let info: RollbackInfo = read_body_json(
+        call_service(
+            &app,
+            rollback(RollbackRequest {
+                account: Some(griefer),
+                region: None,
+                since: 1,
+            }),
+        )
+        .await,
+    )
+    .await;
+
+    assert_eq!(info, RollbackInfo { restored: 3, revision: 8 });
+    assert_eq!(
+        services
+            .canvas
+            .pixel(PixelCoord::new(0, 0)),
+        Some(art)
+    );
+    assert_eq!(
+        services
+            .canvas
+            .pixel(PixelCoord::new(1, 0)),
+        Some(PixelColor::WHITE)
+    );
+    assert_eq!(
+        services
+            .canvas
+            .pixel(PixelCoord::new(5, 5)),
+        Some(GRIEF)
+    );
+
+    let history = services
+        .canvas
+        .pixel_history(PixelCoord::new(0, 0), None, 10)
+        .expect("Pixel to be on the canvas.");
+
+    assert_eq!(history.placements[0].author, Some(moderator_id));
+    assert_eq!(history.placements[1].author, Some(griefer));
+
+    let info: RollbackInfo = read_body_json(
+        call_service(
+            &app,
+            rollback(RollbackRequest {
+                account: None,
+                region: Some(CanvasBounds::new(4, 4, 4, 4)),
+                since: 1,
+            }),
+        )
+        .await,
+    )
+    .await;
+
+    assert_eq!(info.restored, 1);
+    assert_eq!(
+        services
+            .canvas
+            .pixel(PixelCoord::new(5, 5)),
+        Some(PixelColor::WHITE)
+    );
+}
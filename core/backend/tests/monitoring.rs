@@ -0,0 +1,152 @@
+use std::time::Duration;
+
+use actix_web::middleware::from_fn;
+use actix_web::test::{TestRequest, call_service, init_service, read_body};
+use actix_web::web::{Data, scope};
+use actix_web::{App, test};
+use backend::canvas::CanvasStore;
+use backend::cooldown::{CooldownPolicy, Cooldowns};
+use backend::events::Events;
+use backend::moderation::Moderation;
+use backend::monitoring::Readiness;
+use backend::monitoring::metrics::{Metrics, track_requests};
+use backend::realtime::RealtimeConfig;
+use backend::realtime::hub::RealtimeHub;
+use backend::routes::canvas::place_pixel;
+use backend::routes::monitoring::{health_check, metrics_report, readiness_check};
+use backend::utils::api_error::api_errors;
+use backend::utils::client::TrustedProxies;
+use shared::api::{ApiErrorCode, PlacePixelRequest};
+use shared::canvas::{CanvasBounds, PixelColor, PixelCoord};
+
+#[test]
+async fn probes_test() {
+    let readiness = Data::new(Readiness::new());
+    let app = init_service(
+        App::new()
+            .wrap(from_fn(api_errors))
+            .app_data(readiness.clone())
+            .service(health_check)
+            .service(readiness_check),
+    )
+    .await;
+    let status = async |uri: &str| {
+        call_service(
+            &app,
+            TestRequest::get()
+                .uri(uri)
+                .to_request(),
+        )
+        .await
+        .status()
+        .as_u16()
+    };
+
+    assert_eq!(status("/healthz").await, 200);
+    assert_eq!(status("/readyz").await, 503);
+
+    readiness.set_ready(true);
+
+    assert_eq!(status("/readyz").await, 200);
+
+    // shutting down.
+    readiness.set_ready(false);
+
+    assert_eq!(status("/readyz").await, 503);
+    assert_eq!(status("/healthz").await, 200);
+}
+
+#[test]
+async fn metrics_test() {
+    let metrics = Data::new(Metrics::new());
+    let hub = Data::new(RealtimeHub::new(RealtimeConfig::default()));
+    let app = init_service(
+        App::new()
+            .wrap(from_fn(api_errors))
+            .wrap(from_fn(track_requests))
+            .app_data(metrics.clone())
+            .app_data(hub.clone())
+            .app_data(Data::new(CanvasStore::new(
+                8,
+                CanvasBounds::from_chunks(1, 1, 8).expect("Bounds to fit."),
+            )))
+            .app_data(Data::new(Cooldowns::new(CooldownPolicy {
+                interval: Duration::from_secs(60),
+                burst: 2,
+            })))
+            .app_data(Data::new(TrustedProxies::default()))
+            .app_data(Data::new(Events::new()))
+            .app_data(Data::new(Moderation::new()))
+            .service(metrics_report)
+            .service(scope("/api").service(place_pixel)),
+    )
+    .await;
+    let place = |x: i32| {
+        TestRequest::post()
+            .uri("/api/canvas/pixels")
+            .peer_addr(
+                "192.0.2.1:1000"
+                    .parse()
+                    .expect("Valid address."),
+            )
+            .set_json(PlacePixelRequest {
+                coord: PixelCoord::new(x, 0),
+                color: PixelColor::new(0, 0, 0),
+            })
+            .to_request()
+    };
+
+    for x in [0, 1, 2, 20] {
+        call_service(&app, place(x)).await;
+    }
+
+    let _session = hub.connect();
+
+    assert_eq!(metrics.placements(), 2);
+    assert_eq!(metrics.rejections(ApiErrorCode::RateLimited), 1);
+    assert_eq!(metrics.rejections(ApiErrorCode::OutOfBounds), 1);
+
+    let resp = call_service(
+        &app,
+        TestRequest::get()
+            .uri("/metrics")
+            .to_request(),
+    )
+    .await;
+
+    assert!(
+        resp.headers()
+            .get("content-type")
+            .and_then(|value| {
+                value
+                    .to_str()
+                    .ok()
+            })
+            .is_some_and(|value| value.starts_with("text/plain; version=0.0.4"))
+    );
+
+    let body = String::from_utf8(
+        read_body(resp)
+            .await
+            .to_vec(),
+    )
+    .expect("Metrics to be text.");
+
+    for line in [
+        "# TYPE canvadot_placements_total counter",
+        "canvadot_placements_total 2",
+        r#"canvadot_placement_rejections_total{reason="rate_limited"} 1"#,
+        r#"canvadot_placement_rejections_total{reason="out_of_bounds"} 1"#,
+        "canvadot_connected_sockets 1",
+        "canvadot_snapshot_duration_seconds_count 0",
+        "# TYPE canvadot_http_request_duration_seconds histogram",
+        r#"canvadot_http_request_duration_seconds_bucket{method="POST",route="/api/canvas/pixels",le="+Inf"} 4"#,
+        r#"canvadot_http_request_duration_seconds_count{method="POST",route="/api/canvas/pixels"} 4"#,
+    ] {
+        assert!(
+            body.lines()
+                .any(|metric| metric == line),
+            "`{line}` to be in:\n{body}"
+        );
+    }
+}
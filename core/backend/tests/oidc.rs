@@ -0,0 +1,323 @@
+use std::time::Duration;
+
+use actix_web::App;
+use actix_web::cookie::Cookie;
+use actix_web::dev::ServiceResponse;
+use actix_web::test::{TestRequest, call_service, init_service, read_body_json};
+use actix_web::web::{Data, scope};
+use argon2::Params;
+use backend::accounts::oidc::{OidcConfig, OidcProvider, STATE_COOKIE};
+use backend::accounts::session::SESSION_COOKIE;
+use backend::accounts::{Accounts, AccountsConfig};
+use backend::routes::accounts::{login, me, oidc_callback, oidc_login};
+use backend::utils::http::{FetchRequest, HttpClient, TlsHttpClient};
+use mock_issuer::{CLIENT_ID, MockIssuer};
+use serde_json::json;
+use shared::api::{AccountInfo, ApiErrorBody, ApiErrorCode, Credentials};
+use url::Url;
+
+mod mock_issuer;
+
+const REDIRECT_URI: &str = "http://canvadot.test/api/auth/oidc/callback";
+
+fn accounts() -> Data<Accounts> {
+    Data::new(
+        Accounts::new(AccountsConfig {
+            session_ttl: Duration::from_secs(60),
+            secure_cookies: false,
+            password_params: Params::new(Params::MIN_M_COST, 1, 1, None)
+                .expect("Params to be valid."),
+            ..AccountsConfig::default()
+        })
+        .expect("Accounts to be created."),
+    )
+}
+
+fn provider(issuer: &MockIssuer) -> Data<OidcProvider> {
+    Data::new(OidcProvider::new(
+        OidcConfig::new(issuer.url(), CLIENT_ID, REDIRECT_URI),
+        TlsHttpClient::new().expect("Client to be built."),
+    ))
+}
+
+fn cookie(resp: &ServiceResponse, name: &str) -> Option<Cookie<'static>> {
+    resp.response()
+        .cookies()
+        .find(|cookie| cookie.name() == name)
+        .map(Cookie::into_owned)
+}
+
+fn location(resp: &ServiceResponse) -> Url {
+    let location = resp
+        .headers()
+        .get("location")
+        .and_then(|value| {
+            value
+                .to_str()
+                .ok()
+        })
+        .expect("Response to redirect.");
+
+    Url::parse(location).expect("Location to be a URL.")
+}
+
+fn login_request() -> TestRequest {
+    TestRequest::get().uri("/api/auth/oidc/login")
+}
+
+/// Walks a browser from the response of the login route through the
+/// provider, returns the callback path and the state cookie it would send
+/// along.
+async fn authorize(resp: ServiceResponse) -> (String, Cookie<'static>) {
+    assert_eq!(
+        resp.status()
+            .as_u16(),
+        302u16
+    );
+
+    let state = cookie(&resp, STATE_COOKIE).expect("State cookie to be set.");
+    let authorization = location(&resp);
+    let query = authorization
+        .query_pairs()
+        .collect::<Vec<_>>();
+
+    assert!(query.contains(&("code_challenge_method".into(), "S256".into())));
+    assert!(
+        query.contains(&(
+            "state".into(),
+            state
+                .value()
+                .to_owned()
+                .into()
+        ))
+    );
+
+    let resp = TlsHttpClient::new()
+        .expect("Client to be built.")
+        .send(FetchRequest::get(authorization))
+        .await
+        .expect("Mock issuer to answer.");
+
+    assert_eq!(resp.status, 302);
+
+    let callback = Url::parse(
+        resp.header("location")
+            .expect("Mock issuer to redirect."),
+    )
+    .expect("Callback to be a URL.");
+
+    assert!(
+        callback
+            .as_str()
+            .starts_with(REDIRECT_URI)
+    );
+
+    (
+        format!(
+            "{}?{}",
+            callback.path(),
+            callback
+                .query()
+                .unwrap_or_default()
+        ),
+        state,
+    )
+}
+
+#[actix_web::test]
+async fn oidc_login_test() {
+    let issuer = MockIssuer::start();
+    let accounts = accounts();
+    let app = init_service(
+        App::new()
+            .app_data(accounts.clone())
+            .app_data(provider(&issuer))
+            .service(
+                scope("/api")
+                    .service(oidc_login)
+                    .service(oidc_callback)
+                    .service(login)
+                    .service(me),
+            ),
+    )
+    .await;
+
+    // the name the provider suggests is taken by a local account.
+    accounts
+        .register("ada_lovelace", "correct horse", 0)
+        .expect("Account to be registered.");
+
+    let mut ids = Vec::new();
+
+    for _ in 0..2 {
+        let (callback, state) =
+            authorize(call_service(&app, login_request().to_request()).await).await;
+        let req = TestRequest::get()
+            .uri(&callback)
+            .cookie(state)
+            .to_request();
+        let resp = call_service(&app, req).await;
+
+        assert_eq!(
+            resp.status()
+                .as_u16(),
+            302u16
+        );
+        assert_eq!(
+            resp.headers()
+                .get("location")
+                .and_then(|value| {
+                    value
+                        .to_str()
+                        .ok()
+                }),
+            Some("/")
+        );
+        assert_eq!(
+            cookie(&resp, STATE_COOKIE)
+                .expect("State cookie to be cleared.")
+                .value(),
+            ""
+        );
+
+        let session = cookie(&resp, SESSION_COOKIE).expect("Session cookie to be set.");
+        let req = TestRequest::get()
+            .uri("/api/auth/me")
+            .cookie(session)
+            .to_request();
+        let account: AccountInfo = read_body_json(call_service(&app, req).await).await;
+
+        assert_eq!(account.username, "ada_lovelace-2");
+        ids.push(account.id);
+    }
+
+    // logging in again reuses the account.
+    assert_eq!(ids, vec![2, 2]);
+
+    // provider accounts have no password to log in with.
+    let req = TestRequest::post()
+        .uri("/api/auth/login")
+        .set_json(Credentials {
+            username: "ada_lovelace-2".to_owned(),
+            password: String::new(),
+        })
+        .to_request();
+
+    assert_eq!(
+        call_service(&app, req)
+            .await
+            .status()
+            .as_u16(),
+        401u16
+    );
+}
+
+#[actix_web::test]
+async fn oidc_rejected_test() {
+    let issuer = MockIssuer::start();
+    let app = init_service(
+        App::new()
+            .app_data(accounts())
+            .app_data(provider(&issuer))
+            .service(
+                scope("/api")
+                    .service(oidc_login)
+                    .service(oidc_callback),
+            ),
+    )
+    .await;
+
+    let rejected = |resp: ServiceResponse| async move {
+        assert_eq!(
+            resp.status()
+                .as_u16(),
+            401u16
+        );
+
+        let body: ApiErrorBody = read_body_json(resp).await;
+
+        assert_eq!(body.code, ApiErrorCode::Unauthorized);
+
+        body.message
+    };
+
+    // the callback has to come back to the browser that started the login.
+    let (callback, _) = authorize(call_service(&app, login_request().to_request()).await).await;
+    let req = TestRequest::get()
+        .uri(&callback)
+        .to_request();
+
+    assert!(
+        rejected(call_service(&app, req).await)
+            .await
+            .contains("another browser")
+    );
+
+    let req = TestRequest::get()
+        .uri("/api/auth/oidc/callback?error=access_denied&state=whatever")
+        .to_request();
+
+    assert!(
+        rejected(call_service(&app, req).await)
+            .await
+            .contains("access_denied")
+    );
+
+    // a login can't be finished twice.
+    let (callback, state) = authorize(call_service(&app, login_request().to_request()).await).await;
+    let req = TestRequest::get()
+        .uri(&callback)
+        .cookie(state.clone())
+        .to_request();
+
+    assert_eq!(
+        call_service(&app, req)
+            .await
+            .status()
+            .as_u16(),
+        302u16
+    );
+
+    let req = TestRequest::get()
+        .uri(&callback)
+        .cookie(state)
+        .to_request();
+
+    assert!(
+        rejected(call_service(&app, req).await)
+            .await
+            .contains("another browser")
+    );
+
+    type Tamper = fn(&MockIssuer);
+
+    let cases: [(&str, Tamper); 4] = [
+        ("another client", |issuer| {
+            issuer.edit_claims(|claims| claims["aud"] = json!(["someone-else"]));
+        }),
+        ("another login", |issuer| {
+            issuer.edit_claims(|claims| claims["nonce"] = json!("replayed"));
+        }),
+        ("expired", |issuer| {
+            issuer.edit_claims(|claims| claims["exp"] = json!(1000));
+        }),
+        ("signature", MockIssuer::corrupt_signatures),
+    ];
+
+    for (reason, tamper) in cases {
+        tamper(&issuer);
+
+        let (callback, state) =
+            authorize(call_service(&app, login_request().to_request()).await).await;
+        let req = TestRequest::get()
+            .uri(&callback)
+            .cookie(state)
+            .to_request();
+
+        assert!(
+            rejected(call_service(&app, req).await)
+                .await
+                .contains(reason)
+        );
+    }
+}
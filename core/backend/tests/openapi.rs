@@ -0,0 +1,81 @@
+use std::env::var_os;
+use std::fs::{read_to_string, write};
+use std::path::Path;
+
+use actix_web::test::{TestRequest, call_service, init_service, read_body_json};
+use actix_web::web::scope;
+use actix_web::{App, test};
+use backend::routes::openapi::{ApiDoc, openapi_spec};
+use serde_json::Value;
+use utoipa::OpenApi;
+
+/// The document as it was last reviewed, `UPDATE_OPENAPI=1` rewrites it.
+const SNAPSHOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots/openapi.json");
+
+#[test]
+async fn openapi_snapshot_test() {
+    let spec = ApiDoc::openapi()
+        .to_pretty_json()
+        .expect("Document to serialize.")
+        + "\n";
+
+    if var_os("UPDATE_OPENAPI").is_some() {
+        write(SNAPSHOT, &spec).expect("Snapshot to be written.");
+    }
+
+    let snapshot = read_to_string(Path::new(SNAPSHOT)).expect("Snapshot to exist.");
+    let change = spec
+        .lines()
+        .zip(snapshot.lines())
+        .enumerate()
+        .find(|(_, (new, old))| new != old)
+        .map_or_else(
+            || "its end".to_owned(),
+            |(line, (new, old))| format!("line {}, `{old}` became `{new}`", line + 1),
+        );
+
+    assert!(
+        spec == snapshot,
+        "The OpenAPI document changed at {change}, rerun with UPDATE_OPENAPI=1 once the change is \
+         intended."
+    );
+}
+
+#[test]
+async fn openapi_route_test() {
+    let app = init_service(App::new().service(scope("/api").service(openapi_spec))).await;
+    let resp = call_service(
+        &app,
+        TestRequest::get()
+            .uri("/api/openapi.json")
+            .to_request(),
+    )
+    .await;
+
+    assert!(
+        resp.status()
+            .is_success()
+    );
+
+    let served: Value = read_body_json(resp).await;
+
+    assert_eq!(served, serde_json::to_value(ApiDoc::openapi()).expect("Document to serialize."));
+    assert_eq!(served["openapi"], "3.1.0");
+
+    // every route is documented under the path it's mounted at.
+    for (path, method) in [
+        ("/api/canvas/pixels", "post"),
+        ("/api/canvas/chunks/{cx}/{cy}", "get"),
+        ("/api/events/{id}/registrations", "post"),
+        ("/api/moderation/bans/{account}", "delete"),
+        ("/api/openapi.json", "get"),
+        ("/healthz", "get"),
+    ] {
+        assert!(served["paths"][path][method].is_object(), "{method} {path} to be documented.");
+    }
+
+    assert_eq!(
+        served["paths"]["/api/auth/me"]["get"]["security"],
+        serde_json::json!([{ "session": [] }])
+    );
+}
@@ -0,0 +1,601 @@
+use std::time::Duration;
+
+use actix_web::cookie::Cookie;
+use actix_web::dev::ServiceResponse;
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::rt::spawn;
+use actix_web::test::{TestRequest, call_service, init_service, read_body_json};
+use actix_web::web::{Data, ServiceConfig, scope};
+use actix_web::{App, HttpServer, test};
+use argon2::Params;
+use backend::accounts::{Accounts, AccountsConfig};
+use backend::canvas::CanvasStore;
+use backend::events::Events;
+use backend::funding::{Funding, GrowthPolicy};
+use backend::ledger::Ledger;
+use backend::payments::fake::{FakePaymentProvider, SIGNATURE_HEADER, SimulatedWebhook};
+use backend::payments::{PaymentError, Payments};
+use backend::realtime::RealtimeConfig;
+use backend::realtime::hub::RealtimeHub;
+use backend::routes::ledger::ledger_report;
+use backend::routes::payments::{create_checkout, payment_status, payment_webhook};
+use backend::utils::http::{HttpClient, TlsHttpClient};
+use backend::utils::time::unix_millis;
+use shared::api::{
+    ApiErrorBody,
+    ApiErrorCode,
+    CheckoutRequest,
+    LedgerAccount,
+    LedgerBalance,
+    LedgerReport,
+    PaymentInfo,
+    PaymentPurpose,
+    PaymentStatus,
+};
+use shared::canvas::CanvasBounds;
+use tempfile::tempdir;
+use url::Url;
+
+const SECRET: &str = "whsec_test";
+
+fn policy() -> GrowthPolicy {
+    GrowthPolicy {
+        base: CanvasBounds::from_chunks(2, 2, 8).expect("Bounds to fit."),
+        tier_cents: 10_00,
+        ring: 8,
+        max_tiers: None,
+    }
+}
+
+#[derive(Clone)]
+struct Services {
+    canvas: Data<CanvasStore>,
+    funding: Data<Funding>,
+    ledger: Data<Ledger>,
+    events: Data<Events>,
+    hub: Data<RealtimeHub>,
+    accounts: Data<Accounts>,
+    payments: Data<Payments>,
+}
+
+impl Services {
+    fn new(payments: Payments) -> Self {
+        Self {
+            canvas: Data::new(CanvasStore::new(8, policy().base)),
+            funding: Data::new(Funding::new(policy())),
+            ledger: Data::new(Ledger::new()),
+            events: Data::new(Events::new()),
+            hub: Data::new(RealtimeHub::new(RealtimeConfig::default())),
+            accounts: Data::new(
+                Accounts::new(AccountsConfig {
+                    session_ttl: Duration::from_secs(60),
+                    secure_cookies: false,
+                    password_params: Params::new(Params::MIN_M_COST, 1, 1, None)
+                        .expect("Params to be valid."),
+                    ..AccountsConfig::default()
+                })
+                .expect("Accounts to be created."),
+            ),
+            payments: Data::new(payments),
+        }
+    }
+
+    fn configure(&self, config: &mut ServiceConfig) {
+        config
+            .app_data(
+                self.canvas
+                    .clone(),
+            )
+            .app_data(
+                self.funding
+                    .clone(),
+            )
+            .app_data(
+                self.ledger
+                    .clone(),
+            )
+            .app_data(
+                self.events
+                    .clone(),
+            )
+            .app_data(
+                self.hub
+                    .clone(),
+            )
+            .app_data(
+                self.accounts
+                    .clone(),
+            )
+            .app_data(
+                self.payments
+                    .clone(),
+            )
+            .service(
+                scope("/api")
+                    .service(create_checkout)
+                    .service(payment_webhook)
+                    .service(payment_status)
+                    .service(ledger_report),
+            );
+    }
+
+    /// Serves the webhook on a real port for the simulator to deliver to.
+    fn start_server(&self) -> Url {
+        let services = self.clone();
+        let server = HttpServer::new(move || {
+            let services = services.clone();
+
+            App::new().configure(move |config| services.configure(config))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .expect("Test server to bind.");
+
+        let address = server.addrs()[0];
+        spawn(server.run());
+
+        Url::parse(&format!("http://{address}/api/payments/webhook"))
+            .expect("Webhook URL to be valid.")
+    }
+
+    fn total_cents(&self) -> u64 {
+        self.funding
+            .status(&self.canvas)
+            .total_cents
+    }
+
+    fn reference(&self, payment: &PaymentInfo) -> String {
+        self.payments
+            .payment(payment.id)
+            .expect("Payment to exist.")
+            .reference
+    }
+}
+
+fn checkout(amount_cents: u64) -> TestRequest {
+    TestRequest::post()
+        .uri("/api/payments")
+        .set_json(CheckoutRequest {
+            amount_cents,
+            purpose: PaymentPurpose::Contribution,
+        })
+}
+
+fn webhook(webhook: &SimulatedWebhook) -> TestRequest {
+    TestRequest::post()
+        .uri("/api/payments/webhook")
+        .insert_header((
+            SIGNATURE_HEADER,
+            webhook
+                .signature
+                .as_str(),
+        ))
+        .set_payload(
+            webhook
+                .body
+                .clone(),
+        )
+}
+
+fn status(resp: &ServiceResponse) -> u16 {
+    resp.status()
+        .as_u16()
+}
+
+async fn error_code(resp: ServiceResponse) -> ApiErrorCode {
+    read_body_json::<ApiErrorBody, _>(resp)
+        .await
+        .code
+}
+
+#[test]
+async fn checkout_flow_test() {
+    let provider = FakePaymentProvider::new(SECRET);
+    let simulator = provider.simulator();
+    let services = Services::new(Payments::new(provider));
+    let app = init_service(App::new().configure(|config| services.configure(config))).await;
+    let account = services
+        .accounts
+        .register("ada", "correct horse", 0)
+        .expect("Account to be registered.");
+    let (_, session) = services
+        .accounts
+        .start_session(account.id, unix_millis());
+
+    let resp = call_service(
+        &app,
+        checkout(5_00)
+            .cookie(session.clone())
+            .insert_header(("Idempotency-Key", "first"))
+            .to_request(),
+    )
+    .await;
+
+    assert_eq!(status(&resp), 201u16);
+
+    let payment: PaymentInfo = read_body_json(resp).await;
+
+    assert_eq!(payment.status, PaymentStatus::Pending);
+    assert!(
+        payment
+            .checkout_url
+            .starts_with("fake://checkout/")
+    );
+
+    // retrying with the same key doesn't start a second payment.
+    let retried: PaymentInfo = read_body_json(
+        call_service(
+            &app,
+            checkout(5_00)
+                .cookie(session.clone())
+                .insert_header(("Idempotency-Key", "first"))
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+
+    assert_eq!(retried, payment);
+
+    let resp = call_service(
+        &app,
+        checkout(6_00)
+            .cookie(session.clone())
+            .insert_header(("Idempotency-Key", "first"))
+            .to_request(),
+    )
+    .await;
+
+    assert_eq!(status(&resp), 409u16);
+    assert_eq!(error_code(resp).await, ApiErrorCode::Conflict);
+
+    // the provider reports the payment through a real request.
+    let url = services.start_server();
+    let succeeded =
+        simulator.event(&services.reference(&payment), PaymentStatus::Succeeded, unix_millis());
+
+    for _ in 0..2 {
+        let resp = TlsHttpClient::new()
+            .expect("Client to be built.")
+            .send(succeeded.request(url.clone()))
+            .await
+            .expect("Webhook to be delivered.");
+
+        assert_eq!(resp.status, 200);
+        assert_eq!(services.total_cents(), 5_00);
+    }
+
+    let status_request = |cookie: Option<Cookie<'static>>| {
+        let request = TestRequest::get().uri(&format!("/api/payments/{}", payment.id));
+
+        match cookie {
+            Some(cookie) => request.cookie(cookie),
+            None => request,
+        }
+        .to_request()
+    };
+    let paid: PaymentInfo =
+        read_body_json(call_service(&app, status_request(Some(session.clone()))).await).await;
+
+    assert_eq!(paid.status, PaymentStatus::Succeeded);
+
+    // payments of an account are only shown to it.
+    assert_eq!(status(&call_service(&app, status_request(None)).await), 404u16);
+
+    // refunds are recorded but the canvas keeps what it got.
+    let resp = call_service(
+        &app,
+        webhook(&simulator.event(
+            &services.reference(&payment),
+            PaymentStatus::Refunded,
+            unix_millis(),
+        ))
+        .to_request(),
+    )
+    .await;
+
+    assert_eq!(status(&resp), 200u16);
+
+    let refunded: PaymentInfo =
+        read_body_json(call_service(&app, status_request(Some(session))).await).await;
+
+    assert_eq!(refunded.status, PaymentStatus::Refunded);
+    assert_eq!(services.total_cents(), 5_00);
+
+    // the books show the money coming in and going back out.
+    let report: LedgerReport = read_body_json(
+        call_service(
+            &app,
+            TestRequest::get()
+                .uri("/api/ledger")
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+
+    assert_eq!(report.transactions, 2);
+    assert!(report.balanced);
+    assert_eq!(
+        report.balances,
+        vec![
+            LedgerBalance {
+                account: LedgerAccount::Cash,
+                balance_cents: 0
+            },
+            LedgerBalance {
+                account: LedgerAccount::GrowthFund,
+                balance_cents: 0
+            },
+        ]
+    );
+}
+
+#[test]
+async fn webhook_states_test() {
+    let provider = FakePaymentProvider::new(SECRET);
+    let simulator = provider.simulator();
+    let services = Services::new(Payments::new(provider));
+    let app = init_service(App::new().configure(|config| services.configure(config))).await;
+    let payment: PaymentInfo =
+        read_body_json(call_service(&app, checkout(10_00).to_request()).await).await;
+    let reference = services.reference(&payment);
+    let now = unix_millis();
+
+    // anonymous payments can be looked up by anyone.
+    let resp = call_service(
+        &app,
+        TestRequest::get()
+            .uri(&format!("/api/payments/{}", payment.id))
+            .to_request(),
+    )
+    .await;
+
+    assert_eq!(status(&resp), 200u16);
+
+    let forged = FakePaymentProvider::new("not the secret")
+        .simulator()
+        .event(&reference, PaymentStatus::Succeeded, now);
+    let stale = simulator.event(&reference, PaymentStatus::Succeeded, now - 3_600_000);
+    let unsigned = TestRequest::post()
+        .uri("/api/payments/webhook")
+        .set_payload(
+            simulator
+                .event(&reference, PaymentStatus::Succeeded, now)
+                .body,
+        );
+
+    for request in [webhook(&forged), webhook(&stale), unsigned] {
+        let resp = call_service(&app, request.to_request()).await;
+
+        assert_eq!(status(&resp), 401u16);
+        assert_eq!(error_code(resp).await, ApiErrorCode::Unauthorized);
+    }
+
+    let resp = call_service(
+        &app,
+        webhook(&simulator.event("cs_unknown", PaymentStatus::Succeeded, now)).to_request(),
+    )
+    .await;
+
+    assert_eq!(status(&resp), 404u16);
+    assert_eq!(services.total_cents(), 0);
+
+    // a failed payment can still be paid, late events don't undo it.
+    for (reported, expected) in [
+        (PaymentStatus::Failed, PaymentStatus::Failed),
+        (PaymentStatus::Succeeded, PaymentStatus::Succeeded),
+        (PaymentStatus::Pending, PaymentStatus::Succeeded),
+        (PaymentStatus::Failed, PaymentStatus::Succeeded),
+    ] {
+        let resp =
+            call_service(&app, webhook(&simulator.event(&reference, reported, now)).to_request())
+                .await;
+
+        assert_eq!(status(&resp), 200u16);
+        assert_eq!(
+            services
+                .payments
+                .payment(payment.id)
+                .expect("Payment to exist.")
+                .status,
+            expected
+        );
+    }
+
+    // the payment crossed the first tier.
+    assert_eq!(services.total_cents(), 10_00);
+    assert_eq!(
+        services
+            .ledger
+            .balance(LedgerAccount::GrowthFund),
+        10_00
+    );
+    assert_eq!(
+        services
+            .canvas
+            .bounds(),
+        policy().bounds(1)
+    );
+}
+
+#[test]
+async fn webhook_retry_test() {
+    let provider = FakePaymentProvider::new(SECRET);
+    let simulator = provider.simulator();
+    let services = Services::new(Payments::new(provider));
+    let app = init_service(App::new().configure(|config| services.configure(config))).await;
+    let payment: PaymentInfo =
+        read_body_json(call_service(&app, checkout(5_00).to_request()).await).await;
+    let succeeded =
+        simulator.event(&services.reference(&payment), PaymentStatus::Succeeded, unix_millis());
+    let mut headers = HeaderMap::new();
+
+    headers.insert(
+        HeaderName::from_static("fake-signature"),
+        HeaderValue::from_str(&succeeded.signature).expect("Signature to be a header value."),
+    );
+
+    // a delivery that stopped after recording the event, like on a failed
+    // write, is finished by the provider's retry.
+    services
+        .payments
+        .handle_webhook(&headers, &succeeded.body, unix_millis())
+        .expect("Webhook to be accepted.");
+
+    assert_eq!(services.total_cents(), 0);
+
+    let resp = call_service(&app, webhook(&succeeded).to_request()).await;
+
+    assert_eq!(status(&resp), 200u16);
+    assert_eq!(services.total_cents(), 5_00);
+    assert_eq!(
+        services
+            .ledger
+            .balance(LedgerAccount::GrowthFund),
+        5_00
+    );
+}
+
+#[test]
+async fn checkout_rejected_test() {
+    let provider = FakePaymentProvider::new(SECRET);
+    provider.set_unavailable(true);
+
+    let services = Services::new(Payments::new(provider));
+    let app = init_service(App::new().configure(|config| services.configure(config))).await;
+
+    for amount in [0, 100_000_000] {
+        let resp = call_service(&app, checkout(amount).to_request()).await;
+
+        assert_eq!(status(&resp), 400u16);
+        assert_eq!(error_code(resp).await, ApiErrorCode::InvalidRequest);
+    }
+
+    // entry fees only go through the registration of their event.
+    let resp = call_service(
+        &app,
+        TestRequest::post()
+            .uri("/api/payments")
+            .set_json(CheckoutRequest {
+                amount_cents: 1,
+                purpose: PaymentPurpose::EventEntry { event: 1 },
+            })
+            .to_request(),
+    )
+    .await;
+
+    assert_eq!(status(&resp), 400u16);
+    assert_eq!(error_code(resp).await, ApiErrorCode::InvalidRequest);
+
+    let resp = call_service(&app, checkout(5_00).to_request()).await;
+
+    assert_eq!(status(&resp), 502u16);
+    assert_eq!(error_code(resp).await, ApiErrorCode::Unavailable);
+
+    let resp = call_service(
+        &app,
+        TestRequest::get()
+            .uri("/api/payments/1")
+            .to_request(),
+    )
+    .await;
+
+    assert_eq!(status(&resp), 404u16);
+}
+
+#[test]
+async fn payments_persist_test() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+    let provider = FakePaymentProvider::new(SECRET);
+    let simulator = provider.simulator();
+    let payments = Payments::open(dir.path(), provider).expect("Payments to open.");
+    let request = CheckoutRequest {
+        amount_cents: 3_00,
+        purpose: PaymentPurpose::Contribution,
+    };
+    let payment = payments
+        .checkout(Some(1), request.clone(), Some("key".to_owned()), 0)
+        .await
+        .expect("Checkout to open.");
+    let succeeded = simulator.event(&payment.reference, PaymentStatus::Succeeded, unix_millis());
+    let mut headers = HeaderMap::new();
+
+    headers.insert(
+        HeaderName::from_static("fake-signature"),
+        HeaderValue::from_str(&succeeded.signature).expect("Signature to be a header value."),
+    );
+
+    let update = payments
+        .handle_webhook(&headers, &succeeded.body, unix_millis())
+        .expect("Webhook to be accepted.");
+
+    assert_eq!(update.previous, PaymentStatus::Pending);
+    assert_eq!(
+        update
+            .payment
+            .status,
+        PaymentStatus::Succeeded
+    );
+
+    let canvas = CanvasStore::new(8, policy().base);
+    let hub = RealtimeHub::new(RealtimeConfig::default());
+    let funding = Funding::open(dir.path(), policy()).expect("Funding to open.");
+
+    assert!(
+        funding
+            .contribute_payment(&canvas, &hub, &update.payment, 0)
+            .expect("Payment to be counted.")
+            .is_some()
+    );
+
+    drop(payments);
+    drop(funding);
+
+    let payments =
+        Payments::open(dir.path(), FakePaymentProvider::new(SECRET)).expect("Payments to reopen.");
+    let funding = Funding::open(dir.path(), policy()).expect("Funding to reopen.");
+    let stored = payments
+        .payment(payment.id)
+        .expect("Payment to be stored.");
+
+    assert_eq!(stored.status, PaymentStatus::Succeeded);
+
+    // the same event, checkout and payment are recognised after a restart.
+    assert!(
+        !payments
+            .handle_webhook(&headers, &succeeded.body, unix_millis())
+            .expect("Webhook to be accepted.")
+            .changed()
+    );
+    assert_eq!(
+        payments
+            .checkout(Some(1), request.clone(), Some("key".to_owned()), 0)
+            .await
+            .expect("Checkout to be found."),
+        stored
+    );
+    assert!(matches!(
+        payments
+            .checkout(
+                Some(1),
+                CheckoutRequest { amount_cents: 4_00, ..request },
+                Some("key".to_owned()),
+                0
+            )
+            .await,
+        Err(PaymentError::IdempotencyConflict)
+    ));
+    assert_eq!(
+        funding
+            .contribute_payment(&canvas, &hub, &stored, 0)
+            .expect("Payment to be checked."),
+        None
+    );
+    assert_eq!(
+        funding
+            .status(&canvas)
+            .total_cents,
+        3_00
+    );
+}
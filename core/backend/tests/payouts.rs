@@ -0,0 +1,780 @@
+use std::fs::read_to_string;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::cookie::Cookie;
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::test::{TestRequest, call_service, init_service, read_body_json};
+use actix_web::web::{Data, ServiceConfig, scope};
+use actix_web::{App, test};
+use argon2::Params;
+use backend::accounts::{Accounts, AccountsConfig};
+use backend::events::Events;
+use backend::ledger::Ledger;
+use backend::payments::fake::{FakePaymentProvider, WebhookSimulator};
+use backend::payments::{Payment, Payments};
+use backend::payouts::{PayoutPolicy, Payouts};
+use backend::routes::events::cancel_event;
+use backend::routes::payouts::{approve_payout, list_payouts, payout_audit, reject_payout};
+use backend::utils::time::unix_millis;
+use shared::api::{
+    CheckoutRequest,
+    EventRequest,
+    EventState,
+    LedgerAccount,
+    PaymentPurpose,
+    PaymentStatus,
+    PayoutAction,
+    PayoutAuditRecord,
+    PayoutInfo,
+    PayoutStatus,
+    default_tie_breaks,
+};
+use shared::canvas::CanvasBounds;
+use tempfile::tempdir;
+
+const SECRET: &str = "whsec_test";
+const HOLD: u64 = 60 * 1000;
+
+struct Services {
+    provider: Arc<FakePaymentProvider>,
+    simulator: WebhookSimulator,
+    accounts: Data<Accounts>,
+    events: Data<Events>,
+    ledger: Data<Ledger>,
+    payments: Data<Payments>,
+    payouts: Data<Payouts>,
+}
+
+impl Services {
+    fn new(payouts: Payouts) -> Self {
+        let provider = Arc::new(FakePaymentProvider::new(SECRET));
+
+        Self {
+            simulator: provider.simulator(),
+            payments: Data::new(Payments::new(provider.clone())),
+            provider,
+            accounts: Data::new(
+                Accounts::new(AccountsConfig {
+                    session_ttl: Duration::from_secs(60),
+                    secure_cookies: false,
+                    password_params: Params::new(Params::MIN_M_COST, 1, 1, None)
+                        .expect("Params to be valid."),
+                    admins: vec!["admin".to_owned()],
+                    ..AccountsConfig::default()
+                })
+                .expect("Accounts to be created."),
+            ),
+            events: Data::new(Events::new()),
+            ledger: Data::new(Ledger::new()),
+            payouts: Data::new(payouts),
+        }
+    }
+
+    fn configure(&self, config: &mut ServiceConfig) {
+        config
+            .app_data(
+                self.accounts
+                    .clone(),
+            )
+            .app_data(
+                self.events
+                    .clone(),
+            )
+            .app_data(
+                self.ledger
+                    .clone(),
+            )
+            .app_data(
+                self.payouts
+                    .clone(),
+            )
+            .service(
+                scope("/api")
+                    .service(cancel_event)
+                    .service(list_payouts)
+                    .service(payout_audit)
+                    .service(approve_payout)
+                    .service(reject_payout),
+            );
+    }
+
+    fn session(&self, username: &str) -> (u64, Cookie<'static>) {
+        let account = self
+            .accounts
+            .register(username, "correct horse", 0)
+            .expect("Account to be registered.");
+
+        (
+            account.id,
+            self.accounts
+                .start_session(account.id, unix_millis())
+                .1,
+        )
+    }
+
+    /// A published event with an entry fee of 5.00 running from 100 to 200,
+    /// judged until 300.
+    fn event(&self) -> u64 {
+        let event = self
+            .events
+            .create(
+                CanvasBounds::new(0, 0, 64, 64),
+                EventRequest {
+                    title: "Pixel Sprint".to_owned(),
+                    region: CanvasBounds::new(0, 0, 16, 16),
+                    starts_at: 100,
+                    ends_at: 200,
+                    judging_ends_at: 300,
+                    entry_fee_cents: 5_00,
+                    tie_breaks: default_tie_breaks(),
+                },
+                0,
+            )
+            .expect("Event to be created.");
+
+        self.events
+            .publish(event.id, 0)
+            .expect("Event to be published.");
+
+        event.id
+    }
+
+    /// Pays the entry fee of `account` like the payment webhook would.
+    async fn pay_entry(&self, event: u64, account: u64) -> Payment {
+        let payment = self
+            .payments
+            .checkout(
+                Some(account),
+                CheckoutRequest {
+                    amount_cents: 5_00,
+                    purpose: PaymentPurpose::EventEntry { event },
+                },
+                None,
+                0,
+            )
+            .await
+            .expect("Checkout to open.");
+
+        self.deliver(&payment, PaymentStatus::Succeeded)
+    }
+
+    /// Moves `payment` to `status` and books it like the payment webhook
+    /// would.
+    fn deliver(&self, payment: &Payment, status: PaymentStatus) -> Payment {
+        let now = unix_millis();
+        let webhook = self
+            .simulator
+            .event(&payment.reference, status, now);
+        let mut headers = HeaderMap::new();
+
+        headers.insert(
+            HeaderName::from_static("fake-signature"),
+            HeaderValue::from_str(&webhook.signature).expect("Signature to be a header value."),
+        );
+
+        let update = self
+            .payments
+            .handle_webhook(&headers, &webhook.body, now)
+            .expect("Webhook to be accepted.");
+
+        self.ledger
+            .record_payment(&update.payment, 0)
+            .expect("Payment to be booked.");
+        self.events
+            .apply_payment(&update.payment, 0)
+            .expect("Payment to be applied.");
+
+        update.payment
+    }
+
+    async fn process(&self, now: u64) -> Vec<PayoutAction> {
+        self.payouts
+            .process(&self.events, &self.ledger, Some(&self.payments), now)
+            .await
+            .expect("Payouts to be processed.")
+            .iter()
+            .map(|record| record.action)
+            .collect()
+    }
+
+    fn balance(&self, account: LedgerAccount) -> i64 {
+        self.ledger
+            .balance(account)
+    }
+}
+
+fn policy() -> PayoutPolicy {
+    PayoutPolicy {
+        winner_percent: 50,
+        hold: Duration::from_millis(HOLD),
+    }
+}
+
+/// Runs `event` through judging, `first` wins with a vote of `voter`.
+fn judge(services: &Services, event: u64, first: u64, second: u64, voter: u64) {
+    services
+        .events
+        .advance(100)
+        .expect("Events to advance.");
+    services
+        .events
+        .submit(event, first, CanvasBounds::new(0, 0, 8, 8), 110)
+        .expect("Submission to be entered.");
+    services
+        .events
+        .submit(event, second, CanvasBounds::new(8, 0, 8, 8), 120)
+        .expect("Submission to be entered.");
+    services
+        .events
+        .advance(200)
+        .expect("Events to advance.");
+    services
+        .events
+        .vote(event, voter, 0, first, 200)
+        .expect("Vote to be cast.");
+    services
+        .events
+        .advance(300)
+        .expect("Events to advance.");
+}
+
+#[test]
+async fn payout_flow_test() {
+    let services = Services::new(Payouts::new(policy()));
+    let app = init_service(App::new().configure(|config| services.configure(config))).await;
+    let (_, admin) = services.session("admin");
+    let (first, first_session) = services.session("first");
+    let (second, _) = services.session("second");
+    let event = services.event();
+
+    services
+        .pay_entry(event, first)
+        .await;
+    services
+        .pay_entry(event, second)
+        .await;
+
+    // nothing is paid out before the event finished.
+    assert_eq!(
+        services
+            .process(250)
+            .await,
+        vec![]
+    );
+
+    judge(&services, event, first, second, 99);
+
+    assert_eq!(
+        services
+            .process(300)
+            .await,
+        vec![PayoutAction::PayoutCreated]
+    );
+    assert_eq!(
+        services
+            .process(300)
+            .await,
+        vec![]
+    );
+
+    // half of the pool is held for the winner, the rest goes to the platform.
+    assert_eq!(services.balance(LedgerAccount::PrizePool { event }), 0);
+    assert_eq!(services.balance(LedgerAccount::Payable { user: first }), 5_00);
+    assert_eq!(services.balance(LedgerAccount::Platform), 5_00);
+
+    let payouts: Vec<PayoutInfo> = read_body_json(
+        call_service(
+            &app,
+            TestRequest::get()
+                .uri("/api/payouts")
+                .cookie(admin.clone())
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+    let payout = &payouts[0];
+
+    assert_eq!(payout.account, first);
+    assert_eq!(payout.amount_cents, 5_00);
+    assert_eq!(payout.status, PayoutStatus::Pending);
+    assert_eq!(payout.release_at, 300 + HOLD);
+
+    // pending payouts wait for an admin.
+    assert_eq!(
+        services
+            .process(300 + HOLD)
+            .await,
+        vec![]
+    );
+
+    let approve = |cookie: Cookie<'static>| {
+        TestRequest::post()
+            .uri(&format!("/api/payouts/{}/approve", payout.id))
+            .cookie(cookie)
+            .to_request()
+    };
+
+    assert_eq!(
+        call_service(&app, approve(first_session))
+            .await
+            .status()
+            .as_u16(),
+        403u16
+    );
+
+    let approved: PayoutInfo =
+        read_body_json(call_service(&app, approve(admin.clone())).await).await;
+
+    assert_eq!(approved.status, PayoutStatus::Approved);
+    assert_eq!(
+        call_service(&app, approve(admin.clone()))
+            .await
+            .status()
+            .as_u16(),
+        409u16
+    );
+
+    // approved payouts still wait for the hold to pass.
+    assert_eq!(
+        services
+            .process(300 + HOLD - 1)
+            .await,
+        vec![]
+    );
+
+    services
+        .provider
+        .set_unavailable(true);
+
+    assert_eq!(
+        services
+            .process(300 + HOLD)
+            .await,
+        vec![PayoutAction::PayoutFailed]
+    );
+    assert_eq!(
+        services
+            .process(300 + HOLD)
+            .await,
+        vec![]
+    );
+
+    services
+        .provider
+        .set_unavailable(false);
+
+    assert_eq!(
+        services
+            .process(300 + HOLD)
+            .await,
+        vec![PayoutAction::PayoutPaid]
+    );
+    assert_eq!(
+        services
+            .process(300 + HOLD)
+            .await,
+        vec![]
+    );
+    assert_eq!(services.balance(LedgerAccount::Payable { user: first }), 0);
+    assert_eq!(services.balance(LedgerAccount::Cash), 5_00);
+    assert!(
+        services
+            .ledger
+            .verify()
+            .is_ok()
+    );
+
+    let audit: Vec<PayoutAuditRecord> = read_body_json(
+        call_service(
+            &app,
+            TestRequest::get()
+                .uri("/api/payouts/audit")
+                .cookie(admin)
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+
+    assert_eq!(
+        audit
+            .iter()
+            .map(|record| record.action)
+            .collect::<Vec<_>>(),
+        vec![
+            PayoutAction::PayoutCreated,
+            PayoutAction::PayoutApproved,
+            PayoutAction::PayoutFailed,
+            PayoutAction::PayoutPaid,
+        ]
+    );
+    assert!(
+        audit[1]
+            .actor
+            .is_some()
+    );
+    assert!(
+        audit[3]
+            .detail
+            .as_deref()
+            .is_some_and(|reference| reference.starts_with("po_fake_"))
+    );
+}
+
+#[test]
+async fn payout_rejected_test() {
+    let services = Services::new(Payouts::new(policy()));
+    let app = init_service(App::new().configure(|config| services.configure(config))).await;
+    let (_, admin) = services.session("admin");
+    let (first, _) = services.session("first");
+    let (second, _) = services.session("second");
+    let event = services.event();
+
+    services
+        .pay_entry(event, first)
+        .await;
+    services
+        .pay_entry(event, second)
+        .await;
+    judge(&services, event, first, second, 99);
+    services
+        .process(300)
+        .await;
+
+    let rejected: PayoutInfo = read_body_json(
+        call_service(
+            &app,
+            TestRequest::post()
+                .uri("/api/payouts/1/reject")
+                .cookie(admin)
+                .to_request(),
+        )
+        .await,
+    )
+    .await;
+
+    assert_eq!(rejected.status, PayoutStatus::Rejected);
+
+    // the whole award goes back into the pool and is never sent.
+    assert_eq!(services.balance(LedgerAccount::PrizePool { event }), 10_00);
+    assert_eq!(services.balance(LedgerAccount::Payable { user: first }), 0);
+    assert_eq!(services.balance(LedgerAccount::Platform), 0);
+
+    // the pool goes to the runner-up instead.
+    assert_eq!(
+        services
+            .process(300 + HOLD)
+            .await,
+        vec![PayoutAction::PayoutCreated]
+    );
+    assert_eq!(
+        services
+            .process(300 + HOLD)
+            .await,
+        vec![]
+    );
+
+    let replacement = services
+        .payouts
+        .payout(2)
+        .expect("Replacement payout to exist.");
+
+    assert_eq!(replacement.account, second);
+    assert_eq!(replacement.status, PayoutStatus::Pending);
+    assert_eq!(services.balance(LedgerAccount::PrizePool { event }), 0);
+    assert_eq!(services.balance(LedgerAccount::Payable { user: second }), 5_00);
+    assert_eq!(services.balance(LedgerAccount::Platform), 5_00);
+    assert!(
+        services
+            .ledger
+            .verify()
+            .is_ok()
+    );
+}
+
+#[test]
+async fn refund_after_award_test() {
+    let services = Services::new(Payouts::new(policy()));
+    let (first, _) = services.session("first");
+    let (second, _) = services.session("second");
+    let event = services.event();
+
+    services
+        .pay_entry(event, first)
+        .await;
+    let paid = services
+        .pay_entry(event, second)
+        .await;
+    judge(&services, event, first, second, 99);
+    services
+        .process(300)
+        .await;
+
+    // the pool was awarded already, the platform covers the chargeback.
+    let refunded = services.deliver(&paid, PaymentStatus::Refunded);
+
+    assert_eq!(refunded.status, PaymentStatus::Refunded);
+    assert_eq!(services.balance(LedgerAccount::PrizePool { event }), 0);
+    assert_eq!(services.balance(LedgerAccount::Payable { user: first }), 5_00);
+    assert_eq!(services.balance(LedgerAccount::Platform), 0);
+    assert_eq!(services.balance(LedgerAccount::Cash), 5_00);
+    assert!(
+        services
+            .ledger
+            .verify()
+            .is_ok()
+    );
+}
+
+#[test]
+async fn cancel_refund_test() {
+    let services = Services::new(Payouts::new(policy()));
+    let app = init_service(App::new().configure(|config| services.configure(config))).await;
+    let (_, admin) = services.session("admin");
+    let (first, first_session) = services.session("first");
+    let (second, _) = services.session("second");
+    let event = services.event();
+    let paid = [
+        services
+            .pay_entry(event, first)
+            .await,
+        services
+            .pay_entry(event, second)
+            .await,
+    ];
+    let cancel = |cookie: Cookie<'static>| {
+        TestRequest::post()
+            .uri(&format!("/api/events/{event}/cancel"))
+            .cookie(cookie)
+            .to_request()
+    };
+
+    assert_eq!(
+        call_service(&app, cancel(first_session))
+            .await
+            .status()
+            .as_u16(),
+        403u16
+    );
+    assert_eq!(
+        call_service(&app, cancel(admin.clone()))
+            .await
+            .status()
+            .as_u16(),
+        200u16
+    );
+    assert_eq!(
+        call_service(&app, cancel(admin))
+            .await
+            .status()
+            .as_u16(),
+        409u16
+    );
+
+    services
+        .provider
+        .set_unavailable(true);
+
+    assert_eq!(
+        services
+            .process(150)
+            .await,
+        vec![PayoutAction::RefundFailed, PayoutAction::RefundFailed]
+    );
+    assert_eq!(
+        services
+            .process(155)
+            .await,
+        vec![]
+    );
+
+    services
+        .provider
+        .set_unavailable(false);
+
+    assert_eq!(
+        services
+            .process(160)
+            .await,
+        vec![PayoutAction::EntryRefunded, PayoutAction::EntryRefunded]
+    );
+    assert_eq!(
+        services
+            .process(170)
+            .await,
+        vec![]
+    );
+
+    for payment in paid {
+        assert_eq!(
+            services
+                .payments
+                .payment(payment.id)
+                .map(|payment| payment.status),
+            Some(PaymentStatus::Refunded)
+        );
+    }
+
+    let cancelled = services
+        .events
+        .event(event)
+        .expect("Event to exist.");
+
+    assert_eq!(cancelled.state, EventState::Cancelled);
+    assert!(
+        cancelled
+            .participants
+            .is_empty()
+    );
+    assert_eq!(services.balance(LedgerAccount::PrizePool { event }), 0);
+    assert_eq!(services.balance(LedgerAccount::Cash), 0);
+
+    // cancelled events never move on.
+    assert_eq!(
+        services
+            .events
+            .advance(1_000)
+            .expect("Events to advance.")
+            .len(),
+        0
+    );
+}
+
+#[test]
+async fn refund_started_test() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+    let provider = Arc::new(FakePaymentProvider::new(SECRET));
+    let services = Services {
+        payments: Data::new(
+            Payments::open(dir.path(), provider.clone()).expect("Payments to be opened."),
+        ),
+        provider,
+        ..Services::new(Payouts::new(policy()))
+    };
+    let (first, _) = services.session("first");
+    let event = services.event();
+    let paid = services
+        .pay_entry(event, first)
+        .await;
+
+    services
+        .provider
+        .set_unavailable(true);
+
+    assert!(
+        services
+            .payments
+            .refund(paid.id, 150)
+            .await
+            .is_err()
+    );
+
+    // the refund is on record before the provider is asked for it.
+    let journal = read_to_string(
+        dir.path()
+            .join("payments.jsonl"),
+    )
+    .expect("Journal to be readable.");
+
+    assert!(journal.contains(r#""kind":"refund_started""#));
+
+    let reopened = Payments::open(
+        dir.path(),
+        services
+            .provider
+            .clone(),
+    )
+    .expect("Payments to be reopened.");
+
+    assert_eq!(
+        reopened
+            .payment(paid.id)
+            .map(|payment| payment.status),
+        Some(PaymentStatus::Succeeded)
+    );
+
+    services
+        .provider
+        .set_unavailable(false);
+
+    let refunded = reopened
+        .refund(paid.id, 160)
+        .await
+        .expect("Refund to be issued.")
+        .expect("Payment to change.");
+
+    assert_eq!(
+        refunded
+            .payment
+            .status,
+        PaymentStatus::Refunded
+    );
+    assert_eq!(
+        reopened
+            .refund(paid.id, 170)
+            .await
+            .expect("Refund to be skipped."),
+        None
+    );
+}
+
+#[test]
+async fn payout_persist_test() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+    let services =
+        Services::new(Payouts::open(dir.path(), policy()).expect("Payouts to be opened."));
+    let (first, _) = services.session("first");
+    let (second, _) = services.session("second");
+    let event = services.event();
+
+    services
+        .pay_entry(event, first)
+        .await;
+    services
+        .pay_entry(event, second)
+        .await;
+    judge(&services, event, first, second, 99);
+    services
+        .process(300)
+        .await;
+    services
+        .payouts
+        .approve(1, 7, 400)
+        .expect("Payout to be approved.");
+
+    let reopened = Payouts::open(dir.path(), policy()).expect("Payouts to be reopened.");
+
+    assert_eq!(
+        reopened.payouts(),
+        services
+            .payouts
+            .payouts()
+    );
+    assert_eq!(
+        reopened.audit(),
+        services
+            .payouts
+            .audit()
+    );
+
+    // a fresh ledger gets every step posted again.
+    let ledger = Ledger::new();
+
+    for payment in services
+        .payments
+        .payments()
+    {
+        ledger
+            .record_payment(&payment, 0)
+            .expect("Payment to be booked.");
+    }
+
+    reopened
+        .sync_ledger(&ledger, 0)
+        .expect("Ledger to be synced.");
+
+    assert_eq!(ledger.balance(LedgerAccount::Payable { user: first }), 5_00);
+    assert_eq!(ledger.balance(LedgerAccount::PrizePool { event }), 0);
+}
@@ -0,0 +1,350 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use actix_web::rt::spawn;
+use actix_web::rt::time::sleep;
+use actix_web::test::{TestRequest, call_service, init_service, read_body_json};
+use actix_web::web::{Data, scope};
+use actix_web::{App, HttpServer, test};
+use backend::canvas::CanvasStore;
+use backend::cooldown::{CooldownPolicy, Cooldowns};
+use backend::events::Events;
+use backend::moderation::Moderation;
+use backend::realtime::RealtimeConfig;
+use backend::realtime::hub::RealtimeHub;
+use backend::routes::canvas::place_pixel;
+use backend::routes::realtime::canvas_live;
+use backend::utils::client::TrustedProxies;
+use futures_util::{SinkExt, StreamExt};
+use shared::api::{PlacePixelRequest, PlacePixelResponse};
+use shared::canvas::{CanvasBounds, ChunkCoord, PixelColor, PixelCoord, Placement};
+use shared::realtime::{ClientMessage, ServerMessage};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async};
+
+type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+fn start_server(hub: Data<RealtimeHub>) -> SocketAddr {
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(hub.clone())
+            .service(scope("/api").service(canvas_live))
+    })
+    .workers(1)
+    .bind(("127.0.0.1", 0))
+    .expect("Test server to bind.");
+
+    let address = server.addrs()[0];
+    spawn(server.run());
+
+    address
+}
+
+async fn wait_for_sessions(hub: &RealtimeHub, count: usize) {
+    for _ in 0..100 {
+        if hub.session_count() == count {
+            return;
+        }
+
+        sleep(Duration::from_millis(10)).await;
+    }
+
+    panic!("Expected {count} sessions, found {}.", hub.session_count());
+}
+
+async fn wait_for_subscribers(hub: &RealtimeHub, chunk: ChunkCoord, count: usize) {
+    for _ in 0..100 {
+        if hub.subscriber_count(chunk) == count {
+            return;
+        }
+
+        sleep(Duration::from_millis(10)).await;
+    }
+
+    panic!("Expected {count} subscribers, found {}.", hub.subscriber_count(chunk));
+}
+
+async fn next_text(client: &mut Client) -> ServerMessage {
+    loop {
+        match client
+            .next()
+            .await
+        {
+            Some(Ok(Message::Text(text))) => {
+                break serde_json::from_str(&text).expect("Message to be valid.");
+            },
+            Some(Ok(_)) => {},
+            other => panic!("Unexpected websocket event {other:?}"),
+        }
+    }
+}
+
+async fn send(client: &mut Client, message: &ClientMessage) {
+    client
+        .send(Message::text(serde_json::to_string(message).expect("Message to serialize.")))
+        .await
+        .expect("Message to be sent.");
+}
+
+#[test]
+async fn chunk_subscription_test() {
+    let hub = Data::new(RealtimeHub::new(RealtimeConfig::default()));
+    let canvas = Data::new(CanvasStore::new(
+        16,
+        CanvasBounds::from_chunks(2, 1, 16).expect("Bounds to fit."),
+    ));
+    let address = start_server(hub.clone());
+
+    let (mut first, _) = connect_async(format!("ws://{address}/api/canvas/live"))
+        .await
+        .expect("First client to connect.");
+    let (mut second, _) = connect_async(format!("ws://{address}/api/canvas/live"))
+        .await
+        .expect("Second client to connect.");
+
+    wait_for_sessions(&hub, 2).await;
+
+    let left = ChunkCoord::new(0, 0);
+    let right = ChunkCoord::new(1, 0);
+
+    send(&mut first, &ClientMessage::Subscribe { chunks: vec![left] }).await;
+    send(&mut second, &ClientMessage::Subscribe { chunks: vec![right] }).await;
+    wait_for_subscribers(&hub, left, 1).await;
+    wait_for_subscribers(&hub, right, 1).await;
+
+    let app = init_service(
+        App::new()
+            .app_data(canvas)
+            .app_data(hub.clone())
+            .app_data(Data::new(Cooldowns::new(CooldownPolicy {
+                interval: Duration::ZERO,
+                ..CooldownPolicy::default()
+            })))
+            .app_data(Data::new(TrustedProxies::default()))
+            .app_data(Data::new(Events::new()))
+            .app_data(Data::new(Moderation::new()))
+            .service(scope("/api").service(place_pixel)),
+    )
+    .await;
+    let place = async |x: i32| -> Placement {
+        let req = TestRequest::post()
+            .uri("/api/canvas/pixels")
+            .peer_addr(
+                "192.0.2.1:1000"
+                    .parse()
+                    .expect("Valid address."),
+            )
+            .set_json(PlacePixelRequest {
+                coord: PixelCoord::new(x, 2),
+                color: PixelColor::new(3, 4, 5),
+            })
+            .to_request();
+
+        let response: PlacePixelResponse = read_body_json(call_service(&app, req).await).await;
+
+        response.placement
+    };
+
+    let in_left = place(1).await;
+    let in_right = place(17).await;
+
+    assert_eq!(next_text(&mut first).await, ServerMessage::Placement(in_left.clone()));
+    // the second client never sees the placement in the left chunk.
+    assert_eq!(next_text(&mut second).await, ServerMessage::Placement(in_right.clone()));
+
+    send(&mut first, &ClientMessage::Unsubscribe { chunks: vec![left] }).await;
+    send(&mut first, &ClientMessage::Subscribe { chunks: vec![right] }).await;
+    wait_for_subscribers(&hub, left, 0).await;
+    wait_for_subscribers(&hub, right, 2).await;
+
+    place(2).await;
+    let in_right = place(18).await;
+
+    assert_eq!(next_text(&mut first).await, ServerMessage::Placement(in_right.clone()));
+    assert_eq!(next_text(&mut second).await, ServerMessage::Placement(in_right));
+}
+
+#[test]
+async fn invalid_client_message_test() {
+    let hub = Data::new(RealtimeHub::new(RealtimeConfig {
+        max_subscriptions: 1,
+        ..RealtimeConfig::default()
+    }));
+    let address = start_server(hub.clone());
+
+    let (mut unknown, _) = connect_async(format!("ws://{address}/api/canvas/live"))
+        .await
+        .expect("Client to connect.");
+    let (mut greedy, _) = connect_async(format!("ws://{address}/api/canvas/live"))
+        .await
+        .expect("Client to connect.");
+
+    wait_for_sessions(&hub, 2).await;
+
+    unknown
+        .send(Message::text("{\"type\":\"draw\"}"))
+        .await
+        .expect("Message to be sent.");
+    send(
+        &mut greedy,
+        &ClientMessage::Subscribe {
+            chunks: vec![ChunkCoord::new(0, 0), ChunkCoord::new(0, 1)],
+        },
+    )
+    .await;
+
+    for (client, code) in [(&mut unknown, CloseCode::Invalid), (&mut greedy, CloseCode::Policy)] {
+        let close = loop {
+            match client
+                .next()
+                .await
+            {
+                Some(Ok(Message::Close(frame))) => break frame,
+                Some(Ok(_)) => {},
+                other => panic!("Unexpected websocket event {other:?}"),
+            }
+        };
+
+        assert_eq!(
+            close
+                .expect("Close frame to have a reason.")
+                .code,
+            code
+        );
+    }
+
+    wait_for_sessions(&hub, 0).await;
+}
+
+#[test]
+async fn slow_session_dropped_test() {
+    let hub = RealtimeHub::new(RealtimeConfig {
+        send_buffer: 2,
+        ..RealtimeConfig::default()
+    });
+    let (_, mut slow) = hub.connect();
+    let (_, mut fast) = hub.connect();
+
+    let message = ServerMessage::Placement(Placement {
+        seq: 1,
+        coord: PixelCoord::new(0, 0),
+        color: PixelColor::WHITE,
+        placed_at: 0,
+        author: None,
+    });
+
+    assert_eq!(hub.broadcast(&message), 2);
+    assert_eq!(hub.broadcast(&message), 2);
+
+    assert!(
+        fast.recv()
+            .await
+            .is_some()
+    );
+    assert!(
+        fast.recv()
+            .await
+            .is_some()
+    );
+
+    // the slow session never drained its queue.
+    assert_eq!(hub.broadcast(&message), 1);
+    assert_eq!(hub.session_count(), 1);
+
+    assert!(
+        slow.recv()
+            .await
+            .is_some()
+    );
+    assert!(
+        slow.recv()
+            .await
+            .is_some()
+    );
+    assert!(
+        slow.recv()
+            .await
+            .is_none()
+    );
+}
+
+#[test]
+async fn shut_down_test() {
+    let hub = RealtimeHub::new(RealtimeConfig::default());
+    let (_, mut first) = hub.connect();
+    let (_, mut second) = hub.connect();
+
+    assert_eq!(hub.shut_down(Duration::from_secs(5)), 2);
+    assert_eq!(hub.session_count(), 0);
+    assert!(hub.is_shutting_down());
+
+    for receiver in [&mut first, &mut second] {
+        let text = receiver
+            .recv()
+            .await
+            .expect("Shutdown message to be queued.");
+
+        assert_eq!(
+            serde_json::from_str::<ServerMessage>(&text).expect("Message to be valid."),
+            ServerMessage::ShuttingDown { reconnect_after_ms: 5000 }
+        );
+        assert!(
+            receiver
+                .recv()
+                .await
+                .is_none()
+        );
+    }
+
+    // sessions connecting afterwards end right away.
+    let (_, mut late) = hub.connect();
+
+    assert_eq!(hub.session_count(), 0);
+    assert!(
+        late.recv()
+            .await
+            .is_none()
+    );
+}
+
+#[test]
+async fn idle_session_closed_test() {
+    let hub = Data::new(RealtimeHub::new(RealtimeConfig {
+        heartbeat_interval: Duration::from_millis(50),
+        client_timeout: Duration::from_millis(150),
+        ..RealtimeConfig::default()
+    }));
+    let address = start_server(hub.clone());
+
+    let (mut client, _) = connect_async(format!("ws://{address}/api/canvas/live"))
+        .await
+        .expect("Client to connect.");
+
+    wait_for_sessions(&hub, 1).await;
+
+    // not polling the client means no pong ever goes out.
+    sleep(Duration::from_millis(400)).await;
+
+    let mut pings = 0;
+    let close = loop {
+        match client
+            .next()
+            .await
+        {
+            Some(Ok(Message::Ping(_))) => pings += 1,
+            Some(Ok(Message::Close(frame))) => break frame,
+            other => panic!("Unexpected websocket event {other:?}"),
+        }
+    };
+
+    assert!(pings > 0);
+    assert_eq!(
+        close
+            .expect("Close frame to have a reason.")
+            .code,
+        CloseCode::Away
+    );
+    assert_eq!(hub.session_count(), 0);
+}
@@ -0,0 +1,305 @@
+#![cfg(not(feature = "coverage"))]
+
+use std::fs::{OpenOptions, read_dir};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant};
+
+use actix_web::test;
+use backend::canvas::CanvasStore;
+use futures_util::StreamExt;
+use shared::api::{PlacePixelRequest, PlacePixelResponse};
+use shared::canvas::{CanvasBounds, PixelColor, PixelCoord, Placement};
+use shared::realtime::ServerMessage;
+use tempfile::tempdir;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+const CHUNK_SIZE: u16 = 16;
+
+/// The server binary running on its own data directory, killed when dropped.
+struct Server {
+    child: Child,
+    address: SocketAddr,
+}
+
+impl Server {
+    fn start(dir: &Path) -> Self {
+        let address = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .expect("A free port to be found.");
+        let child = Command::new(env!("CARGO_BIN_EXE_backend"))
+            .args(["--bind", &address.to_string()])
+            .args(["--data-dir", &dir.to_string_lossy()])
+            .args(["--chunk-size", &CHUNK_SIZE.to_string()])
+            .args(["--cooldown-interval-ms", "0", "--cooldown-burst", "100000"])
+            .current_dir(dir)
+            .env_clear()
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Server to start.");
+        let server = Self { child, address };
+        let started = Instant::now();
+
+        while request(address, "GET", "/readyz", "").map(|(status, _)| status) != Some(200) {
+            assert!(started.elapsed() < Duration::from_secs(30), "Server never got ready.");
+            sleep(Duration::from_millis(50));
+        }
+
+        server
+    }
+
+    fn signal(&self, name: &str) {
+        let status = Command::new("kill")
+            .args([
+                name,
+                &self
+                    .child
+                    .id()
+                    .to_string(),
+            ])
+            .status()
+            .expect("Signal to be sent.");
+
+        assert!(status.success());
+    }
+
+    fn wait(&mut self) -> ExitStatus {
+        let started = Instant::now();
+
+        loop {
+            if let Some(status) = self
+                .child
+                .try_wait()
+                .expect("Server status to be readable.")
+            {
+                return status;
+            }
+
+            assert!(started.elapsed() < Duration::from_secs(30), "Server never stopped.");
+            sleep(Duration::from_millis(50));
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self
+            .child
+            .kill();
+        let _ = self
+            .child
+            .wait();
+    }
+}
+
+/// A plain HTTP/1.1 request, `None` once the server is gone.
+fn request(address: SocketAddr, method: &str, path: &str, body: &str) -> Option<(u16, String)> {
+    let mut stream = TcpStream::connect(address).ok()?;
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .ok()?;
+    write!(
+        stream,
+        "{method} {path} HTTP/1.1\r\nHost: {address}\r\nConnection: close\r\nContent-Type: \
+         application/json\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    )
+    .ok()?;
+
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .ok()?;
+
+    let (head, body) = response.split_once("\r\n\r\n")?;
+    let status = head
+        .split(' ')
+        .nth(1)?
+        .parse()
+        .ok()?;
+
+    Some((status, body.to_owned()))
+}
+
+fn place(address: SocketAddr, coord: PixelCoord, color: PixelColor) -> Option<Placement> {
+    let body = serde_json::to_string(&PlacePixelRequest { coord, color }).ok()?;
+    let (status, body) = request(address, "POST", "/api/canvas/pixels", &body)?;
+
+    (status == 200)
+        .then(|| serde_json::from_str::<PlacePixelResponse>(&body).ok())
+        .flatten()
+        .map(|response| response.placement)
+}
+
+fn reopen(dir: &Path) -> CanvasStore {
+    CanvasStore::open(
+        dir,
+        CHUNK_SIZE,
+        CanvasBounds::from_chunks(4, 4, CHUNK_SIZE).expect("Bounds to fit."),
+    )
+    .expect("Canvas to be recovered.")
+}
+
+fn newest_file(dir: PathBuf) -> Option<PathBuf> {
+    read_dir(dir)
+        .ok()?
+        .filter_map(|entry| {
+            entry
+                .ok()
+                .map(|entry| entry.path())
+        })
+        .max()
+}
+
+#[test]
+async fn graceful_shutdown_test() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+    let mut server = Server::start(dir.path());
+    let (mut client, _) = connect_async(format!("ws://{}/api/canvas/live", server.address))
+        .await
+        .expect("Websocket to connect.");
+
+    let placements = (0..20)
+        .map(|x| {
+            place(server.address, PixelCoord::new(x, 3), PixelColor::new(x as u8, 0, 255))
+                .expect("Placement to be accepted.")
+        })
+        .collect::<Vec<_>>();
+
+    server.signal("-TERM");
+
+    // connected clients are told when to come back before being let go.
+    let message = loop {
+        match client
+            .next()
+            .await
+        {
+            Some(Ok(Message::Text(text))) => {
+                break serde_json::from_str::<ServerMessage>(&text).expect("Message to be valid.");
+            },
+            Some(Ok(_)) => {},
+            other => panic!("Unexpected websocket event {other:?}"),
+        }
+    };
+
+    assert_eq!(message, ServerMessage::ShuttingDown { reconnect_after_ms: 5000 });
+
+    let close = loop {
+        match client
+            .next()
+            .await
+        {
+            Some(Ok(Message::Close(frame))) => break frame,
+            Some(Ok(_)) => {},
+            other => panic!("Unexpected websocket event {other:?}"),
+        }
+    };
+
+    assert_eq!(close.map(|frame| frame.code), Some(CloseCode::Restart));
+    assert!(
+        server
+            .wait()
+            .success()
+    );
+
+    // a final snapshot covers every placement.
+    assert!(
+        newest_file(
+            dir.path()
+                .join("snapshots")
+        )
+        .is_some()
+    );
+
+    let store = reopen(dir.path());
+
+    for placement in placements {
+        assert_eq!(store.pixel(placement.coord), Some(placement.color));
+    }
+}
+
+#[test]
+async fn killed_mid_write_test() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+    let mut server = Server::start(dir.path());
+    let address = server.address;
+    let acknowledged = Arc::new(AtomicUsize::new(0));
+
+    // every writer paints its own rows, without painting a pixel twice.
+    let writers = (0..4)
+        .map(|row| {
+            let acknowledged = acknowledged.clone();
+
+            spawn(move || {
+                let mut placements = Vec::new();
+
+                for i in 0..i32::from(CHUNK_SIZE) * 64 {
+                    let coord = PixelCoord::new(i % 64, row * 16 + i / 64);
+                    let color = PixelColor::new(row as u8, (i % 256) as u8, 7);
+
+                    let Some(placement) = place(address, coord, color) else {
+                        break;
+                    };
+
+                    placements.push(placement);
+                    acknowledged.fetch_add(1, Ordering::Relaxed);
+                }
+
+                placements
+            })
+        })
+        .collect::<Vec<_>>();
+
+    while acknowledged.load(Ordering::Relaxed) < 200 {
+        sleep(Duration::from_millis(5));
+    }
+
+    server.signal("-KILL");
+    server.wait();
+
+    let placements = writers
+        .into_iter()
+        .flat_map(|writer| {
+            writer
+                .join()
+                .expect("Writer to finish.")
+        })
+        .collect::<Vec<_>>();
+
+    // half of a record, as left when the process dies while appending it.
+    let segment = newest_file(
+        dir.path()
+            .join("log"),
+    )
+    .expect("A log segment to exist.");
+    OpenOptions::new()
+        .append(true)
+        .open(&segment)
+        .expect("Segment to open.")
+        .write_all(&[27, 0, 0, 0, 1, 2, 3])
+        .expect("Garbage to be written.");
+
+    let store = reopen(dir.path());
+
+    assert!(store.revision() >= placements.len() as u64);
+
+    for placement in &placements {
+        assert_eq!(store.pixel(placement.coord), Some(placement.color));
+    }
+
+    // and the server starts again on what was recovered.
+    drop(store);
+    let server = Server::start(dir.path());
+    let placement = place(server.address, PixelCoord::new(0, 63), PixelColor::new(1, 2, 3))
+        .expect("Placement to be accepted.");
+
+    assert!(placement.seq > placements.len() as u64);
+}
@@ -0,0 +1,386 @@
+use std::io::{Cursor, Result as IoResult};
+use std::time::Duration;
+
+use actix_web::App;
+use actix_web::rt::time::sleep;
+use actix_web::test::{TestRequest, call_service, init_service, read_body, read_body_json};
+use actix_web::web::{Data, scope};
+use backend::canvas::CanvasStore;
+use backend::canvas::checkpoint::SnapshotPolicy;
+use backend::canvas::log::LogRecord;
+use backend::routes::timelapse::{create_timelapse, timelapse_output, timelapse_status};
+use backend::timelapse::jobs::TimelapseJobs;
+use backend::timelapse::{TimelapseError, TimelapseLimits, TimelapsePlan, render};
+use crc32fast::hash as crc32;
+use png::Decoder;
+use shared::api::{
+    ApiErrorBody,
+    ApiErrorCode,
+    TimelapseFormat,
+    TimelapseJob,
+    TimelapseRequest,
+    TimelapseStatus,
+};
+use shared::canvas::{CanvasBounds, PixelColor, PixelCoord, Placement};
+use tempfile::tempdir;
+
+const RED: PixelColor = PixelColor::new(255, 0, 0);
+const BLUE: PixelColor = PixelColor::new(0, 0, 255);
+
+fn bounds() -> CanvasBounds {
+    CanvasBounds::new(0, 0, 4, 4)
+}
+
+/// Placements at 1000, 2000 and 3000ms, the last one repaints the first pixel.
+fn placements() -> Vec<(PixelCoord, PixelColor, u64)> {
+    vec![
+        (PixelCoord::new(0, 0), RED, 1_000),
+        (PixelCoord::new(3, 1), BLUE, 2_000),
+        (PixelCoord::new(0, 0), BLUE, 3_000),
+    ]
+}
+
+fn history() -> Vec<IoResult<LogRecord>> {
+    placements()
+        .into_iter()
+        .enumerate()
+        .map(|(index, (coord, color, placed_at))| {
+            Ok(LogRecord::Placement(Placement {
+                seq: index as u64 + 1,
+                coord,
+                color,
+                placed_at,
+                author: None,
+            }))
+        })
+        .collect()
+}
+
+fn request(format: TimelapseFormat) -> TimelapseRequest {
+    TimelapseRequest {
+        region: None,
+        from: 500,
+        to: 3_000,
+        frame_interval: 1_000,
+        frame_delay: Some(50),
+        scale: Some(2),
+        format,
+    }
+}
+
+/// Every frame of an APNG as RGB bytes.
+fn apng_frames(bytes: &[u8]) -> Vec<Vec<u8>> {
+    let mut reader = Decoder::new(Cursor::new(bytes))
+        .read_info()
+        .expect("APNG header to be valid.");
+    let frames = reader
+        .info()
+        .animation_control()
+        .expect("Image to be animated.")
+        .num_frames;
+
+    (0..frames)
+        .map(|_| {
+            let mut frame = vec![
+                0;
+                reader
+                    .output_buffer_size()
+                    .expect("Frame to fit in memory.")
+            ];
+            reader
+                .next_frame(&mut frame)
+                .expect("Frame to be valid.");
+            frame
+        })
+        .collect()
+}
+
+fn pixel(frame: &[u8], width: usize, x: usize, y: usize) -> PixelColor {
+    let index = (y * width + x) * 3;
+
+    PixelColor::new(frame[index], frame[index + 1], frame[index + 2])
+}
+
+#[test]
+fn render_frames() {
+    let plan =
+        TimelapsePlan::new(&request(TimelapseFormat::Apng), bounds(), &TimelapseLimits::default())
+            .expect("Request to be valid.");
+
+    // 500, 1500, 2500 and the cut off 3000.
+    assert_eq!(plan.frames(), 4);
+
+    let mut output = Vec::new();
+    render(&plan, history(), &mut output).expect("Timelapse to render.");
+
+    let frames = apng_frames(&output);
+    let colors = frames
+        .iter()
+        .map(|frame| {
+            assert_eq!(frame.len(), 8 * 8 * 3);
+            (pixel(frame, 8, 1, 1), pixel(frame, 8, 7, 3))
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        colors,
+        vec![
+            (PixelColor::WHITE, PixelColor::WHITE),
+            (RED, PixelColor::WHITE),
+            (RED, BLUE),
+            (BLUE, BLUE),
+        ]
+    );
+}
+
+#[test]
+fn render_is_deterministic() {
+    for (format, checksum) in
+        [(TimelapseFormat::Gif, 431_076_459), (TimelapseFormat::Apng, 878_301_460)]
+    {
+        let plan = TimelapsePlan::new(&request(format), bounds(), &TimelapseLimits::default())
+            .expect("Request to be valid.");
+        let (mut first, mut second) = (Vec::new(), Vec::new());
+
+        render(&plan, history(), &mut first).expect("Timelapse to render.");
+        render(&plan, history(), &mut second).expect("Timelapse to render.");
+
+        assert_eq!(first, second);
+        assert_eq!(crc32(&first), checksum, "{format:?}");
+    }
+}
+
+#[test]
+fn render_needs_full_history() {
+    let plan =
+        TimelapsePlan::new(&request(TimelapseFormat::Gif), bounds(), &TimelapseLimits::default())
+            .expect("Request to be valid.");
+
+    let result = render(
+        &plan,
+        history()
+            .into_iter()
+            .skip(1),
+        &mut Vec::new(),
+    );
+
+    assert!(matches!(result, Err(TimelapseError::HistoryCompacted { first_seq: 2 })));
+}
+
+#[test]
+fn plan_limits() {
+    let limits = TimelapseLimits {
+        max_area: 64,
+        max_frames: 4,
+        max_scale: 2,
+        ..TimelapseLimits::default()
+    };
+    let plan = |change: fn(&mut TimelapseRequest)| {
+        let mut request = request(TimelapseFormat::Gif);
+        change(&mut request);
+        TimelapsePlan::new(&request, bounds(), &limits)
+    };
+
+    assert!(plan(|_| {}).is_ok());
+    assert!(matches!(
+        plan(|request| request.region = Some(CanvasBounds::new(2, 2, 4, 4))),
+        Err(TimelapseError::InvalidRegion(_))
+    ));
+    assert!(matches!(
+        plan(|request| request.scale = Some(3)),
+        Err(TimelapseError::InvalidScale { scale: 3, max: 2 })
+    ));
+    assert!(matches!(
+        plan(|request| request.to = 100),
+        Err(TimelapseError::InvalidRange { from: 500, to: 100 })
+    ));
+    assert!(matches!(
+        plan(|request| request.frame_interval = 0),
+        Err(TimelapseError::InvalidInterval)
+    ));
+    assert!(matches!(
+        plan(|request| request.frame_interval = 500),
+        Err(TimelapseError::TooManyFrames { frames: 6, max: 4 })
+    ));
+    assert!(matches!(
+        plan(|request| request.region = Some(CanvasBounds::new(0, 0, 4, 3)))
+            .map(|plan| plan.frames()),
+        Ok(4)
+    ));
+    assert!(matches!(
+        plan(|request| {
+            request.region = None;
+            request.scale = Some(2);
+            request.to = 500;
+        })
+        .map(|plan| plan.frames()),
+        Ok(1)
+    ));
+
+    let limits = TimelapseLimits { max_area: 63, ..limits };
+
+    assert!(matches!(
+        TimelapsePlan::new(&request(TimelapseFormat::Gif), bounds(), &limits),
+        Err(TimelapseError::TooLarge { width: 8, height: 8 })
+    ));
+}
+
+#[actix_web::test]
+async fn timelapse_job_test() {
+    let dir = tempdir().expect("Temporary directory to be created.");
+    let canvas = Data::new(CanvasStore::open(dir.path(), 4, bounds()).expect("Store to open."));
+
+    for (coord, color, placed_at) in placements() {
+        canvas
+            .place(coord, color, None, placed_at)
+            .expect("Placement to be accepted.");
+
+        // the earliest history is only kept in the archive from now on.
+        canvas
+            .checkpoint(&SnapshotPolicy { retain: 1, ..SnapshotPolicy::default() })
+            .expect("Checkpoint to succeed.");
+    }
+
+    assert!(
+        dir.path()
+            .join("archive")
+            .read_dir()
+            .expect("Archive to exist.")
+            .next()
+            .is_some()
+    );
+
+    let app = init_service(
+        App::new()
+            .app_data(canvas)
+            .app_data(Data::new(TimelapseJobs::new(TimelapseLimits::default())))
+            .service(
+                scope("/api")
+                    .service(create_timelapse)
+                    .service(timelapse_status)
+                    .service(timelapse_output),
+            ),
+    )
+    .await;
+
+    let req = TestRequest::post()
+        .uri("/api/canvas/timelapses")
+        .set_json(request(TimelapseFormat::Apng))
+        .to_request();
+    let resp = call_service(&app, req).await;
+
+    assert_eq!(
+        resp.status()
+            .as_u16(),
+        202u16
+    );
+
+    let TimelapseJob { id, .. } = read_body_json(resp).await;
+    let mut job = None;
+
+    for _ in 0..200 {
+        let req = TestRequest::get()
+            .uri(&format!("/api/canvas/timelapses/{id}"))
+            .to_request();
+        let polled: TimelapseJob = read_body_json(call_service(&app, req).await).await;
+
+        if !matches!(polled.status, TimelapseStatus::Queued | TimelapseStatus::Rendering) {
+            job = Some(polled);
+            break;
+        }
+
+        sleep(Duration::from_millis(10)).await;
+    }
+
+    let job = job.expect("Timelapse to finish in time.");
+
+    assert_eq!(job.format, TimelapseFormat::Apng);
+
+    let TimelapseStatus::Finished { frames: 4, size } = job.status else {
+        panic!("Timelapse to finish, got {:?}.", job.status);
+    };
+
+    let req = TestRequest::get()
+        .uri(&format!("/api/canvas/timelapses/{}/output", job.id))
+        .to_request();
+    let resp = call_service(&app, req).await;
+
+    assert_eq!(
+        resp.headers()
+            .get("content-type")
+            .and_then(|value| {
+                value
+                    .to_str()
+                    .ok()
+            }),
+        Some("image/apng")
+    );
+
+    let body = read_body(resp).await;
+
+    assert_eq!(body.len() as u64, size);
+
+    // the same as rendering the history directly.
+    let plan =
+        TimelapsePlan::new(&request(TimelapseFormat::Apng), bounds(), &TimelapseLimits::default())
+            .expect("Request to be valid.");
+    let mut expected = Vec::new();
+    render(&plan, history(), &mut expected).expect("Timelapse to render.");
+
+    assert_eq!(body.to_vec(), expected);
+}
+
+#[actix_web::test]
+async fn timelapse_rejected_test() {
+    let app = init_service(
+        App::new()
+            .app_data(Data::new(CanvasStore::new(4, bounds())))
+            .app_data(Data::new(TimelapseJobs::new(TimelapseLimits::default())))
+            .service(
+                scope("/api")
+                    .service(create_timelapse)
+                    .service(timelapse_status)
+                    .service(timelapse_output),
+            ),
+    )
+    .await;
+
+    let mut invalid = request(TimelapseFormat::Gif);
+    invalid.frame_interval = 0;
+
+    for (request, status, code) in [
+        (invalid, 400u16, ApiErrorCode::InvalidRequest),
+        // an in memory canvas has no history to replay.
+        (request(TimelapseFormat::Gif), 404u16, ApiErrorCode::NotFound),
+    ] {
+        let req = TestRequest::post()
+            .uri("/api/canvas/timelapses")
+            .set_json(request)
+            .to_request();
+        let resp = call_service(&app, req).await;
+
+        assert_eq!(
+            resp.status()
+                .as_u16(),
+            status
+        );
+
+        let body: ApiErrorBody = read_body_json(resp).await;
+
+        assert_eq!(body.code, code);
+    }
+
+    for uri in ["/api/canvas/timelapses/7", "/api/canvas/timelapses/7/output"] {
+        let req = TestRequest::get()
+            .uri(uri)
+            .to_request();
+
+        assert_eq!(
+            call_service(&app, req)
+                .await
+                .status()
+                .as_u16(),
+            404u16
+        );
+    }
+}
@@ -1,11 +1,17 @@
 use gloo::storage::{LocalStorage, Storage};
 use log::info;
 use palette::Srgb;
+use shared::api::ApiErrorCode;
+use shared::canvas::PixelCoord;
+use yew::platform::spawn_local;
 use yew::prelude::*;
 
+use crate::components::canvas_view::CanvasView;
 use crate::components::color_picker::ColorPicker;
 use crate::components::hooks::notifications::{use_notifications, ResultReport};
 use crate::components::notifications::hub::NotificationHub;
+use crate::utils::api::canvas::place_pixel;
+use crate::utils::api::ApiClientError;
 use crate::utils::notifications::component::{ActionButton, NotificationComponentKind};
 use crate::utils::notifications::notification::{Notification, NotificationLevel};
 use crate::utils::notifications::store::NotificationStore;
@@ -16,6 +22,8 @@ pub type SharedAppContext = UseStateHandle<AppContext>;
 #[derive(Default, Clone, PartialEq)]
 pub struct AppContext {
     pub notifications: InRef<NotificationStore>,
+    /// The pixel the next placement goes to, once one was picked on the canvas.
+    pub cursor: Option<PixelCoord>,
 }
 
 #[function_component(App)]
@@ -23,9 +31,113 @@ pub fn app() -> Html {
     let app_context = use_state(AppContext::default);
     let notification_hub = use_notifications();
 
+    // UNIX MILLISECONDS UNTIL WHICH PLACING IS ON COOLDOWN
+    let cooldown_until = use_state(|| 0u64);
+    // SEQUENCE NUMBER OF THE LAST OWN PLACEMENT, RELOADS THE CANVAS
+    let canvas_revision = use_state(|| 0u64);
+
+    let on_select = {
+        let app_context = app_context.clone();
+
+        Callback::from(move |coord: PixelCoord| {
+            app_context.set(AppContext {
+                cursor: Some(coord),
+                ..(*app_context).clone()
+            });
+        })
+    };
+
     let on_draw = {
-        Callback::from(|color: Srgb<u8>| {
-            info!("The color is: {}, {}, {}", color.red, color.green, color.blue);
+        let notification_hub = notification_hub.clone();
+        let cursor = app_context.cursor;
+        let cooldown_until = cooldown_until.clone();
+        let canvas_revision = canvas_revision.clone();
+
+        Callback::from(move |color: Srgb<u8>| {
+            let Some(cursor) = cursor else {
+                notification_hub.notify(
+                    Notification::builder()
+                        .title("No Pixel Selected")
+                        .message("Pick a pixel on the canvas first.")
+                        .level(NotificationLevel::Info)
+                        .build(),
+                );
+                return;
+            };
+            let notification_hub = notification_hub.clone();
+            let cooldown_until = cooldown_until.clone();
+            let canvas_revision = canvas_revision.clone();
+
+            spawn_local(async move {
+                let response = match place_pixel(cursor, color.into()).await {
+                    Err(ApiClientError::Rejected(body))
+                        if body.code == ApiErrorCode::RateLimited =>
+                    {
+                        cooldown_until.set(
+                            body.retry_at
+                                .unwrap_or_default(),
+                        );
+                        return;
+                    },
+                    // bans, reserved and out of bounds pixels are ordinary
+                    // answers, not application errors.
+                    Err(ref error @ ApiClientError::Rejected(ref body))
+                        if matches!(
+                            body.code,
+                            ApiErrorCode::InvalidRequest
+                                | ApiErrorCode::OutOfBounds
+                                | ApiErrorCode::Forbidden
+                        ) =>
+                    {
+                        notification_hub.notify(
+                            Notification::builder()
+                                .title("Pixel Not Placed")
+                                .message(
+                                    error
+                                        .server_message()
+                                        .unwrap_or_default(),
+                                )
+                                .level(NotificationLevel::Error)
+                                .build(),
+                        );
+                        return;
+                    },
+                    result => result.or_notify(&notification_hub),
+                };
+
+                if response
+                    .cooldown
+                    .remaining
+                    == 0
+                {
+                    cooldown_until.set(
+                        response
+                            .cooldown
+                            .next_placement_at,
+                    );
+                }
+
+                canvas_revision.set(
+                    response
+                        .placement
+                        .seq,
+                );
+
+                info!(
+                    "Placed pixel #{} at ({}, {}).",
+                    response
+                        .placement
+                        .seq,
+                    response
+                        .placement
+                        .coord
+                        .x,
+                    response
+                        .placement
+                        .coord
+                        .y
+                );
+            });
         })
     };
 
@@ -73,7 +185,22 @@ pub fn app() -> Html {
     html! {
         <ContextProvider<SharedAppContext> context={app_context.clone()}>
             <NotificationHub class="global-notification-hub" app_context={app_context.clone()} />
-            <ColorPicker class="global-color-picker" on_draw={on_draw} />
+            <CanvasView
+                class="global-canvas"
+                selected={app_context.cursor}
+                revision={*canvas_revision}
+                on_select={on_select}
+            />
+            {app_context.cursor.map(|cursor| html! {
+                <div class="global-cursor">
+                    {format!("{}, {}", cursor.x, cursor.y)}
+                </div>
+            })}
+            <ColorPicker
+                class="global-color-picker"
+                on_draw={on_draw}
+                cooldown_until={*cooldown_until}
+            />
         </ContextProvider<SharedAppContext>>
     }
 }
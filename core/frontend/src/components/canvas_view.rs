@@ -0,0 +1,114 @@
+use shared::canvas::{CanvasBounds, PixelCoord};
+use yew::platform::spawn_local;
+use yew::prelude::*;
+
+use crate::components::hooks::notifications::{use_notifications, ResultReport};
+use crate::components::pixel_tooltip::PixelTooltip;
+use crate::utils::api::canvas::EXPORT_PNG;
+use crate::utils::api::funding::funding_status;
+
+/// How many screen pixels a pixel of the canvas covers.
+const PIXEL_SIZE: i32 = 4;
+
+#[derive(Properties, PartialEq)]
+pub struct CanvasViewProps {
+    #[prop_or_default]
+    pub class: String,
+
+    /// The pixel marked as the target of the next placement.
+    #[prop_or_default]
+    pub selected: Option<PixelCoord>,
+
+    /// Changing it loads the canvas again, after a placement for example.
+    #[prop_or_default]
+    pub revision: u64,
+
+    #[prop_or_default]
+    pub on_select: Callback<PixelCoord>,
+}
+
+/// The canvas as the server exports it, clicking a pixel selects it.
+#[function_component(CanvasView)]
+pub fn canvas_view(props: &CanvasViewProps) -> Html {
+    let notification_hub = use_notifications();
+    let bounds = use_state(|| None::<CanvasBounds>);
+
+    // THE CANVAS ONLY GROWS, SO ITS BOUNDS ARE ASKED FOR WITH EVERY REVISION
+    {
+        let bounds = bounds.clone();
+
+        use_effect_with(props.revision, move |_| {
+            spawn_local(async move {
+                let status = funding_status().await;
+
+                bounds.set(Some(
+                    status
+                        .or_notify(&notification_hub)
+                        .bounds,
+                ));
+            });
+        });
+    }
+
+    let Some(bounds) = *bounds else {
+        return Html::default();
+    };
+
+    let on_click = {
+        let on_select = props
+            .on_select
+            .clone();
+
+        Callback::from(move |event: MouseEvent| {
+            on_select.emit(PixelCoord::new(
+                bounds.x + event.offset_x() / PIXEL_SIZE,
+                bounds.y + event.offset_y() / PIXEL_SIZE,
+            ));
+        })
+    };
+
+    let locate = Callback::from(move |(x, y): (i32, i32)| {
+        let coord = PixelCoord::new(
+            bounds.x + x.div_euclid(PIXEL_SIZE),
+            bounds.y + y.div_euclid(PIXEL_SIZE),
+        );
+
+        bounds
+            .contains(coord)
+            .then_some(coord)
+    });
+    let marker = props
+        .selected
+        .map(|coord| {
+            let style = format!(
+                r"
+                    left: {}px;
+                    top: {}px;
+
+                    width: {PIXEL_SIZE}px;
+                    height: {PIXEL_SIZE}px;
+                ",
+                (coord.x - bounds.x) * PIXEL_SIZE,
+                (coord.y - bounds.y) * PIXEL_SIZE,
+            );
+
+            html! { <div class="canvas-view-marker" style={style} /> }
+        });
+    let size = |length: u32| i64::from(length) * i64::from(PIXEL_SIZE);
+
+    html! {
+        <div class={classes!("canvas-view", &props.class)}>
+            <PixelTooltip locate={locate}>
+                <img
+                    class="canvas-view-image"
+                    src={format!("{}?revision={}", EXPORT_PNG.url(&[]), props.revision)}
+                    width={size(bounds.width).to_string()}
+                    height={size(bounds.height).to_string()}
+                    draggable="false"
+                    onclick={on_click}
+                />
+            </PixelTooltip>
+            {marker}
+        </div>
+    }
+}
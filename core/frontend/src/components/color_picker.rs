@@ -2,6 +2,7 @@ use std::rc::Rc;
 
 use gloo::storage::errors::StorageError;
 use gloo::storage::{LocalStorage, Storage};
+use gloo::timers::callback::Interval;
 use palette::rgb::channels::Rgba;
 use palette::Srgb;
 use thiserror::Error;
@@ -17,6 +18,7 @@ use crate::components::hooks::notifications::{
 };
 use crate::utils::color_memory::ColorMemory;
 use crate::utils::colors::{compose, decompose};
+use crate::utils::cooldown::{format_countdown, seconds_left, unix_millis};
 
 const MAX_LAST_COLORS: usize = 5;
 const COLOR_MEMORY_KEY: &str = "color_memory";
@@ -35,6 +37,10 @@ pub struct ColorPickerProps {
 
     #[prop_or_default]
     pub on_draw: Callback<Srgb<u8>>,
+
+    /// Unix milliseconds until which painting is disabled.
+    #[prop_or_default]
+    pub cooldown_until: u64,
 }
 
 // TODO: Add num_traits.
@@ -66,6 +72,31 @@ pub fn color_picker(props: &ColorPickerProps) -> Html {
     });
     let picker_expanded = use_state(|| false);
     let picker_pinned = use_state(|| false);
+    let now = use_state(unix_millis);
+
+    // TICK WHILE THE COOLDOWN RUNS
+    {
+        let now = now.clone();
+
+        use_effect_with(props.cooldown_until, move |cooldown_until| {
+            let cooldown_until = *cooldown_until;
+            now.set(unix_millis());
+
+            let interval = Interval::new(250, move || {
+                let current = unix_millis();
+
+                if current <= cooldown_until + 1000 {
+                    now.set(current);
+                }
+            });
+
+            || {
+                interval.cancel();
+            }
+        });
+    }
+
+    let cooldown_left = seconds_left(props.cooldown_until, *now);
 
     // DECOMPOSE COLOR INTO HUE AND LIGHTNESS
     let (hue, lightness) = decompose(*current_color);
@@ -314,9 +345,14 @@ pub fn color_picker(props: &ColorPickerProps) -> Html {
                 <button
                     class="color-picker-paint color-picker-after"
                     onclick={on_draw_event}
+                    disabled={cooldown_left > 0}
                     style={format!("background-color: #{:08X}", current_color.into_u32::<Rgba>())}
                 >
-                    <Icon icon_id={IconId::FontAwesomeSolidPaintbrush} />
+                    if cooldown_left > 0 {
+                        <span class="color-picker-cooldown">{format_countdown(cooldown_left)}</span>
+                    } else {
+                        <Icon icon_id={IconId::FontAwesomeSolidPaintbrush} />
+                    }
                 </button>
                 <div class="color-picker-colors">
                     <button
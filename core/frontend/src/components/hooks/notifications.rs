@@ -1,3 +1,4 @@
+use std::any::Any;
 use std::fmt::Display;
 use std::rc::Rc;
 
@@ -5,11 +6,12 @@ use log::error;
 use yew::prelude::*;
 
 use crate::app::SharedAppContext;
+use crate::utils::api::ApiClientError;
 use crate::utils::notifications::component::{ActionButton, NotificationComponent};
 use crate::utils::notifications::notification::{Notification, NotificationLevel};
 use crate::utils::types::InRef;
 
-pub trait ResultReport<T, E: Display> {
+pub trait ResultReport<T, E: Display + 'static> {
     fn or_notify(self, handle: &NotificationHandle) -> T;
 }
 
@@ -32,16 +34,28 @@ impl NotificationHandle {
     }
 }
 
-impl<T, E: Display> ResultReport<T, E> for Result<T, E> {
+impl<T, E: Display + 'static> ResultReport<T, E> for Result<T, E> {
     fn or_notify(self, handle: &NotificationHandle) -> T {
         match self {
             Ok(value) => value,
 
             Err(error) => {
+                // Rejections are shown as the server explains them.
+                let server_message = (&error as &dyn Any)
+                    .downcast_ref::<ApiClientError>()
+                    .and_then(ApiClientError::server_message);
+                let (title, message) = match server_message {
+                    Some(message) => ("Request Failed", message),
+                    None => (
+                        "Unexpected Error",
+                        "The application was unable to process your request.".to_owned(),
+                    ),
+                };
+
                 handle.notify(
                     Notification::builder()
-                        .title("Unexpected Error")
-                        .message("The application was unable to process your request.")
+                        .title(title)
+                        .message(message)
                         .level(NotificationLevel::Error)
                         .add_action_button(
                             ActionButton::builder()
@@ -4,5 +4,7 @@
 pub mod hooks;
 pub mod notifications;
 
+pub mod canvas_view;
 pub mod color_picker;
 pub mod common;
+pub mod pixel_tooltip;
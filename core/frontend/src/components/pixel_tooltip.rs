@@ -0,0 +1,198 @@
+use gloo::timers::callback::Timeout;
+use gloo::utils::{document, window};
+use shared::canvas::PixelCoord;
+use web_sys::Element;
+use yew::platform::spawn_local;
+use yew::prelude::*;
+
+use crate::components::common::CommonElementError;
+use crate::components::hooks::notifications::{use_notifications, ResultReport};
+use crate::utils::api::canvas::pixel_history;
+use crate::utils::history::latest_author;
+
+/// How long the pointer has to rest on a pixel before the tooltip shows.
+const HOVER_MILLIS: u32 = 300;
+/// How long a touch has to be held before the tooltip shows.
+const LONG_PRESS_MILLIS: u32 = 500;
+
+#[derive(Properties, PartialEq)]
+pub struct PixelTooltipProps {
+    /// The pixel at a point given in CSS pixels from the top left corner of
+    /// the children, `None` where there is none.
+    pub locate: Callback<(i32, i32), Option<PixelCoord>>,
+
+    #[prop_or_default]
+    pub children: Html,
+
+    #[prop_or_default]
+    pub class: String,
+}
+
+/// Shows who placed the pixel under the pointer while the children are
+/// hovered, or under a finger long pressing them.
+#[function_component(PixelTooltip)]
+pub fn pixel_tooltip(props: &PixelTooltipProps) -> Html {
+    let notification_hub = use_notifications();
+    let trigger_ref = use_node_ref();
+    // THE TEXT AND WHERE IT POINTS AT, RELATIVE TO THE CHILDREN
+    let shown = use_state(|| None::<(String, (i32, i32))>);
+    // THE PIXEL THE TOOLTIP IS WANTED FOR, LATE RESPONSES ARE DROPPED OTHERWISE
+    let pointed = use_mut_ref(|| None::<PixelCoord>);
+    let pending = use_mut_ref(|| None::<Timeout>);
+
+    // WHERE A POINT OF THE VIEWPORT LIES RELATIVE TO THE CHILDREN
+    let relative = {
+        let trigger_ref = trigger_ref.clone();
+
+        move |client_x: i32, client_y: i32| {
+            trigger_ref
+                .cast::<Element>()
+                .map(|element| {
+                    let rect = element.get_bounding_client_rect();
+
+                    // TODO: Add num_traits.
+                    #[allow(clippy::cast_possible_truncation)]
+                    (client_x - rect.left() as i32, client_y - rect.top() as i32)
+                })
+        }
+    };
+
+    let point = {
+        let locate = props
+            .locate
+            .clone();
+        let shown = shown.clone();
+        let pointed = pointed.clone();
+        let pending = pending.clone();
+
+        Callback::from(move |(offset, delay): ((i32, i32), u32)| {
+            let coord = locate.emit(offset);
+
+            if *pointed.borrow() == coord {
+                return;
+            }
+
+            *pointed.borrow_mut() = coord;
+            pending
+                .borrow_mut()
+                .take();
+            shown.set(None);
+
+            let Some(coord) = coord else {
+                return;
+            };
+            let shown = shown.clone();
+            let pointed = pointed.clone();
+
+            *pending.borrow_mut() = Some(Timeout::new(delay, move || {
+                spawn_local(async move {
+                    let label = match pixel_history(coord, None, 1).await {
+                        Ok(history) => latest_author(&history),
+                        Err(error) => error.to_string(),
+                    };
+
+                    if *pointed.borrow() == Some(coord) {
+                        shown.set(Some((label, offset)));
+                    }
+                });
+            }));
+        })
+    };
+
+    let hide = {
+        let shown = shown.clone();
+        let pointed = pointed.clone();
+        let pending = pending.clone();
+
+        Callback::from(move |()| {
+            *pointed.borrow_mut() = None;
+            pending
+                .borrow_mut()
+                .take();
+            shown.set(None);
+        })
+    };
+
+    let on_mouse_move = {
+        let point = point.clone();
+        let relative = relative.clone();
+
+        Callback::from(move |event: MouseEvent| {
+            if let Some(offset) = relative(event.client_x(), event.client_y()) {
+                point.emit((offset, HOVER_MILLIS));
+            }
+        })
+    };
+
+    let on_touch_start = Callback::from(move |event: TouchEvent| {
+        let offset = event
+            .touches()
+            .get(0)
+            .and_then(|touch| relative(touch.client_x(), touch.client_y()));
+
+        if let Some(offset) = offset {
+            point.emit((offset, LONG_PRESS_MILLIS));
+        }
+    });
+
+    let portal = match &*shown {
+        Some((text, (x, y))) => {
+            let style = trigger_ref
+                .cast::<Element>()
+                .map(|element| {
+                    let rect = element.get_bounding_client_rect();
+
+                    format!(
+                        r"
+                            position: absolute;
+
+                            top: {}px;
+                            left: {}px;
+
+                            z-index: 100;
+                        ",
+                        rect.top()
+                            + f64::from(*y)
+                            + window()
+                                .scroll_y()
+                                .unwrap_or_default(),
+                        rect.left()
+                            + f64::from(*x)
+                            + window()
+                                .scroll_x()
+                                .unwrap_or_default()
+                    )
+                });
+            let host = document()
+                .get_element_by_id("tooltip-portal")
+                .ok_or(CommonElementError::MissingElement("#tooltip-portal".into()))
+                .or_notify(&notification_hub);
+
+            create_portal(
+                html! {
+                    <div class="pixel-tooltip" style={style}>
+                        {text}
+                    </div>
+                },
+                host,
+            )
+        },
+        None => Html::default(),
+    };
+
+    html! {
+        <div
+            ref={trigger_ref}
+            class={classes!("pixel-tooltip-trigger", &props.class)}
+            onmousemove={on_mouse_move}
+            onmouseleave={hide.reform(|_| ())}
+            ontouchstart={on_touch_start}
+            ontouchmove={hide.reform(|_| ())}
+            ontouchend={hide.reform(|_| ())}
+            ontouchcancel={hide.reform(|_| ())}
+        >
+            {props.children.clone()}
+            {portal}
+        </div>
+    }
+}
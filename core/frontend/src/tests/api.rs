@@ -0,0 +1,120 @@
+use serde_json::Value;
+use shared::api::ApiErrorCode;
+
+use crate::utils::api::events::REGISTER_FOR_EVENT;
+use crate::utils::api::{ApiClientError, OPERATIONS};
+
+/// The document the backend serves at `/api/openapi.json`, as last reviewed.
+const OPENAPI: &str = include_str!("../../../backend/tests/snapshots/openapi.json");
+
+/// Operations the client doesn't make, the realtime channel is a websocket
+/// and the rest isn't requested by the browser.
+const UNUSED_OPERATIONS: [&str; 7] = [
+    "canvas_live",
+    "payment_webhook",
+    "oidc_callback",
+    "openapi_spec",
+    "health_check",
+    "readiness_check",
+    "metrics_report",
+];
+
+#[test]
+fn test_rejected() {
+    let rejection = ApiClientError::rejected(
+        403,
+        "Forbidden",
+        r#"{"code":"forbidden","message":"This account is banned.","status":403,"request_id":"18c-2a"}"#,
+    );
+
+    let ApiClientError::Rejected(body) = &rejection else {
+        panic!("A rejection to be read.");
+    };
+
+    assert_eq!(body.code, ApiErrorCode::Forbidden);
+    assert_eq!(
+        rejection
+            .server_message()
+            .as_deref(),
+        Some("This account is banned. (request 18c-2a)")
+    );
+
+    // proxies answer without an envelope.
+    let rejection = ApiClientError::rejected(502, "Bad Gateway", "<html></html>");
+
+    let ApiClientError::Rejected(body) = &rejection else {
+        panic!("A rejection to be made up.");
+    };
+
+    assert_eq!(body.code, ApiErrorCode::Unavailable);
+    assert_eq!(body.status, Some(502));
+    assert_eq!(
+        rejection
+            .server_message()
+            .as_deref(),
+        Some("Bad Gateway.")
+    );
+    assert_eq!(
+        ApiClientError::rejected(500, "", "")
+            .server_message()
+            .as_deref(),
+        Some("The server answered with 500.")
+    );
+}
+
+#[test]
+fn test_operation_url() {
+    assert_eq!(REGISTER_FOR_EVENT.url(&[&42]), "/api/events/42/registrations");
+    assert_eq!(
+        crate::utils::api::canvas::PIXEL_HISTORY.url(&[&-3, &7]),
+        "/api/canvas/pixels/-3/7/history"
+    );
+}
+
+#[test]
+fn test_operations_documented() {
+    let spec: Value = serde_json::from_str(OPENAPI).expect("OpenAPI document to be valid.");
+    let paths = spec["paths"]
+        .as_object()
+        .expect("OpenAPI document to list paths.");
+
+    for operation in OPERATIONS {
+        let method = operation
+            .method
+            .as_str()
+            .to_lowercase();
+
+        assert!(
+            paths
+                .get(operation.path)
+                .is_some_and(|path| path[&method].is_object()),
+            "{method} {} isn't documented.",
+            operation.path
+        );
+    }
+
+    // and every documented operation is made.
+    for (path, operations) in paths {
+        for (method, operation) in operations
+            .as_object()
+            .into_iter()
+            .flatten()
+        {
+            let id = operation["operationId"]
+                .as_str()
+                .unwrap_or_default();
+
+            assert!(
+                UNUSED_OPERATIONS.contains(&id)
+                    || OPERATIONS
+                        .iter()
+                        .any(|made| made.path == path
+                            && made
+                                .method
+                                .as_str()
+                                .eq_ignore_ascii_case(method)),
+                "{method} {path} ({id}) has no client."
+            );
+        }
+    }
+}
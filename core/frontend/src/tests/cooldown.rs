@@ -0,0 +1,24 @@
+use crate::utils::cooldown::{format_countdown, seconds_left, unix_millis};
+
+#[test]
+fn test_seconds_left() {
+    assert_eq!(seconds_left(10_000, 10_000), 0);
+    assert_eq!(seconds_left(10_000, 12_000), 0);
+    assert_eq!(seconds_left(10_000, 9_999), 1);
+    assert_eq!(seconds_left(10_000, 8_000), 2);
+    assert_eq!(seconds_left(10_000, 7_999), 3);
+}
+
+#[test]
+fn test_format_countdown() {
+    assert_eq!(format_countdown(0), "0");
+    assert_eq!(format_countdown(59), "59");
+    assert_eq!(format_countdown(60), "1:00");
+    assert_eq!(format_countdown(242), "4:02");
+}
+
+#[test]
+fn test_unix_millis() {
+    // any time after this test was written.
+    assert!(unix_millis() > 1_750_000_000_000);
+}
@@ -0,0 +1,29 @@
+use shared::api::{AccountInfo, PixelHistory, PixelHistoryEntry};
+use shared::canvas::{PixelColor, PixelCoord};
+
+use crate::utils::history::latest_author;
+
+fn history(authors: Vec<Option<&str>>) -> PixelHistory {
+    PixelHistory {
+        coord: PixelCoord::new(0, 0),
+        placements: authors
+            .into_iter()
+            .zip(1..)
+            .map(|(author, seq)| PixelHistoryEntry {
+                seq,
+                author: author
+                    .map(|username| AccountInfo { id: seq, username: username.to_owned() }),
+                color: PixelColor::WHITE,
+                placed_at: seq,
+            })
+            .collect(),
+        next_before: None,
+    }
+}
+
+#[test]
+fn test_latest_author() {
+    assert_eq!(latest_author(&history(vec![Some("painter"), None])), "Placed by painter");
+    assert_eq!(latest_author(&history(vec![None, Some("painter")])), "Placed anonymously");
+    assert_eq!(latest_author(&history(vec![])), "Nobody painted here yet");
+}
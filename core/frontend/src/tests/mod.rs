@@ -1,2 +1,6 @@
+#[cfg(not(feature = "coverage"))]
+pub mod api;
 pub mod colors;
+pub mod cooldown;
+pub mod history;
 pub mod notifications;
@@ -0,0 +1,57 @@
+use gloo::net::http::Method;
+use shared::api::{AccountInfo, Credentials};
+
+use crate::utils::api::{read_empty, read_json, ApiClientError, Operation};
+
+pub const REGISTER: Operation = Operation::new(Method::POST, "/api/auth/register");
+pub const LOGIN: Operation = Operation::new(Method::POST, "/api/auth/login");
+pub const LOGOUT: Operation = Operation::new(Method::POST, "/api/auth/logout");
+pub const ME: Operation = Operation::new(Method::GET, "/api/auth/me");
+pub const OIDC_LOGIN: Operation = Operation::new(Method::GET, "/api/auth/oidc/login");
+
+/// Creates an account, the browser keeps the session cookie it's logged in
+/// with.
+pub async fn register(credentials: &Credentials) -> Result<AccountInfo, ApiClientError> {
+    let response = REGISTER
+        .request(&[])
+        .json(credentials)?
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+pub async fn login(credentials: &Credentials) -> Result<AccountInfo, ApiClientError> {
+    let response = LOGIN
+        .request(&[])
+        .json(credentials)?
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+pub async fn logout() -> Result<(), ApiClientError> {
+    let response = LOGOUT
+        .request(&[])
+        .send()
+        .await?;
+
+    read_empty(response).await
+}
+
+/// The account of the session, rejected as unauthorized without one.
+pub async fn me() -> Result<AccountInfo, ApiClientError> {
+    let response = ME
+        .request(&[])
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+/// Where to send the browser to log in through the identity provider, it
+/// comes back logged in.
+pub fn oidc_login_url() -> String {
+    OIDC_LOGIN.url(&[])
+}
@@ -0,0 +1,90 @@
+use gloo::net::http::Method;
+use shared::api::{PixelHistory, PlacePixelRequest, PlacePixelResponse};
+use shared::canvas::{CanvasBounds, ChunkCoord, PixelColor, PixelCoord};
+use shared::encoding::ChunkPayload;
+
+use crate::utils::api::{read_bytes, read_json, ApiClientError, Operation};
+
+pub const PLACE_PIXEL: Operation = Operation::new(Method::POST, "/api/canvas/pixels");
+pub const PIXEL_HISTORY: Operation =
+    Operation::new(Method::GET, "/api/canvas/pixels/{x}/{y}/history");
+pub const CHUNK: Operation = Operation::new(Method::GET, "/api/canvas/chunks/{cx}/{cy}");
+pub const EXPORT_PNG: Operation = Operation::new(Method::GET, "/api/canvas/export.png");
+
+pub async fn place_pixel(
+    coord: PixelCoord,
+    color: PixelColor,
+) -> Result<PlacePixelResponse, ApiClientError> {
+    let response = PLACE_PIXEL
+        .request(&[])
+        .json(&PlacePixelRequest { coord, color })?
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+/// One page of the placements on `coord`, newest first.
+pub async fn pixel_history(
+    coord: PixelCoord,
+    before: Option<u64>,
+    limit: usize,
+) -> Result<PixelHistory, ApiClientError> {
+    let mut query = vec![("limit", limit.to_string())];
+
+    if let Some(before) = before {
+        query.push(("before", before.to_string()));
+    }
+
+    let response = PIXEL_HISTORY
+        .request(&[&coord.x, &coord.y])
+        .query(query)
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+/// A chunk, only with the cells changed after version `since` when given.
+pub async fn chunk(coord: ChunkCoord, since: Option<u64>) -> Result<ChunkPayload, ApiClientError> {
+    let query = since
+        .map(|since| ("since", since.to_string()))
+        .into_iter();
+    let response = CHUNK
+        .request(&[&coord.x, &coord.y])
+        .query(query)
+        .send()
+        .await?;
+
+    Ok(ChunkPayload::decode(&read_bytes(response).await?)?)
+}
+
+/// The canvas as a PNG, or `region` of it, with every pixel `scale` times
+/// as large.
+pub async fn export_png(
+    region: Option<CanvasBounds>,
+    scale: Option<u32>,
+) -> Result<Vec<u8>, ApiClientError> {
+    let mut query = Vec::new();
+
+    if let Some(CanvasBounds { x, y, width, height }) = region {
+        query.extend([
+            ("x", x.to_string()),
+            ("y", y.to_string()),
+            ("w", width.to_string()),
+            ("h", height.to_string()),
+        ]);
+    }
+
+    if let Some(scale) = scale {
+        query.push(("scale", scale.to_string()));
+    }
+
+    let response = EXPORT_PNG
+        .request(&[])
+        .query(query)
+        .send()
+        .await?;
+
+    read_bytes(response).await
+}
@@ -0,0 +1,155 @@
+use gloo::net::http::Method;
+use shared::api::{
+    EventInfo,
+    EventRegistration,
+    EventRequest,
+    EventResults,
+    SubmissionInfo,
+    SubmissionRequest,
+    VoteRequest,
+};
+use shared::canvas::CanvasBounds;
+
+use crate::utils::api::payments::IDEMPOTENCY_KEY_HEADER;
+use crate::utils::api::{read_empty, read_json, ApiClientError, Operation};
+
+pub const LIST_EVENTS: Operation = Operation::new(Method::GET, "/api/events");
+pub const CREATE_EVENT: Operation = Operation::new(Method::POST, "/api/events");
+pub const EVENT_INFO: Operation = Operation::new(Method::GET, "/api/events/{id}");
+pub const PUBLISH_EVENT: Operation = Operation::new(Method::POST, "/api/events/{id}/publish");
+pub const REGISTER_FOR_EVENT: Operation =
+    Operation::new(Method::POST, "/api/events/{id}/registrations");
+pub const EVENT_SUBMISSIONS: Operation =
+    Operation::new(Method::GET, "/api/events/{id}/submissions");
+pub const SUBMIT_TO_EVENT: Operation = Operation::new(Method::POST, "/api/events/{id}/submissions");
+pub const VOTE_IN_EVENT: Operation = Operation::new(Method::POST, "/api/events/{id}/votes");
+pub const JURY_VOTE_IN_EVENT: Operation =
+    Operation::new(Method::POST, "/api/events/{id}/jury-votes");
+pub const OVERRIDE_EVENT_WINNER: Operation =
+    Operation::new(Method::POST, "/api/events/{id}/winner");
+pub const EVENT_RESULTS: Operation = Operation::new(Method::GET, "/api/events/{id}/results");
+pub const CANCEL_EVENT: Operation = Operation::new(Method::POST, "/api/events/{id}/cancel");
+
+/// Every published event, admins also get the drafts.
+pub async fn list_events() -> Result<Vec<EventInfo>, ApiClientError> {
+    let response = LIST_EVENTS
+        .request(&[])
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+pub async fn create_event(request: &EventRequest) -> Result<EventInfo, ApiClientError> {
+    let response = CREATE_EVENT
+        .request(&[])
+        .json(request)?
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+pub async fn event_info(id: u64) -> Result<EventInfo, ApiClientError> {
+    let response = EVENT_INFO
+        .request(&[&id])
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+pub async fn publish_event(id: u64) -> Result<EventInfo, ApiClientError> {
+    let response = PUBLISH_EVENT
+        .request(&[&id])
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+/// Joins an event, paid ones answer with the checkout of the entry fee.
+///
+/// Retries with the same `idempotency_key` answer with the same checkout.
+pub async fn register_for_event(
+    id: u64,
+    idempotency_key: &str,
+) -> Result<EventRegistration, ApiClientError> {
+    let response = REGISTER_FOR_EVENT
+        .request(&[&id])
+        .header(IDEMPOTENCY_KEY_HEADER, idempotency_key)
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+pub async fn event_submissions(id: u64) -> Result<Vec<SubmissionInfo>, ApiClientError> {
+    let response = EVENT_SUBMISSIONS
+        .request(&[&id])
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+pub async fn submit_to_event(
+    id: u64,
+    region: CanvasBounds,
+) -> Result<SubmissionInfo, ApiClientError> {
+    let response = SUBMIT_TO_EVENT
+        .request(&[&id])
+        .json(&SubmissionRequest { region })?
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+pub async fn vote_in_event(id: u64, artist: u64) -> Result<(), ApiClientError> {
+    let response = VOTE_IN_EVENT
+        .request(&[&id])
+        .json(&VoteRequest { artist })?
+        .send()
+        .await?;
+
+    read_empty(response).await
+}
+
+pub async fn jury_vote_in_event(id: u64, artist: u64) -> Result<(), ApiClientError> {
+    let response = JURY_VOTE_IN_EVENT
+        .request(&[&id])
+        .json(&VoteRequest { artist })?
+        .send()
+        .await?;
+
+    read_empty(response).await
+}
+
+pub async fn override_event_winner(id: u64, artist: u64) -> Result<(), ApiClientError> {
+    let response = OVERRIDE_EVENT_WINNER
+        .request(&[&id])
+        .json(&VoteRequest { artist })?
+        .send()
+        .await?;
+
+    read_empty(response).await
+}
+
+pub async fn event_results(id: u64) -> Result<EventResults, ApiClientError> {
+    let response = EVENT_RESULTS
+        .request(&[&id])
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+pub async fn cancel_event(id: u64) -> Result<EventInfo, ApiClientError> {
+    let response = CANCEL_EVENT
+        .request(&[&id])
+        .send()
+        .await?;
+
+    read_json(response).await
+}
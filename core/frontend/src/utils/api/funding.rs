@@ -0,0 +1,15 @@
+use gloo::net::http::Method;
+use shared::api::FundingStatus;
+
+use crate::utils::api::{read_json, ApiClientError, Operation};
+
+pub const FUNDING_STATUS: Operation = Operation::new(Method::GET, "/api/funding");
+
+pub async fn funding_status() -> Result<FundingStatus, ApiClientError> {
+    let response = FUNDING_STATUS
+        .request(&[])
+        .send()
+        .await?;
+
+    read_json(response).await
+}
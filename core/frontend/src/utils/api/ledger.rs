@@ -0,0 +1,15 @@
+use gloo::net::http::Method;
+use shared::api::LedgerReport;
+
+use crate::utils::api::{read_json, ApiClientError, Operation};
+
+pub const LEDGER_REPORT: Operation = Operation::new(Method::GET, "/api/ledger");
+
+pub async fn ledger_report() -> Result<LedgerReport, ApiClientError> {
+    let response = LEDGER_REPORT
+        .request(&[])
+        .send()
+        .await?;
+
+    read_json(response).await
+}
@@ -0,0 +1,196 @@
+#![cfg(not(feature = "coverage"))] // because it's tied to wasm32.
+
+use std::fmt::Display;
+
+use gloo::net::http::{Method, RequestBuilder, Response};
+use gloo::net::Error as NetError;
+use serde::de::DeserializeOwned;
+use shared::api::{ApiErrorBody, ApiErrorCode};
+use shared::encoding::ChunkDecodeError;
+use thiserror::Error;
+
+pub mod accounts;
+pub mod canvas;
+pub mod events;
+pub mod funding;
+pub mod ledger;
+pub mod moderation;
+pub mod payments;
+pub mod payouts;
+pub mod timelapse;
+
+/// Every operation listed in `/api/openapi.json` that the client makes.
+///
+/// The realtime channel, the payment webhook, the probes and the document
+/// itself aren't requested through the client.
+pub const OPERATIONS: &[&Operation] = &[
+    &accounts::REGISTER,
+    &accounts::LOGIN,
+    &accounts::LOGOUT,
+    &accounts::ME,
+    &accounts::OIDC_LOGIN,
+    &canvas::PLACE_PIXEL,
+    &canvas::PIXEL_HISTORY,
+    &canvas::CHUNK,
+    &canvas::EXPORT_PNG,
+    &timelapse::CREATE_TIMELAPSE,
+    &timelapse::TIMELAPSE_STATUS,
+    &timelapse::TIMELAPSE_OUTPUT,
+    &funding::FUNDING_STATUS,
+    &ledger::LEDGER_REPORT,
+    &events::LIST_EVENTS,
+    &events::CREATE_EVENT,
+    &events::EVENT_INFO,
+    &events::PUBLISH_EVENT,
+    &events::REGISTER_FOR_EVENT,
+    &events::EVENT_SUBMISSIONS,
+    &events::SUBMIT_TO_EVENT,
+    &events::VOTE_IN_EVENT,
+    &events::JURY_VOTE_IN_EVENT,
+    &events::OVERRIDE_EVENT_WINNER,
+    &events::EVENT_RESULTS,
+    &events::CANCEL_EVENT,
+    &payouts::LIST_PAYOUTS,
+    &payouts::PAYOUT_AUDIT,
+    &payouts::APPROVE_PAYOUT,
+    &payouts::REJECT_PAYOUT,
+    &moderation::ROLLBACK_PLACEMENTS,
+    &moderation::LIST_BANS,
+    &moderation::BAN_ACCOUNT,
+    &moderation::UNBAN_ACCOUNT,
+    &moderation::REPORT_PIXEL,
+    &moderation::LIST_REPORTS,
+    &moderation::REVIEW_REPORT,
+    &payments::CREATE_CHECKOUT,
+    &payments::PAYMENT_STATUS,
+];
+
+/// An operation of the API as `/api/openapi.json` lists it.
+pub struct Operation {
+    pub method: Method,
+    /// With its parameters in braces, like `/api/events/{id}`.
+    pub path: &'static str,
+}
+
+impl Operation {
+    pub const fn new(method: Method, path: &'static str) -> Self {
+        Self { method, path }
+    }
+
+    /// The path with its parameters replaced by `params`, in order.
+    pub fn url(&self, params: &[&dyn Display]) -> String {
+        let mut params = params.iter();
+        let mut url = String::new();
+        let mut rest = self.path;
+
+        while let Some((before, after)) = rest.split_once('{') {
+            url.push_str(before);
+
+            if let Some(param) = params.next() {
+                url.push_str(&param.to_string());
+            }
+
+            rest = after
+                .split_once('}')
+                .map_or("", |(_, after)| after);
+        }
+
+        url.push_str(rest);
+        url
+    }
+
+    pub fn request(&self, params: &[&dyn Display]) -> RequestBuilder {
+        RequestBuilder::new(&self.url(params)).method(
+            self.method
+                .clone(),
+        )
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ApiClientError {
+    #[error("Couldn't communicate with the server.")]
+    Network(#[from] NetError),
+
+    #[error("The server rejected the request: {0}")]
+    Rejected(ApiErrorBody),
+
+    #[error("The server sent a chunk that couldn't be read: {0}")]
+    Decode(#[from] ChunkDecodeError),
+}
+
+impl ApiClientError {
+    /// The rejection a failed response stands for, read from its body or,
+    /// when that isn't an [`ApiErrorBody`] like when a proxy answered, made
+    /// up from its status.
+    pub fn rejected(status: u16, status_text: &str, body: &str) -> Self {
+        Self::Rejected(serde_json::from_str(body).unwrap_or_else(|_| ApiErrorBody {
+            status: Some(status),
+            ..ApiErrorBody::new(
+                ApiErrorCode::from_status(status),
+                if status_text.is_empty() {
+                    format!("The server answered with {status}.")
+                } else {
+                    format!("{status_text}.")
+                },
+            )
+        }))
+    }
+
+    /// What the server said went wrong, along with the request id to quote
+    /// when reporting it, `None` if the server was never reached.
+    pub fn server_message(&self) -> Option<String> {
+        let Self::Rejected(body) = self else {
+            return None;
+        };
+
+        Some(match &body.request_id {
+            Some(request_id) => format!("{} (request {request_id})", body.message),
+            None => body
+                .message
+                .clone(),
+        })
+    }
+}
+
+/// The rejection a failed `response` stands for.
+async fn rejection(response: Response) -> ApiClientError {
+    match response
+        .text()
+        .await
+    {
+        Ok(body) => ApiClientError::rejected(response.status(), &response.status_text(), &body),
+        Err(error) => error.into(),
+    }
+}
+
+/// The JSON body of `response` when it succeeded, its rejection otherwise.
+pub async fn read_json<T: DeserializeOwned>(response: Response) -> Result<T, ApiClientError> {
+    if response.ok() {
+        return Ok(response
+            .json()
+            .await?);
+    }
+
+    Err(rejection(response).await)
+}
+
+/// The raw body of `response` when it succeeded, its rejection otherwise.
+pub async fn read_bytes(response: Response) -> Result<Vec<u8>, ApiClientError> {
+    if response.ok() {
+        return Ok(response
+            .binary()
+            .await?);
+    }
+
+    Err(rejection(response).await)
+}
+
+/// Nothing when `response` succeeded, its rejection otherwise.
+pub async fn read_empty(response: Response) -> Result<(), ApiClientError> {
+    if response.ok() {
+        return Ok(());
+    }
+
+    Err(rejection(response).await)
+}
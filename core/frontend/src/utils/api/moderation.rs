@@ -0,0 +1,103 @@
+use gloo::net::http::Method;
+use shared::api::{
+    BanInfo,
+    BanRequest,
+    ReportInfo,
+    ReportRequest,
+    ReportReview,
+    ReportStatus,
+    RollbackInfo,
+    RollbackRequest,
+};
+
+use crate::utils::api::{read_empty, read_json, ApiClientError, Operation};
+
+pub const ROLLBACK_PLACEMENTS: Operation =
+    Operation::new(Method::POST, "/api/moderation/rollbacks");
+pub const LIST_BANS: Operation = Operation::new(Method::GET, "/api/moderation/bans");
+pub const BAN_ACCOUNT: Operation = Operation::new(Method::POST, "/api/moderation/bans/{account}");
+pub const UNBAN_ACCOUNT: Operation =
+    Operation::new(Method::DELETE, "/api/moderation/bans/{account}");
+pub const REPORT_PIXEL: Operation = Operation::new(Method::POST, "/api/reports");
+pub const LIST_REPORTS: Operation = Operation::new(Method::GET, "/api/moderation/reports");
+pub const REVIEW_REPORT: Operation =
+    Operation::new(Method::POST, "/api/moderation/reports/{id}/review");
+
+pub async fn rollback_placements(
+    request: &RollbackRequest,
+) -> Result<RollbackInfo, ApiClientError> {
+    let response = ROLLBACK_PLACEMENTS
+        .request(&[])
+        .json(request)?
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+pub async fn list_bans() -> Result<Vec<BanInfo>, ApiClientError> {
+    let response = LIST_BANS
+        .request(&[])
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+pub async fn ban_account(account: u64, request: &BanRequest) -> Result<BanInfo, ApiClientError> {
+    let response = BAN_ACCOUNT
+        .request(&[&account])
+        .json(request)?
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+pub async fn unban_account(account: u64) -> Result<(), ApiClientError> {
+    let response = UNBAN_ACCOUNT
+        .request(&[&account])
+        .send()
+        .await?;
+
+    read_empty(response).await
+}
+
+pub async fn report_pixel(request: &ReportRequest) -> Result<ReportInfo, ApiClientError> {
+    let response = REPORT_PIXEL
+        .request(&[])
+        .json(request)?
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+/// The report queue, only the reports with `status` when given.
+pub async fn list_reports(status: Option<ReportStatus>) -> Result<Vec<ReportInfo>, ApiClientError> {
+    let query = status
+        .and_then(|status| {
+            serde_json::to_value(status)
+                .ok()?
+                .as_str()
+                .map(|status| ("status", status.to_owned()))
+        })
+        .into_iter();
+    let response = LIST_REPORTS
+        .request(&[])
+        .query(query)
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+pub async fn review_report(id: u64, status: ReportStatus) -> Result<ReportInfo, ApiClientError> {
+    let response = REVIEW_REPORT
+        .request(&[&id])
+        .json(&ReportReview { status })?
+        .send()
+        .await?;
+
+    read_json(response).await
+}
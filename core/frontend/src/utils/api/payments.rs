@@ -0,0 +1,36 @@
+use gloo::net::http::Method;
+use shared::api::{CheckoutRequest, PaymentInfo};
+
+use crate::utils::api::{read_json, ApiClientError, Operation};
+
+/// Header that lets a checkout be retried without paying twice.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+pub const CREATE_CHECKOUT: Operation = Operation::new(Method::POST, "/api/payments");
+pub const PAYMENT_STATUS: Operation = Operation::new(Method::GET, "/api/payments/{id}");
+
+/// Starts a payment, the payer finishes it at its checkout URL.
+///
+/// Retries with the same `idempotency_key` answer with the same payment.
+pub async fn create_checkout(
+    request: &CheckoutRequest,
+    idempotency_key: &str,
+) -> Result<PaymentInfo, ApiClientError> {
+    let response = CREATE_CHECKOUT
+        .request(&[])
+        .header(IDEMPOTENCY_KEY_HEADER, idempotency_key)
+        .json(request)?
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+pub async fn payment_status(id: u64) -> Result<PaymentInfo, ApiClientError> {
+    let response = PAYMENT_STATUS
+        .request(&[&id])
+        .send()
+        .await?;
+
+    read_json(response).await
+}
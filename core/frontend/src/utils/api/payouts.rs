@@ -0,0 +1,45 @@
+use gloo::net::http::Method;
+use shared::api::{PayoutAuditRecord, PayoutInfo};
+
+use crate::utils::api::{read_json, ApiClientError, Operation};
+
+pub const LIST_PAYOUTS: Operation = Operation::new(Method::GET, "/api/payouts");
+pub const PAYOUT_AUDIT: Operation = Operation::new(Method::GET, "/api/payouts/audit");
+pub const APPROVE_PAYOUT: Operation = Operation::new(Method::POST, "/api/payouts/{id}/approve");
+pub const REJECT_PAYOUT: Operation = Operation::new(Method::POST, "/api/payouts/{id}/reject");
+
+pub async fn list_payouts() -> Result<Vec<PayoutInfo>, ApiClientError> {
+    let response = LIST_PAYOUTS
+        .request(&[])
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+pub async fn payout_audit() -> Result<Vec<PayoutAuditRecord>, ApiClientError> {
+    let response = PAYOUT_AUDIT
+        .request(&[])
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+pub async fn approve_payout(id: u64) -> Result<PayoutInfo, ApiClientError> {
+    let response = APPROVE_PAYOUT
+        .request(&[&id])
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+pub async fn reject_payout(id: u64) -> Result<PayoutInfo, ApiClientError> {
+    let response = REJECT_PAYOUT
+        .request(&[&id])
+        .send()
+        .await?;
+
+    read_json(response).await
+}
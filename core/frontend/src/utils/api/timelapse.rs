@@ -0,0 +1,39 @@
+use gloo::net::http::Method;
+use shared::api::{TimelapseJob, TimelapseRequest};
+
+use crate::utils::api::{read_bytes, read_json, ApiClientError, Operation};
+
+pub const CREATE_TIMELAPSE: Operation = Operation::new(Method::POST, "/api/canvas/timelapses");
+pub const TIMELAPSE_STATUS: Operation = Operation::new(Method::GET, "/api/canvas/timelapses/{id}");
+pub const TIMELAPSE_OUTPUT: Operation =
+    Operation::new(Method::GET, "/api/canvas/timelapses/{id}/output");
+
+/// Queues a render, its status tells when the output can be downloaded.
+pub async fn create_timelapse(request: &TimelapseRequest) -> Result<TimelapseJob, ApiClientError> {
+    let response = CREATE_TIMELAPSE
+        .request(&[])
+        .json(request)?
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+pub async fn timelapse_status(id: u64) -> Result<TimelapseJob, ApiClientError> {
+    let response = TIMELAPSE_STATUS
+        .request(&[&id])
+        .send()
+        .await?;
+
+    read_json(response).await
+}
+
+/// The rendered image, in the format the job was created with.
+pub async fn timelapse_output(id: u64) -> Result<Vec<u8>, ApiClientError> {
+    let response = TIMELAPSE_OUTPUT
+        .request(&[&id])
+        .send()
+        .await?;
+
+    read_bytes(response).await
+}
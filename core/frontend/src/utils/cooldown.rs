@@ -0,0 +1,26 @@
+use instant::SystemTime;
+
+/// Current time in milliseconds since the unix epoch, the unit the backend
+/// reports cooldowns in.
+pub fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX))
+}
+
+/// Whole seconds left until `until`, rounded up so the countdown only
+/// reaches zero once placing is allowed.
+pub fn seconds_left(until: u64, now: u64) -> u64 {
+    until
+        .saturating_sub(now)
+        .div_ceil(1000)
+}
+
+/// Formats a countdown as `42` below a minute and `4:02` above.
+pub fn format_countdown(seconds: u64) -> String {
+    if seconds < 60 {
+        seconds.to_string()
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
@@ -0,0 +1,15 @@
+use shared::api::PixelHistory;
+
+/// Tooltip text naming whoever placed the newest pixel of `history`.
+pub fn latest_author(history: &PixelHistory) -> String {
+    match history
+        .placements
+        .first()
+    {
+        Some(entry) => match &entry.author {
+            Some(author) => format!("Placed by {}", author.username),
+            None => "Placed anonymously".to_owned(),
+        },
+        None => "Nobody painted here yet".to_owned(),
+    }
+}
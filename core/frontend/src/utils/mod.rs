@@ -1,6 +1,9 @@
 #![allow(dead_code)]
 
+pub mod api;
 pub mod color_memory;
 pub mod colors;
+pub mod cooldown;
+pub mod history;
 pub mod notifications;
 pub mod types;
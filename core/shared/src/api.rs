@@ -0,0 +1,622 @@
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+#[cfg(feature = "openapi")]
+use utoipa::ToSchema;
+
+use crate::canvas::{CanvasBounds, PixelColor, PixelCoord, Placement};
+
+/// Body of `POST /api/canvas/pixels`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct PlacePixelRequest {
+    pub coord: PixelCoord,
+    pub color: PixelColor,
+}
+
+/// When the caller may place again.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct Cooldown {
+    /// Placements that can be made right away.
+    pub remaining: u32,
+    /// Unix milliseconds at which the next placement is allowed, the current
+    /// time while `remaining` isn't zero.
+    pub next_placement_at: u64,
+}
+
+/// Response of `POST /api/canvas/pixels`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct PlacePixelResponse {
+    pub placement: Placement,
+    pub cooldown: Cooldown,
+}
+
+/// One placement on a pixel, as listed by
+/// `GET /api/canvas/pixels/{x}/{y}/history`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct PixelHistoryEntry {
+    pub seq: u64,
+    /// `None` for anonymous placements.
+    pub author: Option<AccountInfo>,
+    pub color: PixelColor,
+    /// Unix milliseconds.
+    pub placed_at: u64,
+}
+
+/// Response of `GET /api/canvas/pixels/{x}/{y}/history`, newest placements
+/// first.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct PixelHistory {
+    pub coord: PixelCoord,
+    pub placements: Vec<PixelHistoryEntry>,
+    /// Pass as `before` to get the next page, `None` on the last one.
+    pub next_before: Option<u64>,
+}
+
+/// Body of `POST /api/auth/register` and `POST /api/auth/login`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl Debug for Credentials {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The account behind a session.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct AccountInfo {
+    pub id: u64,
+    pub username: String,
+}
+
+/// Response of `GET /api/funding`, amounts are in euro cents.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct FundingStatus {
+    pub total_cents: u64,
+    pub contributions: u64,
+    /// Growth tiers unlocked so far.
+    pub tier: u32,
+    /// Total at which the next tier unlocks, `None` once the canvas is fully
+    /// grown.
+    pub next_tier_cents: Option<u64>,
+    pub bounds: CanvasBounds,
+}
+
+/// What a payment is for.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PaymentPurpose {
+    /// Counts towards growing the canvas.
+    Contribution,
+    /// The entry fee of an event, goes into its prize pool.
+    EventEntry { event: u64 },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentStatus {
+    /// Waiting for the payer to finish the checkout.
+    Pending,
+    Succeeded,
+    Failed,
+    /// Succeeded at first and was paid back later.
+    Refunded,
+}
+
+/// Body of `POST /api/payments`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct CheckoutRequest {
+    pub amount_cents: u64,
+    pub purpose: PaymentPurpose,
+}
+
+/// A payment as reported by `POST /api/payments` and
+/// `GET /api/payments/{id}`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct PaymentInfo {
+    pub id: u64,
+    pub amount_cents: u64,
+    pub purpose: PaymentPurpose,
+    pub status: PaymentStatus,
+    /// Where to send the payer to finish the payment.
+    pub checkout_url: String,
+}
+
+/// An account of the double entry ledger.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LedgerAccount {
+    /// Money held at the payment provider, the only account on the debit side.
+    Cash,
+    Platform,
+    /// Contributions towards growing the canvas.
+    GrowthFund,
+    PrizePool {
+        event: u64,
+    },
+    /// Owed to a user, like an artist's share of a prize pool.
+    Payable {
+        user: u64,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct LedgerBalance {
+    pub account: LedgerAccount,
+    pub balance_cents: i64,
+}
+
+/// Response of `GET /api/ledger`.
+///
+/// Balances are positive on the normal side of their account, per user
+/// balances are only reported as their sum.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct LedgerReport {
+    pub balances: Vec<LedgerBalance>,
+    pub payables_cents: i64,
+    pub transactions: u64,
+    /// Whether every invariant of the ledger holds.
+    pub balanced: bool,
+}
+
+/// Where an event is in its lifecycle, events only ever move forward.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum EventState {
+    /// Only visible to admins while it's being prepared.
+    Draft,
+    /// Published, artists can register.
+    Registration,
+    /// Only participants may draw in the region.
+    Running,
+    /// Nobody may draw in the region while the entries are judged.
+    Judging,
+    Finished,
+    /// Called off by an admin, every entry fee is refunded.
+    Cancelled,
+}
+
+/// How submissions with as many votes are ranked, applied in order until
+/// one differs.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum TieBreak {
+    /// The submission made first ranks higher.
+    EarliestSubmission,
+    /// The submission with more jury votes ranks higher.
+    JuryVote,
+}
+
+/// Submission time first, then the jury.
+#[must_use]
+pub fn default_tie_breaks() -> Vec<TieBreak> {
+    vec![TieBreak::EarliestSubmission, TieBreak::JuryVote]
+}
+
+/// Body of `POST /api/events`, times are unix milliseconds.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct EventRequest {
+    pub title: String,
+    /// The part of the canvas reserved for the event.
+    pub region: CanvasBounds,
+    pub starts_at: u64,
+    pub ends_at: u64,
+    pub judging_ends_at: u64,
+    pub entry_fee_cents: u64,
+    #[serde(default = "default_tie_breaks")]
+    pub tie_breaks: Vec<TieBreak>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct EventInfo {
+    pub id: u64,
+    pub title: String,
+    pub region: CanvasBounds,
+    pub state: EventState,
+    /// Unix milliseconds.
+    pub starts_at: u64,
+    /// Unix milliseconds.
+    pub ends_at: u64,
+    /// Unix milliseconds.
+    pub judging_ends_at: u64,
+    pub entry_fee_cents: u64,
+    pub tie_breaks: Vec<TieBreak>,
+    pub participants: u64,
+}
+
+/// Response of `POST /api/events/{id}/registrations`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct EventRegistration {
+    pub event: EventInfo,
+    /// The entry fee to pay before the registration counts, `None` once
+    /// registered.
+    pub payment: Option<PaymentInfo>,
+}
+
+/// Body of `POST /api/events/{id}/submissions`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct SubmissionRequest {
+    /// The part of the event's region the artist claims as their entry.
+    pub region: CanvasBounds,
+}
+
+/// A participant's entry to an event.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct SubmissionInfo {
+    pub artist: u64,
+    pub region: CanvasBounds,
+    /// Unix milliseconds.
+    pub submitted_at: u64,
+}
+
+/// Body of the vote endpoints, `artist` picks their submission.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct VoteRequest {
+    pub artist: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct EventStanding {
+    pub submission: SubmissionInfo,
+    pub votes: u64,
+    pub jury_votes: u64,
+}
+
+/// Response of `GET /api/events/{id}/results`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct EventResults {
+    pub event: u64,
+    /// The winning artist, `None` without submissions.
+    pub winner: Option<u64>,
+    /// Whether the jury picked the winner instead of the votes.
+    pub overridden: bool,
+    /// Every submission from first to last place by votes.
+    pub standings: Vec<EventStanding>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum PayoutStatus {
+    /// Waiting for an admin to approve it.
+    Pending,
+    /// Sent once the hold period is over.
+    Approved,
+    Paid,
+    Rejected,
+}
+
+/// The share of a prize pool owed to the winner of an event.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct PayoutInfo {
+    pub id: u64,
+    pub event: u64,
+    /// The winning artist.
+    pub account: u64,
+    pub amount_cents: u64,
+    pub status: PayoutStatus,
+    /// Unix milliseconds.
+    pub created_at: u64,
+    /// Unix milliseconds before which an approved payout isn't sent.
+    pub release_at: u64,
+    pub approved_by: Option<u64>,
+    /// How the payment provider refers to the transfer once it's sent.
+    pub reference: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum PayoutAction {
+    PayoutCreated,
+    PayoutApproved,
+    PayoutRejected,
+    PayoutPaid,
+    /// Sending the payout failed, it's tried again later.
+    PayoutFailed,
+    /// The entry fee of a cancelled event was returned.
+    EntryRefunded,
+    /// Returning an entry fee failed, it's tried again later.
+    RefundFailed,
+}
+
+/// One step of the payout workflow, as listed by `GET /api/payouts/audit`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct PayoutAuditRecord {
+    /// Unix milliseconds.
+    pub at: u64,
+    pub action: PayoutAction,
+    pub event: u64,
+    pub payout: Option<u64>,
+    pub payment: Option<u64>,
+    /// The admin behind the step, `None` for automatic ones.
+    pub actor: Option<u64>,
+    pub amount_cents: Option<u64>,
+    /// Why a step failed.
+    pub detail: Option<String>,
+}
+
+/// Body of `POST /api/moderation/rollbacks`.
+///
+/// Reverts the placements made at or after `since` by `account`, inside
+/// `region`, or both when both are given.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct RollbackRequest {
+    #[serde(default)]
+    pub account: Option<u64>,
+    #[serde(default)]
+    pub region: Option<CanvasBounds>,
+    /// Unix milliseconds.
+    pub since: u64,
+}
+
+/// Response of `POST /api/moderation/rollbacks`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct RollbackInfo {
+    /// Pixels that got their earlier color back.
+    pub restored: u64,
+    /// Canvas revision after the rollback.
+    pub revision: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum BanKind {
+    /// The account is told it can't place pixels or report anymore.
+    Ban,
+    /// The account's placements seem to succeed but nobody else sees them.
+    ShadowBan,
+}
+
+/// Body of `POST /api/moderation/bans/{account}`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct BanRequest {
+    pub kind: BanKind,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct BanInfo {
+    pub account: u64,
+    pub kind: BanKind,
+    pub reason: Option<String>,
+    /// The moderator behind the ban.
+    pub banned_by: u64,
+    /// Unix milliseconds.
+    pub banned_at: u64,
+}
+
+/// Body of `POST /api/reports`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct ReportRequest {
+    pub coord: PixelCoord,
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ReportStatus {
+    /// Waiting in the review queue.
+    Open,
+    /// A moderator acted on the report.
+    Resolved,
+    /// A moderator found nothing to act on.
+    Dismissed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct ReportInfo {
+    pub id: u64,
+    pub coord: PixelCoord,
+    pub reason: String,
+    pub reporter: u64,
+    pub status: ReportStatus,
+    /// Unix milliseconds.
+    pub created_at: u64,
+    pub reviewed_by: Option<u64>,
+    /// Unix milliseconds.
+    pub reviewed_at: Option<u64>,
+}
+
+/// Body of `POST /api/moderation/reports/{id}/review`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct ReportReview {
+    pub status: ReportStatus,
+}
+
+/// Machine readable reason of an API failure.
+///
+/// Serialized in `snake_case`, clients should branch on this instead of the
+/// message.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ApiErrorCode {
+    InvalidRequest,
+    OutOfBounds,
+    NotFound,
+    /// Missing, invalid or expired credentials.
+    Unauthorized,
+    /// The caller isn't allowed to do this, like drawing in a reserved region.
+    Forbidden,
+    /// The request clashes with existing state, like a taken username.
+    Conflict,
+    /// Too many requests, `retry_at` tells when to try again.
+    RateLimited,
+    /// The server is temporarily unable to take the request, retry later.
+    Unavailable,
+    Internal,
+}
+
+impl Display for ApiErrorCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let code = match self {
+            Self::InvalidRequest => "invalid_request",
+            Self::OutOfBounds => "out_of_bounds",
+            Self::NotFound => "not_found",
+            Self::Unauthorized => "unauthorized",
+            Self::Forbidden => "forbidden",
+            Self::Conflict => "conflict",
+            Self::RateLimited => "rate_limited",
+            Self::Unavailable => "unavailable",
+            Self::Internal => "internal",
+        };
+
+        f.write_str(code)
+    }
+}
+
+impl ApiErrorCode {
+    /// The code that best describes an HTTP error status, for failures that
+    /// didn't come with one.
+    #[must_use]
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            401 => Self::Unauthorized,
+            403 => Self::Forbidden,
+            404 => Self::NotFound,
+            409 => Self::Conflict,
+            429 => Self::RateLimited,
+            502..=504 => Self::Unavailable,
+            400..500 => Self::InvalidRequest,
+            _ => Self::Internal,
+        }
+    }
+}
+
+/// JSON body sent along any non successful API response.
+#[derive(Serialize, Deserialize, Error, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[error("{message} ({code})")]
+pub struct ApiErrorBody {
+    pub code: ApiErrorCode,
+    pub message: String,
+    /// Unix milliseconds after which a rate limited request may be retried.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_at: Option<u64>,
+    /// HTTP status of the response, always set by the server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// Id of the failed request, always set by the server and worth quoting
+    /// when reporting a problem.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl ApiErrorBody {
+    pub fn new(code: ApiErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            retry_at: None,
+            status: None,
+            request_id: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_retry_at(mut self, retry_at: u64) -> Self {
+        self.retry_at = Some(retry_at);
+        self
+    }
+}
+
+/// Image format of a timelapse.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum TimelapseFormat {
+    Gif,
+    Apng,
+}
+
+/// Body of `POST /api/canvas/timelapses`.
+///
+/// Times are unix milliseconds, a frame shows the canvas as it was at
+/// `from`, `from + frame_interval` and so on up to `to`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct TimelapseRequest {
+    /// Defaults to the whole canvas.
+    #[serde(default)]
+    pub region: Option<CanvasBounds>,
+    pub from: u64,
+    pub to: u64,
+    /// Canvas time between two frames in milliseconds.
+    pub frame_interval: u64,
+    /// Playback time of a frame in milliseconds, defaults to 100.
+    #[serde(default)]
+    pub frame_delay: Option<u16>,
+    /// Defaults to 1.
+    #[serde(default)]
+    pub scale: Option<u32>,
+    pub format: TimelapseFormat,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum TimelapseStatus {
+    Queued,
+    Rendering,
+    /// The image can be downloaded, `size` is in bytes.
+    Finished {
+        frames: u32,
+        size: u64,
+    },
+    Failed {
+        message: String,
+    },
+}
+
+/// A timelapse render as reported by `GET /api/canvas/timelapses/{id}`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct TimelapseJob {
+    pub id: u64,
+    pub format: TimelapseFormat,
+    pub status: TimelapseStatus,
+}
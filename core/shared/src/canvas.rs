@@ -0,0 +1,282 @@
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "openapi")]
+use utoipa::ToSchema;
+
+/// Side length in pixels of a canvas chunk unless configured otherwise.
+pub const DEFAULT_CHUNK_SIZE: u16 = 256;
+
+/// An absolute pixel position on the canvas.
+///
+/// Coordinates are signed because the canvas grows outwards
+/// in every direction as it gets funded.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default,
+)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct PixelCoord {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl PixelCoord {
+    #[must_use]
+    #[inline]
+    pub const fn new(x: i32, y: i32) -> Self {
+        Self { x, y }
+    }
+
+    /// The chunk this pixel belongs to.
+    #[must_use]
+    pub fn chunk(self, chunk_size: u16) -> ChunkCoord {
+        let size = i32::from(chunk_size);
+
+        ChunkCoord::new(
+            self.x
+                .div_euclid(size),
+            self.y
+                .div_euclid(size),
+        )
+    }
+
+    /// The position of this pixel relative to the top left corner of its chunk.
+    #[must_use]
+    pub fn offset_in_chunk(self, chunk_size: u16) -> (u16, u16) {
+        let size = i32::from(chunk_size);
+
+        // `rem_euclid` is always in `0..size` which fits in a `u16`.
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        (
+            self.x
+                .rem_euclid(size) as u16,
+            self.y
+                .rem_euclid(size) as u16,
+        )
+    }
+}
+
+/// The position of a chunk, in chunk units.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct ChunkCoord {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl ChunkCoord {
+    #[must_use]
+    #[inline]
+    pub const fn new(x: i32, y: i32) -> Self {
+        Self { x, y }
+    }
+
+    /// The pixels covered by this chunk, `None` if it's too far out for them
+    /// to have coordinates.
+    #[must_use]
+    pub fn bounds(self, chunk_size: u16) -> Option<CanvasBounds> {
+        let origin = self.origin(chunk_size)?;
+
+        Some(CanvasBounds::new(origin.x, origin.y, u32::from(chunk_size), u32::from(chunk_size)))
+    }
+
+    /// The pixel at the top left corner of this chunk, `None` if it's too far
+    /// out to have coordinates.
+    #[must_use]
+    pub fn origin(self, chunk_size: u16) -> Option<PixelCoord> {
+        let size = i32::from(chunk_size);
+
+        Some(PixelCoord::new(
+            self.x
+                .checked_mul(size)?,
+            self.y
+                .checked_mul(size)?,
+        ))
+    }
+}
+
+/// An opaque 24 bit RGB color, the only kind of color a pixel can hold.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct PixelColor {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl PixelColor {
+    pub const WHITE: Self = Self::new(255, 255, 255);
+
+    #[must_use]
+    #[inline]
+    pub const fn new(red: u8, green: u8, blue: u8) -> Self {
+        Self { red, green, blue }
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn to_array(self) -> [u8; 3] {
+        [self.red, self.green, self.blue]
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn from_array([red, green, blue]: [u8; 3]) -> Self {
+        Self { red, green, blue }
+    }
+}
+
+#[cfg(feature = "palette")]
+impl From<palette::Srgb<u8>> for PixelColor {
+    fn from(color: palette::Srgb<u8>) -> Self {
+        Self::new(color.red, color.green, color.blue)
+    }
+}
+
+#[cfg(feature = "palette")]
+impl From<PixelColor> for palette::Srgb<u8> {
+    fn from(color: PixelColor) -> Self {
+        Self::new(color.red, color.green, color.blue)
+    }
+}
+
+/// A pixel accepted by the server.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct Placement {
+    /// Global canvas revision this placement produced, strictly increasing.
+    pub seq: u64,
+    pub coord: PixelCoord,
+    pub color: PixelColor,
+    /// Server time of the placement in milliseconds since the unix epoch.
+    pub placed_at: u64,
+    /// Account that placed the pixel, `None` for anonymous placements.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<u64>,
+}
+
+/// The canvas grew to new bounds.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct BoundsChange {
+    /// Global canvas revision of the change, shared with placements.
+    pub seq: u64,
+    pub bounds: CanvasBounds,
+    /// Server time of the change in milliseconds since the unix epoch.
+    pub changed_at: u64,
+}
+
+/// The drawable area of the canvas, `width` and `height` are in pixels.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct CanvasBounds {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl CanvasBounds {
+    #[must_use]
+    #[inline]
+    pub const fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    /// Bounds covering exactly `columns` by `rows` chunks starting at the
+    /// origin, `None` when they are too large to measure in pixels.
+    #[must_use]
+    pub fn from_chunks(columns: u32, rows: u32, chunk_size: u16) -> Option<Self> {
+        let size = u32::from(chunk_size);
+
+        Some(Self::new(0, 0, columns.checked_mul(size)?, rows.checked_mul(size)?))
+    }
+
+    #[must_use]
+    pub fn contains(&self, coord: PixelCoord) -> bool {
+        let dx = i64::from(coord.x) - i64::from(self.x);
+        let dy = i64::from(coord.y) - i64::from(self.y);
+
+        (0..i64::from(self.width)).contains(&dx) && (0..i64::from(self.height)).contains(&dy)
+    }
+
+    /// Whether `other` is fully inside these bounds.
+    #[must_use]
+    pub fn contains_bounds(&self, other: &CanvasBounds) -> bool {
+        let end = |start: i32, length: u32| i64::from(start) + i64::from(length);
+
+        i64::from(other.x) >= i64::from(self.x)
+            && i64::from(other.y) >= i64::from(self.y)
+            && end(other.x, other.width) <= end(self.x, self.width)
+            && end(other.y, other.height) <= end(self.y, self.height)
+    }
+
+    /// Whether at least one pixel is inside both bounds.
+    #[must_use]
+    pub fn intersects(&self, other: &CanvasBounds) -> bool {
+        let end = |start: i32, length: u32| i64::from(start) + i64::from(length);
+
+        i64::from(self.x) < end(other.x, other.width)
+            && i64::from(other.x) < end(self.x, self.width)
+            && i64::from(self.y) < end(other.y, other.height)
+            && i64::from(other.y) < end(self.y, self.height)
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn area(&self) -> u64 {
+        u64::from(self.width) * u64::from(self.height)
+    }
+
+    /// These bounds grown by `amount` pixels on every side.
+    #[must_use]
+    pub fn expanded(&self, amount: u32) -> Self {
+        let amount_signed = i32::try_from(amount).unwrap_or(i32::MAX);
+
+        Self::new(
+            self.x
+                .saturating_sub(amount_signed),
+            self.y
+                .saturating_sub(amount_signed),
+            self.width
+                .saturating_add(amount.saturating_mul(2)),
+            self.height
+                .saturating_add(amount.saturating_mul(2)),
+        )
+    }
+
+    /// The smallest bounds containing both.
+    #[must_use]
+    pub fn union(&self, other: &CanvasBounds) -> Self {
+        let end = |start: i32, length: u32| i64::from(start) + i64::from(length);
+        let x = self
+            .x
+            .min(other.x);
+        let y = self
+            .y
+            .min(other.y);
+        let length =
+            |start: i32, end: i64| u32::try_from(end - i64::from(start)).unwrap_or(u32::MAX);
+
+        Self::new(
+            x,
+            y,
+            length(x, end(self.x, self.width).max(end(other.x, other.width))),
+            length(y, end(self.y, self.height).max(end(other.y, other.height))),
+        )
+    }
+
+    /// Every chunk that holds at least one pixel inside these bounds.
+    pub fn chunks(&self, chunk_size: u16) -> impl Iterator<Item = ChunkCoord> + use<> {
+        let last = |start: i32, length: u32| {
+            i32::try_from(i64::from(start) + i64::from(length) - 1).unwrap_or(i32::MAX)
+        };
+
+        let first_chunk = PixelCoord::new(self.x, self.y).chunk(chunk_size);
+        let last_chunk =
+            PixelCoord::new(last(self.x, self.width), last(self.y, self.height)).chunk(chunk_size);
+        let empty = self.width == 0 || self.height == 0;
+
+        (first_chunk.y..=last_chunk.y)
+            .filter(move |_| !empty)
+            .flat_map(move |y| (first_chunk.x..=last_chunk.x).map(move |x| ChunkCoord::new(x, y)))
+    }
+}
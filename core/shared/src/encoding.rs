@@ -0,0 +1,179 @@
+use thiserror::Error;
+
+use crate::canvas::PixelColor;
+
+const KIND_FULL: u8 = 0;
+const KIND_DELTA: u8 = 1;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ChunkDecodeError {
+    #[error("The chunk payload ended unexpectedly.")]
+    Truncated,
+
+    #[error("Unknown chunk payload kind {0}.")]
+    UnknownKind(u8),
+
+    #[error("The chunk payload has {0} unexpected trailing bytes.")]
+    TrailingBytes(usize),
+}
+
+/// Binary body of `GET /api/canvas/chunks/{cx}/{cy}`.
+///
+/// Every payload starts with `[kind: u8][size: u16][version: u64]`, all little
+/// endian. A full payload follows with the row major RGB bytes of every cell, a
+/// delta payload follows with `[count: u32]` and `count` times `[x: u16][y:
+/// u16][r][g][b]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChunkPayload {
+    Full {
+        size: u16,
+        version: u64,
+        cells: Vec<u8>,
+    },
+    Delta {
+        size: u16,
+        version: u64,
+        changes: Vec<(u16, u16, PixelColor)>,
+    },
+}
+
+impl ChunkPayload {
+    /// The version of the chunk this payload brings a client up to.
+    #[must_use]
+    pub fn version(&self) -> u64 {
+        match self {
+            Self::Full { version, .. } | Self::Delta { version, .. } => *version,
+        }
+    }
+
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Self::Full { size, version, cells } => {
+                let mut bytes = Vec::with_capacity(11 + cells.len());
+
+                bytes.push(KIND_FULL);
+                bytes.extend_from_slice(&size.to_le_bytes());
+                bytes.extend_from_slice(&version.to_le_bytes());
+                bytes.extend_from_slice(cells);
+
+                bytes
+            },
+
+            Self::Delta { size, version, changes } => {
+                let mut bytes = Vec::with_capacity(15 + changes.len() * 7);
+
+                bytes.push(KIND_DELTA);
+                bytes.extend_from_slice(&size.to_le_bytes());
+                bytes.extend_from_slice(&version.to_le_bytes());
+                bytes.extend_from_slice(
+                    &u32::try_from(changes.len())
+                        .unwrap_or(u32::MAX)
+                        .to_le_bytes(),
+                );
+
+                for (x, y, color) in changes {
+                    bytes.extend_from_slice(&x.to_le_bytes());
+                    bytes.extend_from_slice(&y.to_le_bytes());
+                    bytes.extend_from_slice(&color.to_array());
+                }
+
+                bytes
+            },
+        }
+    }
+
+    /// Parses a payload produced by [`ChunkPayload::encode`].
+    ///
+    /// # Errors
+    /// If the payload is truncated, has trailing bytes or an unknown kind.
+    pub fn decode(bytes: &[u8]) -> Result<Self, ChunkDecodeError> {
+        let mut reader = Reader(bytes);
+
+        let kind = reader.u8()?;
+        let size = reader.u16()?;
+        let version = reader.u64()?;
+
+        let payload = match kind {
+            KIND_FULL => {
+                let cell_count = usize::from(size).pow(2);
+
+                Self::Full {
+                    size,
+                    version,
+                    cells: reader
+                        .take(cell_count * 3)?
+                        .to_vec(),
+                }
+            },
+
+            KIND_DELTA => {
+                let count = reader.u32()?;
+                let mut changes = Vec::new();
+
+                for _ in 0..count {
+                    let (x, y) = (reader.u16()?, reader.u16()?);
+                    let color = PixelColor::new(reader.u8()?, reader.u8()?, reader.u8()?);
+
+                    changes.push((x, y, color));
+                }
+
+                Self::Delta { size, version, changes }
+            },
+
+            kind => return Err(ChunkDecodeError::UnknownKind(kind)),
+        };
+
+        match reader
+            .0
+            .len()
+        {
+            0 => Ok(payload),
+            trailing => Err(ChunkDecodeError::TrailingBytes(trailing)),
+        }
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ChunkDecodeError> {
+        if self
+            .0
+            .len()
+            < len
+        {
+            return Err(ChunkDecodeError::Truncated);
+        }
+
+        let (taken, rest) = self
+            .0
+            .split_at(len);
+        self.0 = rest;
+
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ChunkDecodeError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, ChunkDecodeError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ChunkDecodeError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, ChunkDecodeError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, ChunkDecodeError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+}
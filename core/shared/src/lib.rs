@@ -0,0 +1,11 @@
+#![deny(clippy::pedantic)]
+#![deny(clippy::unwrap_used)]
+#![deny(clippy::match_like_matches_macro)]
+
+#[cfg(test)]
+mod tests;
+
+pub mod api;
+pub mod canvas;
+pub mod encoding;
+pub mod realtime;
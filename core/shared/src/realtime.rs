@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+use crate::canvas::{BoundsChange, ChunkCoord, Placement};
+
+/// Messages pushed by the server over the realtime channel.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Placement(Placement),
+    /// Sent to every client, chunks inside the new bounds can be subscribed
+    /// to from now on.
+    Bounds(BoundsChange),
+    /// Sent to every client right before the server goes down, clients should
+    /// reconnect once `reconnect_after_ms` milliseconds have passed.
+    ShuttingDown {
+        reconnect_after_ms: u64,
+    },
+}
+
+/// Messages sent by clients over the realtime channel.
+///
+/// Placements are only delivered for chunks the client is subscribed to,
+/// so clients should keep their subscriptions in sync with their viewport.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Subscribe { chunks: Vec<ChunkCoord> },
+    Unsubscribe { chunks: Vec<ChunkCoord> },
+}
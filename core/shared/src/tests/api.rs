@@ -0,0 +1,277 @@
+use serde_json::{from_value, json, to_value};
+
+use crate::api::{
+    AccountInfo,
+    ApiErrorBody,
+    ApiErrorCode,
+    BanKind,
+    BanRequest,
+    CheckoutRequest,
+    Credentials,
+    EventRequest,
+    PaymentInfo,
+    PaymentPurpose,
+    PaymentStatus,
+    PlacePixelRequest,
+    ReportReview,
+    ReportStatus,
+    RollbackRequest,
+    TieBreak,
+    TimelapseFormat,
+    TimelapseJob,
+    TimelapseRequest,
+    TimelapseStatus,
+};
+use crate::canvas::{CanvasBounds, PixelColor, PixelCoord};
+
+#[test]
+fn test_place_pixel_request() {
+    let request = from_value::<PlacePixelRequest>(json!({
+        "coord": { "x": 3, "y": 4 },
+        "color": { "red": 255, "green": 0, "blue": 10 }
+    }))
+    .expect("Request to deserialize.");
+
+    assert_eq!(
+        request,
+        PlacePixelRequest {
+            coord: PixelCoord::new(3, 4),
+            color: PixelColor::new(255, 0, 10)
+        }
+    );
+
+    assert!(
+        from_value::<PlacePixelRequest>(json!({
+            "coord": { "x": 3, "y": 4 },
+            "color": { "red": 256, "green": 0, "blue": 10 }
+        }))
+        .is_err()
+    );
+}
+
+#[test]
+fn test_error_body() {
+    let body = ApiErrorBody::new(ApiErrorCode::OutOfBounds, "Outside of the canvas.");
+
+    assert_eq!(
+        to_value(&body).expect("Error body to serialize."),
+        json!({ "code": "out_of_bounds", "message": "Outside of the canvas." })
+    );
+    assert_eq!(body.to_string(), "Outside of the canvas. (out_of_bounds)");
+
+    let body = ApiErrorBody::new(ApiErrorCode::RateLimited, "Slow down.").with_retry_at(1500);
+
+    assert_eq!(
+        to_value(&body).expect("Error body to serialize."),
+        json!({ "code": "rate_limited", "message": "Slow down.", "retry_at": 1500 })
+    );
+    assert_eq!(
+        from_value::<ApiErrorBody>(json!({ "code": "internal", "message": "Oops." }))
+            .expect("Error body to deserialize.")
+            .retry_at,
+        None
+    );
+
+    for code in [
+        ApiErrorCode::InvalidRequest,
+        ApiErrorCode::OutOfBounds,
+        ApiErrorCode::NotFound,
+        ApiErrorCode::Unauthorized,
+        ApiErrorCode::Forbidden,
+        ApiErrorCode::Conflict,
+        ApiErrorCode::RateLimited,
+        ApiErrorCode::Unavailable,
+        ApiErrorCode::Internal,
+    ] {
+        assert_eq!(to_value(code).expect("Code to serialize."), json!(code.to_string()));
+    }
+
+    let body = from_value::<ApiErrorBody>(json!({
+        "code": "forbidden",
+        "message": "This account is banned.",
+        "status": 403,
+        "request_id": "18c-2a"
+    }))
+    .expect("Error envelope to deserialize.");
+
+    assert_eq!(body.status, Some(403));
+    assert_eq!(
+        body.request_id
+            .as_deref(),
+        Some("18c-2a")
+    );
+
+    for (status, code) in [
+        (400, ApiErrorCode::InvalidRequest),
+        (401, ApiErrorCode::Unauthorized),
+        (404, ApiErrorCode::NotFound),
+        (413, ApiErrorCode::InvalidRequest),
+        (429, ApiErrorCode::RateLimited),
+        (500, ApiErrorCode::Internal),
+        (503, ApiErrorCode::Unavailable),
+    ] {
+        assert_eq!(ApiErrorCode::from_status(status), code);
+    }
+}
+
+#[test]
+fn test_timelapse() {
+    let request = from_value::<TimelapseRequest>(json!({
+        "from": 1000,
+        "to": 5000,
+        "frame_interval": 500,
+        "format": "apng"
+    }))
+    .expect("Request to deserialize.");
+
+    assert_eq!(
+        request,
+        TimelapseRequest {
+            region: None,
+            from: 1000,
+            to: 5000,
+            frame_interval: 500,
+            frame_delay: None,
+            scale: None,
+            format: TimelapseFormat::Apng,
+        }
+    );
+
+    let request = from_value::<TimelapseRequest>(json!({
+        "region": { "x": -4, "y": 0, "width": 8, "height": 2 },
+        "from": 0,
+        "to": 1,
+        "frame_interval": 1,
+        "frame_delay": 40,
+        "scale": 2,
+        "format": "gif"
+    }))
+    .expect("Request to deserialize.");
+
+    assert_eq!(request.region, Some(CanvasBounds::new(-4, 0, 8, 2)));
+    assert_eq!(request.format, TimelapseFormat::Gif);
+
+    let job = TimelapseJob {
+        id: 3,
+        format: TimelapseFormat::Gif,
+        status: TimelapseStatus::Finished { frames: 2, size: 120 },
+    };
+
+    assert_eq!(
+        to_value(&job).expect("Job to serialize."),
+        json!({
+            "id": 3,
+            "format": "gif",
+            "status": { "state": "finished", "frames": 2, "size": 120 }
+        })
+    );
+}
+
+#[test]
+fn test_credentials() {
+    let credentials = from_value::<Credentials>(json!({
+        "username": "ada",
+        "password": "correct horse"
+    }))
+    .expect("Credentials to deserialize.");
+
+    assert_eq!(credentials.username, "ada");
+    assert!(!format!("{credentials:?}").contains("correct horse"));
+
+    assert_eq!(
+        to_value(AccountInfo { id: 1, username: "ada".to_owned() }).expect("Account to serialize."),
+        json!({ "id": 1, "username": "ada" })
+    );
+}
+
+#[test]
+fn test_payment() {
+    let request = from_value::<CheckoutRequest>(json!({
+        "amount_cents": 500,
+        "purpose": { "kind": "contribution" }
+    }))
+    .expect("Request to deserialize.");
+
+    assert_eq!(
+        request,
+        CheckoutRequest {
+            amount_cents: 500,
+            purpose: PaymentPurpose::Contribution
+        }
+    );
+    assert_eq!(
+        to_value(PaymentPurpose::EventEntry { event: 3 }).expect("Purpose to serialize."),
+        json!({ "kind": "event_entry", "event": 3 })
+    );
+
+    let payment = PaymentInfo {
+        id: 2,
+        amount_cents: 500,
+        purpose: PaymentPurpose::Contribution,
+        status: PaymentStatus::Succeeded,
+        checkout_url: "fake://checkout/cs_fake_2".to_owned(),
+    };
+
+    assert_eq!(
+        to_value(&payment).expect("Payment to serialize."),
+        json!({
+            "id": 2,
+            "amount_cents": 500,
+            "purpose": { "kind": "contribution" },
+            "status": "succeeded",
+            "checkout_url": "fake://checkout/cs_fake_2"
+        })
+    );
+}
+
+#[test]
+fn test_event_request() {
+    let request = from_value::<EventRequest>(json!({
+        "title": "Pixel Sprint",
+        "region": { "x": 0, "y": 0, "width": 8, "height": 8 },
+        "starts_at": 100,
+        "ends_at": 200,
+        "judging_ends_at": 300,
+        "entry_fee_cents": 0
+    }))
+    .expect("Request to deserialize.");
+
+    assert_eq!(request.tie_breaks, vec![TieBreak::EarliestSubmission, TieBreak::JuryVote]);
+
+    let request = from_value::<EventRequest>(json!({
+        "title": "Pixel Sprint",
+        "region": { "x": 0, "y": 0, "width": 8, "height": 8 },
+        "starts_at": 100,
+        "ends_at": 200,
+        "judging_ends_at": 300,
+        "entry_fee_cents": 0,
+        "tie_breaks": ["jury_vote"]
+    }))
+    .expect("Request to deserialize.");
+
+    assert_eq!(request.tie_breaks, vec![TieBreak::JuryVote]);
+}
+
+#[test]
+fn test_moderation() {
+    let request = from_value::<RollbackRequest>(json!({ "account": 7, "since": 100 }))
+        .expect("Request to deserialize.");
+
+    assert_eq!(
+        request,
+        RollbackRequest {
+            account: Some(7),
+            region: None,
+            since: 100
+        }
+    );
+
+    let request =
+        from_value::<BanRequest>(json!({ "kind": "shadow_ban" })).expect("Request to deserialize.");
+
+    assert_eq!(request, BanRequest { kind: BanKind::ShadowBan, reason: None });
+    assert_eq!(
+        to_value(ReportReview { status: ReportStatus::Dismissed }).expect("Review to serialize."),
+        json!({ "status": "dismissed" })
+    );
+}
@@ -0,0 +1,148 @@
+use serde_json::{from_str, json, to_value};
+
+use crate::canvas::{CanvasBounds, ChunkCoord, PixelColor, PixelCoord, Placement};
+
+#[test]
+fn test_pixel_to_chunk() {
+    assert_eq!(PixelCoord::new(0, 0).chunk(256), ChunkCoord::new(0, 0));
+    assert_eq!(PixelCoord::new(255, 256).chunk(256), ChunkCoord::new(0, 1));
+    assert_eq!(PixelCoord::new(-1, -256).chunk(256), ChunkCoord::new(-1, -1));
+    assert_eq!(PixelCoord::new(-257, 10).chunk(256), ChunkCoord::new(-2, 0));
+
+    assert_eq!(PixelCoord::new(0, 0).offset_in_chunk(256), (0, 0));
+    assert_eq!(PixelCoord::new(257, 511).offset_in_chunk(256), (1, 255));
+    assert_eq!(PixelCoord::new(-1, -256).offset_in_chunk(256), (255, 0));
+}
+
+#[test]
+fn test_chunk_origin() {
+    assert_eq!(ChunkCoord::new(0, 0).origin(16), Some(PixelCoord::new(0, 0)));
+    assert_eq!(ChunkCoord::new(2, -3).origin(16), Some(PixelCoord::new(32, -48)));
+    assert_eq!(ChunkCoord::new(i32::MAX, 0).origin(16), None);
+    assert_eq!(ChunkCoord::new(0, i32::MIN).bounds(2), None);
+    assert_eq!(
+        PixelCoord::new(i32::MIN, i32::MAX)
+            .chunk(16)
+            .origin(16),
+        Some(PixelCoord::new(i32::MIN, i32::MAX - 15))
+    );
+
+    let pixel = PixelCoord::new(-37, 90);
+    let chunk = pixel.chunk(16);
+    let origin = chunk
+        .origin(16)
+        .expect("Chunk to have coordinates.");
+    let (dx, dy) = pixel.offset_in_chunk(16);
+
+    assert_eq!(origin.x + i32::from(dx), pixel.x);
+    assert_eq!(origin.y + i32::from(dy), pixel.y);
+}
+
+#[test]
+fn test_bounds_contains() {
+    let bounds = CanvasBounds::new(-10, -10, 20, 20);
+
+    assert!(bounds.contains(PixelCoord::new(-10, -10)));
+    assert!(bounds.contains(PixelCoord::new(9, 9)));
+    assert!(!bounds.contains(PixelCoord::new(10, 0)));
+    assert!(!bounds.contains(PixelCoord::new(0, -11)));
+    assert!(!CanvasBounds::new(0, 0, 0, 0).contains(PixelCoord::new(0, 0)));
+
+    assert!(bounds.contains_bounds(&CanvasBounds::new(-10, -10, 20, 20)));
+    assert!(bounds.contains_bounds(&CanvasBounds::new(0, 0, 10, 10)));
+    assert!(!bounds.contains_bounds(&CanvasBounds::new(0, 0, 11, 10)));
+
+    assert!(
+        bounds.intersects(
+            &ChunkCoord::new(0, 0)
+                .bounds(16)
+                .expect("Chunk to have coordinates.")
+        )
+    );
+    assert!(
+        bounds.intersects(
+            &ChunkCoord::new(-1, -1)
+                .bounds(16)
+                .expect("Chunk to have coordinates.")
+        )
+    );
+    assert!(
+        !bounds.intersects(
+            &ChunkCoord::new(2, 0)
+                .bounds(8)
+                .expect("Chunk to have coordinates.")
+        )
+    );
+    assert!(!bounds.intersects(&CanvasBounds::new(-10, -10, 0, 5)));
+}
+
+#[test]
+fn test_bounds_expand_and_chunks() {
+    let bounds = CanvasBounds::from_chunks(2, 1, 16).expect("Bounds to fit.");
+
+    assert_eq!(bounds, CanvasBounds::new(0, 0, 32, 16));
+    assert_eq!(bounds.area(), 512);
+    assert_eq!(
+        bounds
+            .chunks(16)
+            .collect::<Vec<_>>(),
+        vec![ChunkCoord::new(0, 0), ChunkCoord::new(1, 0)]
+    );
+
+    let expanded = bounds.expanded(16);
+
+    assert_eq!(expanded, CanvasBounds::new(-16, -16, 64, 48));
+    assert_eq!(
+        expanded
+            .chunks(16)
+            .count(),
+        12
+    );
+    assert_eq!(
+        CanvasBounds::new(5, 5, 0, 3)
+            .chunks(16)
+            .count(),
+        0
+    );
+
+    assert_eq!(bounds.union(&expanded), expanded);
+    assert_eq!(
+        CanvasBounds::new(-10, 0, 20, 5).union(&CanvasBounds::new(0, -5, 5, 30)),
+        CanvasBounds::new(-10, -5, 20, 30)
+    );
+}
+
+#[test]
+fn test_wire_format() {
+    let placement = Placement {
+        seq: 7,
+        coord: PixelCoord::new(-1, 2),
+        color: PixelColor::new(1, 2, 3),
+        placed_at: 1_700_000_000_000,
+        author: None,
+    };
+
+    let value = to_value(&placement).expect("Placement to serialize.");
+
+    assert_eq!(
+        value,
+        json!({
+            "seq": 7,
+            "coord": { "x": -1, "y": 2 },
+            "color": { "red": 1, "green": 2, "blue": 3 },
+            "placed_at": 1_700_000_000_000u64
+        })
+    );
+
+    let parsed = from_str::<Placement>(&value.to_string()).expect("Placement to deserialize.");
+
+    assert_eq!(parsed, placement);
+    assert_eq!(
+        PixelColor::from_array(
+            placement
+                .color
+                .to_array()
+        ),
+        placement.color
+    );
+}
@@ -0,0 +1,53 @@
+use crate::canvas::PixelColor;
+use crate::encoding::{ChunkDecodeError, ChunkPayload};
+
+#[test]
+fn test_full_payload_round_trip() {
+    let payload = ChunkPayload::Full {
+        size: 2,
+        version: 9,
+        cells: (0..12).collect(),
+    };
+    let bytes = payload.encode();
+
+    assert_eq!(bytes.len(), 11 + 12);
+    assert_eq!(&bytes[..3], &[0, 2, 0]);
+    assert_eq!(ChunkPayload::decode(&bytes), Ok(payload.clone()));
+    assert_eq!(payload.version(), 9);
+}
+
+#[test]
+fn test_delta_payload_round_trip() {
+    let payload = ChunkPayload::Delta {
+        size: 256,
+        version: 300,
+        changes: vec![(0, 1, PixelColor::new(1, 2, 3)), (255, 255, PixelColor::WHITE)],
+    };
+    let bytes = payload.encode();
+
+    assert_eq!(bytes.len(), 15 + 2 * 7);
+    assert_eq!(ChunkPayload::decode(&bytes), Ok(payload));
+}
+
+#[test]
+fn test_invalid_payloads() {
+    let bytes = ChunkPayload::Full {
+        size: 1,
+        version: 1,
+        cells: vec![1, 2, 3],
+    }
+    .encode();
+
+    assert_eq!(ChunkPayload::decode(&bytes[..bytes.len() - 1]), Err(ChunkDecodeError::Truncated));
+    assert_eq!(ChunkPayload::decode(&[]), Err(ChunkDecodeError::Truncated));
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+
+    assert_eq!(ChunkPayload::decode(&trailing), Err(ChunkDecodeError::TrailingBytes(1)));
+
+    let mut unknown = bytes;
+    unknown[0] = 7;
+
+    assert_eq!(ChunkPayload::decode(&unknown), Err(ChunkDecodeError::UnknownKind(7)));
+}
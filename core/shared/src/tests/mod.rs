@@ -0,0 +1,4 @@
+pub mod api;
+pub mod canvas;
+pub mod encoding;
+pub mod realtime;
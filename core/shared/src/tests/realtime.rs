@@ -0,0 +1,68 @@
+use serde_json::{from_value, json, to_value};
+
+use crate::canvas::{BoundsChange, CanvasBounds, ChunkCoord, PixelColor, PixelCoord, Placement};
+use crate::realtime::{ClientMessage, ServerMessage};
+
+#[test]
+fn test_server_message_format() {
+    let message = ServerMessage::Placement(Placement {
+        seq: 1,
+        coord: PixelCoord::new(2, 3),
+        color: PixelColor::new(4, 5, 6),
+        placed_at: 7,
+        author: None,
+    });
+    let value = to_value(&message).expect("Message to serialize.");
+
+    assert_eq!(
+        value,
+        json!({
+            "type": "placement",
+            "seq": 1,
+            "coord": { "x": 2, "y": 3 },
+            "color": { "red": 4, "green": 5, "blue": 6 },
+            "placed_at": 7
+        })
+    );
+    assert_eq!(from_value::<ServerMessage>(value).expect("Message to deserialize."), message);
+
+    let message = ServerMessage::Bounds(BoundsChange {
+        seq: 2,
+        bounds: CanvasBounds::new(-16, -16, 64, 64),
+        changed_at: 8,
+    });
+    let value = to_value(&message).expect("Message to serialize.");
+
+    assert_eq!(
+        value,
+        json!({
+            "type": "bounds",
+            "seq": 2,
+            "bounds": { "x": -16, "y": -16, "width": 64, "height": 64 },
+            "changed_at": 8
+        })
+    );
+    assert_eq!(from_value::<ServerMessage>(value).expect("Message to deserialize."), message);
+
+    let message = ServerMessage::ShuttingDown { reconnect_after_ms: 5000 };
+    let value = to_value(&message).expect("Message to serialize.");
+
+    assert_eq!(value, json!({ "type": "shutting_down", "reconnect_after_ms": 5000 }));
+    assert_eq!(from_value::<ServerMessage>(value).expect("Message to deserialize."), message);
+}
+
+#[test]
+fn test_client_message_format() {
+    let message = from_value::<ClientMessage>(json!({
+        "type": "subscribe",
+        "chunks": [{ "x": 0, "y": -1 }]
+    }))
+    .expect("Message to deserialize.");
+
+    assert_eq!(message, ClientMessage::Subscribe { chunks: vec![ChunkCoord::new(0, -1)] });
+
+    assert_eq!(
+        to_value(ClientMessage::Unsubscribe { chunks: Vec::new() }).expect("Message to serialize."),
+        json!({ "type": "unsubscribe", "chunks": [] })
+    );
+}